@@ -506,4 +506,8 @@ struct RawSegmentedChangelogConfig {
     1: optional bool enabled,
     // Specifies which algorithm to use in updating the Segmented Chagelog.
     2: optional string update_algorithm,
+    // Bookmarks, such as release branches, whose ancestry is assigned to the
+    // master group in addition to the master bookmark. Heads are assigned in
+    // the order they are listed, after the master bookmark.
+    3: optional list<string> tracked_bookmarks,
 }
@@ -18,6 +18,7 @@ use filenodes::Filenodes;
 use filestore::FilestoreConfig;
 use metaconfig_types::DerivedDataConfig;
 use repo_blobstore::RepoBlobstoreArgs;
+use segmented_changelog::SegmentedChangelog;
 use std::sync::Arc;
 
 /// Create new instance of implementing object with overridden field of specified type.
@@ -162,6 +163,22 @@ impl DangerousOverride<Arc<dyn BonsaiHgMapping>> for BlobRepoInner {
     }
 }
 
+impl DangerousOverride<Arc<dyn SegmentedChangelog>> for BlobRepoInner {
+    fn dangerous_override<F>(&self, modify: F) -> Self
+    where
+        F: FnOnce(Arc<dyn SegmentedChangelog>) -> Arc<dyn SegmentedChangelog>,
+    {
+        let segmented_changelog =
+            modify(self.attribute_expected::<dyn SegmentedChangelog>().clone());
+        let mut attributes = self.attributes.as_ref().clone();
+        attributes.insert::<dyn SegmentedChangelog>(segmented_changelog);
+        Self {
+            attributes: Arc::new(attributes),
+            ..self.clone()
+        }
+    }
+}
+
 impl DangerousOverride<DerivedDataConfig> for BlobRepoInner {
     fn dangerous_override<F>(&self, modify: F) -> Self
     where
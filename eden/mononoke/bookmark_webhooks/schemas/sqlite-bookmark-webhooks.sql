@@ -0,0 +1,19 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+CREATE TABLE bookmark_webhooks_dead_letters (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  repo_id INT UNSIGNED NOT NULL,
+  bookmark_update_log_id BIGINT NOT NULL,
+  url VARCHAR(1024) NOT NULL,
+  payload MEDIUMTEXT NOT NULL,
+  attempts INT UNSIGNED NOT NULL,
+  last_error TEXT NOT NULL,
+  failed_at BIGINT NOT NULL
+);
+
+CREATE INDEX repo_id_log_id ON bookmark_webhooks_dead_letters (repo_id, bookmark_update_log_id);
@@ -0,0 +1,125 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{Context, Result};
+use context::{CoreContext, PerfCounterType};
+use futures::compat::Future01CompatExt;
+use mononoke_types::{RepositoryId, Timestamp};
+use sql::queries;
+use sql_construct::{SqlConstruct, SqlConstructFromMetadataDatabaseConfig};
+use sql_ext::SqlConnections;
+
+/// A webhook delivery that was given up on.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeadLetter {
+    pub bookmark_update_log_id: i64,
+    pub url: String,
+    pub payload: String,
+    pub attempts: u32,
+    pub last_error: String,
+    pub failed_at: Timestamp,
+}
+
+/// Stores deliveries that failed after all retries, so that they can be inspected and replayed.
+#[derive(Clone)]
+pub struct SqlWebhookDeadLetters {
+    connections: SqlConnections,
+}
+
+impl SqlConstruct for SqlWebhookDeadLetters {
+    const LABEL: &'static str = "bookmark_webhooks";
+
+    const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-bookmark-webhooks.sql");
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        Self { connections }
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for SqlWebhookDeadLetters {}
+
+impl SqlWebhookDeadLetters {
+    pub async fn add(
+        &self,
+        ctx: &CoreContext,
+        repo_id: RepositoryId,
+        letter: &DeadLetter,
+    ) -> Result<()> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+        InsertDeadLetter::query(
+            &self.connections.write_connection,
+            &[(
+                &repo_id,
+                &letter.bookmark_update_log_id,
+                &letter.url,
+                &letter.payload,
+                &letter.attempts,
+                &letter.last_error,
+                &letter.failed_at.timestamp_nanos(),
+            )],
+        )
+        .compat()
+        .await
+        .context("inserting webhook dead letter")?;
+        Ok(())
+    }
+
+    /// List the dead letters of a repo, oldest first.
+    pub async fn list(&self, ctx: &CoreContext, repo_id: RepositoryId) -> Result<Vec<DeadLetter>> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsMaster);
+        let rows = SelectDeadLetters::query(&self.connections.read_master_connection, &repo_id)
+            .compat()
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(
+                |(bookmark_update_log_id, url, payload, attempts, last_error, failed_at)| {
+                    DeadLetter {
+                        bookmark_update_log_id,
+                        url,
+                        payload,
+                        attempts,
+                        last_error,
+                        failed_at: Timestamp::from_timestamp_nanos(failed_at),
+                    }
+                },
+            )
+            .collect())
+    }
+}
+
+queries! {
+    write InsertDeadLetter(
+        values: (
+            repo_id: RepositoryId,
+            bookmark_update_log_id: i64,
+            url: String,
+            payload: String,
+            attempts: u32,
+            last_error: String,
+            failed_at: i64,
+        )
+    ) {
+        none,
+        "
+        INSERT INTO bookmark_webhooks_dead_letters
+            (repo_id, bookmark_update_log_id, url, payload, attempts, last_error, failed_at)
+        VALUES {values}
+        "
+    }
+
+    read SelectDeadLetters(repo_id: RepositoryId) -> (i64, String, String, u32, String, i64) {
+        "
+        SELECT bookmark_update_log_id, url, payload, attempts, last_error, failed_at
+        FROM bookmark_webhooks_dead_letters
+        WHERE repo_id = {repo_id}
+        ORDER BY id ASC
+        "
+    }
+}
@@ -0,0 +1,392 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Error, Result};
+use blobrepo::BlobRepo;
+use blobstore::Loadable;
+use bookmarks::{BookmarkUpdateLogEntry, Freshness};
+use context::CoreContext;
+use futures::compat::Future01CompatExt;
+use futures::stream::TryStreamExt;
+use http::{header, uri::Uri, Request, StatusCode};
+use hyper::{client::HttpConnector, Body, Client};
+use hyper_openssl::HttpsConnector;
+use mononoke_types::Timestamp;
+use mutable_counters::MutableCounters;
+use regex::Regex;
+use slog::{info, warn};
+use stats::prelude::*;
+use thiserror::Error;
+
+use crate::dead_letter::{DeadLetter, SqlWebhookDeadLetters};
+use crate::payload::{sign_payload, WebhookPayload, SIGNATURE_HEADER};
+
+/// Name of the mutable counter holding the id of the last dispatched bookmarks update log entry.
+pub const LATEST_DISPATCHED_COUNTER: &str = "bookmark-webhooks-latest-dispatched";
+
+const DEFAULT_BATCH_SIZE: u64 = 100;
+
+define_stats! {
+    prefix = "mononoke.bookmark_webhooks";
+    entries: timeseries(Sum),
+    delivered: timeseries(Sum),
+    retried: timeseries(Sum),
+    dead_lettered: timeseries(Sum),
+}
+
+type HttpsHyperClient = Client<HttpsConnector<HttpConnector>>;
+
+#[derive(Debug, Error)]
+enum DeliveryError {
+    /// The endpoint rejected the payload, retrying won't help.
+    #[error("Webhook rejected the payload with status {0}")]
+    Rejected(StatusCode),
+    #[error("Webhook returned status {0}")]
+    Unavailable(StatusCode),
+    #[error("Webhook request timed out after {0:?}")]
+    TimedOut(Duration),
+    #[error("Webhook request failed: {0}")]
+    RequestFailed(#[source] hyper::Error),
+}
+
+impl DeliveryError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            DeliveryError::Rejected(_) => false,
+            _ => true,
+        }
+    }
+}
+
+/// An endpoint that gets notified about bookmark moves.
+#[derive(Clone, Debug)]
+pub struct WebhookTarget {
+    pub url: Uri,
+    /// Shared secret used to sign payloads. Unsigned payloads are sent if this is not set.
+    pub secret: Option<Vec<u8>>,
+    /// Only notify about bookmarks matching this regex. All bookmarks match if not set.
+    pub bookmark_regex: Option<Regex>,
+}
+
+impl WebhookTarget {
+    pub fn new(url: Uri) -> Self {
+        Self {
+            url,
+            secret: None,
+            bookmark_regex: None,
+        }
+    }
+
+    pub fn with_secret(self, secret: impl Into<Vec<u8>>) -> Self {
+        Self {
+            secret: Some(secret.into()),
+            ..self
+        }
+    }
+
+    pub fn with_bookmark_regex(self, bookmark_regex: Regex) -> Self {
+        Self {
+            bookmark_regex: Some(bookmark_regex),
+            ..self
+        }
+    }
+
+    fn wants(&self, entry: &BookmarkUpdateLogEntry) -> bool {
+        match &self.bookmark_regex {
+            Some(regex) => regex.is_match(entry.bookmark_name.as_str()),
+            None => true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Total number of delivery attempts for a single payload, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first retry. It doubles with every subsequent retry.
+    pub base_delay: Duration,
+    /// Upper bound on the delay between two attempts.
+    pub max_delay: Duration,
+    pub request_timeout: Duration,
+}
+
+impl RetryPolicy {
+    /// Delay to wait after the given (1-based) failed attempt before trying again.
+    pub fn retry_delay(&self, attempt: u32) -> Duration {
+        2u32.checked_pow(attempt.saturating_sub(1))
+            .and_then(|factor| self.base_delay.checked_mul(factor))
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            request_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+pub struct WebhookDispatcher {
+    repo: BlobRepo,
+    mutable_counters: Arc<dyn MutableCounters>,
+    dead_letters: SqlWebhookDeadLetters,
+    targets: Vec<WebhookTarget>,
+    retry_policy: RetryPolicy,
+    batch_size: u64,
+    client: HttpsHyperClient,
+}
+
+impl WebhookDispatcher {
+    pub fn new(
+        repo: BlobRepo,
+        mutable_counters: Arc<dyn MutableCounters>,
+        dead_letters: SqlWebhookDeadLetters,
+        targets: Vec<WebhookTarget>,
+    ) -> Result<Self> {
+        let connector = HttpsConnector::new()
+            .map_err(Error::from)
+            .context("creating HTTPS connector")?;
+        let client = Client::builder().build(connector);
+
+        Ok(Self {
+            repo,
+            mutable_counters,
+            dead_letters,
+            targets,
+            retry_policy: RetryPolicy::default(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            client,
+        })
+    }
+
+    pub fn with_retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        Self {
+            retry_policy,
+            ..self
+        }
+    }
+
+    pub fn with_batch_size(self, batch_size: u64) -> Self {
+        Self { batch_size, ..self }
+    }
+
+    /// Set the position in the bookmarks update log from which dispatching continues.
+    pub async fn set_position(&self, ctx: &CoreContext, id: i64) -> Result<()> {
+        self.mutable_counters
+            .set_counter(
+                ctx.clone(),
+                self.repo.get_repoid(),
+                LATEST_DISPATCHED_COUNTER,
+                id,
+                None,
+            )
+            .compat()
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_position(&self, ctx: &CoreContext) -> Result<Option<i64>> {
+        self.mutable_counters
+            .get_counter(
+                ctx.clone(),
+                self.repo.get_repoid(),
+                LATEST_DISPATCHED_COUNTER,
+            )
+            .compat()
+            .await
+    }
+
+    /// Periodically dispatch new bookmark moves. Errors are logged and the next iteration starts
+    /// from the last successfully dispatched entry.
+    pub async fn run(&self, ctx: &CoreContext, delay: Duration) {
+        loop {
+            match self.once(ctx).await {
+                Ok(0) => tokio::time::delay_for(delay).await,
+                Ok(_) => {}
+                Err(err) => {
+                    warn!(
+                        ctx.logger(),
+                        "repo {}: failed to dispatch bookmark webhooks: {:?}",
+                        self.repo.get_repoid(),
+                        err
+                    );
+                    tokio::time::delay_for(delay).await;
+                }
+            }
+        }
+    }
+
+    /// Dispatch the next batch of bookmark moves. Returns the number of log entries processed.
+    pub async fn once(&self, ctx: &CoreContext) -> Result<usize> {
+        let repo_id = self.repo.get_repoid();
+        let mut position = self.get_position(ctx).await?.ok_or_else(|| {
+            anyhow!(
+                "repo {}: counter {} is not set, initialize it with the starting log id",
+                repo_id,
+                LATEST_DISPATCHED_COUNTER
+            )
+        })?;
+
+        let entries: Vec<_> = self
+            .repo
+            .bookmarks_log()
+            .read_next_bookmark_log_entries(
+                ctx.clone(),
+                position as u64,
+                self.batch_size,
+                Freshness::MostRecent,
+            )
+            .try_collect()
+            .await
+            .context("reading bookmarks update log")?;
+
+        for entry in &entries {
+            STATS::entries.add_value(1);
+            self.dispatch_entry(ctx, entry).await?;
+
+            let updated = self
+                .mutable_counters
+                .set_counter(
+                    ctx.clone(),
+                    repo_id,
+                    LATEST_DISPATCHED_COUNTER,
+                    entry.id,
+                    Some(position),
+                )
+                .compat()
+                .await?;
+            if !updated {
+                return Err(anyhow!(
+                    "repo {}: counter {} was moved concurrently, is another dispatcher running?",
+                    repo_id,
+                    LATEST_DISPATCHED_COUNTER
+                ));
+            }
+            position = entry.id;
+        }
+
+        if !entries.is_empty() {
+            info!(
+                ctx.logger(),
+                "repo {}: dispatched bookmark moves up to log id {}", repo_id, position
+            );
+        }
+
+        Ok(entries.len())
+    }
+
+    async fn dispatch_entry(
+        &self,
+        ctx: &CoreContext,
+        entry: &BookmarkUpdateLogEntry,
+    ) -> Result<()> {
+        let targets: Vec<_> = self.targets.iter().filter(|t| t.wants(entry)).collect();
+        if targets.is_empty() {
+            return Ok(());
+        }
+
+        let author = match entry.to_changeset_id {
+            Some(cs_id) => {
+                let bcs = cs_id
+                    .load(ctx, self.repo.blobstore())
+                    .await
+                    .with_context(|| format!("loading changeset {}", cs_id))?;
+                Some(bcs.author().to_string())
+            }
+            None => None,
+        };
+        let payload = WebhookPayload::new(self.repo.name().clone(), entry, author);
+        let body = serde_json::to_string(&payload)?;
+
+        for target in targets {
+            let (attempts, res) = self.deliver(target, &body).await;
+            match res {
+                Ok(()) => STATS::delivered.add_value(1),
+                Err(err) => {
+                    warn!(
+                        ctx.logger(),
+                        "giving up on delivering log entry {} to {} after {} attempts: {}",
+                        entry.id,
+                        target.url,
+                        attempts,
+                        err
+                    );
+                    STATS::dead_lettered.add_value(1);
+                    let letter = DeadLetter {
+                        bookmark_update_log_id: entry.id,
+                        url: target.url.to_string(),
+                        payload: body.clone(),
+                        attempts,
+                        last_error: err.to_string(),
+                        failed_at: Timestamp::now(),
+                    };
+                    self.dead_letters
+                        .add(ctx, self.repo.get_repoid(), &letter)
+                        .await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deliver `body` to `target`, retrying transient failures. Returns the number of attempts
+    /// made along with the outcome of the last one.
+    async fn deliver(
+        &self,
+        target: &WebhookTarget,
+        body: &str,
+    ) -> (u32, Result<(), DeliveryError>) {
+        let mut attempt = 1;
+        loop {
+            let res = self.post(target, body).await;
+            match res {
+                Err(ref err) if err.is_retryable() && attempt < self.retry_policy.max_attempts => {
+                    STATS::retried.add_value(1);
+                    tokio::time::delay_for(self.retry_policy.retry_delay(attempt)).await;
+                    attempt += 1;
+                }
+                res => return (attempt, res),
+            }
+        }
+    }
+
+    async fn post(&self, target: &WebhookTarget, body: &str) -> Result<(), DeliveryError> {
+        let mut request =
+            Request::post(target.url.clone()).header(header::CONTENT_TYPE, "application/json");
+        if let Some(secret) = &target.secret {
+            request = request.header(SIGNATURE_HEADER, sign_payload(secret, body.as_bytes()));
+        }
+        // The URL was already validated and the headers are static or hex, so building the
+        // request can't fail.
+        let request = request
+            .body(Body::from(body.to_string()))
+            .expect("invalid webhook request");
+
+        let timeout = self.retry_policy.request_timeout;
+        let response = tokio::time::timeout(timeout, self.client.request(request))
+            .await
+            .map_err(|_| DeliveryError::TimedOut(timeout))?
+            .map_err(DeliveryError::RequestFailed)?;
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+            Err(DeliveryError::Unavailable(status))
+        } else {
+            Err(DeliveryError::Rejected(status))
+        }
+    }
+}
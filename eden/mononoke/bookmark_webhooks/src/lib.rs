@@ -0,0 +1,28 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![deny(warnings)]
+
+//! Notifies external systems about bookmark moves.
+//!
+//! The dispatcher tails the bookmarks update log starting from a position that is stored in
+//! mutable counters, and for every entry POSTs a signed JSON payload to each configured webhook.
+//! Deliveries are retried with exponential backoff. Entries that could not be delivered are
+//! recorded in a dead-letter table so that the tailer never gets stuck on a broken endpoint.
+
+mod dead_letter;
+mod dispatcher;
+mod payload;
+
+#[cfg(test)]
+mod tests;
+
+pub use crate::dead_letter::{DeadLetter, SqlWebhookDeadLetters};
+pub use crate::dispatcher::{
+    RetryPolicy, WebhookDispatcher, WebhookTarget, LATEST_DISPATCHED_COUNTER,
+};
+pub use crate::payload::{sign_payload, WebhookPayload, SIGNATURE_HEADER};
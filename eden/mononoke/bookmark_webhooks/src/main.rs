@@ -0,0 +1,218 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![deny(warnings)]
+
+use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{format_err, Context, Error};
+use bookmark_webhooks::{RetryPolicy, SqlWebhookDeadLetters, WebhookDispatcher, WebhookTarget};
+use clap::Arg;
+use cmdlib::{
+    args::{self, MononokeMatches},
+    helpers::block_execute,
+};
+use context::CoreContext;
+use fbinit::FacebookInit;
+use mutable_counters::SqlMutableCounters;
+use regex::Regex;
+use slog::info;
+
+const ARG_URL: &str = "url";
+const ARG_SECRET_FILE: &str = "secret-file";
+const ARG_BOOKMARK_REGEX: &str = "bookmark-regex";
+const ARG_START_ID: &str = "start-id";
+const ARG_BATCH_SIZE: &str = "batch-size";
+const ARG_MAX_ATTEMPTS: &str = "max-attempts";
+const ARG_BASE_RETRY_DELAY_MS: &str = "base-retry-delay-ms";
+const ARG_MAX_RETRY_DELAY_MS: &str = "max-retry-delay-ms";
+const ARG_REQUEST_TIMEOUT_MS: &str = "request-timeout-ms";
+const ARG_DELAY: &str = "delay";
+const ARG_ONCE: &str = "once";
+
+async fn run<'a>(ctx: CoreContext, matches: &'a MononokeMatches<'a>) -> Result<(), Error> {
+    let config_store = args::init_config_store(ctx.fb, ctx.logger(), matches)?;
+
+    let secret = matches
+        .value_of(ARG_SECRET_FILE)
+        .map(|path| fs::read(path).with_context(|| format!("reading webhook secret from {}", path)))
+        .transpose()?;
+    let bookmark_regex = matches
+        .value_of(ARG_BOOKMARK_REGEX)
+        .map(Regex::new)
+        .transpose()
+        .context("parsing bookmark regex")?;
+    let targets = matches
+        .values_of(ARG_URL)
+        .ok_or_else(|| format_err!("--{} argument is required", ARG_URL))?
+        .map(|url| {
+            let url = url
+                .parse()
+                .with_context(|| format!("parsing webhook url {}", url))?;
+            let mut target = WebhookTarget::new(url);
+            if let Some(secret) = &secret {
+                target = target.with_secret(secret.clone());
+            }
+            if let Some(bookmark_regex) = &bookmark_regex {
+                target = target.with_bookmark_regex(bookmark_regex.clone());
+            }
+            Ok(target)
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let default_policy = RetryPolicy::default();
+    let retry_policy = RetryPolicy {
+        max_attempts: args::get_u64(
+            matches,
+            ARG_MAX_ATTEMPTS,
+            default_policy.max_attempts as u64,
+        ) as u32,
+        base_delay: args::get_u64_opt(matches, ARG_BASE_RETRY_DELAY_MS)
+            .map(Duration::from_millis)
+            .unwrap_or(default_policy.base_delay),
+        max_delay: args::get_u64_opt(matches, ARG_MAX_RETRY_DELAY_MS)
+            .map(Duration::from_millis)
+            .unwrap_or(default_policy.max_delay),
+        request_timeout: args::get_u64_opt(matches, ARG_REQUEST_TIMEOUT_MS)
+            .map(Duration::from_millis)
+            .unwrap_or(default_policy.request_timeout),
+    };
+
+    let repo = args::open_repo(ctx.fb, ctx.logger(), matches).await?;
+    let mutable_counters =
+        args::open_sql::<SqlMutableCounters>(ctx.fb, config_store, matches).await?;
+    let dead_letters =
+        args::open_sql::<SqlWebhookDeadLetters>(ctx.fb, config_store, matches).await?;
+
+    let mut dispatcher =
+        WebhookDispatcher::new(repo, Arc::new(mutable_counters), dead_letters, targets)?
+            .with_retry_policy(retry_policy);
+    if let Some(batch_size) = args::get_u64_opt(matches, ARG_BATCH_SIZE) {
+        dispatcher = dispatcher.with_batch_size(batch_size);
+    }
+
+    if let Some(start_id) = args::get_i64_opt(matches, ARG_START_ID) {
+        info!(
+            ctx.logger(),
+            "starting from bookmarks update log id {}", start_id
+        );
+        dispatcher.set_position(&ctx, start_id).await?;
+    }
+
+    if matches.is_present(ARG_ONCE) {
+        dispatcher.once(&ctx).await?;
+    } else {
+        let delay = Duration::from_secs(args::get_u64(matches, ARG_DELAY, 5));
+        dispatcher.run(&ctx, delay).await;
+    }
+
+    Ok(())
+}
+
+#[fbinit::main]
+fn main(fb: FacebookInit) -> Result<(), Error> {
+    let app = args::MononokeAppBuilder::new("Mononoke bookmark webhooks dispatcher")
+        .with_advanced_args_hidden()
+        .with_fb303_args()
+        .build()
+        .about("Notifies webhooks about bookmark moves recorded in the bookmarks update log.")
+        .arg(
+            Arg::with_name(ARG_URL)
+                .long(ARG_URL)
+                .takes_value(true)
+                .required(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Webhook URL to POST bookmark moves to"),
+        )
+        .arg(
+            Arg::with_name(ARG_SECRET_FILE)
+                .long(ARG_SECRET_FILE)
+                .takes_value(true)
+                .required(false)
+                .help("File with the shared secret used to sign payloads"),
+        )
+        .arg(
+            Arg::with_name(ARG_BOOKMARK_REGEX)
+                .long(ARG_BOOKMARK_REGEX)
+                .takes_value(true)
+                .required(false)
+                .help("Only notify about bookmarks matching this regex"),
+        )
+        .arg(
+            Arg::with_name(ARG_START_ID)
+                .long(ARG_START_ID)
+                .takes_value(true)
+                .required(false)
+                .help("Reset the dispatcher position to this bookmarks update log id"),
+        )
+        .arg(
+            Arg::with_name(ARG_BATCH_SIZE)
+                .long(ARG_BATCH_SIZE)
+                .takes_value(true)
+                .required(false)
+                .help("Maximum number of log entries to read at once"),
+        )
+        .arg(
+            Arg::with_name(ARG_MAX_ATTEMPTS)
+                .long(ARG_MAX_ATTEMPTS)
+                .takes_value(true)
+                .required(false)
+                .help("How many times to try delivering a payload before dead-lettering it"),
+        )
+        .arg(
+            Arg::with_name(ARG_BASE_RETRY_DELAY_MS)
+                .long(ARG_BASE_RETRY_DELAY_MS)
+                .takes_value(true)
+                .required(false)
+                .help("Delay before the first retry, doubled on every subsequent retry"),
+        )
+        .arg(
+            Arg::with_name(ARG_MAX_RETRY_DELAY_MS)
+                .long(ARG_MAX_RETRY_DELAY_MS)
+                .takes_value(true)
+                .required(false)
+                .help("Upper bound on the delay between two retries"),
+        )
+        .arg(
+            Arg::with_name(ARG_REQUEST_TIMEOUT_MS)
+                .long(ARG_REQUEST_TIMEOUT_MS)
+                .takes_value(true)
+                .required(false)
+                .help("Timeout for a single webhook request"),
+        )
+        .arg(
+            Arg::with_name(ARG_DELAY)
+                .long(ARG_DELAY)
+                .takes_value(true)
+                .required(false)
+                .help("Delay in seconds between polls of the bookmarks update log"),
+        )
+        .arg(
+            Arg::with_name(ARG_ONCE)
+                .long(ARG_ONCE)
+                .takes_value(false)
+                .required(false)
+                .help("Dispatch a single batch of log entries and exit"),
+        );
+    let matches = app.get_matches();
+
+    let logger = args::init_logging(fb, &matches)?;
+    args::init_cachelib(fb, &matches);
+    let ctx = CoreContext::new_with_logger(fb, logger.clone());
+
+    block_execute(
+        run(ctx, &matches),
+        fb,
+        "bookmark_webhooks",
+        &logger,
+        &matches,
+        cmdlib::monitoring::AliveService,
+    )
+}
@@ -0,0 +1,62 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use bookmarks::BookmarkUpdateLogEntry;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// Header carrying the HMAC-SHA256 of the request body, in the form `sha256=<hex digest>`.
+pub const SIGNATURE_HEADER: &str = "X-Mononoke-Signature";
+
+/// JSON body POSTed to webhooks for a single bookmark move.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    /// Id of the bookmarks update log entry. Receivers can use it to deduplicate deliveries.
+    pub log_id: i64,
+    pub repo_id: i32,
+    pub repo_name: String,
+    pub bookmark: String,
+    /// Previous position of the bookmark, `None` if it was created or force-set.
+    pub old_commit: Option<String>,
+    /// New position of the bookmark, `None` if it was deleted.
+    pub new_commit: Option<String>,
+    /// Author of the new bookmark target, `None` if the bookmark was deleted. This is not
+    /// necessarily who moved the bookmark, which the update log doesn't record.
+    pub author: Option<String>,
+    pub reason: String,
+    /// Time of the bookmark move, in seconds since the epoch.
+    pub timestamp: i64,
+}
+
+impl WebhookPayload {
+    pub fn new(
+        repo_name: impl Into<String>,
+        entry: &BookmarkUpdateLogEntry,
+        author: Option<String>,
+    ) -> Self {
+        Self {
+            log_id: entry.id,
+            repo_id: entry.repo_id.id(),
+            repo_name: repo_name.into(),
+            bookmark: entry.bookmark_name.to_string(),
+            old_commit: entry.from_changeset_id.map(|cs_id| cs_id.to_string()),
+            new_commit: entry.to_changeset_id.map(|cs_id| cs_id.to_string()),
+            author,
+            reason: entry.reason.to_string(),
+            timestamp: entry.timestamp.timestamp_seconds(),
+        }
+    }
+}
+
+/// Compute the value of the `SIGNATURE_HEADER` for `body` using the shared `secret`.
+pub fn sign_payload(secret: &[u8], body: &[u8]) -> String {
+    // HMAC accepts keys of any length, so this can't fail.
+    let mut mac = Hmac::<Sha256>::new_varkey(secret).expect("HMAC accepts any key length");
+    mac.input(body);
+    format!("sha256={}", hex::encode(mac.result().code()))
+}
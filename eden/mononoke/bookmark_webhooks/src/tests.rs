@@ -0,0 +1,215 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use blobrepo::BlobRepo;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use http::{Response, StatusCode};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Server};
+use mutable_counters::SqlMutableCounters;
+use regex::Regex;
+use sql_construct::SqlConstruct;
+use tests_utils::{bookmark, CreateCommitContext};
+
+use crate::{
+    sign_payload, RetryPolicy, SqlWebhookDeadLetters, WebhookDispatcher, WebhookPayload,
+    WebhookTarget, SIGNATURE_HEADER,
+};
+
+/// Local HTTP stand-in for a webhook receiver. It answers with the queued statuses in order and
+/// with 200 once the queue is exhausted.
+struct StandIn {
+    addr: SocketAddr,
+    received: Arc<Mutex<Vec<(Option<String>, Vec<u8>)>>>,
+}
+
+impl StandIn {
+    fn start(statuses: Vec<StatusCode>) -> Self {
+        let statuses = Arc::new(Mutex::new(VecDeque::from(statuses)));
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        let make_svc = make_service_fn({
+            let statuses = statuses.clone();
+            let received = received.clone();
+            move |_conn| {
+                let statuses = statuses.clone();
+                let received = received.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |req: http::Request<Body>| {
+                        let statuses = statuses.clone();
+                        let received = received.clone();
+                        async move {
+                            let signature = req
+                                .headers()
+                                .get(SIGNATURE_HEADER)
+                                .map(|v| v.to_str().unwrap().to_string());
+                            let body = hyper::body::to_bytes(req.into_body()).await?;
+                            received.lock().unwrap().push((signature, body.to_vec()));
+                            let status = statuses.lock().unwrap().pop_front();
+                            Response::builder()
+                                .status(status.unwrap_or(StatusCode::OK))
+                                .body(Body::empty())
+                                .map_err(anyhow::Error::from)
+                        }
+                    }))
+                }
+            }
+        });
+
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_svc);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        Self { addr, received }
+    }
+
+    fn url(&self) -> http::Uri {
+        format!("http://{}/hook", self.addr).parse().unwrap()
+    }
+
+    fn received(&self) -> Vec<(Option<String>, Vec<u8>)> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+fn fast_retries() -> RetryPolicy {
+    RetryPolicy {
+        max_attempts: 3,
+        base_delay: Duration::from_millis(1),
+        max_delay: Duration::from_millis(4),
+        request_timeout: Duration::from_secs(10),
+    }
+}
+
+#[test]
+fn test_retry_delay_is_capped() {
+    let policy = fast_retries();
+    assert_eq!(policy.retry_delay(1), Duration::from_millis(1));
+    assert_eq!(policy.retry_delay(2), Duration::from_millis(2));
+    assert_eq!(policy.retry_delay(3), Duration::from_millis(4));
+    assert_eq!(policy.retry_delay(4), Duration::from_millis(4));
+    // Large attempt counts must not overflow.
+    assert_eq!(policy.retry_delay(40), Duration::from_millis(4));
+    assert_eq!(policy.retry_delay(u32::MAX), Duration::from_millis(4));
+}
+
+async fn setup(
+    ctx: &CoreContext,
+    targets: Vec<WebhookTarget>,
+) -> Result<(BlobRepo, WebhookDispatcher, SqlWebhookDeadLetters)> {
+    let repo = blobrepo_factory::new_memblob_empty(None)?;
+    let dead_letters = SqlWebhookDeadLetters::with_sqlite_in_memory()?;
+    let dispatcher = WebhookDispatcher::new(
+        repo.clone(),
+        Arc::new(SqlMutableCounters::with_sqlite_in_memory()?),
+        dead_letters.clone(),
+        targets,
+    )?
+    .with_retry_policy(fast_retries());
+    dispatcher.set_position(ctx, 0).await?;
+    Ok((repo, dispatcher, dead_letters))
+}
+
+#[fbinit::compat_test]
+async fn test_signed_delivery(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let stand_in = StandIn::start(vec![]);
+    let target = WebhookTarget::new(stand_in.url()).with_secret("s3cret");
+    let (repo, dispatcher, dead_letters) = setup(&ctx, vec![target]).await?;
+
+    let root = CreateCommitContext::new_root(&ctx, &repo)
+        .set_author("alice")
+        .commit()
+        .await?;
+    let child = CreateCommitContext::new(&ctx, &repo, vec![root])
+        .set_author("bob")
+        .commit()
+        .await?;
+    bookmark(&ctx, &repo, "master").set_to(root).await?;
+    bookmark(&ctx, &repo, "master").set_to(child).await?;
+
+    assert_eq!(dispatcher.once(&ctx).await?, 2);
+    assert_eq!(dispatcher.once(&ctx).await?, 0);
+    assert_eq!(dispatcher.get_position(&ctx).await?, Some(2));
+
+    let received = stand_in.received();
+    assert_eq!(received.len(), 2);
+    let (signature, body) = &received[1];
+    assert_eq!(
+        signature.as_deref(),
+        Some(sign_payload(b"s3cret", body).as_str())
+    );
+
+    let payload: WebhookPayload = serde_json::from_slice(body)?;
+    assert_eq!(payload.log_id, 2);
+    assert_eq!(payload.bookmark, "master");
+    assert_eq!(payload.old_commit, Some(root.to_string()));
+    assert_eq!(payload.new_commit, Some(child.to_string()));
+    assert_eq!(payload.author.as_deref(), Some("bob"));
+    assert_eq!(payload.reason, "testmove");
+
+    assert!(dead_letters.list(&ctx, repo.get_repoid()).await?.is_empty());
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn test_retry_then_dead_letter(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    // First entry: two transient failures, then success. Second entry: rejected outright.
+    let stand_in = StandIn::start(vec![
+        StatusCode::SERVICE_UNAVAILABLE,
+        StatusCode::TOO_MANY_REQUESTS,
+        StatusCode::OK,
+        StatusCode::BAD_REQUEST,
+    ]);
+    let (repo, dispatcher, dead_letters) =
+        setup(&ctx, vec![WebhookTarget::new(stand_in.url())]).await?;
+
+    let cs_id = CreateCommitContext::new_root(&ctx, &repo).commit().await?;
+    bookmark(&ctx, &repo, "master").set_to(cs_id).await?;
+    bookmark(&ctx, &repo, "release").set_to(cs_id).await?;
+
+    assert_eq!(dispatcher.once(&ctx).await?, 2);
+    assert_eq!(stand_in.received().len(), 4);
+    assert_eq!(dispatcher.get_position(&ctx).await?, Some(2));
+
+    let letters = dead_letters.list(&ctx, repo.get_repoid()).await?;
+    assert_eq!(letters.len(), 1);
+    assert_eq!(letters[0].bookmark_update_log_id, 2);
+    assert_eq!(letters[0].attempts, 1);
+    let payload: WebhookPayload = serde_json::from_str(&letters[0].payload)?;
+    assert_eq!(payload.bookmark, "release");
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn test_bookmark_filter(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let stand_in = StandIn::start(vec![]);
+    let target = WebhookTarget::new(stand_in.url()).with_bookmark_regex(Regex::new("^release/")?);
+    let (repo, dispatcher, _) = setup(&ctx, vec![target]).await?;
+
+    let cs_id = CreateCommitContext::new_root(&ctx, &repo).commit().await?;
+    bookmark(&ctx, &repo, "master").set_to(cs_id).await?;
+    bookmark(&ctx, &repo, "release/1.0").set_to(cs_id).await?;
+
+    assert_eq!(dispatcher.once(&ctx).await?, 2);
+    let received = stand_in.received();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].0, None);
+    let payload: WebhookPayload = serde_json::from_slice(&received[0].1)?;
+    assert_eq!(payload.bookmark, "release/1.0");
+    Ok(())
+}
@@ -16,10 +16,9 @@ use clap::{App, Arg, ArgMatches, SubCommand};
 use cmdlib::args::{self, MononokeMatches};
 use context::CoreContext;
 use fbinit::FacebookInit;
-use fbthrift::compact_protocol;
 use futures::{future::try_join, stream, StreamExt, TryStreamExt};
 use mononoke_types::{BlobstoreBytes, ChangesetId, Generation};
-use skiplist::{deserialize_skiplist_index, sparse, SkiplistIndex, SkiplistNodeType};
+use skiplist::{deserialize_skiplist_index, serialize_skiplist_index, sparse, SkiplistIndex};
 use slog::{debug, info, Logger};
 use std::collections::HashMap;
 use std::num::NonZeroU64;
@@ -217,20 +216,7 @@ async fn build_skiplist_index<'a, S: ToString>(
 
     info!(logger, "build {} skiplist nodes", updated_skiplist.len());
 
-    // We store only latest skip entry (i.e. entry with the longest jump)
-    // This saves us storage space
-    let mut thrift_merge_graph = HashMap::new();
-    for (cs_id, skiplist_node_type) in updated_skiplist {
-        let skiplist_node_type = if let SkiplistNodeType::SkipEdges(skip_edges) = skiplist_node_type
-        {
-            SkiplistNodeType::SkipEdges(skip_edges.last().cloned().into_iter().collect())
-        } else {
-            skiplist_node_type
-        };
-
-        thrift_merge_graph.insert(cs_id.into_thrift(), skiplist_node_type.to_thrift());
-    }
-    let bytes = compact_protocol::serialize(&thrift_merge_graph);
+    let bytes = serialize_skiplist_index(&updated_skiplist);
 
     debug!(logger, "storing {} bytes", bytes.len());
     blobstore
@@ -12,7 +12,7 @@ use std::sync::Arc;
 use anyhow::{Context, Error};
 use clap::Arg;
 use futures::compat::Future01CompatExt;
-use slog::info;
+use slog::{info, warn};
 
 use blobstore_factory::{make_metadata_sql_factory, ReadOnlyStorage};
 use cmdlib::{
@@ -136,8 +136,30 @@ async fn run<'a>(ctx: CoreContext, matches: &'a MononokeMatches<'a>) -> Result<(
         .with_context(|| format!("resolving head csid for '{}'", head_arg))?;
     info!(ctx.logger(), "using '{}' for head", head);
 
+    let mut heads = vec![head];
+    for bookmark_name in config.segmented_changelog_config.tracked_bookmarks.iter() {
+        let cs_id = repo
+            .bookmarks()
+            .get(ctx.clone(), bookmark_name)
+            .await
+            .with_context(|| format!("resolving tracked bookmark '{}'", bookmark_name))?;
+        match cs_id {
+            Some(cs_id) => {
+                info!(
+                    ctx.logger(),
+                    "using '{}' for tracked bookmark '{}'", cs_id, bookmark_name
+                );
+                heads.push(cs_id);
+            }
+            None => warn!(
+                ctx.logger(),
+                "tracked bookmark '{}' could not be found, skipping it", bookmark_name
+            ),
+        }
+    }
+
     segmented_changelog_seeder
-        .run(&ctx, head)
+        .run(&ctx, heads)
         .await
         .context("seeding segmented changelog")?;
 
@@ -163,6 +163,7 @@ async fn run<'a>(ctx: CoreContext, matches: &'a MononokeMatches<'a>) -> Result<(
             .with_blobrepo(&blobrepo)
             .with_replica_lag_monitor(replica_lag_monitor)
             .with_bookmark_name(track_bookmark.clone())
+            .with_tracked_bookmarks(config.segmented_changelog_config.tracked_bookmarks.clone())
             .build_tailer()
             .with_context(|| format!("repo {}: building SegmentedChangelogTailer", repo_id))?;
 
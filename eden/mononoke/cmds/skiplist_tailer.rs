@@ -0,0 +1,162 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![deny(warnings)]
+
+use std::num::NonZeroU64;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{format_err, Context, Error};
+use clap::Arg;
+use futures::future::join_all;
+use slog::{error, info};
+
+use cmdlib::{
+    args::{self, MononokeMatches},
+    helpers,
+};
+use context::CoreContext;
+use fbinit::FacebookInit;
+use skiplist::tailer::SkiplistTailer;
+
+const DELAY_ARG: &str = "delay";
+const EXPONENT_ARG: &str = "exponent";
+const ONCE_ARG: &str = "once";
+const PERSIST_INTERVAL_ARG: &str = "persist-interval";
+const REPO_ARG: &str = "repo";
+
+#[fbinit::main]
+fn main(fb: FacebookInit) -> Result<(), Error> {
+    let app = args::MononokeAppBuilder::new("Updates skiplist indexes.")
+        .with_advanced_args_hidden()
+        .with_fb303_args()
+        .build()
+        .about("Incrementally updates the sparse skiplist from the bookmark update log.")
+        .arg(
+            Arg::with_name(REPO_ARG)
+                .long(REPO_ARG)
+                .takes_value(true)
+                .required(true)
+                .multiple(true)
+                .help("Repository name to update the skiplist of"),
+        )
+        .arg(
+            Arg::with_name(DELAY_ARG)
+                .long(DELAY_ARG)
+                .takes_value(true)
+                .required(false)
+                .help("Delay period in seconds between reads of the bookmark update log."),
+        )
+        .arg(
+            Arg::with_name(PERSIST_INTERVAL_ARG)
+                .long(PERSIST_INTERVAL_ARG)
+                .takes_value(true)
+                .required(false)
+                .help("Minimum period in seconds between publishing new skiplists."),
+        )
+        .arg(
+            Arg::with_name(EXPONENT_ARG)
+                .long(EXPONENT_ARG)
+                .takes_value(true)
+                .required(false)
+                .help("Skiplist will skip up to 2^EXPONENT commits"),
+        )
+        .arg(
+            Arg::with_name(ONCE_ARG)
+                .long(ONCE_ARG)
+                .takes_value(false)
+                .required(false)
+                .help("When set, the tailer will perform a single update and exit."),
+        );
+    let matches = app.get_matches();
+
+    let logger = args::init_logging(fb, &matches)?;
+    args::init_cachelib(fb, &matches);
+    let ctx = CoreContext::new_with_logger(fb, logger.clone());
+    helpers::block_execute(
+        run(ctx, &matches),
+        fb,
+        &std::env::var("TW_JOB_NAME").unwrap_or_else(|_| "skiplist_tailer".to_string()),
+        &logger,
+        &matches,
+        cmdlib::monitoring::AliveService,
+    )
+}
+
+async fn run<'a>(ctx: CoreContext, matches: &'a MononokeMatches<'a>) -> Result<(), Error> {
+    let reponames: Vec<_> = matches
+        .values_of(REPO_ARG)
+        .ok_or_else(|| format_err!("--{} argument is required", REPO_ARG))?
+        .map(ToString::to_string)
+        .collect();
+    if reponames.is_empty() {
+        error!(ctx.logger(), "At least one repo had to be specified");
+        return Ok(());
+    }
+
+    let config_store = args::init_config_store(ctx.fb, ctx.logger(), matches)?;
+    let configs = args::load_repo_configs(config_store, matches)?;
+
+    // skiplist will jump up to 2^9 changesets by default
+    let exponent = args::get_u64(matches, EXPONENT_ARG, 9);
+    let max_skip = NonZeroU64::new(2u64.pow(exponent as u32))
+        .ok_or_else(|| format_err!("invalid skiplist exponent"))?;
+
+    let mut tasks = Vec::new();
+    let repo_count = reponames.len() as u32;
+    for (index, reponame) in reponames.into_iter().enumerate() {
+        let config = configs
+            .repos
+            .get(&reponame)
+            .ok_or_else(|| format_err!("unknown repository: {}", reponame))?;
+        let repo_id = config.repoid;
+        info!(
+            ctx.logger(),
+            "repo name '{}' translates to id {}", reponame, repo_id
+        );
+        let key = config
+            .skiplist_index_blobstore_key
+            .clone()
+            .ok_or_else(|| format_err!("repo {}: no skiplist blobstore key configured", repo_id))?;
+
+        let blobrepo = args::open_repo_with_repo_id(ctx.fb, ctx.logger(), repo_id, matches).await?;
+        let skiplist_tailer = SkiplistTailer::new(
+            repo_id,
+            Arc::new(blobrepo.get_blobstore()),
+            blobrepo.get_changeset_fetcher(),
+            blobrepo.bookmarks(),
+            blobrepo.bookmarks_log(),
+            key,
+            max_skip,
+        );
+        info!(ctx.logger(), "repo {}: SkiplistTailer initialized", repo_id);
+
+        if matches.is_present(ONCE_ARG) {
+            skiplist_tailer
+                .once(&ctx)
+                .await
+                .with_context(|| format!("repo {}: updating skiplist", repo_id))?;
+            info!(ctx.logger(), "repo {}: SkiplistTailer is done", repo_id);
+        } else {
+            let delay = Duration::from_secs(args::get_u64(matches, DELAY_ARG, 10));
+            let persist_interval =
+                Duration::from_secs(args::get_u64(matches, PERSIST_INTERVAL_ARG, 600));
+            // spread out repo operations
+            let offset_delay = delay / repo_count;
+            let ctx = ctx.clone();
+            tasks.push(async move {
+                tokio::time::delay_for(offset_delay * index as u32).await;
+                skiplist_tailer.run(&ctx, delay, persist_interval).await;
+            });
+        }
+    }
+
+    join_all(tasks).await;
+
+    Ok(())
+}
@@ -10,6 +10,7 @@ use std::fmt::Display;
 use anyhow::Error;
 use thiserror::Error;
 
+use edenapi_types::{AnyId, FileContentId};
 use gotham_ext::error::HttpError;
 use mononoke_api::MononokeError;
 use types::{HgId, Key};
@@ -52,6 +53,10 @@ pub enum ErrorKind {
     DeserializationFailed,
     #[error("Failed to fetch file for key: {0:?}")]
     FileFetchFailed(Key),
+    #[error("Failed to fetch file content: {0:?}")]
+    FileContentFetchFailed(FileContentId),
+    #[error("File content does not exist: {0:?}")]
+    FileContentDoesNotExist(FileContentId),
     #[error("Failed to fetch tree for key: {0:?}")]
     TreeFetchFailed(Key),
     #[error("Failed to fetch history for key: {0:?}")]
@@ -62,8 +67,34 @@ pub enum ErrorKind {
     CommitLocationToHashRequestFailed,
     #[error("Commit data request failed")]
     CommitRevlogDataRequestFailed,
+    #[error("Commit graph request failed")]
+    CommitGraphRequestFailed,
+    #[error("Commit graph request too large: {0}")]
+    CommitGraphRequestTooLarge(String),
+    #[error("Commit ancestry request failed")]
+    CommitIsAncestorRequestFailed,
     #[error("HgId not found: {0}")]
     HgIdNotFound(HgId),
+    #[error("Failed to resolve bookmark: {0}")]
+    BookmarkResolutionFailed(String),
+    #[error("Failed to list bookmarks with prefix: {0}")]
+    BookmarkListingFailed(String),
+    #[error("Not permitted to write to repository: {0}")]
+    WritePermissionDenied(String),
+    #[error("Failed to look up object: {0:?}")]
+    LookupFailed(AnyId),
+    #[error("Failed to upload file content")]
+    FileContentUploadFailed,
+    #[error("Failed to upload filenode for key: {0:?}")]
+    FilenodeUploadFailed(Key),
+    #[error("Failed to upload tree for key: {0:?}")]
+    TreeUploadFailed(Key),
+    #[error("Invalid changeset data for: {0}")]
+    InvalidChangeset(HgId),
+    #[error("Failed to upload changesets")]
+    ChangesetsUploadFailed,
+    #[error("Failed to land stack onto bookmark: {0}")]
+    LandStackFailed(String),
 }
 
 /// Extension trait for converting `MononokeError`s into `HttpErrors`.
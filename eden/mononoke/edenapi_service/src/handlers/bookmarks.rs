@@ -0,0 +1,97 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{Context, Error};
+use futures::{stream, Stream, StreamExt, TryStreamExt};
+use gotham::state::{FromState, State};
+use gotham_derive::{StateData, StaticResponseExtender};
+use serde::Deserialize;
+
+use edenapi_types::{
+    wire::{ToWire, WireBookmarkRequest},
+    BookmarkEntry, BookmarkRequest,
+};
+use gotham_ext::{error::HttpError, response::TryIntoResponse};
+use mononoke_api_hg::HgRepoContext;
+
+use crate::context::ServerContext;
+use crate::errors::ErrorKind;
+use crate::middleware::RequestContext;
+use crate::utils::{cbor_stream, get_repo, parse_wire_request};
+
+use super::{EdenApiMethod, HandlerInfo};
+
+/// XXX: This number was chosen arbitrarily.
+const MAX_CONCURRENT_LOOKUPS_PER_REQUEST: usize = 100;
+
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct BookmarkParams {
+    repo: String,
+}
+
+/// Resolve the bookmarks requested by the client, either by name or by prefix.
+pub async fn bookmarks(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
+    let params = BookmarkParams::take_from(state);
+
+    state.put(HandlerInfo::new(&params.repo, EdenApiMethod::Bookmarks));
+
+    let rctx = RequestContext::borrow_from(state).clone();
+    let sctx = ServerContext::borrow_from(state);
+
+    let repo = get_repo(&sctx, &rctx, &params.repo, None).await?;
+    let request = parse_wire_request::<WireBookmarkRequest>(state).await?;
+
+    Ok(cbor_stream(
+        rctx,
+        resolve_all_bookmarks(repo, request).map(|r| r.map(|v| v.to_wire())),
+    ))
+}
+
+/// Look up all of the named bookmarks concurrently, followed by the
+/// contents of each requested prefix.
+fn resolve_all_bookmarks(
+    repo: HgRepoContext,
+    request: BookmarkRequest,
+) -> impl Stream<Item = Result<BookmarkEntry, Error>> {
+    let lookups = request.bookmarks.into_iter().map({
+        let repo = repo.clone();
+        move |bookmark| resolve_bookmark(repo.clone(), bookmark)
+    });
+    let listings = request
+        .prefixes
+        .into_iter()
+        .map(move |prefix| list_bookmarks(repo.clone(), prefix));
+
+    stream::iter(lookups)
+        .buffered(MAX_CONCURRENT_LOOKUPS_PER_REQUEST)
+        .chain(
+            stream::iter(listings)
+                .buffered(MAX_CONCURRENT_LOOKUPS_PER_REQUEST)
+                .map_ok(|entries| stream::iter(entries.into_iter().map(Ok)))
+                .try_flatten(),
+        )
+}
+
+async fn resolve_bookmark(repo: HgRepoContext, bookmark: String) -> Result<BookmarkEntry, Error> {
+    let hg_cs_id = repo
+        .resolve_bookmark(&bookmark)
+        .await
+        .with_context(|| ErrorKind::BookmarkResolutionFailed(bookmark.clone()))?;
+    let hgid = hg_cs_id.map(|id| id.into_nodehash().into());
+    Ok(BookmarkEntry::new(bookmark, hgid))
+}
+
+async fn list_bookmarks(repo: HgRepoContext, prefix: String) -> Result<Vec<BookmarkEntry>, Error> {
+    let bookmarks = repo
+        .bookmarks_with_prefix(&prefix)
+        .await
+        .with_context(|| ErrorKind::BookmarkListingFailed(prefix.clone()))?;
+    Ok(bookmarks
+        .into_iter()
+        .map(|(name, hg_cs_id)| BookmarkEntry::new(name, Some(hg_cs_id.into_nodehash().into())))
+        .collect())
+}
@@ -12,6 +12,7 @@ use gotham_derive::{StateData, StaticResponseExtender};
 use serde::Deserialize;
 
 use edenapi_types::{
+    CommitGraphEntry, CommitGraphRequest, CommitIsAncestor, CommitIsAncestorRequest,
     CommitLocation, CommitLocationToHash, CommitLocationToHashRequest, CommitRevlogData,
     CommitRevlogDataRequest,
 };
@@ -21,7 +22,7 @@ use mononoke_api_hg::HgRepoContext;
 use types::HgId;
 
 use crate::context::ServerContext;
-use crate::errors::ErrorKind;
+use crate::errors::{ErrorKind, MononokeErrorExt};
 use crate::middleware::RequestContext;
 use crate::utils::{cbor_stream, get_repo, parse_cbor_request};
 
@@ -29,6 +30,10 @@ use super::{EdenApiMethod, HandlerInfo};
 
 /// XXX: This number was chosen arbitrarily.
 const MAX_CONCURRENT_FETCHES_PER_REQUEST: usize = 100;
+/// XXX: These numbers were chosen arbitrarily. The segmented changelog enforces its own limits
+/// on the depth and size of a graph slice.
+const MAX_GRAPH_HEADS_PER_REQUEST: usize = 1_000;
+const MAX_GRAPH_DEPTH_PER_REQUEST: u64 = 1_000;
 
 #[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
 pub struct LocationToHashParams {
@@ -40,6 +45,16 @@ pub struct RevlogDataParams {
     repo: String,
 }
 
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct GraphParams {
+    repo: String,
+}
+
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct IsAncestorParams {
+    repo: String,
+}
+
 pub async fn location_to_hash(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
     let params = LocationToHashParams::take_from(state);
 
@@ -85,6 +100,78 @@ pub async fn revlog_data(state: &mut State) -> Result<impl TryIntoResponse, Http
     Ok(cbor_stream(rctx, response))
 }
 
+pub async fn graph(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
+    let params = GraphParams::take_from(state);
+
+    state.put(HandlerInfo::new(&params.repo, EdenApiMethod::CommitGraph));
+
+    let sctx = ServerContext::borrow_from(state);
+    let rctx = RequestContext::borrow_from(state).clone();
+
+    let hg_repo_ctx = get_repo(&sctx, &rctx, &params.repo, None).await?;
+
+    let request: CommitGraphRequest = parse_cbor_request(state).await?;
+    if request.hgids.len() > MAX_GRAPH_HEADS_PER_REQUEST {
+        return Err(HttpError::e400(ErrorKind::CommitGraphRequestTooLarge(
+            format!(
+                "{} heads requested, at most {} are allowed",
+                request.hgids.len(),
+                MAX_GRAPH_HEADS_PER_REQUEST
+            ),
+        )));
+    }
+    if request.depth > MAX_GRAPH_DEPTH_PER_REQUEST {
+        return Err(HttpError::e400(ErrorKind::CommitGraphRequestTooLarge(
+            format!(
+                "depth {} requested, at most {} is allowed",
+                request.depth, MAX_GRAPH_DEPTH_PER_REQUEST
+            ),
+        )));
+    }
+    let heads = request
+        .hgids
+        .into_iter()
+        .map(|hg_id| HgChangesetId::new(HgNodeHash::from(hg_id)))
+        .collect();
+    let slice = hg_repo_ctx
+        .commit_graph(heads, request.depth)
+        .await
+        .map_err(|e| e.into_http_error(ErrorKind::CommitGraphRequestFailed))?;
+    let entries = slice.into_iter().map(|(hg_cs_id, parents)| {
+        let parents = parents
+            .into_iter()
+            .map(|p| p.into_nodehash().into())
+            .collect();
+        let hgid = hg_cs_id.into_nodehash().into();
+        Ok::<_, Error>(CommitGraphEntry::new(hgid, parents))
+    });
+    Ok(cbor_stream(rctx, stream::iter(entries)))
+}
+
+pub async fn is_ancestor(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
+    let params = IsAncestorParams::take_from(state);
+
+    state.put(HandlerInfo::new(
+        &params.repo,
+        EdenApiMethod::CommitIsAncestor,
+    ));
+
+    let sctx = ServerContext::borrow_from(state);
+    let rctx = RequestContext::borrow_from(state).clone();
+
+    let hg_repo_ctx = get_repo(&sctx, &rctx, &params.repo, None).await?;
+
+    let request: CommitIsAncestorRequest = parse_cbor_request(state).await?;
+    let answers = request
+        .pairs
+        .into_iter()
+        .map(move |(ancestor, descendant)| {
+            check_is_ancestor(hg_repo_ctx.clone(), ancestor, descendant)
+        });
+    let response = stream::iter(answers).buffer_unordered(MAX_CONCURRENT_FETCHES_PER_REQUEST);
+    Ok(cbor_stream(rctx, response))
+}
+
 async fn translate_location(
     hg_repo_ctx: HgRepoContext,
     location: CommitLocation,
@@ -118,3 +205,18 @@ async fn commit_revlog_data(
     let answer = CommitRevlogData::new(hg_id, bytes);
     Ok(answer)
 }
+
+async fn check_is_ancestor(
+    hg_repo_ctx: HgRepoContext,
+    ancestor: HgId,
+    descendant: HgId,
+) -> Result<CommitIsAncestor, Error> {
+    let is_ancestor = hg_repo_ctx
+        .is_ancestor(
+            HgChangesetId::new(HgNodeHash::from(ancestor)),
+            HgChangesetId::new(HgNodeHash::from(descendant)),
+        )
+        .await
+        .with_context(|| ErrorKind::CommitIsAncestorRequestFailed)?;
+    Ok(CommitIsAncestor::new(ancestor, descendant, is_ancestor))
+}
@@ -15,7 +15,7 @@ use edenapi_types::{
     wire::{ToWire, WireCompleteTreeRequest},
     CompleteTreeRequest, EdenApiServerError, TreeEntry,
 };
-use gotham_ext::{error::HttpError, response::TryIntoResponse};
+use gotham_ext::{content::ContentEncoding, error::HttpError, response::TryIntoResponse};
 use load_limiter::Metric;
 use mercurial_types::{HgManifestId, HgNodeHash};
 use mononoke_api::path::MononokePath;
@@ -25,7 +25,9 @@ use types::Key;
 use crate::context::ServerContext;
 use crate::errors::ErrorKind;
 use crate::middleware::RequestContext;
-use crate::utils::{cbor_stream, get_repo, parse_wire_request, to_hg_path, to_mononoke_path};
+use crate::utils::{
+    compressed_cbor_stream, get_repo, parse_wire_request, to_hg_path, to_mononoke_path,
+};
 
 use super::{EdenApiMethod, HandlerInfo};
 
@@ -40,13 +42,15 @@ pub async fn complete_trees(state: &mut State) -> Result<impl TryIntoResponse, H
     state.put(HandlerInfo::new(&params.repo, EdenApiMethod::CompleteTrees));
 
     let rctx = RequestContext::borrow_from(state).clone();
+    let encoding = ContentEncoding::from_state(state);
     let sctx = ServerContext::borrow_from(state);
 
     let repo = get_repo(&sctx, &rctx, &params.repo, Metric::EgressTotalManifests).await?;
     let request = parse_wire_request::<WireCompleteTreeRequest>(state).await?;
 
-    Ok(cbor_stream(
+    Ok(compressed_cbor_stream(
         rctx,
+        encoding,
         fetch_trees_under_path(&repo, request)?.map(|r| Ok(r.to_wire())),
     ))
 }
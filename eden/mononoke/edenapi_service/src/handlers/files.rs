@@ -13,9 +13,10 @@ use serde::Deserialize;
 
 use edenapi_types::{
     wire::{ToWire, WireFileRequest},
-    FileEntry, FileRequest,
+    FileContentEntry, FileContentId, FileContentRequest, FileEntry, FileRequest,
 };
-use gotham_ext::{error::HttpError, response::TryIntoResponse};
+use filestore::{Alias, FetchKey};
+use gotham_ext::{content::ContentEncoding, error::HttpError, response::TryIntoResponse};
 use load_limiter::Metric;
 use mercurial_types::{HgFileNodeId, HgNodeHash};
 use mononoke_api_hg::{HgDataContext, HgDataId, HgRepoContext};
@@ -24,7 +25,7 @@ use types::Key;
 use crate::context::ServerContext;
 use crate::errors::ErrorKind;
 use crate::middleware::RequestContext;
-use crate::utils::{cbor_stream, get_repo, parse_wire_request};
+use crate::utils::{compressed_cbor_stream, get_repo, parse_cbor_request, parse_wire_request};
 
 use super::{EdenApiMethod, HandlerInfo};
 
@@ -43,17 +44,44 @@ pub async fn files(state: &mut State) -> Result<impl TryIntoResponse, HttpError>
     state.put(HandlerInfo::new(&params.repo, EdenApiMethod::Files));
 
     let rctx = RequestContext::borrow_from(state).clone();
+    let encoding = ContentEncoding::from_state(state);
     let sctx = ServerContext::borrow_from(state);
 
     let repo = get_repo(&sctx, &rctx, &params.repo, Metric::EgressGetpackFiles).await?;
     let request = parse_wire_request::<WireFileRequest>(state).await?;
 
-    Ok(cbor_stream(
+    Ok(compressed_cbor_stream(
         rctx,
+        encoding,
         fetch_all_files(repo, request).map(|r| r.map(|v| v.to_wire())),
     ))
 }
 
+/// Fetch file content by content hash. Since content is addressed by hash
+/// rather than by filenode, files that share the same content are only
+/// sent once, regardless of their path or history.
+pub async fn files_by_content(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
+    let params = FileParams::take_from(state);
+
+    state.put(HandlerInfo::new(
+        &params.repo,
+        EdenApiMethod::FilesByContent,
+    ));
+
+    let rctx = RequestContext::borrow_from(state).clone();
+    let encoding = ContentEncoding::from_state(state);
+    let sctx = ServerContext::borrow_from(state);
+
+    let repo = get_repo(&sctx, &rctx, &params.repo, Metric::EgressGetpackFiles).await?;
+    let request: FileContentRequest = parse_cbor_request(state).await?;
+
+    Ok(compressed_cbor_stream(
+        rctx,
+        encoding,
+        fetch_all_file_contents(repo, request),
+    ))
+}
+
 /// Fetch files for all of the requested keys concurrently.
 fn fetch_all_files(
     repo: HgRepoContext,
@@ -93,3 +121,43 @@ async fn fetch_file(repo: HgRepoContext, key: Key) -> Result<FileEntry, Error> {
 
     Ok(FileEntry::new(key, data, parents, metadata))
 }
+
+/// Fetch file content for all of the requested content ids concurrently.
+fn fetch_all_file_contents(
+    repo: HgRepoContext,
+    request: FileContentRequest,
+) -> impl Stream<Item = Result<FileContentEntry, Error>> {
+    let ctx = repo.ctx().clone();
+
+    let fetches = request
+        .ids
+        .into_iter()
+        .map(move |id| fetch_file_content(repo.clone(), id));
+
+    stream::iter(fetches)
+        .buffer_unordered(MAX_CONCURRENT_FILE_FETCHES_PER_REQUEST)
+        .inspect_ok(move |_| {
+            ctx.session().bump_load(Metric::EgressGetpackFiles, 1.0);
+        })
+}
+
+/// Fetch file content for a single content id, resolving hash aliases
+/// through the filestore.
+async fn fetch_file_content(
+    repo: HgRepoContext,
+    id: FileContentId,
+) -> Result<FileContentEntry, Error> {
+    let key = match id {
+        FileContentId::ContentId(content_id) => FetchKey::Canonical(content_id.into()),
+        FileContentId::Sha1(sha1) => FetchKey::Aliased(Alias::Sha1(sha1.into())),
+        FileContentId::Sha256(sha256) => FetchKey::Aliased(Alias::Sha256(sha256.into())),
+    };
+
+    let data = repo
+        .file_content(key)
+        .await
+        .with_context(|| ErrorKind::FileContentFetchFailed(id))?
+        .with_context(|| ErrorKind::FileContentDoesNotExist(id))?;
+
+    Ok(FileContentEntry::new(id, data))
+}
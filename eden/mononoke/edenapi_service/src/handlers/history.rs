@@ -20,7 +20,7 @@ use cloned::cloned;
 use edenapi_types::{
     wire::WireHistoryRequest, HistoryRequest, HistoryResponseChunk, ToWire, WireHistoryEntry,
 };
-use gotham_ext::{error::HttpError, response::TryIntoResponse};
+use gotham_ext::{content::ContentEncoding, error::HttpError, response::TryIntoResponse};
 use mercurial_types::{HgFileNodeId, HgNodeHash};
 use mononoke_api_hg::HgRepoContext;
 use types::Key;
@@ -28,7 +28,7 @@ use types::Key;
 use crate::context::ServerContext;
 use crate::errors::ErrorKind;
 use crate::middleware::RequestContext;
-use crate::utils::{cbor_stream, get_repo, parse_wire_request, to_mpath};
+use crate::utils::{compressed_cbor_stream, get_repo, parse_wire_request, to_mpath};
 
 use super::{EdenApiMethod, HandlerInfo};
 
@@ -48,13 +48,15 @@ pub async fn history(state: &mut State) -> Result<impl TryIntoResponse, HttpErro
     state.put(HandlerInfo::new(&params.repo, EdenApiMethod::History));
 
     let rctx = RequestContext::borrow_from(state).clone();
+    let encoding = ContentEncoding::from_state(state);
     let sctx = ServerContext::borrow_from(state);
 
     let repo = get_repo(&sctx, &rctx, &params.repo, None).await?;
     let request = parse_wire_request::<WireHistoryRequest>(state).await?;
 
-    Ok(cbor_stream(
+    Ok(compressed_cbor_stream(
         rctx,
+        encoding,
         fetch_history(repo, request)
             .await
             .map(|r| r.map(|e| e.to_wire())),
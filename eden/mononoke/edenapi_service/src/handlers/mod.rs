@@ -26,6 +26,7 @@ use gotham_ext::response::build_response;
 use crate::context::ServerContext;
 use crate::middleware::RequestContext;
 
+mod bookmarks;
 mod clone;
 mod commit;
 mod complete_trees;
@@ -33,32 +34,53 @@ mod files;
 mod history;
 mod repos;
 mod trees;
+mod upload;
 
 /// Enum identifying the EdenAPI method that each handler corresponds to.
 /// Used to identify the handler for logging and stats collection.
 #[derive(Copy, Clone)]
 pub enum EdenApiMethod {
     Files,
+    FilesByContent,
     Trees,
     CompleteTrees,
     History,
     CommitLocationToHash,
     CommitRevlogData,
+    CommitGraph,
+    CommitIsAncestor,
     Clone,
     FullIdMapClone,
+    Bookmarks,
+    Lookup,
+    UploadFileContents,
+    UploadFilenodes,
+    UploadTrees,
+    UploadChangesets,
+    LandStack,
 }
 
 impl fmt::Display for EdenApiMethod {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let name = match self {
             Self::Files => "files",
+            Self::FilesByContent => "files_by_content",
             Self::Trees => "trees",
             Self::CompleteTrees => "complete_trees",
             Self::History => "history",
             Self::CommitLocationToHash => "commit_location_to_hash",
             Self::CommitRevlogData => "commit_revlog_data",
+            Self::CommitGraph => "commit_graph",
+            Self::CommitIsAncestor => "commit_is_ancestor",
             Self::Clone => "clone",
             Self::FullIdMapClone => "full_idmap_clone",
+            Self::Bookmarks => "bookmarks",
+            Self::Lookup => "lookup",
+            Self::UploadFileContents => "upload_file_contents",
+            Self::UploadFilenodes => "upload_filenodes",
+            Self::UploadTrees => "upload_trees",
+            Self::UploadChangesets => "upload_changesets",
+            Self::LandStack => "land_stack",
         };
         write!(f, "{}", name)
     }
@@ -115,13 +137,23 @@ macro_rules! define_handler {
 
 define_handler!(repos_handler, repos::repos);
 define_handler!(files_handler, files::files);
+define_handler!(files_by_content_handler, files::files_by_content);
 define_handler!(trees_handler, trees::trees);
 define_handler!(complete_trees_handler, complete_trees::complete_trees);
 define_handler!(history_handler, history::history);
 define_handler!(commit_location_to_hash_handler, commit::location_to_hash);
 define_handler!(commit_revlog_data_handler, commit::revlog_data);
+define_handler!(commit_graph_handler, commit::graph);
+define_handler!(commit_is_ancestor_handler, commit::is_ancestor);
 define_handler!(clone_handler, clone::clone_data);
 define_handler!(full_idmap_clone_handler, clone::full_idmap_clone_data);
+define_handler!(bookmarks_handler, bookmarks::bookmarks);
+define_handler!(lookup_handler, upload::lookup);
+define_handler!(upload_file_contents_handler, upload::upload_file_contents);
+define_handler!(upload_filenodes_handler, upload::upload_filenodes);
+define_handler!(upload_trees_handler, upload::upload_trees);
+define_handler!(upload_changesets_handler, upload::upload_changesets);
+define_handler!(land_stack_handler, upload::land_stack);
 
 fn health_handler(state: State) -> (State, &'static str) {
     if ServerContext::borrow_from(&state).will_exit() {
@@ -142,6 +174,10 @@ pub fn build_router(ctx: ServerContext) -> Router {
             .post("/:repo/files")
             .with_path_extractor::<files::FileParams>()
             .to(files_handler);
+        route
+            .post("/:repo/files/content")
+            .with_path_extractor::<files::FileParams>()
+            .to(files_by_content_handler);
         route
             .post("/:repo/trees")
             .with_path_extractor::<trees::TreeParams>()
@@ -162,6 +198,14 @@ pub fn build_router(ctx: ServerContext) -> Router {
             .post("/:repo/commit/revlog_data")
             .with_path_extractor::<commit::RevlogDataParams>()
             .to(commit_revlog_data_handler);
+        route
+            .post("/:repo/commit/graph")
+            .with_path_extractor::<commit::GraphParams>()
+            .to(commit_graph_handler);
+        route
+            .post("/:repo/commit/is_ancestor")
+            .with_path_extractor::<commit::IsAncestorParams>()
+            .to(commit_is_ancestor_handler);
         route
             .post("/:repo/clone")
             .with_path_extractor::<clone::CloneParams>()
@@ -170,5 +214,33 @@ pub fn build_router(ctx: ServerContext) -> Router {
             .post("/:repo/full_idmap_clone")
             .with_path_extractor::<clone::CloneParams>()
             .to(full_idmap_clone_handler);
+        route
+            .post("/:repo/bookmarks")
+            .with_path_extractor::<bookmarks::BookmarkParams>()
+            .to(bookmarks_handler);
+        route
+            .post("/:repo/lookup")
+            .with_path_extractor::<upload::UploadParams>()
+            .to(lookup_handler);
+        route
+            .post("/:repo/upload/file_contents")
+            .with_path_extractor::<upload::UploadParams>()
+            .to(upload_file_contents_handler);
+        route
+            .post("/:repo/upload/filenodes")
+            .with_path_extractor::<upload::UploadParams>()
+            .to(upload_filenodes_handler);
+        route
+            .post("/:repo/upload/trees")
+            .with_path_extractor::<upload::UploadParams>()
+            .to(upload_trees_handler);
+        route
+            .post("/:repo/upload/changesets")
+            .with_path_extractor::<upload::UploadParams>()
+            .to(upload_changesets_handler);
+        route
+            .post("/:repo/land_stack")
+            .with_path_extractor::<upload::UploadParams>()
+            .to(land_stack_handler);
     })
 }
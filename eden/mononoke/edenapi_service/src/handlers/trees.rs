@@ -15,7 +15,7 @@ use edenapi_types::{
     wire::{ToWire, WireTreeRequest},
     EdenApiServerError, FileMetadata, TreeChildEntry, TreeEntry, TreeRequest,
 };
-use gotham_ext::{error::HttpError, response::TryIntoResponse};
+use gotham_ext::{content::ContentEncoding, error::HttpError, response::TryIntoResponse};
 use load_limiter::Metric;
 use manifest::Entry;
 use mercurial_types::{FileType, HgFileNodeId, HgManifestId, HgNodeHash};
@@ -25,7 +25,7 @@ use types::{Key, RepoPathBuf};
 use crate::context::ServerContext;
 use crate::errors::ErrorKind;
 use crate::middleware::RequestContext;
-use crate::utils::{cbor_stream, get_repo, parse_wire_request};
+use crate::utils::{compressed_cbor_stream, get_repo, parse_wire_request};
 
 use super::{EdenApiMethod, HandlerInfo};
 
@@ -45,13 +45,15 @@ pub async fn trees(state: &mut State) -> Result<impl TryIntoResponse, HttpError>
     state.put(HandlerInfo::new(&params.repo, EdenApiMethod::Trees));
 
     let rctx = RequestContext::borrow_from(state).clone();
+    let encoding = ContentEncoding::from_state(state);
     let sctx = ServerContext::borrow_from(state);
 
     let repo = get_repo(&sctx, &rctx, &params.repo, Metric::EgressTotalManifests).await?;
     let request = parse_wire_request::<WireTreeRequest>(state).await?;
 
-    Ok(cbor_stream(
+    Ok(compressed_cbor_stream(
         rctx,
+        encoding,
         fetch_all_trees(repo, request).map(|r| Ok(r.to_wire())),
     ))
 }
@@ -0,0 +1,326 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{Context, Error};
+use bytes::Bytes;
+use futures::{stream, StreamExt, TryStreamExt};
+use gotham::state::{FromState, State};
+use gotham_derive::{StateData, StaticResponseExtender};
+use serde::Deserialize;
+
+use edenapi_types::{
+    AnyId, LandStackRequest, LandStackResponse, LookupRequest, LookupResponse,
+    UploadFileContentsRequest, UploadHgChangesetEntry, UploadHgChangesetsRequest,
+    UploadHgFilenodeEntry, UploadHgFilenodesRequest, UploadResponse, UploadTreeEntry,
+    UploadTreesRequest,
+};
+use gotham_ext::{error::HttpError, response::TryIntoResponse};
+use mercurial_types::{
+    blobs::RevlogChangeset, HgBlobNode, HgChangesetId, HgFileNodeId, HgManifestId, HgNodeHash,
+    RepoPath,
+};
+use mononoke_api_hg::HgRepoContext;
+use types::HgId;
+
+use crate::context::ServerContext;
+use crate::errors::{ErrorKind, MononokeErrorExt};
+use crate::middleware::RequestContext;
+use crate::utils::{cbor_stream, get_repo, parse_cbor_request, to_mpath};
+
+use super::{EdenApiMethod, HandlerInfo};
+
+/// XXX: This number was chosen arbitrarily.
+const MAX_CONCURRENT_UPLOADS_PER_REQUEST: usize = 100;
+
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct UploadParams {
+    repo: String,
+}
+
+/// Check which of the requested objects are already present on the server.
+pub async fn lookup(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
+    let params = UploadParams::take_from(state);
+
+    state.put(HandlerInfo::new(&params.repo, EdenApiMethod::Lookup));
+
+    let rctx = RequestContext::borrow_from(state).clone();
+    let sctx = ServerContext::borrow_from(state);
+
+    let repo = get_repo(&sctx, &rctx, &params.repo, None).await?;
+    let request: LookupRequest = parse_cbor_request(state).await?;
+
+    let lookups = request
+        .ids
+        .into_iter()
+        .map(move |id| lookup_id(repo.clone(), id));
+    let response = stream::iter(lookups).buffer_unordered(MAX_CONCURRENT_UPLOADS_PER_REQUEST);
+    Ok(cbor_stream(rctx, response))
+}
+
+/// Store file contents in the repo's filestore.
+pub async fn upload_file_contents(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
+    let params = UploadParams::take_from(state);
+
+    state.put(HandlerInfo::new(
+        &params.repo,
+        EdenApiMethod::UploadFileContents,
+    ));
+
+    let rctx = RequestContext::borrow_from(state).clone();
+    let sctx = ServerContext::borrow_from(state);
+
+    let repo = get_repo(&sctx, &rctx, &params.repo, None).await?;
+    check_write_permissions(&repo, &params.repo).await?;
+    let request: UploadFileContentsRequest = parse_cbor_request(state).await?;
+
+    let uploads = request
+        .contents
+        .into_iter()
+        .map(move |data| store_file_content(repo.clone(), data));
+    let response = stream::iter(uploads).buffer_unordered(MAX_CONCURRENT_UPLOADS_PER_REQUEST);
+    Ok(cbor_stream(rctx, response))
+}
+
+/// Store Mercurial filenodes for previously uploaded file contents.
+pub async fn upload_filenodes(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
+    let params = UploadParams::take_from(state);
+
+    state.put(HandlerInfo::new(
+        &params.repo,
+        EdenApiMethod::UploadFilenodes,
+    ));
+
+    let rctx = RequestContext::borrow_from(state).clone();
+    let sctx = ServerContext::borrow_from(state);
+
+    let repo = get_repo(&sctx, &rctx, &params.repo, None).await?;
+    check_write_permissions(&repo, &params.repo).await?;
+    let request: UploadHgFilenodesRequest = parse_cbor_request(state).await?;
+
+    let uploads = request
+        .filenodes
+        .into_iter()
+        .map(move |entry| store_filenode(repo.clone(), entry));
+    let response = stream::iter(uploads).buffer_unordered(MAX_CONCURRENT_UPLOADS_PER_REQUEST);
+    Ok(cbor_stream(rctx, response))
+}
+
+/// Store Mercurial tree manifests.
+pub async fn upload_trees(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
+    let params = UploadParams::take_from(state);
+
+    state.put(HandlerInfo::new(&params.repo, EdenApiMethod::UploadTrees));
+
+    let rctx = RequestContext::borrow_from(state).clone();
+    let sctx = ServerContext::borrow_from(state);
+
+    let repo = get_repo(&sctx, &rctx, &params.repo, None).await?;
+    check_write_permissions(&repo, &params.repo).await?;
+    let request: UploadTreesRequest = parse_cbor_request(state).await?;
+
+    let uploads = request
+        .trees
+        .into_iter()
+        .map(move |entry| store_tree(repo.clone(), entry));
+    let response = stream::iter(uploads).buffer_unordered(MAX_CONCURRENT_UPLOADS_PER_REQUEST);
+    Ok(cbor_stream(rctx, response))
+}
+
+/// Create Mercurial changesets whose trees and filenodes have already been
+/// uploaded. Unlike the other upload methods, all of the changesets in the
+/// request are created together, since each one depends on its parents.
+pub async fn upload_changesets(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
+    let params = UploadParams::take_from(state);
+
+    state.put(HandlerInfo::new(
+        &params.repo,
+        EdenApiMethod::UploadChangesets,
+    ));
+
+    let rctx = RequestContext::borrow_from(state).clone();
+    let sctx = ServerContext::borrow_from(state);
+
+    let repo = get_repo(&sctx, &rctx, &params.repo, None).await?;
+    check_write_permissions(&repo, &params.repo).await?;
+    let request: UploadHgChangesetsRequest = parse_cbor_request(state).await?;
+
+    let response = stream::once(store_changesets(repo, request.changesets))
+        .map_ok(|ids| stream::iter(ids.into_iter().map(Ok)))
+        .try_flatten();
+    Ok(cbor_stream(rctx, response))
+}
+
+/// Land a stack of uploaded commits onto a bookmark via pushrebase.
+pub async fn land_stack(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
+    let params = UploadParams::take_from(state);
+
+    state.put(HandlerInfo::new(&params.repo, EdenApiMethod::LandStack));
+
+    let rctx = RequestContext::borrow_from(state).clone();
+    let sctx = ServerContext::borrow_from(state);
+
+    let repo = get_repo(&sctx, &rctx, &params.repo, None).await?;
+    check_write_permissions(&repo, &params.repo).await?;
+    let request: LandStackRequest = parse_cbor_request(state).await?;
+
+    let response = stream::once(land(repo, request));
+    Ok(cbor_stream(rctx, response))
+}
+
+/// Reject requests from users who are not permitted to write to the repo
+/// with a 403, before reading the request body.
+async fn check_write_permissions(repo: &HgRepoContext, repo_name: &str) -> Result<(), HttpError> {
+    repo.check_write_permissions()
+        .await
+        .map_err(|e| e.into_http_error(ErrorKind::WritePermissionDenied(repo_name.to_string())))
+}
+
+async fn lookup_id(repo: HgRepoContext, id: AnyId) -> Result<LookupResponse, Error> {
+    let present = match &id {
+        AnyId::FileContentSha256(sha256) => repo.file_content_exists((*sha256).into()).await,
+        AnyId::HgFilenodeId(hgid) => {
+            repo.filenode_exists(HgFileNodeId::new(HgNodeHash::from(*hgid)))
+                .await
+        }
+        AnyId::HgTreeId(hgid) => {
+            repo.tree_exists(HgManifestId::new(HgNodeHash::from(*hgid)))
+                .await
+        }
+        AnyId::HgChangesetId(hgid) => {
+            repo.changeset_exists(HgChangesetId::new(HgNodeHash::from(*hgid)))
+                .await
+        }
+    }
+    .with_context(|| ErrorKind::LookupFailed(id.clone()))?;
+    Ok(LookupResponse { id, present })
+}
+
+async fn store_file_content(repo: HgRepoContext, data: Bytes) -> Result<UploadResponse, Error> {
+    let metadata = repo
+        .store_file_content(data)
+        .await
+        .context(ErrorKind::FileContentUploadFailed)?;
+    Ok(UploadResponse {
+        id: AnyId::FileContentSha256(metadata.sha256.into()),
+    })
+}
+
+async fn store_filenode(
+    repo: HgRepoContext,
+    entry: UploadHgFilenodeEntry,
+) -> Result<UploadResponse, Error> {
+    let UploadHgFilenodeEntry {
+        key,
+        parents,
+        content_sha256,
+        copy_from,
+    } = entry;
+
+    let to_filenode_id = |hgid: &HgId| HgFileNodeId::new(HgNodeHash::from(*hgid));
+    let copy_from = match copy_from {
+        Some(copy_from) => {
+            let path = to_mpath(&copy_from.path)?.ok_or(ErrorKind::UnexpectedEmptyPath)?;
+            Some((path, to_filenode_id(&copy_from.hgid)))
+        }
+        None => None,
+    };
+
+    repo.store_hg_filenode(
+        to_filenode_id(&key.hgid),
+        parents.p1().map(to_filenode_id),
+        parents.p2().map(to_filenode_id),
+        content_sha256.into(),
+        copy_from,
+    )
+    .await
+    .with_context(|| ErrorKind::FilenodeUploadFailed(key.clone()))?;
+
+    Ok(UploadResponse {
+        id: AnyId::HgFilenodeId(key.hgid),
+    })
+}
+
+async fn store_tree(repo: HgRepoContext, entry: UploadTreeEntry) -> Result<UploadResponse, Error> {
+    let UploadTreeEntry { key, parents, data } = entry;
+
+    let to_manifest_id = |hgid: &HgId| HgManifestId::new(HgNodeHash::from(*hgid));
+    let path = match to_mpath(&key.path)? {
+        Some(path) => RepoPath::DirectoryPath(path),
+        None => RepoPath::RootPath,
+    };
+
+    repo.store_hg_tree(
+        to_manifest_id(&key.hgid),
+        path,
+        parents.p1().map(to_manifest_id),
+        parents.p2().map(to_manifest_id),
+        data,
+    )
+    .await
+    .with_context(|| ErrorKind::TreeUploadFailed(key.clone()))?;
+
+    Ok(UploadResponse {
+        id: AnyId::HgTreeId(key.hgid),
+    })
+}
+
+async fn store_changesets(
+    repo: HgRepoContext,
+    changesets: Vec<UploadHgChangesetEntry>,
+) -> Result<Vec<UploadResponse>, Error> {
+    let changesets = changesets
+        .into_iter()
+        .map(|entry| {
+            let to_nodehash = |hgid: &HgId| HgNodeHash::from(*hgid);
+            let node = HgBlobNode::new(
+                entry.revlog_data,
+                entry.parents.p1().map(to_nodehash),
+                entry.parents.p2().map(to_nodehash),
+            );
+            let revlog_cs = RevlogChangeset::new(node)
+                .with_context(|| ErrorKind::InvalidChangeset(entry.hgid))?;
+            Ok((HgChangesetId::new(to_nodehash(&entry.hgid)), revlog_cs))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let created = repo
+        .store_hg_changesets(changesets)
+        .await
+        .context(ErrorKind::ChangesetsUploadFailed)?;
+
+    Ok(created
+        .into_iter()
+        .map(|(hg_cs_id, _)| UploadResponse {
+            id: AnyId::HgChangesetId(hg_cs_id.into_nodehash().into()),
+        })
+        .collect())
+}
+
+async fn land(repo: HgRepoContext, request: LandStackRequest) -> Result<LandStackResponse, Error> {
+    let LandStackRequest {
+        bookmark,
+        head,
+        base,
+    } = request;
+
+    let (new_head, rebased) = repo
+        .land_stack(
+            &bookmark,
+            HgChangesetId::new(HgNodeHash::from(head)),
+            HgChangesetId::new(HgNodeHash::from(base)),
+        )
+        .await
+        .with_context(|| ErrorKind::LandStackFailed(bookmark.clone()))?;
+
+    Ok(LandStackResponse {
+        new_head: new_head.into_nodehash().into(),
+        old_to_new_hgids: rebased
+            .into_iter()
+            .map(|(old, new)| (old.into_nodehash().into(), new.into_nodehash().into()))
+            .collect(),
+    })
+}
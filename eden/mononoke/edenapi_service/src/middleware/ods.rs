@@ -5,13 +5,17 @@
  * GNU General Public License version 2.
  */
 
+use std::sync::atomic::Ordering;
+
 use gotham::state::State;
 use gotham_ext::middleware::{ClientIdentity, Middleware, PostRequestCallbacks};
+use gotham_ext::response::ResponseContentMeta;
 use hyper::StatusCode;
 use hyper::{Body, Response};
 use stats::prelude::*;
 
 use crate::handlers::{EdenApiMethod, HandlerInfo};
+use crate::middleware::RequestContext;
 
 define_stats! {
     prefix = "mononoke.edenapi.request";
@@ -20,14 +24,26 @@ define_stats! {
     failure_4xx: dynamic_timeseries("{}.failure_4xx", (repo_and_method: String); Rate, Sum),
     failure_5xx: dynamic_timeseries("{}.failure_5xx", (repo_and_method: String); Rate, Sum),
     response_bytes_sent: dynamic_histogram("{}.response_bytes_sent", (repo_and_method: String); 1_500_000, 0, 150_000_000, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
+    response_bytes_uncompressed: dynamic_histogram("{}.response_bytes_uncompressed", (repo_and_method: String); 1_500_000, 0, 150_000_000, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
+    response_bytes_saved: dynamic_histogram("{}.response_bytes_saved", (repo_and_method: String); 1_500_000, 0, 150_000_000, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
     files_duration: dynamic_histogram("{}.files_ms", (repo: String); 100, 0, 5000, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
+    files_by_content_duration: dynamic_histogram("{}.files_by_content_ms", (repo: String); 100, 0, 5000, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
     trees_duration: dynamic_histogram("{}.trees_ms", (repo: String); 100, 0, 5000, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
     complete_trees_duration: dynamic_histogram("{}.complete_trees_ms", (repo: String); 100, 0, 5000, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
     history_duration: dynamic_histogram("{}.history_ms", (repo: String); 100, 0, 5000, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
     commit_location_to_hash_duration: dynamic_histogram("{}.commit_location_to_hash_ms", (repo: String); 10, 0, 500, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
     commit_revlog_data_duration: dynamic_histogram("{}.commit_revlog_data_ms", (repo: String); 10, 0, 500, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
+    commit_graph_duration: dynamic_histogram("{}.commit_graph_ms", (repo: String); 10, 0, 500, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
+    commit_is_ancestor_duration: dynamic_histogram("{}.commit_is_ancestor_ms", (repo: String); 10, 0, 500, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
     clone_duration: dynamic_histogram("{}.clone_data_ms", (repo: String); 10, 0, 500, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
     full_idmap_clone_duration: dynamic_histogram("{}.full_idmap_clone_data_ms", (repo: String); 10, 0, 500, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
+    bookmarks_duration: dynamic_histogram("{}.bookmarks_ms", (repo: String); 10, 0, 500, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
+    lookup_duration: dynamic_histogram("{}.lookup_ms", (repo: String); 10, 0, 500, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
+    upload_file_contents_duration: dynamic_histogram("{}.upload_file_contents_ms", (repo: String); 100, 0, 5000, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
+    upload_filenodes_duration: dynamic_histogram("{}.upload_filenodes_ms", (repo: String); 100, 0, 5000, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
+    upload_trees_duration: dynamic_histogram("{}.upload_trees_ms", (repo: String); 100, 0, 5000, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
+    upload_changesets_duration: dynamic_histogram("{}.upload_changesets_ms", (repo: String); 100, 0, 5000, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
+    land_stack_duration: dynamic_histogram("{}.land_stack_ms", (repo: String); 100, 0, 5000, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
 }
 
 fn log_stats(state: &mut State, status: StatusCode) -> Option<()> {
@@ -44,6 +60,10 @@ fn log_stats(state: &mut State, status: StatusCode) -> Option<()> {
     let repo = hander_info.repo.clone()?;
     let repo_and_method = format!("{}.{}", &repo, method.to_string());
 
+    let uncompressed_bytes = state
+        .try_borrow::<RequestContext>()
+        .map(|rctx| rctx.response_bytes_uncompressed.clone());
+
     let callbacks = state.try_borrow_mut::<PostRequestCallbacks>()?;
 
     callbacks.add(move |info| {
@@ -53,6 +73,7 @@ fn log_stats(state: &mut State, status: StatusCode) -> Option<()> {
             use EdenApiMethod::*;
             match method {
                 Files => STATS::files_duration.add_value(dur_ms, (repo,)),
+                FilesByContent => STATS::files_by_content_duration.add_value(dur_ms, (repo,)),
                 Trees => STATS::trees_duration.add_value(dur_ms, (repo,)),
                 CompleteTrees => STATS::complete_trees_duration.add_value(dur_ms, (repo,)),
                 History => STATS::history_duration.add_value(dur_ms, (repo,)),
@@ -60,8 +81,19 @@ fn log_stats(state: &mut State, status: StatusCode) -> Option<()> {
                     STATS::commit_location_to_hash_duration.add_value(dur_ms, (repo,))
                 }
                 CommitRevlogData => STATS::commit_revlog_data_duration.add_value(dur_ms, (repo,)),
+                CommitGraph => STATS::commit_graph_duration.add_value(dur_ms, (repo,)),
+                CommitIsAncestor => STATS::commit_is_ancestor_duration.add_value(dur_ms, (repo,)),
                 Clone => STATS::clone_duration.add_value(dur_ms, (repo,)),
                 FullIdMapClone => STATS::full_idmap_clone_duration.add_value(dur_ms, (repo,)),
+                Bookmarks => STATS::bookmarks_duration.add_value(dur_ms, (repo,)),
+                Lookup => STATS::lookup_duration.add_value(dur_ms, (repo,)),
+                UploadFileContents => {
+                    STATS::upload_file_contents_duration.add_value(dur_ms, (repo,))
+                }
+                UploadFilenodes => STATS::upload_filenodes_duration.add_value(dur_ms, (repo,)),
+                UploadTrees => STATS::upload_trees_duration.add_value(dur_ms, (repo,)),
+                UploadChangesets => STATS::upload_changesets_duration.add_value(dur_ms, (repo,)),
+                LandStack => STATS::land_stack_duration.add_value(dur_ms, (repo,)),
             }
         }
 
@@ -75,6 +107,16 @@ fn log_stats(state: &mut State, status: StatusCode) -> Option<()> {
             STATS::failure_5xx.add_value(1, (repo_and_method.clone(),));
         }
 
+        if let (Some(ResponseContentMeta::Compressed(_)), Some(bytes_sent), Some(uncompressed)) =
+            (info.content_meta, info.bytes_sent, uncompressed_bytes)
+        {
+            let uncompressed = uncompressed.load(Ordering::Relaxed);
+            let saved = uncompressed.saturating_sub(bytes_sent);
+            STATS::response_bytes_uncompressed
+                .add_value(uncompressed as i64, (repo_and_method.clone(),));
+            STATS::response_bytes_saved.add_value(saved as i64, (repo_and_method.clone(),));
+        }
+
         if let Some(response_bytes_sent) = info.bytes_sent {
             STATS::response_bytes_sent.add_value(response_bytes_sent as i64, (repo_and_method,))
         }
@@ -5,6 +5,9 @@
  * GNU General Public License version 2.
  */
 
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+
 use anyhow::Error;
 use futures::{
     channel::mpsc::{self, Sender},
@@ -31,6 +34,9 @@ pub struct RequestContext {
     pub logger: Logger,
     pub error_tx: Sender<Error>,
     pub handler_error_msg: Option<String>,
+    /// Size of the response body before compression. Only populated for
+    /// responses that support compression.
+    pub response_bytes_uncompressed: Arc<AtomicU64>,
 }
 
 impl RequestContext {
@@ -42,6 +48,7 @@ impl RequestContext {
             logger,
             error_tx,
             handler_error_msg: None,
+            response_bytes_uncompressed: Arc::new(AtomicU64::new(0)),
         };
 
         // Spawn error logging task.
@@ -7,6 +7,8 @@
 
 //! cbor.rs - Utilities for working with CBOR data in HTTP requests and responses.
 
+use std::sync::atomic::Ordering;
+
 use anyhow::{Context, Error};
 use bytes::Bytes;
 use edenapi_types::ToApi;
@@ -17,7 +19,7 @@ use once_cell::sync::Lazy;
 use serde::{de::DeserializeOwned, Serialize};
 
 use gotham_ext::{
-    content::ContentStream,
+    content::{CompressedContentStream, ContentEncoding, ContentStream},
     error::HttpError,
     response::{StreamBody, TryIntoResponse},
     stream_ext::GothamTryStreamExt,
@@ -55,6 +57,47 @@ where
     StreamBody::new(content_stream, cbor_mime())
 }
 
+/// Like `cbor_stream`, but compresses the response with the given encoding,
+/// which should be negotiated with the client via `ContentEncoding::from_state`.
+/// The size of the uncompressed response is recorded in the request context
+/// so that the savings from compression can be reported once the response
+/// has been sent.
+///
+/// Compression is opt-in on a per-route basis, since it is only worthwhile
+/// for methods that return substantial amounts of data.
+pub fn compressed_cbor_stream<S, T>(
+    rctx: RequestContext,
+    encoding: ContentEncoding,
+    stream: S,
+) -> impl TryIntoResponse
+where
+    S: Stream<Item = Result<T, Error>> + Send + 'static,
+    T: Serialize + Send + 'static,
+{
+    let uncompressed_bytes = rctx.response_bytes_uncompressed.clone();
+    let byte_stream = stream
+        .and_then(|item| async { to_cbor_bytes(item) })
+        .inspect_ok(move |bytes| {
+            uncompressed_bytes.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+        });
+
+    let content_stream = match encoding {
+        ContentEncoding::Identity => ContentStream::new(byte_stream)
+            .forward_err(rctx.error_tx)
+            .left_stream(),
+        ContentEncoding::Compressed(compression) => {
+            // Filter out errors before compression, since an error would
+            // otherwise terminate the compressed stream.
+            let byte_stream = byte_stream.forward_err(rctx.error_tx.clone()).map(Ok);
+            CompressedContentStream::new(byte_stream, compression)
+                .forward_err(rctx.error_tx)
+                .right_stream()
+        }
+    };
+
+    StreamBody::new(content_stream, cbor_mime())
+}
+
 pub async fn parse_cbor_request<R: DeserializeOwned>(state: &mut State) -> Result<R, HttpError> {
     let body = get_request_body(state).await?;
     serde_cbor::from_slice(&body)
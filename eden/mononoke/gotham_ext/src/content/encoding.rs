@@ -68,7 +68,7 @@ impl ContentEncoding {
         let mut gzip = false;
         let mut zstd = false;
 
-        let encodings = std::str::from_utf8(header)?.split(' ');
+        let encodings = std::str::from_utf8(header)?.split(',');
 
         for encoding in encodings {
             let encoding = match encoding.split(';').next() {
@@ -123,6 +123,16 @@ mod test {
             ContentEncoding::Compressed(ContentCompression::Zstd),
         );
 
+        assert_eq!(
+            ContentEncoding::from_header(b"zstd, gzip")?,
+            ContentEncoding::Compressed(ContentCompression::Zstd),
+        );
+
+        assert_eq!(
+            ContentEncoding::from_header(b"gzip,identity")?,
+            ContentEncoding::Compressed(ContentCompression::Gzip),
+        );
+
         assert_eq!(
             ContentEncoding::from_header(b"deflate, gzip;q=1.0, *;q=0.5")?,
             ContentEncoding::Compressed(ContentCompression::Gzip),
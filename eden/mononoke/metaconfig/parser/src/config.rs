@@ -795,6 +795,7 @@ mod test {
             [segmented_changelog_config]
             enabled = true
             update_algorithm = "ondemand"
+            tracked_bookmarks = ["release"]
         "#;
         let www_content = r#"
             repoid=1
@@ -1034,6 +1035,7 @@ mod test {
                 segmented_changelog_config: SegmentedChangelogConfig {
                     enabled: true,
                     update_algorithm: Some(String::from("ondemand")),
+                    tracked_bookmarks: vec![BookmarkName::new("release").unwrap()],
                 },
                 warm_bookmark_cache_check_blobimport: true,
                 repo_client_knobs: RepoClientKnobs {
@@ -1090,6 +1092,7 @@ mod test {
                 segmented_changelog_config: SegmentedChangelogConfig {
                     enabled: false,
                     update_algorithm: None,
+                    tracked_bookmarks: vec![],
                 },
                 warm_bookmark_cache_check_blobimport: false,
                 repo_client_knobs: RepoClientKnobs::default(),
@@ -419,6 +419,12 @@ impl Convert for RawSegmentedChangelogConfig {
         let mut config = SegmentedChangelogConfig::default();
         config.enabled = self.enabled.unwrap_or(config.enabled);
         config.update_algorithm = self.update_algorithm.or(config.update_algorithm);
+        if let Some(tracked_bookmarks) = self.tracked_bookmarks {
+            config.tracked_bookmarks = tracked_bookmarks
+                .into_iter()
+                .map(BookmarkName::new)
+                .collect::<Result<Vec<_>>>()?;
+        }
         Ok(config)
     }
 }
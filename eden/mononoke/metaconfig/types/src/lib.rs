@@ -1334,6 +1334,9 @@ pub struct SegmentedChangelogConfig {
     /// Specifies which update algorithm segmented changelog should be instantiate with. Check
     /// SegmentedChangelogBuilder for valid options.
     pub update_algorithm: Option<String>,
+    /// Bookmarks, such as release branches, whose ancestry is assigned to the master group in
+    /// addition to the master bookmark. They are assigned in order, after the master bookmark.
+    pub tracked_bookmarks: Vec<BookmarkName>,
 }
 
 impl Default for SegmentedChangelogConfig {
@@ -1341,6 +1344,7 @@ impl Default for SegmentedChangelogConfig {
         SegmentedChangelogConfig {
             enabled: false,
             update_algorithm: None,
+            tracked_bookmarks: Vec::new(),
         }
     }
 }
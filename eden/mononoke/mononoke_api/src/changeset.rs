@@ -9,6 +9,7 @@ use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::convert::TryInto;
 use std::fmt;
 use std::future::Future;
+use std::iter;
 use std::pin::Pin;
 
 use anyhow::anyhow;
@@ -18,6 +19,7 @@ use changeset_info::ChangesetInfo;
 use chrono::{DateTime, FixedOffset};
 use cloned::cloned;
 use context::CoreContext;
+use cross_repo_sync::CommitSyncOutcome;
 use derived_data::BonsaiDerived;
 use fsnodes::RootFsnodeId;
 use futures::future::{self, try_join, try_join_all, FutureExt, Shared};
@@ -25,9 +27,11 @@ use futures::stream::{self, Stream, StreamExt, TryStreamExt};
 use manifest::{Diff as ManifestDiff, Entry as ManifestEntry, ManifestOps, PathOrPrefix};
 use maplit::hashset;
 use mercurial_types::Globalrev;
+use metaconfig_types::DefaultSmallToLargeCommitSyncPathAction;
 pub use mononoke_types::Generation;
-use mononoke_types::{BonsaiChangeset, FileChange, MPath, MPathElement};
+use mononoke_types::{fsnode::FsnodeFile, BonsaiChangeset, FileChange, MPath, MPathElement};
 use reachabilityindex::ReachabilityIndex;
+use slog::warn;
 use unodes::RootUnodeManifestId;
 
 use crate::changeset_path::ChangesetPathContext;
@@ -319,7 +323,23 @@ impl ChangesetContext {
 
     /// Returns `true` if this commit is an ancestor of `other_commit`.  A commit is considered its
     /// own ancestor for the purpose of this call.
+    ///
+    /// Segmented changelog is used to answer this when it is enabled and knows about both commits,
+    /// otherwise (or if it fails) the skiplist index is used.
     pub async fn is_ancestor_of(&self, other_commit: ChangesetId) -> Result<bool, MononokeError> {
+        if let Some(segmented_changelog) = self.repo().enabled_segmented_changelog() {
+            match segmented_changelog
+                .is_ancestor(self.ctx(), self.id, other_commit)
+                .await
+            {
+                Ok(Some(is_ancestor_of)) => return Ok(is_ancestor_of),
+                Ok(None) => {}
+                Err(e) => warn!(
+                    self.ctx().logger(),
+                    "segmented changelog is_ancestor failed, falling back to skiplist: {:?}", e
+                ),
+            }
+        }
         let is_ancestor_of = self
             .repo()
             .skiplist_index()
@@ -341,6 +361,25 @@ impl ChangesetContext {
         &self,
         other_commit: ChangesetId,
     ) -> Result<Option<ChangesetContext>, MononokeError> {
+        if let Some(segmented_changelog) = self.repo().enabled_segmented_changelog() {
+            match segmented_changelog
+                .common_ancestors(self.ctx(), vec![self.id, other_commit])
+                .await
+            {
+                Ok(Some(gca)) => {
+                    return Ok(gca
+                        .into_iter()
+                        .next()
+                        .map(|id| Self::new(self.repo.clone(), id)));
+                }
+                Ok(None) => {}
+                Err(e) => warn!(
+                    self.ctx().logger(),
+                    "segmented changelog common_ancestors failed, falling back to skiplist: {:?}",
+                    e
+                ),
+            }
+        }
         let lca = self
             .repo()
             .skiplist_index()
@@ -627,6 +666,184 @@ impl ChangesetContext {
         return Ok(change_contexts);
     }
 
+    /// Returns content differences between this changeset and a changeset in another repo that
+    /// this repo is synced with.
+    ///
+    /// Paths of this changeset are mapped into the other repo using the movers of the commit
+    /// sync config version this changeset was synced with, or of the current version if it
+    /// hasn't been synced. Files that the movers drop, and files in the other repo that don't
+    /// come from this repo, are ignored, so only real content divergences are reported.
+    ///
+    /// As with `diff`, `self` is considered the "new" changeset: files present only here are
+    /// "Added", files present only in `other` are "Removed". `path_restrictions` are paths in
+    /// this repo.
+    pub async fn diff_xrepo(
+        &self,
+        other: &ChangesetContext,
+        path_restrictions: Option<Vec<MononokePath>>,
+    ) -> Result<Vec<ChangesetPathDiffContext>, MononokeError> {
+        let commit_syncer = self.repo().xrepo_commit_syncer(other.repo())?;
+        let version = match commit_syncer
+            .get_commit_sync_outcome(self.ctx(), self.id)
+            .await?
+        {
+            Some(CommitSyncOutcome::RewrittenAs(_, version))
+            | Some(CommitSyncOutcome::EquivalentWorkingCopyAncestor(_, version)) => version,
+            Some(CommitSyncOutcome::NotSyncCandidate) | None => {
+                commit_syncer.get_current_version(self.ctx())?
+            }
+        };
+        let mover = commit_syncer.get_mover_by_version(&version)?;
+        let reverse_mover = commit_syncer.get_reverse_mover_by_version(&version)?;
+
+        let mut other_restrictions = match &path_restrictions {
+            Some(path_restrictions) => {
+                let mut other_restrictions = Vec::new();
+                for path in path_restrictions {
+                    let mpath = path.as_mpath().ok_or_else(|| {
+                        MononokeError::InvalidRequest(String::from(
+                            "cross-repo diffs can't be restricted to the repository root",
+                        ))
+                    })?;
+                    if let Some(mpath) = mover(mpath)? {
+                        other_restrictions.push(MononokePath::new(Some(mpath)));
+                    }
+                }
+                if other_restrictions.is_empty() {
+                    return Ok(Vec::new());
+                }
+                Some(other_restrictions)
+            }
+            None => None,
+        };
+
+        // Only the parts of the large repo that the small repo is synced to can match. If the
+        // small repo is synced under a prefix, an unrestricted walk of the large repo is limited
+        // to that prefix and to the targets of the path mapping.
+        let mut self_restrictions = path_restrictions;
+        let repo_id = self.repo().blob_repo().get_repoid();
+        let config = self
+            .repo()
+            .live_commit_sync_config()
+            .get_commit_sync_config_by_version(repo_id, &version)?;
+        let (small_repo_id, large_restrictions) = if config.large_repo_id == repo_id {
+            (
+                other.repo().blob_repo().get_repoid(),
+                &mut self_restrictions,
+            )
+        } else {
+            (repo_id, &mut other_restrictions)
+        };
+        let synced_prefixes = config
+            .small_repos
+            .get(&small_repo_id)
+            .and_then(|small_config| match &small_config.default_action {
+                DefaultSmallToLargeCommitSyncPathAction::PrependPrefix(prefix) => Some(
+                    iter::once(prefix)
+                        .chain(small_config.map.values())
+                        .map(|path| MononokePath::new(Some(path.clone())))
+                        .collect(),
+                ),
+                DefaultSmallToLargeCommitSyncPathAction::Preserve => None,
+            });
+        if large_restrictions.is_none() {
+            *large_restrictions = synced_prefixes;
+        }
+
+        let (self_files, other_files) = try_join(
+            self.leaf_entries(self_restrictions),
+            other.leaf_entries(other_restrictions),
+        )
+        .await?;
+
+        // Files in the other repo that don't map back into this repo come from elsewhere.
+        let mut other_files = {
+            let mut synced_files = BTreeMap::new();
+            for (path, file) in other_files {
+                if reverse_mover(&path)?.is_some() {
+                    synced_files.insert(path, file);
+                }
+            }
+            synced_files
+        };
+
+        let mut diff = Vec::new();
+        for (path, file) in self_files {
+            let other_path = match mover(&path)? {
+                Some(other_path) => other_path,
+                None => continue,
+            };
+            match other_files.remove(&other_path) {
+                Some(other_file) => {
+                    if file.content_id() != other_file.content_id()
+                        || file.file_type() != other_file.file_type()
+                    {
+                        diff.push(ChangesetPathDiffContext::Changed(
+                            ChangesetPathContext::new_with_fsnode_entry(
+                                self.clone(),
+                                path,
+                                ManifestEntry::Leaf(file),
+                            ),
+                            ChangesetPathContext::new_with_fsnode_entry(
+                                other.clone(),
+                                other_path,
+                                ManifestEntry::Leaf(other_file),
+                            ),
+                        ));
+                    }
+                }
+                None => diff.push(ChangesetPathDiffContext::Added(
+                    ChangesetPathContext::new_with_fsnode_entry(
+                        self.clone(),
+                        path,
+                        ManifestEntry::Leaf(file),
+                    ),
+                )),
+            }
+        }
+        diff.extend(other_files.into_iter().map(|(path, file)| {
+            ChangesetPathDiffContext::Removed(ChangesetPathContext::new_with_fsnode_entry(
+                other.clone(),
+                path,
+                ManifestEntry::Leaf(file),
+            ))
+        }));
+
+        Ok(diff)
+    }
+
+    /// All files under the given prefixes (or in the whole commit), in path order.
+    async fn leaf_entries(
+        &self,
+        prefixes: Option<Vec<MononokePath>>,
+    ) -> Result<BTreeMap<MPath, FsnodeFile>, MononokeError> {
+        let prefixes = match prefixes {
+            Some(prefixes) => prefixes
+                .into_iter()
+                .map(|prefix| PathOrPrefix::Prefix(prefix.into()))
+                .collect(),
+            None => vec![PathOrPrefix::Prefix(None)],
+        };
+        let entries = self
+            .root_fsnode_id()
+            .await?
+            .fsnode_id()
+            .find_entries(
+                self.ctx().clone(),
+                self.repo().blob_repo().get_blobstore(),
+                prefixes,
+            )
+            .try_filter_map(|(path, entry)| async move {
+                match (path, entry) {
+                    (Some(mpath), ManifestEntry::Leaf(file)) => Ok(Some((mpath, file))),
+                    _ => Ok(None),
+                }
+            })
+            .try_collect()
+            .await?;
+        Ok(entries)
+    }
+
     pub async fn find_files(
         &self,
         prefixes: Option<Vec<MononokePath>>,
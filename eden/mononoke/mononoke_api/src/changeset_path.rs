@@ -24,7 +24,7 @@ use derived_data::BonsaiDerived;
 use fastlog::{list_file_history, FastlogError, HistoryAcrossDeletions, Visitor};
 use filestore::FetchKey;
 use futures::future::{try_join_all, FutureExt, Shared, TryFutureExt};
-use futures::stream::{Stream, TryStreamExt};
+use futures::stream::{self, Stream, TryStreamExt};
 use futures::try_join;
 use manifest::{Entry, ManifestOps};
 use mononoke_types::{
@@ -43,7 +43,7 @@ use crate::errors::MononokeError;
 use crate::file::FileContext;
 use crate::path::MononokePath;
 use crate::repo::RepoContext;
-use crate::tree::TreeContext;
+use crate::tree::{TreeContext, TreeSummary};
 
 pub struct HistoryEntry {
     pub name: String,
@@ -58,12 +58,40 @@ pub struct ChangesetPathHistoryOptions {
     pub follow_history_across_deletions: bool,
 }
 
+/// How to pick the commits at which the summary of a directory is sampled.
+#[derive(Clone, Copy, Debug)]
+pub enum SummaryHistorySampling {
+    /// Sample every N-th commit along first-parent history.
+    EveryNthCommit(u64),
+    /// Sample the first commit along first-parent history that is at least this many seconds
+    /// older than the previous sample.
+    EveryNSeconds(i64),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ChangesetPathSummaryHistoryOptions {
+    pub sampling: SummaryHistorySampling,
+    /// Stop after this many samples.
+    pub max_samples: usize,
+    /// Stop at commits older than this timestamp.
+    pub until_timestamp: Option<i64>,
+}
+
 pub enum PathEntry {
     NotPresent,
     Tree(TreeContext),
     File(FileContext, FileType),
 }
 
+async fn first_parent(
+    changeset: &ChangesetContext,
+) -> Result<Option<ChangesetContext>, MononokeError> {
+    let parents = changeset.parents().await?;
+    Ok(parents
+        .first()
+        .map(|id| ChangesetContext::new(changeset.repo().clone(), *id)))
+}
+
 /// A diff between two files in extended unified diff format
 pub struct UnifiedDiff {
     /// Raw diff as bytes.
@@ -280,6 +308,80 @@ impl ChangesetPathContext {
             .await
     }
 
+    /// Returns a time series of the summary (size, file count and descendant counts) of the
+    /// directory at this path, sampled along first-parent history starting at this commit.
+    ///
+    /// The series ends at the first sampled commit where the path is not a directory.
+    pub async fn summary_history(
+        &self,
+        opts: ChangesetPathSummaryHistoryOptions,
+    ) -> Result<
+        impl Stream<Item = Result<(ChangesetContext, TreeSummary), MononokeError>>,
+        MononokeError,
+    > {
+        match opts.sampling {
+            SummaryHistorySampling::EveryNthCommit(0) => {
+                return Err(MononokeError::InvalidRequest(String::from(
+                    "the sampling interval must be at least one commit",
+                )));
+            }
+            SummaryHistorySampling::EveryNSeconds(secs) if secs <= 0 => {
+                return Err(MononokeError::InvalidRequest(String::from(
+                    "the sampling interval must be at least one second",
+                )));
+            }
+            _ => {}
+        }
+
+        let path = self.path.clone();
+        let start = (Some(self.changeset.clone()), 0);
+        Ok(stream::try_unfold(start, move |(changeset, samples)| {
+            cloned!(path);
+            async move {
+                let changeset = match changeset {
+                    Some(changeset) if samples < opts.max_samples => changeset,
+                    _ => return Ok(None),
+                };
+                let timestamp = changeset.author_date().await?.timestamp();
+                if opts
+                    .until_timestamp
+                    .map_or(false, |until| timestamp < until)
+                {
+                    return Ok(None);
+                }
+                let summary = match ChangesetPathContext::new(changeset.clone(), path)
+                    .tree()
+                    .await?
+                {
+                    Some(tree) => tree.summary().await?,
+                    None => return Ok(None),
+                };
+
+                let mut next = first_parent(&changeset).await?;
+                match opts.sampling {
+                    SummaryHistorySampling::EveryNthCommit(n) => {
+                        for _ in 1..n {
+                            next = match next {
+                                Some(next) => first_parent(&next).await?,
+                                None => break,
+                            };
+                        }
+                    }
+                    SummaryHistorySampling::EveryNSeconds(secs) => {
+                        while let Some(candidate) = next.as_ref() {
+                            if candidate.author_date().await?.timestamp() <= timestamp - secs {
+                                break;
+                            }
+                            next = first_parent(candidate).await?;
+                        }
+                    }
+                }
+
+                Ok(Some(((changeset, summary), (next, samples + 1))))
+            }
+        }))
+    }
+
     /// Returns a list of `ChangesetContext` for the file at this path that represents
     /// a history of the path.
     pub async fn history(
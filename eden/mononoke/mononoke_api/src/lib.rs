@@ -45,8 +45,9 @@ pub use crate::changeset::{
     ChangesetContext, ChangesetDiffItem, ChangesetHistoryOptions, Generation,
 };
 pub use crate::changeset_path::{
-    unified_diff, ChangesetPathContext, ChangesetPathHistoryOptions, CopyInfo, PathEntry,
-    UnifiedDiff, UnifiedDiffMode,
+    unified_diff, ChangesetPathContext, ChangesetPathHistoryOptions,
+    ChangesetPathSummaryHistoryOptions, CopyInfo, PathEntry, SummaryHistorySampling, UnifiedDiff,
+    UnifiedDiffMode,
 };
 pub use crate::changeset_path_diff::ChangesetPathDiffContext;
 pub use crate::errors::MononokeError;
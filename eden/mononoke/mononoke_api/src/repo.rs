@@ -6,12 +6,14 @@
  */
 
 use std::fmt;
+use std::str::FromStr;
 use std::{
     sync::Arc,
     time::{SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::{format_err, Error};
+use async_trait::async_trait;
 use blobrepo::BlobRepo;
 use blobrepo_factory::{BlobrepoBuilder, ReadOnlyStorage};
 use blobrepo_hg::BlobRepoHg;
@@ -51,10 +53,15 @@ use permission_checker::{ArcPermissionChecker, PermissionCheckerBuilder};
 use reachabilityindex::LeastCommonAncestorsHint;
 use regex::Regex;
 use repo_read_write_status::{RepoReadWriteFetcher, SqlRepoReadWriteStatus};
-use revset::AncestorsNodeStream;
-use segmented_changelog::{CloneData, SegmentedChangelog, StreamCloneData};
-use skiplist::{fetch_skiplist_index, SkiplistIndex};
-use slog::{debug, error, o, Logger};
+use revset::{
+    parse_revset, AncestorsNodeStream, ErrorKind as RevsetErrorKind, ResolvedRange,
+    RevsetEvaluator, RevsetLimits, RevsetResolver,
+};
+use segmented_changelog::{
+    CloneData, Location, SegmentedChangelog, StreamCloneData, GRAPH_SLICE_MAX_DEPTH,
+};
+use skiplist::{fetch_skiplist_index, spawn_skiplist_reloader, SkiplistIndex};
+use slog::{debug, error, o, warn, Logger};
 use sql_construct::facebook::FbSqlConstruct;
 use sql_construct::SqlConstruct;
 use sql_construct::SqlConstructFromMetadataDatabaseConfig;
@@ -273,6 +280,10 @@ impl Repo {
             sql_read_write_status.watched(&logger),
         )?;
 
+        if let Some(key) = skiplist_index_blobstore_key {
+            spawn_skiplist_reloader(ctx.clone(), blobstore, key, &skiplist_index);
+        }
+
         let readonly_fetcher = RepoReadWriteFetcher::new(
             sql_read_write_status,
             config.readonly.clone(),
@@ -305,6 +316,20 @@ impl Repo {
         .await
     }
 
+    /// Construct a Repo from a test BlobRepo, checking repo permissions
+    /// with the given checker
+    pub async fn new_test_with_permission_checker(
+        ctx: CoreContext,
+        blob_repo: BlobRepo,
+        repo_permission_checker: ArcPermissionChecker,
+    ) -> Result<Self, Error> {
+        let repo = Self::new_test(ctx, blob_repo).await?;
+        Ok(Self {
+            repo_permission_checker,
+            ..repo
+        })
+    }
+
     /// Construct a Repo from a test BlobRepo and commit_sync_config
     pub async fn new_test_xrepo(
         ctx: CoreContext,
@@ -1226,13 +1251,11 @@ impl RepoContext {
         }
     }
 
-    /// Get the equivalent changeset from another repo - it will sync it if needed
-    pub async fn xrepo_commit_lookup(
+    /// Build a `CommitSyncer` that syncs commits from this repo to `other`.
+    pub(crate) fn xrepo_commit_syncer(
         &self,
         other: &Self,
-        specifier: impl Into<ChangesetSpecifier>,
-        maybe_candidate_selection_hint_args: Option<CandidateSelectionHintArgs>,
-    ) -> Result<Option<ChangesetContext>, MononokeError> {
+    ) -> Result<CommitSyncer<Arc<dyn SyncedCommitMapping>>, MononokeError> {
         let commit_sync_config = self
             .live_commit_sync_config()
             .get_current_commit_sync_config(self.ctx(), self.blob_repo().get_repoid())
@@ -1243,16 +1266,33 @@ impl RepoContext {
                 ))
             })?;
 
-        let candidate_selection_hint: CandidateSelectionHint = self
-            .build_candidate_selection_hint(maybe_candidate_selection_hint_args, &other)
-            .await?;
-
         let commit_sync_repos = CommitSyncRepos::new(
             self.blob_repo().clone(),
             other.blob_repo().clone(),
             &commit_sync_config,
         )?;
 
+        Ok(CommitSyncer::new(
+            &self.ctx,
+            self.synced_commit_mapping().clone(),
+            commit_sync_repos,
+            self.live_commit_sync_config(),
+        ))
+    }
+
+    /// Get the equivalent changeset from another repo - it will sync it if needed
+    pub async fn xrepo_commit_lookup(
+        &self,
+        other: &Self,
+        specifier: impl Into<ChangesetSpecifier>,
+        maybe_candidate_selection_hint_args: Option<CandidateSelectionHintArgs>,
+    ) -> Result<Option<ChangesetContext>, MononokeError> {
+        let commit_syncer = self.xrepo_commit_syncer(other)?;
+
+        let candidate_selection_hint: CandidateSelectionHint = self
+            .build_candidate_selection_hint(maybe_candidate_selection_hint_args, &other)
+            .await?;
+
         let specifier = specifier.into();
         let changeset =
             self.resolve_specifier(specifier)
@@ -1262,13 +1302,6 @@ impl RepoContext {
                     specifier
                 )))?;
 
-        let commit_syncer = CommitSyncer::new(
-            &self.ctx,
-            self.synced_commit_mapping().clone(),
-            commit_sync_repos,
-            self.live_commit_sync_config(),
-        );
-
         let maybe_cs_id = commit_syncer
             .sync_commit(
                 &self.ctx,
@@ -1280,6 +1313,14 @@ impl RepoContext {
         Ok(maybe_cs_id.map(|cs_id| ChangesetContext::new(other.clone(), cs_id)))
     }
 
+    /// Check the user is permitted to write to this repository.
+    ///
+    /// Writes through a `RepoWriteContext` are checked by `write`. This is
+    /// for other ways of adding data to the repository, such as uploads.
+    pub async fn check_write_permissions(&self) -> Result<(), MononokeError> {
+        self.repo.check_permissions(&self.ctx, "write").await
+    }
+
     /// Get a write context to make changes to this repository.
     pub async fn write(mut self) -> Result<RepoWriteContext, MononokeError> {
         if !self.config().source_control_service.permit_writes {
@@ -1376,6 +1417,67 @@ impl RepoContext {
         Ok(ancestor)
     }
 
+    /// The reverse of `location_to_changeset_id`: find the location of a commit relative to
+    /// `master_heads` or to a parent of a merge commit, both of which are known to clients.
+    /// Returns `None` if the commit is not an ancestor of `master_heads`.
+    pub async fn changeset_id_to_location(
+        &self,
+        master_heads: Vec<ChangesetId>,
+        cs_id: ChangesetId,
+    ) -> Result<Option<Location<ChangesetId>>, MononokeError> {
+        let blob_repo = self.blob_repo();
+        let segmented_changelog =
+            blob_repo
+                .attribute::<dyn SegmentedChangelog>()
+                .ok_or_else(|| {
+                    MononokeError::InvalidRequest(String::from(
+                        "Segmented Changelog is not enabled for this repo",
+                    ))
+                })?;
+        let location = segmented_changelog
+            .changeset_id_to_location(&self.ctx, master_heads, cs_id)
+            .await
+            .map_err(MononokeError::from)?;
+        Ok(location)
+    }
+
+    /// The segmented changelog for this repo, if it is enabled in the repo's config.
+    pub(crate) fn enabled_segmented_changelog(&self) -> Option<&Arc<dyn SegmentedChangelog>> {
+        if !self.config().segmented_changelog_config.enabled {
+            return None;
+        }
+        self.blob_repo().attribute::<dyn SegmentedChangelog>()
+    }
+
+    /// Get a slice of the commit graph: the given heads and their ancestors up to `depth`
+    /// generations back, each with its parents.
+    pub async fn segmented_changelog_graph_slice(
+        &self,
+        heads: Vec<ChangesetId>,
+        depth: u64,
+    ) -> Result<Vec<(ChangesetId, Vec<ChangesetId>)>, MononokeError> {
+        if depth > GRAPH_SLICE_MAX_DEPTH {
+            return Err(MononokeError::InvalidRequest(format!(
+                "graph slice depth {} exceeds the maximum of {}",
+                depth, GRAPH_SLICE_MAX_DEPTH
+            )));
+        }
+        let blob_repo = self.blob_repo();
+        let segmented_changelog =
+            blob_repo
+                .attribute::<dyn SegmentedChangelog>()
+                .ok_or_else(|| {
+                    MononokeError::InvalidRequest(String::from(
+                        "Segmented Changelog is not enabled for this repo",
+                    ))
+                })?;
+        let slice = segmented_changelog
+            .graph_slice(&self.ctx, heads, depth)
+            .await
+            .map_err(MononokeError::from)?;
+        Ok(slice)
+    }
+
     pub async fn segmented_changelog_clone_data(
         &self,
     ) -> Result<CloneData<ChangesetId>, MononokeError> {
@@ -1413,13 +1515,121 @@ impl RepoContext {
             .map_err(MononokeError::from)?;
         Ok(clone_data)
     }
+
+    /// Evaluate a revset expression such as `master % release` and return
+    /// up to `limit` matching commits, in descending generation order.
+    ///
+    /// Symbols in the expression are bookmark names or full commit hashes.
+    pub async fn query_commits(
+        &self,
+        expression: &str,
+        limit: usize,
+    ) -> Result<Vec<ChangesetId>, MononokeError> {
+        let expr =
+            parse_revset(expression).map_err(|e| MononokeError::InvalidRequest(e.to_string()))?;
+        let evaluator = RevsetEvaluator::new(
+            self.ctx.clone(),
+            self.blob_repo().get_changeset_fetcher(),
+            self.skiplist_index().clone(),
+            Arc::new(RepoRevsetResolver { repo: self.clone() }),
+            RevsetLimits::default(),
+        );
+        let cs_ids = evaluator
+            .evaluate(&expr)
+            .await
+            .map_err(revset_error)?
+            .compat()
+            .take(limit)
+            .try_collect()
+            .await
+            .map_err(revset_error)?;
+        Ok(cs_ids)
+    }
+}
+
+/// Resolves revset symbols and filters against a repo.
+struct RepoRevsetResolver {
+    repo: RepoContext,
+}
+
+#[async_trait]
+impl RevsetResolver for RepoRevsetResolver {
+    async fn resolve_symbol(
+        &self,
+        _ctx: &CoreContext,
+        symbol: &str,
+    ) -> Result<Option<ChangesetId>, Error> {
+        if BookmarkName::new(symbol).is_ok() {
+            if let Some(changeset) = self
+                .repo
+                .resolve_bookmark(symbol, BookmarkFreshness::MaybeStale)
+                .await?
+            {
+                return Ok(Some(changeset.id()));
+            }
+        }
+        let specifier = if let Ok(cs_id) = ChangesetId::from_str(symbol) {
+            ChangesetSpecifier::Bonsai(cs_id)
+        } else if let Ok(hg_cs_id) = HgChangesetId::from_str(symbol) {
+            ChangesetSpecifier::Hg(hg_cs_id)
+        } else {
+            return Ok(None);
+        };
+        Ok(self.repo.resolve_specifier(specifier).await?)
+    }
+
+    async fn author(&self, _ctx: &CoreContext, cs_id: ChangesetId) -> Result<String, Error> {
+        Ok(ChangesetContext::new(self.repo.clone(), cs_id)
+            .author()
+            .await?)
+    }
+
+    async fn range(
+        &self,
+        ctx: &CoreContext,
+        roots: Vec<ChangesetId>,
+        heads: Vec<ChangesetId>,
+        limit: u64,
+    ) -> Result<ResolvedRange, Error> {
+        let segmented_changelog = match self.repo.enabled_segmented_changelog() {
+            Some(segmented_changelog) => segmented_changelog,
+            None => return Ok(ResolvedRange::Unsupported),
+        };
+        match segmented_changelog.range(ctx, roots, heads, limit).await {
+            Ok(Some(cs_ids)) => Ok(ResolvedRange::Commits(cs_ids)),
+            Ok(None) => Ok(ResolvedRange::TooLarge),
+            // Segmented changelog may not know about the most recent
+            // commits yet, in which case the range is computed by walking
+            // the graph instead.
+            Err(e) => {
+                warn!(
+                    ctx.logger(),
+                    "segmented changelog range failed, walking the graph instead: {:?}", e
+                );
+                Ok(ResolvedRange::Unsupported)
+            }
+        }
+    }
+}
+
+/// Errors caused by the revset itself are the caller's fault.
+fn revset_error(error: Error) -> MononokeError {
+    match error.downcast_ref::<RevsetErrorKind>() {
+        Some(kind @ RevsetErrorKind::InvalidRevset(..))
+        | Some(kind @ RevsetErrorKind::UnknownRevision(..))
+        | Some(kind @ RevsetErrorKind::RevsetTooComplex(..))
+        | Some(kind @ RevsetErrorKind::RevsetCostExceeded(..))
+        | Some(kind @ RevsetErrorKind::UnboundedRevsetFilter(..)) => {
+            MononokeError::InvalidRequest(kind.to_string())
+        }
+        _ => MononokeError::from(error),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use fixtures::{linear, merge_even};
-    use std::str::FromStr;
 
     #[fbinit::compat_test]
     async fn test_try_find_child(fb: FacebookInit) -> Result<(), Error> {
@@ -11,7 +11,9 @@ use std::str::FromStr;
 use std::sync::Arc;
 
 use anyhow::{anyhow, Error};
+use assert_matches::assert_matches;
 use blobrepo_factory::new_memblob_empty;
+use blobrepo_override::DangerousOverride;
 use blobstore::Loadable;
 use bytes::Bytes;
 use chrono::{FixedOffset, TimeZone};
@@ -22,19 +24,27 @@ use maplit::{btreeset, hashmap};
 
 use crate::{
     BookmarkFreshness, ChangesetDiffItem, ChangesetId, ChangesetIdPrefix, ChangesetPathDiffContext,
-    ChangesetPrefixSpecifier, ChangesetSpecifier, ChangesetSpecifierPrefixResolution, CoreContext,
-    FileId, FileMetadata, FileType, HgChangesetId, HgChangesetIdPrefix, Mononoke, MononokePath,
-    TreeEntry, TreeId,
+    ChangesetPathSummaryHistoryOptions, ChangesetPrefixSpecifier, ChangesetSpecifier,
+    ChangesetSpecifierPrefixResolution, CoreContext, FileId, FileMetadata, FileType, HgChangesetId,
+    HgChangesetIdPrefix, Mononoke, MononokeError, MononokePath, Repo, RepoContext,
+    SummaryHistorySampling, TreeEntry, TreeId,
 };
 use cross_repo_sync::{update_mapping_with_version, CommitSyncRepos, CommitSyncer};
 use cross_repo_sync_test_utils::init_small_large_repo;
 use live_commit_sync_config::TestLiveCommitSyncConfigSource;
-use metaconfig_types::{CommitSyncConfigVersion, DefaultSmallToLargeCommitSyncPathAction};
+use metaconfig_types::{
+    CommitSyncConfigVersion, DefaultSmallToLargeCommitSyncPathAction, RepoConfig,
+    SegmentedChangelogConfig,
+};
 use mononoke_types::{
     hash::{GitSha1, RichGitSha1, Sha1, Sha256},
-    MPath,
+    DateTime, MPath,
+};
+use segmented_changelog::{
+    DisabledSegmentedChangelog, SegmentedChangelog, SegmentedChangelogBuilder,
 };
 use slog::info;
+use sql_construct::SqlConstruct;
 use synced_commit_mapping::SyncedCommitMapping;
 use tests_utils::{bookmark, resolve_cs_id, CreateCommitContext};
 
@@ -209,6 +219,111 @@ async fn commit_is_ancestor_of(fb: FacebookInit) -> Result<(), Error> {
     Ok(())
 }
 
+#[fbinit::compat_test]
+async fn commit_ancestry_with_segmented_changelog(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let blobrepo = linear::getrepo(fb).await;
+    let on_demand: Arc<dyn SegmentedChangelog> = Arc::new(
+        SegmentedChangelogBuilder::with_sqlite_in_memory()?
+            .with_blobrepo(&blobrepo)
+            .build_on_demand_update()?,
+    );
+    // Every query fails with the disabled segmented changelog, so the skiplist answers them.
+    let failing: Arc<dyn SegmentedChangelog> = Arc::new(DisabledSegmentedChangelog::new());
+
+    for segmented_changelog in vec![on_demand, failing] {
+        let blobrepo =
+            blobrepo.dangerous_override(|_: Arc<dyn SegmentedChangelog>| segmented_changelog);
+        let repo = Repo::new_test(ctx.clone(), blobrepo).await?;
+        let repo = Repo {
+            config: RepoConfig {
+                segmented_changelog_config: SegmentedChangelogConfig {
+                    enabled: true,
+                    ..Default::default()
+                },
+                ..repo.config.clone()
+            },
+            ..repo
+        };
+        let repo = RepoContext::new(ctx.clone(), Arc::new(repo)).await?;
+        assert!(repo.enabled_segmented_changelog().is_some());
+
+        let head = repo
+            .changeset(HgChangesetId::from_str(
+                "79a13814c5ce7330173ec04d279bf95ab3f652fb",
+            )?)
+            .await?
+            .expect("changeset exists");
+        let ancestor = repo
+            .changeset(HgChangesetId::from_str(
+                "0ed509bf086fadcb8a8a5384dc3b550729b0fc17",
+            )?)
+            .await?
+            .expect("changeset exists");
+
+        assert!(ancestor.is_ancestor_of(head.id()).await?);
+        assert!(!head.is_ancestor_of(ancestor.id()).await?);
+        assert_eq!(
+            head.common_base_with(ancestor.id())
+                .await?
+                .map(|cs| cs.id()),
+            Some(ancestor.id())
+        );
+    }
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn query_commits(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let mononoke = Mononoke::new_test(
+        ctx.clone(),
+        vec![("test".to_string(), linear::getrepo(fb).await)],
+    )
+    .await?;
+    let repo = mononoke.repo(ctx, "test").await?.expect("repo exists");
+    let mut changesets = Vec::new();
+    for hg_hash in [
+        "79a13814c5ce7330173ec04d279bf95ab3f652fb", // master
+        "a5ffa77602a066db7d5cfb9fb5823a0895717c5a",
+        "3c15267ebf11807f3d772eb891272b911ec68759",
+        "a9473beb2eb03ddb1cccc3fbaeb8a4820f9cd157",
+    ]
+    .iter()
+    {
+        let changeset = repo
+            .changeset(HgChangesetId::from_str(hg_hash)?)
+            .await?
+            .expect("changeset exists");
+        changesets.push(changeset.id());
+    }
+
+    assert_eq!(
+        repo.query_commits("master % a9473beb2eb03ddb1cccc3fbaeb8a4820f9cd157", 10)
+            .await?,
+        changesets[0..3].to_vec(),
+    );
+    assert_eq!(
+        repo.query_commits("a9473beb2eb03ddb1cccc3fbaeb8a4820f9cd157::master", 2)
+            .await?,
+        changesets[0..2].to_vec(),
+    );
+    assert_matches!(
+        repo.query_commits("master &", 10).await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+    assert_matches!(
+        repo.query_commits("nonexistent", 10).await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+    assert_matches!(
+        repo.query_commits("author(jsgf)", 10).await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+
+    Ok(())
+}
+
 #[fbinit::compat_test]
 async fn commit_find_files(fb: FacebookInit) -> Result<(), Error> {
     let ctx = CoreContext::test_mock(fb);
@@ -451,6 +566,91 @@ async fn tree_list(fb: FacebookInit) -> Result<(), Error> {
     Ok(())
 }
 
+#[fbinit::compat_test]
+async fn tree_summary_history(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let blobrepo = new_memblob_empty(None)?;
+    let mut commits = Vec::new();
+    let mut parents = Vec::new();
+    for (index, (path, content)) in [
+        ("dir/a", "a"),
+        ("dir/sub/b", "bb"),
+        ("dir/sub/c", "ccc"),
+        ("other", "other"),
+    ]
+    .iter()
+    .enumerate()
+    {
+        let cs_id = CreateCommitContext::new(&ctx, &blobrepo, parents)
+            .add_file(*path, *content)
+            .set_author_date(DateTime::from_timestamp(1000 * index as i64, 0)?)
+            .commit()
+            .await?;
+        commits.push(cs_id);
+        parents = vec![cs_id];
+    }
+
+    let mononoke =
+        Mononoke::new_test(ctx.clone(), vec![("test".to_string(), blobrepo.clone())]).await?;
+    let repo = mononoke.repo(ctx, "test").await?.expect("repo exists");
+    let cs = repo.changeset(commits[3]).await?.expect("changeset exists");
+    let path = cs.path("dir")?;
+
+    let history = |sampling, max_samples| {
+        let path = path.clone();
+        async move {
+            let opts = ChangesetPathSummaryHistoryOptions {
+                sampling,
+                max_samples,
+                until_timestamp: None,
+            };
+            path.summary_history(opts)
+                .await?
+                .map_ok(|(cs, summary)| {
+                    (
+                        cs.id(),
+                        summary.descendant_files_count,
+                        summary.descendant_files_total_size,
+                    )
+                })
+                .try_collect::<Vec<_>>()
+                .await
+        }
+    };
+
+    // The series stops before the directory was created.
+    assert_eq!(
+        history(SummaryHistorySampling::EveryNthCommit(1), 10).await?,
+        vec![
+            (commits[3], 3, 6),
+            (commits[2], 3, 6),
+            (commits[1], 2, 3),
+            (commits[0], 1, 1),
+        ]
+    );
+    assert_eq!(
+        history(SummaryHistorySampling::EveryNthCommit(2), 10).await?,
+        vec![(commits[3], 3, 6), (commits[1], 2, 3)]
+    );
+    assert_eq!(
+        history(SummaryHistorySampling::EveryNthCommit(1), 2).await?,
+        vec![(commits[3], 3, 6), (commits[2], 3, 6)]
+    );
+    assert_eq!(
+        history(SummaryHistorySampling::EveryNSeconds(1500), 10).await?,
+        vec![(commits[3], 3, 6), (commits[1], 2, 3)]
+    );
+
+    let tree = path.tree().await?.expect("dir is a tree");
+    let largest = tree.largest_subdirectories(10).await?;
+    assert_eq!(largest.len(), 1);
+    assert_eq!(largest[0].0, "sub");
+    assert_eq!(largest[0].1.descendant_files_total_size, 5);
+    assert!(tree.largest_subdirectories(0).await?.is_empty());
+
+    Ok(())
+}
+
 #[fbinit::compat_test]
 async fn file_metadata(fb: FacebookInit) -> Result<(), Error> {
     let ctx = CoreContext::test_mock(fb);
@@ -804,6 +1004,102 @@ async fn xrepo_commit_lookup_config_changing_live(fb: FacebookInit) -> Result<()
     Ok(())
 }
 
+#[fbinit::compat_test]
+async fn xrepo_diff(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let (mononoke, _cfg_src) = init_x_repo(&ctx).await?;
+
+    let smallrepo = mononoke
+        .repo(ctx.clone(), "smallrepo")
+        .await?
+        .expect("repo exists");
+    let small_master_cs_id = resolve_cs_id(&ctx, smallrepo.blob_repo(), "master").await?;
+    let largerepo = mononoke
+        .repo(ctx.clone(), "largerepo")
+        .await?
+        .expect("repo exists");
+    let large_master_cs_id = resolve_cs_id(&ctx, largerepo.blob_repo(), "master").await?;
+
+    // Synced commits have no divergences.
+    let small_master = smallrepo
+        .changeset(small_master_cs_id)
+        .await?
+        .expect("changeset exists");
+    let large_master = largerepo
+        .changeset(large_master_cs_id)
+        .await?
+        .expect("changeset exists");
+    assert!(
+        small_master
+            .diff_xrepo(&large_master, None)
+            .await?
+            .is_empty()
+    );
+    assert!(
+        large_master
+            .diff_xrepo(&small_master, None)
+            .await?
+            .is_empty()
+    );
+
+    let small_draft =
+        CreateCommitContext::new(&ctx, smallrepo.blob_repo(), vec![small_master_cs_id])
+            .add_file("remapped", "small content")
+            .add_file("only_small", "content")
+            .commit()
+            .await?;
+    let large_draft =
+        CreateCommitContext::new(&ctx, largerepo.blob_repo(), vec![large_master_cs_id])
+            .add_file("prefix/remapped", "large content")
+            .add_file("prefix/only_large", "content")
+            .add_file("not_remapped", "content")
+            .commit()
+            .await?;
+    let small_draft = smallrepo
+        .changeset(small_draft)
+        .await?
+        .expect("changeset exists");
+    let large_draft = largerepo
+        .changeset(large_draft)
+        .await?
+        .expect("changeset exists");
+
+    let diff = small_draft.diff_xrepo(&large_draft, None).await?;
+    assert_eq!(diff.len(), 3);
+    match &diff[..] {
+        [ChangesetPathDiffContext::Added(added), ChangesetPathDiffContext::Changed(to, from), ChangesetPathDiffContext::Removed(removed)] =>
+        {
+            assert_eq!(added.path(), &MononokePath::try_from("only_small")?);
+            assert_eq!(to.path(), &MononokePath::try_from("remapped")?);
+            assert_eq!(from.path(), &MononokePath::try_from("prefix/remapped")?);
+            assert_eq!(from.repo().name(), "largerepo");
+            assert_eq!(
+                removed.path(),
+                &MononokePath::try_from("prefix/only_large")?
+            );
+        }
+        _ => panic!("unexpected diff"),
+    }
+
+    // Path restrictions are in the coordinates of the first repo.
+    let diff = small_draft
+        .diff_xrepo(
+            &large_draft,
+            Some(vec![MononokePath::try_from("remapped")?]),
+        )
+        .await?;
+    assert_eq!(diff.len(), 1);
+    match diff.get(0) {
+        Some(ChangesetPathDiffContext::Changed(to, from)) => {
+            assert_eq!(to.path(), &MononokePath::try_from("remapped")?);
+            assert_eq!(from.path(), &MononokePath::try_from("prefix/remapped")?);
+        }
+        _ => panic!("unexpected diff"),
+    }
+
+    Ok(())
+}
+
 async fn init_x_repo(
     ctx: &CoreContext,
 ) -> Result<(Mononoke, TestLiveCommitSyncConfigSource), Error> {
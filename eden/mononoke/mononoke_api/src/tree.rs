@@ -102,6 +102,34 @@ impl TreeContext {
         Ok(summary)
     }
 
+    /// Returns the subdirectories of this tree with the largest total size of their
+    /// descendant files, largest first.
+    pub async fn largest_subdirectories(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<(String, TreeSummary)>, MononokeError> {
+        let fsnode = self.fsnode().await?;
+        let mut subdirs: Vec<_> = fsnode
+            .into_subentries()
+            .into_iter()
+            .filter_map(|(elem, entry)| match entry {
+                TreeEntry::Directory(dir) => Some((
+                    String::from_utf8_lossy(elem.as_ref()).to_string(),
+                    dir.summary().clone(),
+                )),
+                TreeEntry::File(_) => None,
+            })
+            .collect();
+        subdirs.sort_by(|(name_a, summary_a), (name_b, summary_b)| {
+            summary_b
+                .descendant_files_total_size
+                .cmp(&summary_a.descendant_files_total_size)
+                .then_with(|| name_a.cmp(name_b))
+        });
+        subdirs.truncate(limit);
+        Ok(subdirs)
+    }
+
     pub async fn list(&self) -> Result<impl Iterator<Item = (String, TreeEntry)>, MononokeError> {
         let fsnode = self.fsnode().await?;
         let entries = fsnode
@@ -5,21 +5,31 @@
  * GNU General Public License version 2.
  */
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use anyhow::{self, format_err, Context};
 use blobrepo::BlobRepo;
-use blobrepo_hg::BlobRepoHg;
+use blobrepo_hg::{BlobRepoHg, ChangesetHandle, CreateChangeset};
+use blobstore::Blobstore;
 use bytes::Bytes;
 use context::CoreContext;
-use futures::compat::Stream01CompatExt;
-use futures::{future, stream, Stream, StreamExt, TryStream, TryStreamExt};
+use filestore::{Alias, FetchKey, StoreRequest};
+use futures::compat::{Future01CompatExt, Stream01CompatExt};
+use futures::{future, stream, FutureExt, Stream, StreamExt, TryStream, TryStreamExt};
 use hgproto::GettreepackArgs;
-use mercurial_types::blobs::RevlogChangeset;
-use mercurial_types::{HgChangesetId, HgFileNodeId, HgManifestId};
+use mercurial_types::blobs::{
+    ChangesetMetadata, ContentBlobMeta, RevlogChangeset, UploadHgFileContents, UploadHgFileEntry,
+    UploadHgNodeHash, UploadHgTreeEntry,
+};
+use mercurial_types::{HgChangesetId, HgFileNodeId, HgManifestId, HgNodeHash, RepoPath, NULL_HASH};
 use metaconfig_types::RepoConfig;
-use mononoke_api::{errors::MononokeError, path::MononokePath, repo::RepoContext};
-use mononoke_types::{ChangesetId, MPath};
+use mononoke_api::{
+    errors::MononokeError,
+    path::MononokePath,
+    repo::{BookmarkFreshness, RepoContext},
+    ChangesetSpecifier,
+};
+use mononoke_types::{hash::Sha256, ChangesetId, ContentMetadata, MPath};
 use repo_client::gettreepack_entries;
 use segmented_changelog::{CloneData, StreamCloneData, Vertex};
 
@@ -55,6 +65,12 @@ impl HgRepoContext {
         self.repo.config()
     }
 
+    /// Check the user is permitted to write to this repository. The
+    /// `store_*` methods check this before storing anything.
+    pub async fn check_write_permissions(&self) -> Result<(), MononokeError> {
+        self.repo.check_write_permissions().await
+    }
+
     /// Look up a file in the repo by `HgFileNodeId`.
     pub async fn file(
         &self,
@@ -151,6 +167,143 @@ impl HgRepoContext {
             .map_err(MononokeError::from)
     }
 
+    /// This provides the same functionality as
+    /// `mononoke_api::RepoContext::segmented_changelog_graph_slice`, translating the commits
+    /// to and from their Mercurial identifiers.
+    pub async fn commit_graph(
+        &self,
+        heads: Vec<HgChangesetId>,
+        depth: u64,
+    ) -> Result<Vec<(HgChangesetId, Vec<HgChangesetId>)>, MononokeError> {
+        let head_mapping = self
+            .blob_repo()
+            .get_hg_bonsai_mapping(self.ctx().clone(), heads.clone())
+            .await
+            .context("error fetching hg bonsai mapping")?
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let head_csids = heads
+            .iter()
+            .map(|hg_cs_id| {
+                head_mapping.get(hg_cs_id).copied().ok_or_else(|| {
+                    MononokeError::InvalidRequest(format!("hg changeset {} not found", hg_cs_id))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let slice = self
+            .repo()
+            .segmented_changelog_graph_slice(head_csids, depth)
+            .await?;
+
+        let csids = slice
+            .iter()
+            .flat_map(|(csid, parents)| std::iter::once(*csid).chain(parents.iter().copied()))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let mapping = self
+            .blob_repo()
+            .get_hg_bonsai_mapping(self.ctx().clone(), csids)
+            .await
+            .context("error fetching hg bonsai mapping")?
+            .into_iter()
+            .map(|(hgid, csid)| (csid, hgid))
+            .collect::<HashMap<_, _>>();
+        let to_hg = |csid: ChangesetId| {
+            mapping.get(&csid).copied().ok_or_else(|| {
+                MononokeError::from(format_err!(
+                    "failed to find bonsai '{}' mapping to hg",
+                    csid
+                ))
+            })
+        };
+        slice
+            .into_iter()
+            .map(|(csid, parents)| {
+                let parents = parents
+                    .into_iter()
+                    .map(to_hg)
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok((to_hg(csid)?, parents))
+            })
+            .collect()
+    }
+
+    /// Returns `true` if `ancestor` is an ancestor of `descendant`, using the skiplist index.
+    /// A commit is considered its own ancestor.
+    pub async fn is_ancestor(
+        &self,
+        ancestor: HgChangesetId,
+        descendant: HgChangesetId,
+    ) -> Result<bool, MononokeError> {
+        let resolve = |hg_cs_id: HgChangesetId| async move {
+            self.repo()
+                .changeset(ChangesetSpecifier::Hg(hg_cs_id))
+                .await?
+                .ok_or_else(|| {
+                    MononokeError::InvalidRequest(format!("hg changeset {} not found", hg_cs_id))
+                })
+        };
+        let (ancestor, descendant) =
+            future::try_join(resolve(ancestor), resolve(descendant)).await?;
+        ancestor.is_ancestor_of(descendant.id()).await
+    }
+
+    /// Resolve a bookmark to the Mercurial commit it points to. The warm
+    /// bookmarks cache is consulted first, so the result may be slightly stale.
+    pub async fn resolve_bookmark(
+        &self,
+        bookmark: impl AsRef<str>,
+    ) -> Result<Option<HgChangesetId>, MononokeError> {
+        let maybe_cs_id = self
+            .repo()
+            .resolve_bookmark(bookmark, BookmarkFreshness::MaybeStale)
+            .await?
+            .map(|cs| cs.id());
+        match maybe_cs_id {
+            Some(cs_id) => Ok(Some(
+                self.blob_repo()
+                    .get_hg_from_bonsai_changeset(self.ctx().clone(), cs_id)
+                    .await?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// List the warm publishing bookmarks whose names start with `prefix`,
+    /// along with the Mercurial commits they point to, sorted by name.
+    pub async fn bookmarks_with_prefix(
+        &self,
+        prefix: impl AsRef<str>,
+    ) -> Result<Vec<(String, HgChangesetId)>, MononokeError> {
+        let prefix = prefix.as_ref();
+        let mut bookmarks = self
+            .repo()
+            .warm_bookmarks_cache()
+            .get_all()
+            .into_iter()
+            .filter_map(|(name, (cs_id, _kind))| {
+                let name = name.into_string();
+                if name.starts_with(prefix) {
+                    Some((name, cs_id))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        bookmarks.sort();
+
+        let hg_id_futures = bookmarks.into_iter().map(|(name, cs_id)| async move {
+            let hg_cs_id = self
+                .blob_repo()
+                .get_hg_from_bonsai_changeset(self.ctx().clone(), cs_id)
+                .await?;
+            Ok::<_, MononokeError>((name, hg_cs_id))
+        });
+        future::try_join_all(hg_id_futures).await
+    }
+
     pub async fn revlog_commit_data(
         &self,
         hg_cs_id: HgChangesetId,
@@ -172,6 +325,240 @@ impl HgRepoContext {
         Ok(Some(buffer.into()))
     }
 
+    /// Check whether file content with the given SHA-256 hash is present in the repo.
+    pub async fn file_content_exists(&self, sha256: Sha256) -> Result<bool, MononokeError> {
+        let key = FetchKey::Aliased(Alias::Sha256(sha256));
+        Ok(filestore::exists(self.blob_repo().blobstore(), self.ctx(), &key).await?)
+    }
+
+    /// Fetch file content from the repo's filestore by its canonical content id
+    /// or by one of its hash aliases. Returns `None` if the content is not present.
+    pub async fn file_content(&self, key: FetchKey) -> Result<Option<Bytes>, MononokeError> {
+        Ok(filestore::fetch_concat_opt(self.blob_repo().blobstore(), self.ctx(), &key).await?)
+    }
+
+    /// Check whether a Mercurial filenode is present in the repo.
+    pub async fn filenode_exists(&self, filenode_id: HgFileNodeId) -> Result<bool, MononokeError> {
+        Ok(self
+            .blob_repo()
+            .blobstore()
+            .is_present(self.ctx(), &filenode_id.blobstore_key())
+            .await?)
+    }
+
+    /// Check whether a Mercurial tree manifest is present in the repo.
+    pub async fn tree_exists(&self, manifest_id: HgManifestId) -> Result<bool, MononokeError> {
+        Ok(self
+            .blob_repo()
+            .blobstore()
+            .is_present(self.ctx(), &manifest_id.blobstore_key())
+            .await?)
+    }
+
+    /// Check whether a Mercurial changeset is present in the repo.
+    pub async fn changeset_exists(&self, hg_cs_id: HgChangesetId) -> Result<bool, MononokeError> {
+        Ok(self
+            .blob_repo()
+            .get_bonsai_from_hg(self.ctx().clone(), hg_cs_id)
+            .await?
+            .is_some())
+    }
+
+    /// Store file content in the repo's filestore.
+    pub async fn store_file_content(&self, data: Bytes) -> Result<ContentMetadata, MononokeError> {
+        self.check_write_permissions().await?;
+        let request = StoreRequest::new(data.len() as u64);
+        Ok(filestore::store(
+            self.blob_repo().blobstore(),
+            self.blob_repo().filestore_config(),
+            self.ctx(),
+            &request,
+            stream::once(future::ok(data)),
+        )
+        .await?)
+    }
+
+    /// Store a Mercurial filenode for file content that has already been
+    /// stored with `store_file_content`. The filenode id is verified against
+    /// the content, parents and copy information.
+    pub async fn store_hg_filenode(
+        &self,
+        filenode_id: HgFileNodeId,
+        p1: Option<HgFileNodeId>,
+        p2: Option<HgFileNodeId>,
+        content_sha256: Sha256,
+        copy_from: Option<(MPath, HgFileNodeId)>,
+    ) -> Result<(), MononokeError> {
+        self.check_write_permissions().await?;
+        let key = FetchKey::Aliased(Alias::Sha256(content_sha256));
+        let metadata = filestore::get_metadata(self.blob_repo().blobstore(), self.ctx(), &key)
+            .await?
+            .ok_or_else(|| {
+                MononokeError::InvalidRequest(format!(
+                    "file content with sha256 {} not found",
+                    content_sha256
+                ))
+            })?;
+
+        let upload = UploadHgFileEntry {
+            upload_node_id: UploadHgNodeHash::Checked(filenode_id.into_nodehash()),
+            contents: UploadHgFileContents::ContentUploaded(ContentBlobMeta {
+                id: metadata.content_id,
+                size: metadata.total_size,
+                copy_from,
+            }),
+            p1,
+            p2,
+        };
+        upload
+            .upload(
+                self.ctx().clone(),
+                self.blob_repo().get_blobstore().boxed(),
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Store a Mercurial tree manifest. The manifest id is verified against
+    /// the contents and parents. The entries of the tree don't need to be
+    /// present yet; they are checked when a changeset referencing the tree is
+    /// created.
+    pub async fn store_hg_tree(
+        &self,
+        manifest_id: HgManifestId,
+        path: RepoPath,
+        p1: Option<HgManifestId>,
+        p2: Option<HgManifestId>,
+        contents: Bytes,
+    ) -> Result<(), MononokeError> {
+        self.check_write_permissions().await?;
+        let upload = UploadHgTreeEntry {
+            upload_node_id: UploadHgNodeHash::Checked(manifest_id.into_nodehash()),
+            contents,
+            p1: p1.map(HgManifestId::into_nodehash),
+            p2: p2.map(HgManifestId::into_nodehash),
+            path,
+        };
+        let (_, upload) =
+            upload.upload(self.ctx().clone(), self.blob_repo().get_blobstore().boxed())?;
+        upload.compat().await?;
+        Ok(())
+    }
+
+    /// Create Mercurial changesets, along with their bonsai counterparts. All
+    /// of the trees and filenodes the changesets refer to must already be
+    /// stored. A changeset may have an earlier changeset in the same batch as
+    /// its parent. Returns the bonsai changeset id for each changeset.
+    pub async fn store_hg_changesets(
+        &self,
+        changesets: Vec<(HgChangesetId, RevlogChangeset)>,
+    ) -> Result<Vec<(HgChangesetId, ChangesetId)>, MononokeError> {
+        self.check_write_permissions().await?;
+        let ctx = self.ctx();
+        let blob_repo = self.blob_repo();
+
+        let mut handles: HashMap<HgChangesetId, ChangesetHandle> = HashMap::new();
+        let mut scheduled = Vec::new();
+        for (hg_cs_id, revlog_cs) in changesets {
+            let parent = |p: Option<HgNodeHash>| {
+                p.map(|p| {
+                    let p = HgChangesetId::new(p);
+                    handles.get(&p).cloned().unwrap_or_else(|| {
+                        ChangesetHandle::ready_cs_handle(ctx.clone(), blob_repo.clone(), p)
+                    })
+                })
+            };
+            let p1 = parent(revlog_cs.p1());
+            let p2 = parent(revlog_cs.p2());
+
+            let cs_metadata = ChangesetMetadata {
+                user: String::from_utf8(revlog_cs.user().to_vec())
+                    .with_context(|| format!("invalid user in changeset {}", hg_cs_id))?,
+                time: revlog_cs.time().clone(),
+                extra: revlog_cs.extra().clone(),
+                message: String::from_utf8(revlog_cs.message().to_vec())
+                    .with_context(|| format!("invalid message in changeset {}", hg_cs_id))?,
+            };
+
+            let root_manifest = match revlog_cs.manifestid() {
+                mfid if mfid.into_nodehash() == NULL_HASH => None,
+                mfid => Some((mfid, RepoPath::RootPath)),
+            };
+
+            let create_changeset = CreateChangeset {
+                expected_nodeid: Some(hg_cs_id.into_nodehash()),
+                expected_files: Some(Vec::from(revlog_cs.files())),
+                p1,
+                p2,
+                root_manifest: future::ok(root_manifest).boxed(),
+                // Trees and filenodes were uploaded separately, so there are no
+                // new entries to upload along with the changeset.
+                sub_entries: stream::empty().boxed(),
+                cs_metadata,
+                must_check_case_conflicts: true,
+                create_bonsai_changeset_hook: None,
+            };
+            let handle = create_changeset.create(ctx.clone(), blob_repo, ctx.scuba().clone());
+            handles.insert(hg_cs_id, handle.clone());
+            scheduled.push((hg_cs_id, handle));
+        }
+
+        let completed = scheduled.into_iter().map(|(hg_cs_id, handle)| async move {
+            let (bonsai, _) = handle
+                .get_completed_changeset()
+                .await
+                .map_err(anyhow::Error::from)
+                .with_context(|| format!("failed to create changeset {}", hg_cs_id))?;
+            Ok::<_, MononokeError>((hg_cs_id, bonsai.get_changeset_id()))
+        });
+        future::try_join_all(completed).await
+    }
+
+    /// Land a stack of commits onto a bookmark via pushrebase. `base` must be
+    /// an ancestor of `head`; the commits between them (excluding `base`) are
+    /// rebased. Returns the new bookmark position and the mapping from the
+    /// original commits to their rebased counterparts.
+    pub async fn land_stack(
+        &self,
+        bookmark: impl AsRef<str>,
+        head: HgChangesetId,
+        base: HgChangesetId,
+    ) -> Result<(HgChangesetId, Vec<(HgChangesetId, HgChangesetId)>), MononokeError> {
+        let to_bonsai = |hg_cs_id: HgChangesetId| async move {
+            self.blob_repo()
+                .get_bonsai_from_hg(self.ctx().clone(), hg_cs_id)
+                .await?
+                .ok_or_else(|| {
+                    MononokeError::InvalidRequest(format!("hg changeset {} not found", hg_cs_id))
+                })
+        };
+        let to_hg = |cs_id: ChangesetId| async move {
+            self.blob_repo()
+                .get_hg_from_bonsai_changeset(self.ctx().clone(), cs_id)
+                .await
+                .map_err(MononokeError::from)
+        };
+
+        let (head, base) = future::try_join(to_bonsai(head), to_bonsai(base)).await?;
+        let outcome = self
+            .repo()
+            .clone()
+            .write()
+            .await?
+            .land_stack(bookmark, head, base, None)
+            .await?;
+
+        let new_head = to_hg(outcome.head).await?;
+        let rebased = future::try_join_all(outcome.rebased_changesets.into_iter().map(
+            |pair| async move {
+                Ok::<_, MononokeError>((to_hg(pair.id_old).await?, to_hg(pair.id_new).await?))
+            },
+        ))
+        .await?;
+        Ok((new_head, rebased))
+    }
+
     pub async fn segmented_changelog_clone_data(
         &self,
     ) -> Result<CloneData<HgChangesetId>, MononokeError> {
@@ -265,15 +652,19 @@ async fn hg_convert_idmap_chunk(
 mod tests {
     use super::*;
 
-    use std::collections::BTreeSet;
+    use std::collections::{BTreeMap, BTreeSet};
     use std::sync::Arc;
 
     use anyhow::Error;
+    use async_trait::async_trait;
     use blobstore::Loadable;
     use fbinit::FacebookInit;
+    use mercurial_types::blobs::HgChangesetContent;
+    use mercurial_types::{HgBlobNode, HgParents};
     use mononoke_api::repo::Repo;
-    use mononoke_types::ChangesetId;
-    use tests_utils::CreateCommitContext;
+    use mononoke_types::DateTime;
+    use permission_checker::{MononokeIdentitySet, PermissionChecker};
+    use tests_utils::{bookmark, CreateCommitContext};
 
     use crate::RepoContextHgExt;
 
@@ -340,6 +731,318 @@ mod tests {
         Ok(())
     }
 
+    #[fbinit::compat_test]
+    async fn test_bookmarks(fb: FacebookInit) -> Result<(), MononokeError> {
+        let ctx = CoreContext::test_mock(fb);
+        let blob_repo = blobrepo_factory::new_memblob_empty(None)?;
+
+        let commit_1 = CreateCommitContext::new_root(&ctx, &blob_repo)
+            .add_file("a", "1")
+            .commit()
+            .await?;
+        let commit_2 = CreateCommitContext::new(&ctx, &blob_repo, vec![commit_1])
+            .add_file("a", "2")
+            .commit()
+            .await?;
+        bookmark(&ctx, &blob_repo, "master")
+            .set_to(commit_2)
+            .await?;
+        bookmark(&ctx, &blob_repo, "release/1")
+            .set_to(commit_1)
+            .await?;
+        bookmark(&ctx, &blob_repo, "release/2")
+            .set_to(commit_2)
+            .await?;
+
+        let hg_cs_id_1 = blob_repo
+            .get_hg_from_bonsai_changeset(ctx.clone(), commit_1)
+            .await?;
+        let hg_cs_id_2 = blob_repo
+            .get_hg_from_bonsai_changeset(ctx.clone(), commit_2)
+            .await?;
+
+        let repo = Repo::new_test(ctx.clone(), blob_repo).await?;
+        let repo_ctx = RepoContext::new(ctx, Arc::new(repo)).await?;
+        let hg = repo_ctx.hg();
+
+        assert_eq!(hg.resolve_bookmark("master").await?, Some(hg_cs_id_2));
+        assert_eq!(hg.resolve_bookmark("missing").await?, None);
+        assert_eq!(
+            hg.bookmarks_with_prefix("release/").await?,
+            vec![
+                ("release/1".to_string(), hg_cs_id_1),
+                ("release/2".to_string(), hg_cs_id_2),
+            ]
+        );
+        assert!(hg.bookmarks_with_prefix("stable/").await?.is_empty());
+
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_upload_lookup(fb: FacebookInit) -> Result<(), MononokeError> {
+        let ctx = CoreContext::test_mock(fb);
+        let blob_repo = blobrepo_factory::new_memblob_empty(None)?;
+
+        let commit = CreateCommitContext::new_root(&ctx, &blob_repo)
+            .add_file("a", "1")
+            .commit()
+            .await?;
+        let hg_cs_id = blob_repo
+            .get_hg_from_bonsai_changeset(ctx.clone(), commit)
+            .await?;
+        let root_mfid = root_manifest_id(ctx.clone(), &blob_repo, commit).await?;
+
+        let repo = Repo::new_test(ctx.clone(), blob_repo).await?;
+        let repo_ctx = RepoContext::new(ctx, Arc::new(repo)).await?;
+        let hg = repo_ctx.hg();
+
+        let metadata = hg.store_file_content(Bytes::from("content")).await?;
+        assert!(hg.file_content_exists(metadata.sha256).await?);
+        assert!(
+            !hg.file_content_exists(Sha256::from_byte_array([0x11; 32]))
+                .await?
+        );
+        assert_eq!(
+            hg.file_content(FetchKey::Aliased(Alias::Sha256(metadata.sha256)))
+                .await?,
+            Some(Bytes::from("content"))
+        );
+        assert_eq!(
+            hg.file_content(FetchKey::Canonical(metadata.content_id))
+                .await?,
+            Some(Bytes::from("content"))
+        );
+        assert_eq!(
+            hg.file_content(FetchKey::Aliased(Alias::Sha256(Sha256::from_byte_array(
+                [0x11; 32]
+            ))))
+            .await?,
+            None
+        );
+
+        assert!(hg.tree_exists(root_mfid).await?);
+        assert!(hg.changeset_exists(hg_cs_id).await?);
+        assert!(
+            !hg.changeset_exists(HgChangesetId::new(HgNodeHash::from_static_str(
+                "1111111111111111111111111111111111111111"
+            )?))
+            .await?
+        );
+
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_store_filenode_and_tree(fb: FacebookInit) -> Result<(), MononokeError> {
+        let ctx = CoreContext::test_mock(fb);
+        let blob_repo = blobrepo_factory::new_memblob_empty(None)?;
+        let repo = Repo::new_test(ctx.clone(), blob_repo).await?;
+        let repo_ctx = RepoContext::new(ctx, Arc::new(repo)).await?;
+        let hg = repo_ctx.hg();
+
+        let content = Bytes::from("content\n");
+        let metadata = hg.store_file_content(content.clone()).await?;
+        let filenode_id = HgFileNodeId::new(HgBlobNode::new(content, None, None).nodeid());
+        hg.store_hg_filenode(filenode_id, None, None, metadata.sha256, None)
+            .await?;
+        assert!(hg.filenode_exists(filenode_id).await?);
+
+        let tree = Bytes::from(format!("a\0{}\n", filenode_id));
+        let manifest_id = HgManifestId::new(HgBlobNode::new(tree.clone(), None, None).nodeid());
+        hg.store_hg_tree(manifest_id, RepoPath::RootPath, None, None, tree.clone())
+            .await?;
+        assert!(hg.tree_exists(manifest_id).await?);
+
+        // Ids that do not match the contents are rejected.
+        let bad_filenode_id = HgFileNodeId::new(HgNodeHash::from_static_str(
+            "1111111111111111111111111111111111111111",
+        )?);
+        assert!(hg
+            .store_hg_filenode(bad_filenode_id, None, None, metadata.sha256, None)
+            .await
+            .is_err());
+        assert!(!hg.filenode_exists(bad_filenode_id).await?);
+        let bad_manifest_id = HgManifestId::new(HgNodeHash::from_static_str(
+            "2222222222222222222222222222222222222222",
+        )?);
+        assert!(hg
+            .store_hg_tree(bad_manifest_id, RepoPath::RootPath, None, None, tree)
+            .await
+            .is_err());
+        assert!(!hg.tree_exists(bad_manifest_id).await?);
+
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_store_and_land_stack(fb: FacebookInit) -> Result<(), MononokeError> {
+        let ctx = CoreContext::test_mock(fb);
+        let blob_repo = blobrepo_factory::new_memblob_empty(None)?;
+        let base = CreateCommitContext::new_root(&ctx, &blob_repo)
+            .add_file("a", "a\n")
+            .commit()
+            .await?;
+        let master = CreateCommitContext::new(&ctx, &blob_repo, vec![base])
+            .add_file("m", "m\n")
+            .commit()
+            .await?;
+        bookmark(&ctx, &blob_repo, "master").set_to(master).await?;
+        let base_hg = blob_repo
+            .get_hg_from_bonsai_changeset(ctx.clone(), base)
+            .await?;
+        let base_tree = root_manifest_id(ctx.clone(), &blob_repo, base).await?;
+
+        let repo = Repo::new_test(ctx.clone(), blob_repo).await?;
+        let repo_ctx = RepoContext::new(ctx, Arc::new(repo)).await?;
+        let hg = repo_ctx.hg();
+
+        // Upload a stack of two commits on top of base, the way EdenAPI
+        // clients do. The second commit's parent is in the same batch.
+        let (b_id, b_cs, b_tree) = upload_commit_parts(&hg, base_hg, base_tree, "b").await?;
+        let (c_id, c_cs, _) = upload_commit_parts(&hg, b_id, b_tree, "c").await?;
+        let stored = hg
+            .store_hg_changesets(vec![(b_id, b_cs), (c_id, c_cs)])
+            .await?;
+        assert_eq!(
+            stored.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+            vec![b_id, c_id]
+        );
+        assert!(hg.changeset_exists(b_id).await?);
+        assert!(hg.changeset_exists(c_id).await?);
+
+        // Land the stack. It is rebased onto master.
+        let (new_head, rebased) = hg.land_stack("master", c_id, base_hg).await?;
+        assert_eq!(hg.resolve_bookmark("master").await?, Some(new_head));
+        let rebased: HashMap<_, _> = rebased.into_iter().collect();
+        assert_eq!(rebased.len(), 2);
+        assert_eq!(rebased[&c_id], new_head);
+        assert_ne!(rebased[&b_id], b_id);
+        assert!(hg.changeset_exists(rebased[&b_id]).await?);
+
+        Ok(())
+    }
+
+    /// Permits reading, but not writing.
+    struct ReadOnly;
+
+    #[async_trait]
+    impl PermissionChecker for ReadOnly {
+        async fn check_set(
+            &self,
+            _accessors: &MononokeIdentitySet,
+            actions: &[&str],
+        ) -> anyhow::Result<bool> {
+            Ok(actions.iter().all(|action| *action == "read"))
+        }
+    }
+
+    #[fbinit::compat_test]
+    async fn test_store_not_permitted(fb: FacebookInit) -> Result<(), MononokeError> {
+        let ctx = CoreContext::test_mock(fb);
+        let blob_repo = blobrepo_factory::new_memblob_empty(None)?;
+        let base = CreateCommitContext::new_root(&ctx, &blob_repo)
+            .add_file("a", "a\n")
+            .commit()
+            .await?;
+        bookmark(&ctx, &blob_repo, "master").set_to(base).await?;
+        let base_hg = blob_repo
+            .get_hg_from_bonsai_changeset(ctx.clone(), base)
+            .await?;
+        let base_tree = root_manifest_id(ctx.clone(), &blob_repo, base).await?;
+        let repo =
+            Repo::new_test_with_permission_checker(ctx.clone(), blob_repo, Arc::new(ReadOnly))
+                .await?;
+        let repo_ctx = RepoContext::new(ctx, Arc::new(repo)).await?;
+        let hg = repo_ctx.hg();
+
+        let content = Bytes::from("content\n");
+        match hg.store_file_content(content.clone()).await {
+            Err(MononokeError::PermissionDenied { mode, .. }) => assert_eq!(mode, "write"),
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+        let sha256 = Sha256::from_byte_array([0x11; 32]);
+        let filenode_id = HgFileNodeId::new(HgBlobNode::new(content, None, None).nodeid());
+        assert!(matches!(
+            hg.store_hg_filenode(filenode_id, None, None, sha256, None)
+                .await,
+            Err(MononokeError::PermissionDenied { .. })
+        ));
+
+        let revlog_cs = RevlogChangeset::new_from_parts(
+            HgParents::new(Some(base_hg.into_nodehash()), None),
+            base_tree,
+            b"test".to_vec(),
+            DateTime::from_timestamp(0, 0)?,
+            BTreeMap::new(),
+            Vec::new(),
+            b"not permitted".to_vec(),
+        );
+        let hg_cs_id = HgChangesetContent::from_revlogcs(revlog_cs.clone()).compute_hash()?;
+        assert!(matches!(
+            hg.store_hg_changesets(vec![(hg_cs_id, revlog_cs)]).await,
+            Err(MononokeError::PermissionDenied { .. })
+        ));
+        assert!(!hg.changeset_exists(hg_cs_id).await?);
+
+        match hg.land_stack("master", base_hg, base_hg).await {
+            Err(MononokeError::PermissionDenied { mode, .. }) => assert_eq!(mode, "write"),
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+        assert_eq!(hg.resolve_bookmark("master").await?, Some(base_hg));
+
+        Ok(())
+    }
+
+    /// Store a file at `path` and a root tree adding it to `parent_tree`.
+    /// Return the changeset on top of `parent` referring to them, without
+    /// storing it, and the root tree.
+    async fn upload_commit_parts(
+        hg: &HgRepoContext,
+        parent: HgChangesetId,
+        parent_tree: HgManifestId,
+        path: &str,
+    ) -> Result<(HgChangesetId, RevlogChangeset, HgManifestId), Error> {
+        let content = Bytes::from(format!("{}\n", path));
+        let metadata = hg.store_file_content(content.clone()).await?;
+        let filenode_id = HgFileNodeId::new(HgBlobNode::new(content, None, None).nodeid());
+        hg.store_hg_filenode(filenode_id, None, None, metadata.sha256, None)
+            .await?;
+
+        // Entries are sorted by name. New paths sort after existing ones.
+        let parent_content = hg
+            .tree(parent_tree)
+            .await?
+            .ok_or_else(|| format_err!("tree {} not found", parent_tree))?
+            .content_bytes();
+        let mut tree = parent_content.to_vec();
+        tree.extend_from_slice(format!("{}\0{}\n", path, filenode_id).as_bytes());
+        let tree = Bytes::from(tree);
+        let manifest_id = HgManifestId::new(
+            HgBlobNode::new(tree.clone(), Some(parent_tree.into_nodehash()), None).nodeid(),
+        );
+        hg.store_hg_tree(
+            manifest_id,
+            RepoPath::RootPath,
+            Some(parent_tree),
+            None,
+            tree,
+        )
+        .await?;
+
+        let revlog_cs = RevlogChangeset::new_from_parts(
+            HgParents::new(Some(parent.into_nodehash()), None),
+            manifest_id,
+            b"test".to_vec(),
+            DateTime::from_timestamp(0, 0)?,
+            BTreeMap::new(),
+            vec![MPath::new(path)?],
+            path.as_bytes().to_vec(),
+        );
+        let hg_cs_id = HgChangesetContent::from_revlogcs(revlog_cs.clone()).compute_hash()?;
+        Ok((hg_cs_id, revlog_cs, manifest_id))
+    }
+
     /// Get the HgManifestId of the root tree manifest for the given commit.
     async fn root_manifest_id(
         ctx: CoreContext,
@@ -8,7 +8,8 @@
 use std::cmp::min;
 #[deny(warnings)]
 use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 
 use anyhow::{Error, Result};
 use async_trait::async_trait;
@@ -21,7 +22,7 @@ use futures::future::try_join_all;
 use futures::stream::{futures_unordered::FuturesUnordered, TryStreamExt};
 use futures_util::try_join;
 use maplit::{hashmap, hashset};
-use slog::{info, Logger};
+use slog::{info, warn, Logger};
 use tokio::task;
 
 use changeset_fetcher::ChangesetFetcher;
@@ -36,6 +37,7 @@ use reachabilityindex::{errors::*, LeastCommonAncestorsHint, NodeFrontier, Reach
 use fbthrift::compact_protocol;
 
 pub mod sparse;
+pub mod tailer;
 
 const DEFAULT_EDGE_COUNT: u32 = 10;
 
@@ -167,6 +169,123 @@ pub async fn fetch_skiplist_index(
     }
 }
 
+/// Serialize a skiplist index for storage in the blobstore.
+///
+/// Only the latest skip edge (i.e. the edge with the longest jump) of each
+/// node is stored to save space.
+pub fn serialize_skiplist_index(index: &HashMap<ChangesetId, SkiplistNodeType>) -> Bytes {
+    let mut thrift_merge_graph = HashMap::new();
+    for (cs_id, skiplist_node_type) in index {
+        let skiplist_node_type = match skiplist_node_type {
+            SkiplistNodeType::SkipEdges(skip_edges) => {
+                SkiplistNodeType::SkipEdges(skip_edges.last().cloned().into_iter().collect())
+            }
+            _ => skiplist_node_type.clone(),
+        };
+
+        thrift_merge_graph.insert(cs_id.into_thrift(), skiplist_node_type.to_thrift());
+    }
+    compact_protocol::serialize(&thrift_merge_graph)
+}
+
+/// Blobstore key under which the bookmark update log id that the skiplist
+/// stored at `skiplist_key` is up to date with gets published. Servers watch
+/// this key to find out when to reload the skiplist.
+pub fn published_log_id_key(skiplist_key: &str) -> String {
+    format!("{}.published_log_id", skiplist_key)
+}
+
+async fn fetch_published_log_id(
+    ctx: &CoreContext,
+    blobstore: &Arc<dyn Blobstore>,
+    skiplist_key: &str,
+) -> Result<Option<u64>, Error> {
+    let maybebytes = blobstore
+        .get(ctx, &published_log_id_key(skiplist_key))
+        .await?;
+    match maybebytes {
+        Some(bytes) => {
+            let log_id = std::str::from_utf8(bytes.as_raw_bytes())?.parse()?;
+            Ok(Some(log_id))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Reload the skiplist stored at `skiplist_key` into `index` if a version
+/// newer than `last_published` has been published. Returns the log id of the
+/// currently published version.
+pub async fn reload_skiplist_index(
+    ctx: &CoreContext,
+    blobstore: &Arc<dyn Blobstore>,
+    skiplist_key: &str,
+    index: &SkiplistIndex,
+    last_published: Option<u64>,
+) -> Result<Option<u64>, Error> {
+    let published = fetch_published_log_id(ctx, blobstore, skiplist_key).await?;
+    if published.is_none() || published == last_published {
+        return Ok(published);
+    }
+    if let Some(bytes) = blobstore.get(ctx, skiplist_key).await? {
+        let bytes = bytes.into_raw_bytes();
+        let logger = ctx.logger().clone();
+        let skiplist =
+            task::spawn_blocking(move || deserialize_skiplist_index(logger, bytes)).await??;
+        let added = index.merge_missing(skiplist);
+        info!(
+            ctx.logger(),
+            "Reloaded skiplist published at log id {:?}, {} new nodes", published, added
+        );
+    }
+    Ok(published)
+}
+
+/// Periodically reload the skiplist stored at `skiplist_key` into `index`
+/// whenever the skiplist tailer publishes a new version. The interval is
+/// controlled by the `skiplist_reload_interval_secs` tunable; reloading is
+/// disabled while it is not positive. The task stops once `index` is
+/// dropped.
+pub fn spawn_skiplist_reloader(
+    ctx: CoreContext,
+    blobstore: Arc<dyn Blobstore>,
+    skiplist_key: String,
+    index: &Arc<SkiplistIndex>,
+) {
+    const DISABLED_POLL_INTERVAL: Duration = Duration::from_secs(60);
+    let index: Weak<SkiplistIndex> = Arc::downgrade(index);
+    task::spawn(async move {
+        let published = fetch_published_log_id(&ctx, &blobstore, &skiplist_key).await;
+        let mut last_published = match published {
+            Ok(published) => published,
+            Err(err) => {
+                warn!(
+                    ctx.logger(),
+                    "Failed to fetch published skiplist: {:?}", err
+                );
+                None
+            }
+        };
+        loop {
+            let interval = tunables::tunables().get_skiplist_reload_interval_secs();
+            if interval <= 0 {
+                tokio::time::delay_for(DISABLED_POLL_INTERVAL).await;
+                continue;
+            }
+            tokio::time::delay_for(Duration::from_secs(interval as u64)).await;
+            let index = match index.upgrade() {
+                Some(index) => index,
+                None => break,
+            };
+            match reload_skiplist_index(&ctx, &blobstore, &skiplist_key, &index, last_published)
+                .await
+            {
+                Ok(published) => last_published = published,
+                Err(err) => warn!(ctx.logger(), "Failed to reload skiplist: {:?}", err),
+            }
+        }
+    });
+}
+
 pub fn deserialize_skiplist_index(logger: Logger, bytes: Bytes) -> Result<SkiplistIndex> {
     let map: HashMap<_, skiplist_thrift::SkiplistNodeType> = compact_protocol::deserialize(&bytes)?;
     let cmap: CHashMap<ChangesetId, SkiplistNodeType> = CHashMap::with_capacity(map.len());
@@ -565,6 +684,20 @@ impl SkiplistIndex {
         self.skip_list_edges.mapping.len()
     }
 
+    /// Add the nodes of `other` that this index doesn't have yet, and return
+    /// how many were added. Nodes that were already indexed, e.g. lazily, are
+    /// kept as they are, so this can be used to refresh an index in use.
+    pub fn merge_missing(&self, other: SkiplistIndex) -> usize {
+        let mut added = 0;
+        for (cs_id, node) in other.get_all_skip_edges() {
+            if !self.skip_list_edges.mapping.contains_key(&cs_id) {
+                self.skip_list_edges.mapping.insert(cs_id, node);
+                added += 1;
+            }
+        }
+        added
+    }
+
     // Remove all but latest skip entry (i.e. entry with the longest jump) to save space.
     pub fn trim_to_single_entry_per_changeset(&self) {
         for (cs_id, old_node) in self.skip_list_edges.mapping.clone().into_iter() {
@@ -0,0 +1,347 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Keeps a sparse skiplist up to date by following the bookmark update log,
+//! instead of rebuilding it from scratch.
+
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Error, Result};
+use blobstore::{Blobstore, BlobstoreBytes};
+use bookmarks::{
+    BookmarkKind, BookmarkName, BookmarkPagination, BookmarkPrefix, BookmarkUpdateLog, Bookmarks,
+    Freshness,
+};
+use changeset_fetcher::ChangesetFetcher;
+use context::CoreContext;
+use futures::stream::TryStreamExt;
+use mononoke_types::{ChangesetId, RepositoryId};
+use slog::{debug, error, info};
+
+use crate::sparse::update_sparse_skiplist;
+use crate::{
+    deserialize_skiplist_index, published_log_id_key, serialize_skiplist_index, SkiplistNodeType,
+};
+
+/// Maximum number of bookmark update log entries processed at once.
+const LOG_ENTRIES_BATCH_SIZE: u64 = 1000;
+
+/// In-memory state of the tailer.
+pub struct SkiplistTailerState {
+    index: HashMap<ChangesetId, SkiplistNodeType>,
+    heads: HashMap<BookmarkName, ChangesetId>,
+    log_id: u64,
+    // Whether bookmarks moved since the index was last updated.
+    needs_update: bool,
+    // Whether the index changed since it was last persisted.
+    dirty: bool,
+}
+
+impl SkiplistTailerState {
+    /// Id of the last bookmark update log entry that was processed.
+    pub fn log_id(&self) -> u64 {
+        self.log_id
+    }
+
+    pub fn is_node_indexed(&self, cs_id: ChangesetId) -> bool {
+        self.index.contains_key(&cs_id)
+    }
+}
+
+pub struct SkiplistTailer {
+    repo_id: RepositoryId,
+    blobstore: Arc<dyn Blobstore>,
+    changeset_fetcher: Arc<dyn ChangesetFetcher>,
+    bookmarks: Arc<dyn Bookmarks>,
+    bookmark_update_log: Arc<dyn BookmarkUpdateLog>,
+    key: String,
+    max_skip: NonZeroU64,
+}
+
+impl SkiplistTailer {
+    pub fn new(
+        repo_id: RepositoryId,
+        blobstore: Arc<dyn Blobstore>,
+        changeset_fetcher: Arc<dyn ChangesetFetcher>,
+        bookmarks: Arc<dyn Bookmarks>,
+        bookmark_update_log: Arc<dyn BookmarkUpdateLog>,
+        key: String,
+        max_skip: NonZeroU64,
+    ) -> Self {
+        Self {
+            repo_id,
+            blobstore,
+            changeset_fetcher,
+            bookmarks,
+            bookmark_update_log,
+            key,
+            max_skip,
+        }
+    }
+
+    /// Tail the bookmark update log forever, checking for new entries every
+    /// `delay` and persisting the skiplist at most every `persist_interval`.
+    pub async fn run(&self, ctx: &CoreContext, delay: Duration, persist_interval: Duration) {
+        let mut state = None;
+        let mut last_persisted = Instant::now();
+        loop {
+            let result = async {
+                if state.is_none() {
+                    state = Some(self.load(ctx).await?);
+                }
+                let state = state.as_mut().expect("state was just loaded");
+                self.tail(ctx, state).await?;
+                if state.dirty && last_persisted.elapsed() >= persist_interval {
+                    self.persist(ctx, state).await?;
+                    last_persisted = Instant::now();
+                }
+                Result::<_, Error>::Ok(())
+            }
+            .await;
+            if let Err(err) = result {
+                error!(
+                    ctx.logger(),
+                    "repo {}: failed to update skiplist: {:?}", self.repo_id, err
+                );
+                // Start over from the persisted state, the in-memory one
+                // might be inconsistent.
+                state = None;
+            }
+            debug!(
+                ctx.logger(),
+                "repo {}: sleeping for {} seconds",
+                self.repo_id,
+                delay.as_secs()
+            );
+            tokio::time::delay_for(delay).await;
+        }
+    }
+
+    /// Load the skiplist, process all pending bookmark moves and persist it.
+    pub async fn once(&self, ctx: &CoreContext) -> Result<SkiplistTailerState> {
+        let mut state = self.load(ctx).await?;
+        self.tail(ctx, &mut state).await?;
+        self.persist(ctx, &mut state).await?;
+        Ok(state)
+    }
+
+    /// Load the stored skiplist and the current positions of all publishing
+    /// bookmarks. The skiplist is extended to cover the bookmarks during the
+    /// next call to `tail`.
+    pub async fn load(&self, ctx: &CoreContext) -> Result<SkiplistTailerState> {
+        // Read the log id before the bookmarks, so that no bookmark move is
+        // missed. Moves that are already reflected in the bookmarks are
+        // harmless to process again.
+        let log_id = self
+            .bookmark_update_log
+            .get_largest_log_id(ctx.clone(), Freshness::MostRecent)
+            .await
+            .context("fetching largest bookmark update log id")?
+            .unwrap_or(0);
+        let heads = self
+            .bookmarks
+            .list(
+                ctx.clone(),
+                Freshness::MostRecent,
+                &BookmarkPrefix::empty(),
+                BookmarkKind::ALL_PUBLISHING,
+                &BookmarkPagination::FromStart,
+                std::u64::MAX,
+            )
+            .map_ok(|(bookmark, cs_id)| (bookmark.name, cs_id))
+            .try_collect()
+            .await
+            .context("listing bookmarks")?;
+
+        let index = match self.blobstore.get(ctx, &self.key).await? {
+            Some(bytes) => {
+                let logger = ctx.logger().clone();
+                let bytes = bytes.into_raw_bytes();
+                tokio::task::spawn_blocking(move || deserialize_skiplist_index(logger, bytes))
+                    .await??
+                    .get_all_skip_edges()
+            }
+            None => {
+                info!(
+                    ctx.logger(),
+                    "repo {}: no skiplist found at {}, starting from scratch",
+                    self.repo_id,
+                    self.key
+                );
+                HashMap::new()
+            }
+        };
+        info!(
+            ctx.logger(),
+            "repo {}: loaded skiplist with {} nodes, starting at log id {}",
+            self.repo_id,
+            index.len(),
+            log_id
+        );
+
+        Ok(SkiplistTailerState {
+            index,
+            heads,
+            log_id,
+            needs_update: true,
+            dirty: false,
+        })
+    }
+
+    /// Process all bookmark moves after `state.log_id()` and extend the
+    /// skiplist to cover the new bookmark positions.
+    pub async fn tail(&self, ctx: &CoreContext, state: &mut SkiplistTailerState) -> Result<()> {
+        loop {
+            let entries: Vec<_> = self
+                .bookmark_update_log
+                .read_next_bookmark_log_entries(
+                    ctx.clone(),
+                    state.log_id,
+                    LOG_ENTRIES_BATCH_SIZE,
+                    Freshness::MostRecent,
+                )
+                .try_collect()
+                .await
+                .context("reading bookmark update log")?;
+            let last_id = match entries.last() {
+                Some(entry) => entry.id as u64,
+                None => break,
+            };
+            for entry in entries {
+                match entry.to_changeset_id {
+                    Some(cs_id) => state.heads.insert(entry.bookmark_name, cs_id),
+                    None => state.heads.remove(&entry.bookmark_name),
+                };
+            }
+            debug!(
+                ctx.logger(),
+                "repo {}: processed bookmark update log up to id {}", self.repo_id, last_id
+            );
+            state.log_id = last_id;
+            state.needs_update = true;
+        }
+
+        if state.needs_update {
+            // The index is trimmed to the nodes reachable from the heads, so
+            // all heads have to be passed, not only the ones that moved.
+            let heads = state.heads.values().copied().collect();
+            update_sparse_skiplist(
+                ctx,
+                heads,
+                &mut state.index,
+                self.max_skip,
+                &self.changeset_fetcher,
+            )
+            .await
+            .context("updating skiplist")?;
+            state.needs_update = false;
+            state.dirty = true;
+        }
+        Ok(())
+    }
+
+    /// Store the skiplist and publish it, so that servers reload it.
+    pub async fn persist(&self, ctx: &CoreContext, state: &mut SkiplistTailerState) -> Result<()> {
+        let bytes = serialize_skiplist_index(&state.index);
+        debug!(
+            ctx.logger(),
+            "repo {}: storing {} bytes",
+            self.repo_id,
+            bytes.len()
+        );
+        self.blobstore
+            .put(ctx, self.key.clone(), BlobstoreBytes::from_bytes(bytes))
+            .await
+            .context("storing skiplist")?;
+        // Publish only after the skiplist itself was stored.
+        self.blobstore
+            .put(
+                ctx,
+                published_log_id_key(&self.key),
+                BlobstoreBytes::from_bytes(state.log_id.to_string()),
+            )
+            .await
+            .context("publishing skiplist")?;
+        state.dirty = false;
+        info!(
+            ctx.logger(),
+            "repo {}: published skiplist with {} nodes at log id {}",
+            self.repo_id,
+            state.index.len(),
+            state.log_id
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{reload_skiplist_index, SkiplistIndex};
+    use blobrepo::BlobRepo;
+    use fbinit::FacebookInit;
+    use fixtures::linear;
+    use tests_utils::{bookmark, CreateCommitContext};
+
+    fn tailer(repo: &BlobRepo) -> SkiplistTailer {
+        SkiplistTailer::new(
+            repo.get_repoid(),
+            Arc::new(repo.get_blobstore()),
+            repo.get_changeset_fetcher(),
+            repo.bookmarks(),
+            repo.bookmarks_log(),
+            "skiplist".to_string(),
+            NonZeroU64::new(2).unwrap(),
+        )
+    }
+
+    #[fbinit::compat_test]
+    async fn test_tail_bookmark_moves(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = linear::getrepo(fb).await;
+        let blobstore: Arc<dyn Blobstore> = Arc::new(repo.get_blobstore());
+
+        let state = tailer(&repo).once(&ctx).await?;
+        let master = repo
+            .get_bonsai_bookmark(ctx.clone(), &BookmarkName::new("master")?)
+            .await?
+            .expect("master exists");
+        assert!(state.is_node_indexed(master));
+        let published =
+            reload_skiplist_index(&ctx, &blobstore, "skiplist", &SkiplistIndex::new(), None)
+                .await?;
+        assert_eq!(published, Some(state.log_id()));
+
+        let tailer = tailer(&repo);
+        let mut state = tailer.load(&ctx).await?;
+        let old_log_id = state.log_id();
+        let new_master = CreateCommitContext::new(&ctx, &repo, vec![master])
+            .add_file("new_file", "content")
+            .commit()
+            .await?;
+        bookmark(&ctx, &repo, "master").set_to(new_master).await?;
+        assert!(!state.is_node_indexed(new_master));
+        tailer.tail(&ctx, &mut state).await?;
+        assert!(state.is_node_indexed(new_master));
+        assert!(state.log_id() > old_log_id);
+        tailer.persist(&ctx, &mut state).await?;
+
+        // A server holding the old skiplist picks up the new nodes.
+        let index = SkiplistIndex::new();
+        let old = reload_skiplist_index(&ctx, &blobstore, "skiplist", &index, None).await?;
+        assert_eq!(old, Some(state.log_id()));
+        assert!(index.is_node_indexed(new_master));
+        assert_eq!(
+            reload_skiplist_index(&ctx, &blobstore, "skiplist", &index, old).await?,
+            old
+        );
+        Ok(())
+    }
+}
@@ -22,4 +22,14 @@ pub enum ErrorKind {
     ParentsFetchFailed,
     #[error("Bonsai mapping not found for {0}")]
     BonsaiMappingNotFound(HgChangesetId),
+    #[error("invalid revset at position {0}: {1}")]
+    InvalidRevset(usize, String),
+    #[error("unknown revision '{0}'")]
+    UnknownRevision(String),
+    #[error("revset has {0} nodes, more than the limit of {1}")]
+    RevsetTooComplex(usize, usize),
+    #[error("revset visited more than {0} commits")]
+    RevsetCostExceeded(u64),
+    #[error("{0} must be used to filter another set, e.g. 'ancestors(master) & {0}'")]
+    UnboundedRevsetFilter(String),
 }
@@ -0,0 +1,391 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use crate::errors::ErrorKind;
+
+/// A parsed revset expression.
+///
+/// The grammar is a subset of Mercurial revsets:
+///
+/// ```text
+/// expr    := setop (('|' | '+') setop)*
+/// setop   := range (('&' | '-' | '%') range)*
+/// range   := '::' primary | primary ('::' primary)?
+/// primary := '(' expr ')' | name '(' [expr (',' expr)*] ')' | symbol
+/// ```
+///
+/// Symbols are bookmark names or commit hashes. Symbols containing characters
+/// other than alphanumerics, `_`, `.` and `/` must be quoted.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RevsetExpr {
+    /// A bookmark name or commit hash.
+    Symbol(String),
+    /// `ancestors(x)` or `::x`.
+    Ancestors(Box<RevsetExpr>),
+    /// `x::y`: commits that are descendants of `x` and ancestors of `y`.
+    Range(Box<RevsetExpr>, Box<RevsetExpr>),
+    /// `x | y` or `x + y`.
+    Union(Box<RevsetExpr>, Box<RevsetExpr>),
+    /// `x & y`.
+    Intersection(Box<RevsetExpr>, Box<RevsetExpr>),
+    /// `x - y`. `only(x, y)` and `x % y` are parsed as
+    /// `ancestors(x) - ancestors(y)`.
+    Difference(Box<RevsetExpr>, Box<RevsetExpr>),
+    /// `author(pattern)`: commits whose author contains `pattern`, ignoring
+    /// case. Only valid as a filter of another set.
+    Author(String),
+}
+
+impl RevsetExpr {
+    /// Number of nodes in the expression.
+    pub fn size(&self) -> usize {
+        use RevsetExpr::*;
+        match self {
+            Symbol(_) | Author(_) => 1,
+            Ancestors(x) => 1 + x.size(),
+            Range(x, y) | Union(x, y) | Intersection(x, y) | Difference(x, y) => {
+                1 + x.size() + y.size()
+            }
+        }
+    }
+}
+
+/// Parse a revset expression.
+pub fn parse_revset(text: &str) -> Result<RevsetExpr, ErrorKind> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        end: text.len(),
+    };
+    let expr = parser.expr()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some((pos, token)) => Err(invalid(*pos, format!("unexpected {}", token))),
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    LParen,
+    RParen,
+    Comma,
+    Union,
+    Intersection,
+    Difference,
+    Only,
+    DoubleColon,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Word(w) => write!(f, "'{}'", w),
+            Token::Quoted(s) => write!(f, "\"{}\"", s),
+            Token::LParen => write!(f, "'('"),
+            Token::RParen => write!(f, "')'"),
+            Token::Comma => write!(f, "','"),
+            Token::Union => write!(f, "'|'"),
+            Token::Intersection => write!(f, "'&'"),
+            Token::Difference => write!(f, "'-'"),
+            Token::Only => write!(f, "'%'"),
+            Token::DoubleColon => write!(f, "'::'"),
+        }
+    }
+}
+
+fn invalid(pos: usize, msg: impl Into<String>) -> ErrorKind {
+    ErrorKind::InvalidRevset(pos, msg.into())
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.' || c == '/'
+}
+
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>, ErrorKind> {
+    let mut tokens = Vec::new();
+    let mut chars: Peekable<CharIndices> = text.char_indices().peekable();
+    while let Some((pos, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ',' => Token::Comma,
+            '|' | '+' => Token::Union,
+            '&' => Token::Intersection,
+            '-' => Token::Difference,
+            '%' => Token::Only,
+            ':' => match chars.next() {
+                Some((_, ':')) => Token::DoubleColon,
+                _ => return Err(invalid(pos, "expected '::'")),
+            },
+            '"' | '\'' => {
+                let quote = c;
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, c)) if c == quote => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, c)) => value.push(c),
+                            None => return Err(invalid(pos, "unterminated string")),
+                        },
+                        Some((_, c)) => value.push(c),
+                        None => return Err(invalid(pos, "unterminated string")),
+                    }
+                }
+                Token::Quoted(value)
+            }
+            c if is_word_char(c) => {
+                let mut value = c.to_string();
+                while let Some((_, c)) = chars.peek() {
+                    if !is_word_char(*c) {
+                        break;
+                    }
+                    value.push(*c);
+                    chars.next();
+                }
+                Token::Word(value)
+            }
+            c => return Err(invalid(pos, format!("unexpected character '{}'", c))),
+        };
+        tokens.push((pos, token));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&(usize, Token)> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<(usize, Token)> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        match self.peek() {
+            Some((_, t)) if t == token => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, token: &Token) -> Result<(), ErrorKind> {
+        match self.next() {
+            Some((_, ref t)) if t == token => Ok(()),
+            Some((pos, t)) => Err(invalid(pos, format!("expected {}, got {}", token, t))),
+            None => Err(invalid(self.end, format!("expected {}", token))),
+        }
+    }
+
+    fn expr(&mut self) -> Result<RevsetExpr, ErrorKind> {
+        let mut lhs = self.setop()?;
+        while self.eat(&Token::Union) {
+            let rhs = self.setop()?;
+            lhs = RevsetExpr::Union(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn setop(&mut self) -> Result<RevsetExpr, ErrorKind> {
+        let mut lhs = self.range()?;
+        loop {
+            lhs = if self.eat(&Token::Intersection) {
+                RevsetExpr::Intersection(Box::new(lhs), Box::new(self.range()?))
+            } else if self.eat(&Token::Difference) {
+                RevsetExpr::Difference(Box::new(lhs), Box::new(self.range()?))
+            } else if self.eat(&Token::Only) {
+                only(lhs, self.range()?)
+            } else {
+                return Ok(lhs);
+            };
+        }
+    }
+
+    fn range(&mut self) -> Result<RevsetExpr, ErrorKind> {
+        if self.eat(&Token::DoubleColon) {
+            return Ok(RevsetExpr::Ancestors(Box::new(self.primary()?)));
+        }
+        let lhs = self.primary()?;
+        if self.eat(&Token::DoubleColon) {
+            match self.peek() {
+                Some((_, Token::Word(_)))
+                | Some((_, Token::Quoted(_)))
+                | Some((_, Token::LParen)) => {
+                    Ok(RevsetExpr::Range(Box::new(lhs), Box::new(self.primary()?)))
+                }
+                Some((pos, _)) => Err(invalid(*pos, "descendants ('x::') are not supported")),
+                None => Err(invalid(self.end, "descendants ('x::') are not supported")),
+            }
+        } else {
+            Ok(lhs)
+        }
+    }
+
+    fn primary(&mut self) -> Result<RevsetExpr, ErrorKind> {
+        match self.next() {
+            Some((_, Token::LParen)) => {
+                let expr = self.expr()?;
+                self.expect(&Token::RParen)?;
+                Ok(expr)
+            }
+            Some((pos, Token::Word(name))) => {
+                if self.eat(&Token::LParen) {
+                    let args = self.args()?;
+                    function(pos, &name, args)
+                } else {
+                    Ok(RevsetExpr::Symbol(name))
+                }
+            }
+            Some((_, Token::Quoted(value))) => Ok(RevsetExpr::Symbol(value)),
+            Some((pos, t)) => Err(invalid(pos, format!("unexpected {}", t))),
+            None => Err(invalid(self.end, "unexpected end of expression")),
+        }
+    }
+
+    fn args(&mut self) -> Result<Vec<RevsetExpr>, ErrorKind> {
+        let mut args = Vec::new();
+        if self.eat(&Token::RParen) {
+            return Ok(args);
+        }
+        loop {
+            args.push(self.expr()?);
+            if !self.eat(&Token::Comma) {
+                self.expect(&Token::RParen)?;
+                return Ok(args);
+            }
+        }
+    }
+}
+
+fn only(x: RevsetExpr, y: RevsetExpr) -> RevsetExpr {
+    RevsetExpr::Difference(
+        Box::new(RevsetExpr::Ancestors(Box::new(x))),
+        Box::new(RevsetExpr::Ancestors(Box::new(y))),
+    )
+}
+
+fn function(pos: usize, name: &str, args: Vec<RevsetExpr>) -> Result<RevsetExpr, ErrorKind> {
+    let arg_count = |expected: usize| {
+        if args.len() == expected {
+            Ok(())
+        } else {
+            Err(invalid(
+                pos,
+                format!(
+                    "{}() takes {} arguments, got {}",
+                    name,
+                    expected,
+                    args.len()
+                ),
+            ))
+        }
+    };
+    match name {
+        "ancestors" => {
+            arg_count(1)?;
+            let mut args = args.into_iter();
+            Ok(RevsetExpr::Ancestors(Box::new(args.next().unwrap())))
+        }
+        "only" => {
+            arg_count(2)?;
+            let mut args = args.into_iter();
+            Ok(only(args.next().unwrap(), args.next().unwrap()))
+        }
+        "author" => {
+            arg_count(1)?;
+            match args.into_iter().next().unwrap() {
+                RevsetExpr::Symbol(pattern) => Ok(RevsetExpr::Author(pattern)),
+                _ => Err(invalid(pos, "author() takes a string argument")),
+            }
+        }
+        _ => Err(invalid(pos, format!("unknown function '{}'", name))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use RevsetExpr::*;
+
+    fn sym(s: &str) -> Box<RevsetExpr> {
+        Box::new(Symbol(s.to_string()))
+    }
+
+    fn ancestors(s: &str) -> Box<RevsetExpr> {
+        Box::new(Ancestors(sym(s)))
+    }
+
+    #[test]
+    fn parse_symbols() {
+        assert_eq!(parse_revset("master").unwrap(), *sym("master"));
+        assert_eq!(
+            parse_revset("\"release-2020\"").unwrap(),
+            *sym("release-2020")
+        );
+        assert_eq!(parse_revset(" (a/b.c) ").unwrap(), *sym("a/b.c"));
+    }
+
+    #[test]
+    fn parse_precedence() {
+        assert_eq!(
+            parse_revset("ancestors(master) - ancestors(release) & author(bob)").unwrap(),
+            Intersection(
+                Box::new(Difference(ancestors("master"), ancestors("release"))),
+                Box::new(Author("bob".to_string())),
+            )
+        );
+        assert_eq!(
+            parse_revset("a | b & c").unwrap(),
+            Union(sym("a"), Box::new(Intersection(sym("b"), sym("c"))))
+        );
+        assert_eq!(
+            parse_revset("::a + b::c").unwrap(),
+            Union(ancestors("a"), Box::new(Range(sym("b"), sym("c"))))
+        );
+    }
+
+    #[test]
+    fn parse_only() {
+        let expected = Difference(ancestors("a"), ancestors("b"));
+        assert_eq!(parse_revset("only(a, b)").unwrap(), expected);
+        assert_eq!(parse_revset("a % b").unwrap(), expected);
+        assert_eq!(expected.size(), 5);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error_pos = |text| match parse_revset(text) {
+            Err(ErrorKind::InvalidRevset(pos, _)) => pos,
+            other => panic!("unexpected result for {}: {:?}", text, other),
+        };
+        assert_eq!(error_pos("a &"), 3);
+        assert_eq!(error_pos("a b"), 2);
+        assert_eq!(error_pos("a::"), 3);
+        assert_eq!(error_pos("foo(a)"), 0);
+        assert_eq!(error_pos("ancestors(a, b)"), 0);
+        assert_eq!(error_pos("author(a | b)"), 0);
+        assert_eq!(error_pos("\"a"), 0);
+        assert_eq!(error_pos("a:b"), 1);
+        assert_eq!(error_pos("(a"), 2);
+    }
+}
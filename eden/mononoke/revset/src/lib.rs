@@ -40,6 +40,12 @@ pub use crate::ancestorscombinators::DifferenceOfUnionsOfAncestorsNodeStream;
 mod range;
 pub use crate::range::RangeNodeStream;
 
+mod expr;
+pub use crate::expr::{parse_revset, RevsetExpr};
+
+mod query;
+pub use crate::query::{ResolvedRange, RevsetEvaluator, RevsetLimits, RevsetResolver};
+
 use uniqueheap::UniqueHeap;
 
 pub use crate::test::*;
@@ -0,0 +1,578 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use anyhow::Error;
+use async_trait::async_trait;
+use cloned::cloned;
+use futures::compat::Future01CompatExt;
+use futures::future::{try_join_all, BoxFuture};
+use futures::{FutureExt, TryFutureExt};
+use futures_ext::StreamExt;
+use futures_old::future::Future;
+use futures_old::stream::{self, Stream};
+
+use changeset_fetcher::ChangesetFetcher;
+use context::CoreContext;
+use mononoke_types::ChangesetId;
+use reachabilityindex::LeastCommonAncestorsHint;
+
+use crate::errors::ErrorKind;
+use crate::expr::RevsetExpr;
+use crate::{
+    BonsaiNodeStream, DifferenceOfUnionsOfAncestorsNodeStream, IntersectNodeStream,
+    RangeNodeStream, SetDifferenceNodeStream, UnionNodeStream,
+};
+
+/// Resolves the repository-specific parts of a revset.
+#[async_trait]
+pub trait RevsetResolver: Send + Sync {
+    /// Resolve a bookmark name or commit hash to a changeset.
+    async fn resolve_symbol(
+        &self,
+        ctx: &CoreContext,
+        symbol: &str,
+    ) -> Result<Option<ChangesetId>, Error>;
+
+    /// Author of a changeset.
+    async fn author(&self, ctx: &CoreContext, cs_id: ChangesetId) -> Result<String, Error>;
+
+    /// Compute `roots::heads` without walking the graph, e.g. using segmented
+    /// changelog. Ranges with more than `limit` commits don't need to be
+    /// resolved, as evaluating them would exceed the cost limit anyway.
+    async fn range(
+        &self,
+        _ctx: &CoreContext,
+        _roots: Vec<ChangesetId>,
+        _heads: Vec<ChangesetId>,
+        _limit: u64,
+    ) -> Result<ResolvedRange, Error> {
+        Ok(ResolvedRange::Unsupported)
+    }
+}
+
+/// Outcome of `RevsetResolver::range`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResolvedRange {
+    /// The commits in the range, in any order.
+    Commits(Vec<ChangesetId>),
+    /// The range has more commits than the limit.
+    TooLarge,
+    /// The range can't be computed this way, so it is computed by walking
+    /// the graph.
+    Unsupported,
+}
+
+/// Limits on the cost of evaluating a revset.
+#[derive(Clone, Copy, Debug)]
+pub struct RevsetLimits {
+    /// Maximum number of nodes in the expression.
+    pub max_expression_size: usize,
+    /// Maximum number of commits produced by the expression and all of its
+    /// subexpressions.
+    pub max_commits_visited: u64,
+}
+
+impl Default for RevsetLimits {
+    fn default() -> Self {
+        Self {
+            max_expression_size: 50,
+            max_commits_visited: 100_000,
+        }
+    }
+}
+
+/// Compiles revset expressions into node streams.
+pub struct RevsetEvaluator {
+    ctx: CoreContext,
+    changeset_fetcher: Arc<dyn ChangesetFetcher>,
+    lca_hint: Arc<dyn LeastCommonAncestorsHint>,
+    resolver: Arc<dyn RevsetResolver>,
+    limits: RevsetLimits,
+    visited: Arc<AtomicU64>,
+}
+
+impl RevsetEvaluator {
+    pub fn new(
+        ctx: CoreContext,
+        changeset_fetcher: Arc<dyn ChangesetFetcher>,
+        lca_hint: Arc<dyn LeastCommonAncestorsHint>,
+        resolver: Arc<dyn RevsetResolver>,
+        limits: RevsetLimits,
+    ) -> Self {
+        Self {
+            ctx,
+            changeset_fetcher,
+            lca_hint,
+            resolver,
+            limits,
+            visited: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Evaluate `expr` into a stream of changesets in descending generation
+    /// order. Symbols are resolved before the stream is returned, and the
+    /// stream fails once the cost limit is exceeded.
+    pub async fn evaluate(&self, expr: &RevsetExpr) -> Result<BonsaiNodeStream, Error> {
+        let size = expr.size();
+        if size > self.limits.max_expression_size {
+            return Err(ErrorKind::RevsetTooComplex(size, self.limits.max_expression_size).into());
+        }
+        self.compile(expr).await
+    }
+
+    // Cannot use "async fn" because of the recursion.
+    fn compile<'a>(
+        &'a self,
+        expr: &'a RevsetExpr,
+    ) -> BoxFuture<'a, Result<BonsaiNodeStream, Error>> {
+        use RevsetExpr::*;
+        async move {
+            let ctx = self.ctx.clone();
+            let stream = match expr {
+                Symbol(symbol) => {
+                    stream::iter_ok::<_, Error>(vec![self.resolve(symbol).await?]).boxify()
+                }
+                Ancestors(x) => {
+                    let heads = self.collect(x).await?;
+                    DifferenceOfUnionsOfAncestorsNodeStream::new_union(
+                        ctx,
+                        &self.changeset_fetcher,
+                        self.lca_hint.clone(),
+                        heads,
+                    )
+                }
+                Range(x, y) => {
+                    let roots = self.collect(x).await?;
+                    let heads = self.collect(y).await?;
+                    self.range(roots, heads).await?
+                }
+                Union(x, y) => UnionNodeStream::new(
+                    ctx,
+                    &self.changeset_fetcher,
+                    vec![self.compile(x).await?, self.compile(y).await?],
+                )
+                .boxify(),
+                Intersection(x, y) => match (&**x, &**y) {
+                    (x, Author(pattern)) | (Author(pattern), x) => {
+                        self.filter_author(self.compile(x).await?, pattern, true)
+                    }
+                    (x, y) => IntersectNodeStream::new(
+                        ctx,
+                        &self.changeset_fetcher,
+                        vec![self.compile(x).await?, self.compile(y).await?],
+                    )
+                    .boxify(),
+                },
+                Difference(x, y) => match (&**x, &**y) {
+                    (x, Author(pattern)) => {
+                        self.filter_author(self.compile(x).await?, pattern, false)
+                    }
+                    (Ancestors(x), Ancestors(y)) => {
+                        // Excluding ancestors can skip whole parts of the graph
+                        // using the lca hint.
+                        let heads = self.collect(x).await?;
+                        let excludes = self.collect(y).await?;
+                        DifferenceOfUnionsOfAncestorsNodeStream::new_with_excludes(
+                            ctx,
+                            &self.changeset_fetcher,
+                            self.lca_hint.clone(),
+                            heads,
+                            excludes,
+                        )
+                    }
+                    (x, y) => SetDifferenceNodeStream::new(
+                        ctx,
+                        &self.changeset_fetcher,
+                        self.compile(x).await?,
+                        self.compile(y).await?,
+                    )
+                    .boxify(),
+                },
+                Author(_) => return Err(ErrorKind::UnboundedRevsetFilter("author()".into()).into()),
+            };
+            Ok(self.count_visited(stream))
+        }
+        .boxed()
+    }
+
+    async fn resolve(&self, symbol: &str) -> Result<ChangesetId, Error> {
+        self.resolver
+            .resolve_symbol(&self.ctx, symbol)
+            .await?
+            .ok_or_else(|| ErrorKind::UnknownRevision(symbol.to_string()).into())
+    }
+
+    async fn collect(&self, expr: &RevsetExpr) -> Result<Vec<ChangesetId>, Error> {
+        self.compile(expr).await?.collect().compat().await
+    }
+
+    async fn range(
+        &self,
+        roots: Vec<ChangesetId>,
+        heads: Vec<ChangesetId>,
+    ) -> Result<BonsaiNodeStream, Error> {
+        let ctx = &self.ctx;
+        // Everything the range returns is counted as visited, so a range
+        // larger than the remaining budget fails without looking at it.
+        let max_commits_visited = self.limits.max_commits_visited;
+        let limit = max_commits_visited.saturating_sub(self.visited.load(Ordering::Relaxed));
+        match self
+            .resolver
+            .range(ctx, roots.clone(), heads.clone(), limit)
+            .await?
+        {
+            ResolvedRange::Commits(cs_ids) => {
+                if cs_ids.len() as u64 > limit {
+                    return Err(ErrorKind::RevsetCostExceeded(max_commits_visited).into());
+                }
+                // Node streams must be in descending generation order.
+                let mut cs_ids = try_join_all(cs_ids.into_iter().map(|cs_id| async move {
+                    let generation = self
+                        .changeset_fetcher
+                        .get_generation_number(ctx.clone(), cs_id)
+                        .await?;
+                    Ok::<_, Error>((generation, cs_id))
+                }))
+                .await?;
+                cs_ids.sort_unstable_by(|a, b| b.cmp(a));
+                return Ok(
+                    stream::iter_ok::<_, Error>(cs_ids.into_iter().map(|(_, cs_id)| cs_id))
+                        .boxify(),
+                );
+            }
+            ResolvedRange::TooLarge => {
+                return Err(ErrorKind::RevsetCostExceeded(max_commits_visited).into());
+            }
+            ResolvedRange::Unsupported => {}
+        }
+
+        let mut ranges = Vec::with_capacity(roots.len() * heads.len());
+        for root in roots.iter() {
+            for head in heads.iter() {
+                ranges.push(
+                    RangeNodeStream::new(ctx.clone(), self.changeset_fetcher.clone(), *root, *head)
+                        .boxify(),
+                );
+            }
+        }
+        Ok(UnionNodeStream::new(ctx.clone(), &self.changeset_fetcher, ranges).boxify())
+    }
+
+    fn filter_author(
+        &self,
+        stream: BonsaiNodeStream,
+        pattern: &str,
+        keep_matching: bool,
+    ) -> BonsaiNodeStream {
+        let pattern = pattern.to_lowercase();
+        let ctx = self.ctx.clone();
+        let resolver = self.resolver.clone();
+        stream
+            .map(move |cs_id| {
+                cloned!(ctx, resolver);
+                async move { resolver.author(&ctx, cs_id).await }
+                    .boxed()
+                    .compat()
+                    .map(move |author| (cs_id, author))
+            })
+            .buffered(100)
+            .filter_map(move |(cs_id, author)| {
+                if author.to_lowercase().contains(&pattern) == keep_matching {
+                    Some(cs_id)
+                } else {
+                    None
+                }
+            })
+            .boxify()
+    }
+
+    fn count_visited(&self, stream: BonsaiNodeStream) -> BonsaiNodeStream {
+        let visited = self.visited.clone();
+        let max_commits_visited = self.limits.max_commits_visited;
+        stream
+            .and_then(move |cs_id| {
+                if visited.fetch_add(1, Ordering::Relaxed) >= max_commits_visited {
+                    Err(ErrorKind::RevsetCostExceeded(max_commits_visited).into())
+                } else {
+                    Ok(cs_id)
+                }
+            })
+            .boxify()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::async_unit;
+    use crate::expr::parse_revset;
+    use crate::fixtures::linear;
+    use blobrepo::BlobRepo;
+    use blobrepo_hg::BlobRepoHg;
+    use fbinit::FacebookInit;
+    use mercurial_types::{HgChangesetId, HgNodeHash};
+    use revset_test_helper::string_to_bonsai;
+    use skiplist::SkiplistIndex;
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    struct TestResolver {
+        repo: Arc<BlobRepo>,
+        bookmarks: HashMap<String, ChangesetId>,
+        authors: HashMap<ChangesetId, String>,
+        range: ResolvedRange,
+    }
+
+    #[async_trait]
+    impl RevsetResolver for TestResolver {
+        async fn resolve_symbol(
+            &self,
+            ctx: &CoreContext,
+            symbol: &str,
+        ) -> Result<Option<ChangesetId>, Error> {
+            if let Some(cs_id) = self.bookmarks.get(symbol) {
+                return Ok(Some(*cs_id));
+            }
+            match HgNodeHash::from_str(symbol) {
+                Ok(node) => {
+                    let hg_cs_id = HgChangesetId::new(node);
+                    self.repo.get_bonsai_from_hg(ctx.clone(), hg_cs_id).await
+                }
+                Err(_) => Ok(None),
+            }
+        }
+
+        async fn author(&self, _ctx: &CoreContext, cs_id: ChangesetId) -> Result<String, Error> {
+            Ok(self
+                .authors
+                .get(&cs_id)
+                .cloned()
+                .unwrap_or_else(|| "Bob <bob@example.com>".to_string()))
+        }
+
+        async fn range(
+            &self,
+            _ctx: &CoreContext,
+            _roots: Vec<ChangesetId>,
+            _heads: Vec<ChangesetId>,
+            _limit: u64,
+        ) -> Result<ResolvedRange, Error> {
+            Ok(self.range.clone())
+        }
+    }
+
+    async fn query(
+        ctx: &CoreContext,
+        repo: &Arc<BlobRepo>,
+        resolver: &Arc<TestResolver>,
+        limits: RevsetLimits,
+        text: &str,
+    ) -> Result<Vec<ChangesetId>, Error> {
+        let evaluator = RevsetEvaluator::new(
+            ctx.clone(),
+            repo.get_changeset_fetcher(),
+            Arc::new(SkiplistIndex::new()),
+            resolver.clone(),
+            limits,
+        );
+        let expr = parse_revset(text)?;
+        evaluator.evaluate(&expr).await?.collect().compat().await
+    }
+
+    #[fbinit::test]
+    fn linear_queries(fb: FacebookInit) {
+        async_unit::tokio_unit_test(async move {
+            let ctx = CoreContext::test_mock(fb);
+            let repo = Arc::new(linear::getrepo(fb).await);
+            let mut commits = Vec::new();
+            for hash in &[
+                "a9473beb2eb03ddb1cccc3fbaeb8a4820f9cd157",
+                "0ed509bf086fadcb8a8a5384dc3b550729b0fc17",
+                "eed3a8c0ec67b6a6fe2eb3543334df3f0b4f202b",
+                "cb15ca4a43a59acff5388cea9648c162afde8372",
+                "d0a361e9022d226ae52f689667bd7d212a19cfe0",
+            ] {
+                commits.push(string_to_bonsai(fb, &repo, hash).await);
+            }
+            let resolver = Arc::new(TestResolver {
+                repo: repo.clone(),
+                bookmarks: vec![
+                    ("master".to_string(), commits[0]),
+                    ("release".to_string(), commits[3]),
+                ]
+                .into_iter()
+                .collect(),
+                authors: vec![(commits[1], "Alice <alice@example.com>".to_string())]
+                    .into_iter()
+                    .collect(),
+                range: ResolvedRange::Unsupported,
+            });
+            let limits = RevsetLimits::default();
+
+            assert_eq!(
+                query(&ctx, &repo, &resolver, limits, "master % release")
+                    .await
+                    .unwrap(),
+                commits[0..3].to_vec(),
+            );
+            assert_eq!(
+                query(
+                    &ctx,
+                    &repo,
+                    &resolver,
+                    limits,
+                    "ancestors(master) - ancestors(release) & author(alice)"
+                )
+                .await
+                .unwrap(),
+                vec![commits[1]],
+            );
+            assert_eq!(
+                query(
+                    &ctx,
+                    &repo,
+                    &resolver,
+                    limits,
+                    "release::master - author(ALICE)"
+                )
+                .await
+                .unwrap(),
+                vec![commits[0], commits[2], commits[3]],
+            );
+            assert_eq!(
+                query(
+                    &ctx,
+                    &repo,
+                    &resolver,
+                    limits,
+                    "d0a361e9022d226ae52f689667bd7d212a19cfe0 | release"
+                )
+                .await
+                .unwrap(),
+                vec![commits[3], commits[4]],
+            );
+            assert_eq!(
+                query(
+                    &ctx,
+                    &repo,
+                    &resolver,
+                    limits,
+                    "::release & d0a361e9022d226ae52f689667bd7d212a19cfe0::master"
+                )
+                .await
+                .unwrap(),
+                vec![commits[3], commits[4]],
+            );
+
+            let error = query(&ctx, &repo, &resolver, limits, "author(alice)")
+                .await
+                .unwrap_err();
+            match error.downcast_ref::<ErrorKind>() {
+                Some(ErrorKind::UnboundedRevsetFilter(_)) => {}
+                _ => panic!("unexpected error: {:?}", error),
+            }
+
+            let error = query(&ctx, &repo, &resolver, limits, "missing")
+                .await
+                .unwrap_err();
+            match error.downcast_ref::<ErrorKind>() {
+                Some(ErrorKind::UnknownRevision(_)) => {}
+                _ => panic!("unexpected error: {:?}", error),
+            }
+
+            let small_limits = RevsetLimits {
+                max_expression_size: 2,
+                max_commits_visited: 3,
+            };
+            let error = query(&ctx, &repo, &resolver, small_limits, "master | release")
+                .await
+                .unwrap_err();
+            match error.downcast_ref::<ErrorKind>() {
+                Some(ErrorKind::RevsetTooComplex(3, 2)) => {}
+                _ => panic!("unexpected error: {:?}", error),
+            }
+            let error = query(&ctx, &repo, &resolver, small_limits, "::master")
+                .await
+                .unwrap_err();
+            match error.downcast_ref::<ErrorKind>() {
+                Some(ErrorKind::RevsetCostExceeded(3)) => {}
+                _ => panic!("unexpected error: {:?}", error),
+            }
+        })
+    }
+
+    #[fbinit::test]
+    fn resolved_ranges(fb: FacebookInit) {
+        async_unit::tokio_unit_test(async move {
+            let ctx = CoreContext::test_mock(fb);
+            let repo = Arc::new(linear::getrepo(fb).await);
+            let mut commits = Vec::new();
+            for hash in &[
+                "a9473beb2eb03ddb1cccc3fbaeb8a4820f9cd157",
+                "0ed509bf086fadcb8a8a5384dc3b550729b0fc17",
+                "eed3a8c0ec67b6a6fe2eb3543334df3f0b4f202b",
+                "cb15ca4a43a59acff5388cea9648c162afde8372",
+            ] {
+                commits.push(string_to_bonsai(fb, &repo, hash).await);
+            }
+            let resolver = |range| {
+                Arc::new(TestResolver {
+                    repo: repo.clone(),
+                    bookmarks: vec![
+                        ("master".to_string(), commits[0]),
+                        ("release".to_string(), commits[3]),
+                    ]
+                    .into_iter()
+                    .collect(),
+                    authors: HashMap::new(),
+                    range,
+                })
+            };
+            let limits = RevsetLimits {
+                max_expression_size: 50,
+                max_commits_visited: 6,
+            };
+
+            // Resolved ranges are returned in descending generation order.
+            let mut range = commits.clone();
+            range.reverse();
+            let resolved = resolver(ResolvedRange::Commits(range));
+            assert_eq!(
+                query(&ctx, &repo, &resolved, limits, "release::master")
+                    .await
+                    .unwrap(),
+                commits,
+            );
+
+            // Ranges larger than the budget left fail before their commits
+            // are looked at. Resolving the symbols already used two.
+            let tight_limits = RevsetLimits {
+                max_commits_visited: 5,
+                ..limits
+            };
+            let error = query(&ctx, &repo, &resolved, tight_limits, "release::master")
+                .await
+                .unwrap_err();
+            match error.downcast_ref::<ErrorKind>() {
+                Some(ErrorKind::RevsetCostExceeded(5)) => {}
+                _ => panic!("unexpected error: {:?}", error),
+            }
+
+            let too_large = resolver(ResolvedRange::TooLarge);
+            let error = query(&ctx, &repo, &too_large, limits, "release::master")
+                .await
+                .unwrap_err();
+            match error.downcast_ref::<ErrorKind>() {
+                Some(ErrorKind::RevsetCostExceeded(6)) => {}
+                _ => panic!("unexpected error: {:?}", error),
+            }
+        })
+    }
+}
@@ -18,7 +18,7 @@ use mononoke_api::specifiers::{GitSha1, Globalrev};
 use mononoke_api::{
     BookmarkName, CandidateSelectionHintArgs, ChangesetId, ChangesetIdPrefix,
     ChangesetPrefixSpecifier, ChangesetSpecifier, CopyInfo, CreateCopyInfo, FileId, FileType,
-    HgChangesetId, HgChangesetIdPrefix, MononokePath, TreeId,
+    HgChangesetId, HgChangesetIdPrefix, MononokePath, SummaryHistorySampling, TreeId,
 };
 use mononoke_types::hash::{Sha1, Sha256};
 use source_control as thrift;
@@ -81,6 +81,28 @@ impl FromRequest<thrift::CandidateSelectionHint> for CandidateSelectionHintArgs
     }
 }
 
+impl FromRequest<thrift::SummaryHistorySampling> for SummaryHistorySampling {
+    fn from_request(
+        sampling: &thrift::SummaryHistorySampling,
+    ) -> Result<Self, thrift::RequestError> {
+        match sampling {
+            thrift::SummaryHistorySampling::every_nth_commit(n) if *n > 0 => {
+                Ok(SummaryHistorySampling::EveryNthCommit(*n as u64))
+            }
+            thrift::SummaryHistorySampling::every_n_seconds(secs) if *secs > 0 => {
+                Ok(SummaryHistorySampling::EveryNSeconds(*secs))
+            }
+            thrift::SummaryHistorySampling::UnknownField(f) => Err(errors::invalid_request(
+                format!("unsupported summary history sampling: {:?}", f),
+            )),
+            other => Err(errors::invalid_request(format!(
+                "summary history sampling interval must be positive: {:?}",
+                other
+            ))),
+        }
+    }
+}
+
 impl FromRequest<thrift::CommitId> for ChangesetSpecifier {
     fn from_request(commit: &thrift::CommitId) -> Result<Self, thrift::RequestError> {
         match commit {
@@ -122,6 +122,28 @@ impl IntoResponse<thrift::TreeInfo> for (TreeId, TreeSummary) {
     }
 }
 
+impl IntoResponse<thrift::TreeSummary> for TreeSummary {
+    fn into_response(self) -> thrift::TreeSummary {
+        thrift::TreeSummary {
+            child_files_count: self.child_files_count as i64,
+            child_files_total_size: self.child_files_total_size as i64,
+            child_dirs_count: self.child_dirs_count as i64,
+            descendant_files_count: self.descendant_files_count as i64,
+            descendant_files_total_size: self.descendant_files_total_size as i64,
+        }
+    }
+}
+
+impl IntoResponse<thrift::TreeSubdirectory> for (String, TreeSummary) {
+    fn into_response(self) -> thrift::TreeSubdirectory {
+        let (name, summary) = self;
+        thrift::TreeSubdirectory {
+            name,
+            summary: summary.into_response(),
+        }
+    }
+}
+
 impl IntoResponse<thrift::Diff> for UnifiedDiff {
     fn into_response(self) -> thrift::Diff {
         thrift::Diff::raw_diff(thrift::RawDiff {
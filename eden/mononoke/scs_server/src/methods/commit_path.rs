@@ -11,7 +11,10 @@ use futures::stream::TryStreamExt;
 use futures::{future, try_join};
 use maplit::btreeset;
 use mononoke_api::MononokePath;
-use mononoke_api::{ChangesetPathHistoryOptions, ChangesetSpecifier, MononokeError, PathEntry};
+use mononoke_api::{
+    ChangesetPathHistoryOptions, ChangesetPathSummaryHistoryOptions, ChangesetSpecifier,
+    MononokeError, PathEntry, SummaryHistorySampling,
+};
 use source_control as thrift;
 use std::borrow::Cow;
 use std::collections::{BTreeMap, BTreeSet, HashMap};
@@ -19,7 +22,7 @@ use std::convert::TryFrom;
 
 use crate::commit_id::map_commit_identities;
 use crate::errors;
-use crate::from_request::{check_range_and_convert, validate_timestamp};
+use crate::from_request::{check_range_and_convert, validate_timestamp, FromRequest};
 use crate::history::collect_history;
 use crate::into_response::IntoResponse;
 use crate::source_control_impl::SourceControlServiceImpl;
@@ -377,4 +380,68 @@ impl SourceControlServiceImpl {
 
         Ok(thrift::CommitPathHistoryResponse { history })
     }
+
+    /// Returns how the size and file counts of the directory at a path grew,
+    /// sampled along first-parent history, and its largest subdirectories.
+    pub(crate) async fn commit_path_summary_history(
+        &self,
+        ctx: CoreContext,
+        commit_path: thrift::CommitPathSpecifier,
+        params: thrift::CommitPathSummaryHistoryParams,
+    ) -> Result<thrift::CommitPathSummaryHistoryResponse, errors::ServiceError> {
+        let (repo, changeset) = self.repo_changeset(ctx, &commit_path.commit).await?;
+        let path = changeset.path(&commit_path.path)?;
+        let sampling = SummaryHistorySampling::from_request(&params.sampling)?;
+        let max_samples: usize = check_range_and_convert(
+            "limit",
+            params.limit,
+            0..=source_control::COMMIT_PATH_SUMMARY_HISTORY_MAX_LIMIT,
+        )?;
+        let largest_subdirectories_limit: usize = check_range_and_convert(
+            "largest_subdirectories_limit",
+            params.largest_subdirectories_limit,
+            0..=source_control::TREE_LIST_MAX_LIMIT,
+        )?;
+        let after_timestamp = validate_timestamp(params.after_timestamp, "after_timestamp")?;
+
+        let largest_subdirectories = match path.tree().await? {
+            Some(tree) => tree
+                .largest_subdirectories(largest_subdirectories_limit)
+                .await?
+                .into_iter()
+                .map(IntoResponse::into_response)
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let history: Vec<_> = path
+            .summary_history(ChangesetPathSummaryHistoryOptions {
+                sampling,
+                max_samples,
+                until_timestamp: after_timestamp,
+            })
+            .await?
+            .try_collect()
+            .await?;
+        let csids = history.iter().map(|(cs, _)| cs.id()).collect();
+        let mut commit_ids = map_commit_identities(&repo, csids, &params.identity_schemes).await?;
+        let samples = future::try_join_all(history.into_iter().map(|(cs, summary)| {
+            let ids = commit_ids.remove(&cs.id()).unwrap_or_default();
+            async move {
+                let date = cs.author_date().await?;
+                Ok::<_, errors::ServiceError>(thrift::TreeSummarySample {
+                    ids,
+                    date: date.timestamp(),
+                    tz: date.offset().local_minus_utc(),
+                    summary: summary.into_response(),
+                })
+            }
+        }))
+        .await?;
+
+        Ok(thrift::CommitPathSummaryHistoryResponse {
+            samples,
+            largest_subdirectories,
+        })
+    }
 }
@@ -258,6 +258,29 @@ impl AddScubaParams for thrift::CommitPathHistoryParams {
     }
 }
 
+impl AddScubaParams for thrift::CommitPathSummaryHistoryParams {
+    fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        match self.sampling {
+            thrift::SummaryHistorySampling::every_nth_commit(n) => {
+                scuba.add("param_every_nth_commit", n);
+            }
+            thrift::SummaryHistorySampling::every_n_seconds(secs) => {
+                scuba.add("param_every_n_seconds", secs);
+            }
+            thrift::SummaryHistorySampling::UnknownField(_) => {}
+        }
+        scuba.add("param_limit", self.limit);
+        if let Some(after) = self.after_timestamp {
+            scuba.add("param_after_timestamp", after);
+        }
+        scuba.add(
+            "param_largest_subdirectories_limit",
+            self.largest_subdirectories_limit,
+        );
+        self.identity_schemes.add_scuba_params(scuba);
+    }
+}
+
 impl AddScubaParams for thrift::CommitPathInfoParams {}
 
 impl AddScubaParams for thrift::CommitMultiplePathInfoParams {
@@ -61,6 +61,8 @@ impl AddScubaResponse for thrift::CommitPathBlameResponse {}
 
 impl AddScubaResponse for thrift::CommitPathHistoryResponse {}
 
+impl AddScubaResponse for thrift::CommitPathSummaryHistoryResponse {}
+
 impl AddScubaResponse for thrift::CommitPathInfoResponse {}
 
 impl AddScubaResponse for thrift::CommitMultiplePathInfoResponse {}
@@ -518,6 +518,11 @@ impl SourceControlService for SourceControlServiceThriftImpl {
             params: thrift::CommitPathHistoryParams,
         ) -> Result<thrift::CommitPathHistoryResponse, service::CommitPathHistoryExn>;
 
+        async fn commit_path_summary_history(
+            commit_path: thrift::CommitPathSpecifier,
+            params: thrift::CommitPathSummaryHistoryParams,
+        ) -> Result<thrift::CommitPathSummaryHistoryResponse, service::CommitPathSummaryHistoryExn>;
+
         async fn tree_list(
             tree: thrift::TreeSpecifier,
             params: thrift::TreeListParams,
@@ -53,6 +53,7 @@ pub struct SegmentedChangelogBuilder {
     blobstore: Option<Arc<dyn Blobstore>>,
     bookmarks: Option<Arc<dyn Bookmarks>>,
     bookmark_name: Option<BookmarkName>,
+    tracked_bookmarks: Vec<BookmarkName>,
     cache_handlers: Option<CacheHandlers>,
 }
 
@@ -72,6 +73,7 @@ impl SqlConstruct for SegmentedChangelogBuilder {
             blobstore: None,
             bookmarks: None,
             bookmark_name: None,
+            tracked_bookmarks: Vec::new(),
             cache_handlers: None,
         }
     }
@@ -140,6 +142,7 @@ impl SegmentedChangelogBuilder {
             self.changeset_fetcher()?,
             self.bookmarks()?,
             self.bookmark_name()?,
+            std::mem::take(&mut self.tracked_bookmarks),
             self.build_manager()?,
         );
         Ok(tailer)
@@ -196,6 +199,11 @@ impl SegmentedChangelogBuilder {
         self
     }
 
+    pub fn with_tracked_bookmarks(mut self, tracked_bookmarks: Vec<BookmarkName>) -> Self {
+        self.tracked_bookmarks = tracked_bookmarks;
+        self
+    }
+
     pub fn with_caching(
         mut self,
         fb: FacebookInit,
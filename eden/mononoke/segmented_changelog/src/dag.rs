@@ -5,36 +5,51 @@
  * GNU General Public License version 2.
  */
 
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use anyhow::{format_err, Context, Result};
 use async_trait::async_trait;
 use futures::stream::{self, StreamExt, TryStreamExt};
-use maplit::hashset;
 use slog::{debug, trace};
 
 use cloned::cloned;
-use dag::{self, CloneData, Group, Id as Vertex, InProcessIdDag};
+use dag::{self, CloneData, FirstAncestorConstraint, Group, Id as Vertex, IdSet, InProcessIdDag};
 use stats::prelude::*;
 
 use context::CoreContext;
 use mononoke_types::ChangesetId;
 
 use crate::idmap::{IdMap, MemIdMap};
-use crate::{SegmentedChangelog, StreamCloneData};
+use crate::{Location, SegmentedChangelog, StreamCloneData};
 
 const IDMAP_CHANGESET_FETCH_BATCH: usize = 500;
 
+/// Largest depth that a graph slice may be requested with.
+pub const GRAPH_SLICE_MAX_DEPTH: u64 = 10_000;
+/// Largest number of commits that a graph slice may return.
+pub const GRAPH_SLICE_MAX_ENTRIES: usize = 100_000;
+
 define_stats! {
     prefix = "mononoke.segmented_changelog.dag";
     build: timeseries(Sum),
     location_to_changeset_id: timeseries(Sum),
+    graph_slice: timeseries(Sum),
+    changeset_id_to_location: timeseries(Sum),
+    is_ancestor: timeseries(Sum),
+    common_ancestors: timeseries(Sum),
+    range: timeseries(Sum),
 }
 
 // Note. The equivalent graph in the scm/lib/dag crate is `NameDag`.
 pub struct Dag {
     pub(crate) iddag: InProcessIdDag,
     pub(crate) idmap: Arc<dyn IdMap>,
+    // Vertex of the main bookmark when the dag was built. Tracked bookmarks may be assigned
+    // higher vertexes so the last assigned vertex is not necessarily the main bookmark.
+    pub(crate) master_head: Option<Vertex>,
 }
 
 #[async_trait]
@@ -52,6 +67,75 @@ impl SegmentedChangelog for Dag {
             .await
     }
 
+    async fn graph_slice(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        depth: u64,
+    ) -> Result<Vec<(ChangesetId, Vec<ChangesetId>)>> {
+        STATS::graph_slice.add_value(1);
+        let head_vertexes = self.get_vertexes(ctx, heads).await?;
+        self.known_graph_slice(ctx, head_vertexes, depth).await
+    }
+
+    async fn changeset_id_to_location(
+        &self,
+        ctx: &CoreContext,
+        master_heads: Vec<ChangesetId>,
+        cs_id: ChangesetId,
+    ) -> Result<Option<Location<ChangesetId>>> {
+        STATS::changeset_id_to_location.add_value(1);
+        let mut vertexes = match self.find_known_vertexes(ctx, vec![cs_id]).await? {
+            Some(vertexes) => vertexes,
+            None => return Ok(None),
+        };
+        let head_vertexes = self.get_vertexes(ctx, master_heads).await?;
+        self.known_changeset_id_to_location(ctx, head_vertexes, vertexes.remove(0))
+            .await
+    }
+
+    async fn is_ancestor(
+        &self,
+        ctx: &CoreContext,
+        ancestor: ChangesetId,
+        descendant: ChangesetId,
+    ) -> Result<Option<bool>> {
+        STATS::is_ancestor.add_value(1);
+        match self
+            .find_known_vertexes(ctx, vec![ancestor, descendant])
+            .await?
+        {
+            Some(vertexes) => self.known_is_ancestor(vertexes[0], vertexes[1]).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    async fn common_ancestors(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: Vec<ChangesetId>,
+    ) -> Result<Option<Vec<ChangesetId>>> {
+        STATS::common_ancestors.add_value(1);
+        match self.find_known_vertexes(ctx, cs_ids).await? {
+            Some(vertexes) => self.known_common_ancestors(ctx, vertexes).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    async fn range(
+        &self,
+        ctx: &CoreContext,
+        roots: Vec<ChangesetId>,
+        heads: Vec<ChangesetId>,
+        limit: u64,
+    ) -> Result<Option<Vec<ChangesetId>>> {
+        STATS::range.add_value(1);
+        let (root_vertexes, head_vertexes) =
+            futures::try_join!(self.get_vertexes(ctx, roots), self.get_vertexes(ctx, heads))?;
+        self.known_range(ctx, root_vertexes, head_vertexes, limit)
+            .await
+    }
+
     async fn clone_data(&self, ctx: &CoreContext) -> Result<CloneData<ChangesetId>> {
         let group = Group::MASTER;
         let head_id = self.clone_data_head_id()?;
@@ -86,11 +170,12 @@ impl SegmentedChangelog for Dag {
         const BUFFERED_BATCHES: usize = 5;
         let group = Group::MASTER;
         let head_id = self.clone_data_head_id()?;
+        let last_id = self.last_assigned_id()?;
         let flat_segments = self
             .iddag
             .flat_segments(group)
             .context("error during flat segment retrieval")?;
-        let idmap_stream = stream::iter((group.min_id().0..=head_id.0).into_iter().map(Vertex))
+        let idmap_stream = stream::iter((group.min_id().0..=last_id.0).into_iter().map(Vertex))
             .chunks(CHUNK_SIZE)
             .map({
                 cloned!(ctx, self.idmap);
@@ -114,7 +199,20 @@ impl SegmentedChangelog for Dag {
 
 impl Dag {
     pub fn new(iddag: InProcessIdDag, idmap: Arc<dyn IdMap>) -> Self {
-        Self { iddag, idmap }
+        Self {
+            iddag,
+            idmap,
+            master_head: None,
+        }
+    }
+
+    pub fn with_master_head(mut self, master_head: Option<Vertex>) -> Self {
+        self.master_head = master_head;
+        self
+    }
+
+    pub fn master_head(&self) -> Option<Vertex> {
+        self.master_head
     }
 
     pub(crate) async fn known_location_to_many_changeset_ids(
@@ -154,54 +252,268 @@ impl Dag {
             .await
     }
 
+    async fn get_vertexes(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: Vec<ChangesetId>,
+    ) -> Result<Vec<Vertex>> {
+        stream::iter(cs_ids)
+            .map(|cs_id| self.idmap.get_vertex(ctx, cs_id))
+            .buffered(IDMAP_CHANGESET_FETCH_BATCH)
+            .try_collect()
+            .await
+    }
+
+    /// Find the vertexes of the given changesets. Returns `None` if any of them has not been
+    /// assigned a vertex or has not been added to the IdDag yet.
+    async fn find_known_vertexes(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: Vec<ChangesetId>,
+    ) -> Result<Option<Vec<Vertex>>> {
+        let vertexes: Vec<Option<Vertex>> = stream::iter(cs_ids)
+            .map(|cs_id| self.idmap.find_vertex(ctx, cs_id))
+            .buffered(IDMAP_CHANGESET_FETCH_BATCH)
+            .try_collect()
+            .await?;
+        let mut known = Vec::with_capacity(vertexes.len());
+        for vertex in vertexes {
+            match vertex {
+                Some(vertex) if self.iddag.contains_id(vertex)? => known.push(vertex),
+                _ => return Ok(None),
+            }
+        }
+        Ok(Some(known))
+    }
+
+    /// Map vertexes to changeset ids, preserving their order.
+    async fn get_changeset_ids(
+        &self,
+        ctx: &CoreContext,
+        vertexes: Vec<Vertex>,
+    ) -> Result<Vec<ChangesetId>> {
+        let changeset_ids = self
+            .idmap
+            .find_many_changeset_ids(ctx, vertexes.clone())
+            .await?;
+        vertexes
+            .into_iter()
+            .map(|vertex| {
+                changeset_ids.get(&vertex).copied().ok_or_else(|| {
+                    format_err!("Failed to find segmented changelog id {} in IdMap", vertex)
+                })
+            })
+            .collect()
+    }
+
+    pub(crate) async fn known_changeset_id_to_location(
+        &self,
+        ctx: &CoreContext,
+        master_heads: Vec<Vertex>,
+        vertex: Vertex,
+    ) -> Result<Option<Location<ChangesetId>>> {
+        let constraint = FirstAncestorConstraint::KnownUniversally {
+            heads: IdSet::from_spans(master_heads),
+        };
+        let (descendant, distance) = match self
+            .iddag
+            .to_first_ancestor_nth(vertex, constraint)
+            .with_context(|| format!("computing location for {}", vertex))?
+        {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+        let descendant = self.idmap.get_changeset_id(ctx, descendant).await?;
+        Ok(Some(Location::new(descendant, distance)))
+    }
+
+    pub(crate) fn known_is_ancestor(&self, ancestor: Vertex, descendant: Vertex) -> Result<bool> {
+        self.iddag
+            .is_ancestor(ancestor, descendant)
+            .with_context(|| {
+                format!(
+                    "checking whether {} is an ancestor of {}",
+                    ancestor, descendant
+                )
+            })
+    }
+
+    pub(crate) async fn known_common_ancestors(
+        &self,
+        ctx: &CoreContext,
+        vertexes: Vec<Vertex>,
+    ) -> Result<Vec<ChangesetId>> {
+        let gca = self
+            .iddag
+            .gca_all(IdSet::from_spans(vertexes))
+            .context("computing common ancestors")?;
+        self.get_changeset_ids(ctx, gca.iter().collect()).await
+    }
+
+    pub(crate) async fn known_range(
+        &self,
+        ctx: &CoreContext,
+        roots: Vec<Vertex>,
+        heads: Vec<Vertex>,
+        limit: u64,
+    ) -> Result<Option<Vec<ChangesetId>>> {
+        let range = self
+            .iddag
+            .range(IdSet::from_spans(roots), IdSet::from_spans(heads))
+            .context("computing range")?;
+        if range.count() > limit {
+            return Ok(None);
+        }
+        // Vertexes are assigned in topological order, so ascending order puts ancestors first.
+        let cs_ids = self
+            .get_changeset_ids(ctx, range.iter().rev().collect())
+            .await?;
+        Ok(Some(cs_ids))
+    }
+
+    pub(crate) async fn known_graph_slice(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<Vertex>,
+        depth: u64,
+    ) -> Result<Vec<(ChangesetId, Vec<ChangesetId>)>> {
+        self.known_graph_slice_with_limit(ctx, heads, depth, GRAPH_SLICE_MAX_ENTRIES)
+            .await
+    }
+
+    pub(crate) async fn known_graph_slice_with_limit(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<Vertex>,
+        depth: u64,
+        max_entries: usize,
+    ) -> Result<Vec<(ChangesetId, Vec<ChangesetId>)>> {
+        if depth > GRAPH_SLICE_MAX_DEPTH {
+            return Err(format_err!(
+                "invalid request: graph slice depth {} exceeds the maximum of {}",
+                depth,
+                GRAPH_SLICE_MAX_DEPTH
+            ));
+        }
+        // Walk the graph breadth-first in the vertex space, so that the idmap only needs to be
+        // queried once for all of the commits in the slice.
+        let mut slice = Vec::new();
+        let mut seen = HashSet::new();
+        let mut frontier = heads;
+        let mut generation = 0;
+        while !frontier.is_empty() {
+            let mut next = Vec::new();
+            for vertex in frontier {
+                if !seen.insert(vertex) {
+                    continue;
+                }
+                if slice.len() >= max_entries {
+                    return Err(format_err!(
+                        "invalid request: graph slice exceeds the maximum of {} commits",
+                        max_entries
+                    ));
+                }
+                let parents = self
+                    .iddag
+                    .parent_ids(vertex)
+                    .with_context(|| format!("looking up parents ids for {}", vertex))?;
+                if generation < depth {
+                    next.extend(parents.iter().copied());
+                }
+                slice.push((vertex, parents));
+            }
+            frontier = next;
+            generation += 1;
+        }
+
+        let vertexes = slice
+            .iter()
+            .flat_map(|(vertex, parents)| std::iter::once(vertex).chain(parents))
+            .copied()
+            .collect::<HashSet<_>>();
+        let changeset_ids = self
+            .idmap
+            .find_many_changeset_ids(ctx, vertexes.into_iter().collect())
+            .await
+            .context("error retrieving mappings for graph slice")?;
+        let get = |vertex: Vertex| {
+            changeset_ids.get(&vertex).copied().ok_or_else(|| {
+                format_err!("Failed to find segmented changelog id {} in IdMap", vertex)
+            })
+        };
+
+        slice
+            .into_iter()
+            .map(|(vertex, parents)| {
+                let parents = parents.into_iter().map(get).collect::<Result<_>>()?;
+                Ok((get(vertex)?, parents))
+            })
+            .collect()
+    }
+
+    /// Assign vertexes to the ancestors of `heads` and add them to the IdDag. Heads are processed
+    /// in order, so the ancestors of earlier heads are assigned lower vertexes. Returns the vertex
+    /// of each head.
     pub(crate) async fn build(
         &mut self,
         ctx: &CoreContext,
         low_vertex: Vertex,
-        head: ChangesetId,
+        heads: &[ChangesetId],
         start_state: StartState,
-    ) -> Result<Vertex> {
+    ) -> Result<Vec<Vertex>> {
         enum Todo {
             Visit(ChangesetId),
             Assign(ChangesetId),
         }
-        let mut todo_stack = vec![Todo::Visit(head)];
+        let mut todo_stack = vec![];
         let mut mem_idmap = MemIdMap::new();
-        let mut seen = hashset![head];
-
-        while let Some(todo) = todo_stack.pop() {
-            match todo {
-                Todo::Visit(cs_id) => {
-                    let parents = match start_state.get_parents_if_not_assigned(cs_id) {
-                        None => continue,
-                        Some(v) => v,
-                    };
-                    todo_stack.push(Todo::Assign(cs_id));
-                    for parent in parents.iter().rev() {
-                        // Note: iterating parents in reverse is a small optimization because
-                        // in our setup p1 is master.
-                        if seen.insert(*parent) {
-                            todo_stack.push(Todo::Visit(*parent));
+        let mut seen = HashSet::new();
+
+        for head in heads {
+            if seen.insert(*head) {
+                todo_stack.push(Todo::Visit(*head));
+            }
+            while let Some(todo) = todo_stack.pop() {
+                match todo {
+                    Todo::Visit(cs_id) => {
+                        let parents = match start_state.get_parents_if_not_assigned(cs_id) {
+                            None => continue,
+                            Some(v) => v,
+                        };
+                        todo_stack.push(Todo::Assign(cs_id));
+                        for parent in parents.iter().rev() {
+                            // Note: iterating parents in reverse is a small optimization because
+                            // in our setup p1 is master.
+                            if seen.insert(*parent) {
+                                todo_stack.push(Todo::Visit(*parent));
+                            }
                         }
                     }
-                }
-                Todo::Assign(cs_id) => {
-                    let vertex = low_vertex + mem_idmap.len() as u64;
-                    mem_idmap.insert(vertex, cs_id);
-                    trace!(
-                        ctx.logger(),
-                        "assigning vertex id '{}' to changeset id '{}'",
-                        vertex,
-                        cs_id
-                    );
+                    Todo::Assign(cs_id) => {
+                        let vertex = low_vertex + mem_idmap.len() as u64;
+                        mem_idmap.insert(vertex, cs_id);
+                        trace!(
+                            ctx.logger(),
+                            "assigning vertex id '{}' to changeset id '{}'",
+                            vertex,
+                            cs_id
+                        );
+                    }
                 }
             }
         }
 
-        let head_vertex = mem_idmap
-            .find_vertex(head)
-            .or_else(|| start_state.assignments.find_vertex(head))
-            .ok_or_else(|| format_err!("error building IdMap; failed to assign head {}", head))?;
+        let head_vertexes = heads
+            .iter()
+            .map(|head| {
+                mem_idmap
+                    .find_vertex(*head)
+                    .or_else(|| start_state.assignments.find_vertex(*head))
+                    .ok_or_else(|| {
+                        format_err!("error building IdMap; failed to assign head {}", head)
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
 
         debug!(
             ctx.logger(),
@@ -244,18 +556,27 @@ impl Dag {
 
         // TODO(sfilip, T67731559): Prefetch parents for IdDag from last processed Vertex
         debug!(ctx.logger(), "building iddag");
-        self.iddag
-            .build_segments_volatile(head_vertex, &get_vertex_parents)
-            .context("building iddag")?;
+        for head_vertex in head_vertexes.iter() {
+            self.iddag
+                .build_segments_volatile(*head_vertex, &get_vertex_parents)
+                .context("building iddag")?;
+        }
         debug!(
             ctx.logger(),
             "successfully finished building building iddag"
         );
 
-        Ok(head_vertex)
+        Ok(head_vertexes)
     }
 
     fn clone_data_head_id(&self) -> Result<Vertex> {
+        match self.master_head {
+            Some(master_head) => Ok(master_head),
+            None => self.last_assigned_id(),
+        }
+    }
+
+    fn last_assigned_id(&self) -> Result<Vertex> {
         let group = Group::MASTER;
         let level = 0;
         let next_id = self
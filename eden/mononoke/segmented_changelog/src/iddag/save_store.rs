@@ -9,7 +9,7 @@ use std::sync::Arc;
 
 use anyhow::{format_err, Context, Result};
 
-use dag::InProcessIdDag;
+use dag::{Id as Vertex, InProcessIdDag};
 
 use blobstore::{Blobstore, BlobstoreBytes};
 use context::CoreContext;
@@ -85,6 +85,50 @@ impl IdDagSaveStore {
         Ok(iddag_version)
     }
 
+    /// Record the vertex of the main bookmark for a saved iddag. Tracked bookmarks may be
+    /// assigned vertexes after it so it can't be derived from the iddag alone.
+    pub async fn save_master_head<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        iddag_version: IdDagVersion,
+        master_head: Vertex,
+    ) -> Result<()> {
+        let buffer = mincode::serialize(&master_head.0)?;
+        self.blobstore
+            .put(
+                ctx,
+                self.master_head_key(iddag_version),
+                BlobstoreBytes::from_bytes(buffer),
+            )
+            .await
+            .context("saving iddag master head in blobstore")?;
+        Ok(())
+    }
+
+    /// Returns `None` for iddags saved before the master head was recorded.
+    pub async fn find_master_head<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        iddag_version: IdDagVersion,
+    ) -> Result<Option<Vertex>> {
+        let bytes_opt = self
+            .blobstore
+            .get(ctx, &self.master_head_key(iddag_version))
+            .await
+            .with_context(|| {
+                format!(
+                    "loading master head for segmented changelog iddag version {}",
+                    iddag_version.0
+                )
+            })?;
+        let bytes = match bytes_opt {
+            None => return Ok(None),
+            Some(b) => b,
+        };
+        let master_head: u64 = mincode::deserialize(&bytes.into_raw_bytes())?;
+        Ok(Some(Vertex(master_head)))
+    }
+
     pub async fn save_from_dag<'a>(
         &'a self,
         ctx: &'a CoreContext,
@@ -96,4 +140,8 @@ impl IdDagSaveStore {
     fn key(&self, iddag_version: IdDagVersion) -> String {
         format!("segmented_changelog_iddag.blake2.{}", iddag_version.0)
     }
+
+    fn master_head_key(&self, iddag_version: IdDagVersion) -> String {
+        format!("segmented_changelog_master_head.blake2.{}", iddag_version.0)
+    }
 }
@@ -37,6 +37,7 @@ mod tests;
 pub use ::dag::{CloneData, FlatSegment, Id as Vertex, PreparedFlatSegments};
 
 pub use crate::builder::SegmentedChangelogBuilder;
+pub use crate::dag::{GRAPH_SLICE_MAX_DEPTH, GRAPH_SLICE_MAX_ENTRIES};
 
 // TODO(T74420661): use `thiserror` to represent error case
 
@@ -46,6 +47,23 @@ pub struct StreamCloneData<T> {
     pub idmap_stream: BoxStream<'static, Result<(Vertex, T)>>,
 }
 
+/// The position of a commit in the commit graph, expressed as the ancestor that is `distance`
+/// first parents away from `descendant`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Location<T> {
+    pub descendant: T,
+    pub distance: u64,
+}
+
+impl<T> Location<T> {
+    pub fn new(descendant: T, distance: u64) -> Self {
+        Self {
+            descendant,
+            distance,
+        }
+    }
+}
+
 #[async_trait]
 #[auto_impl(Arc)]
 pub trait SegmentedChangelog: Send + Sync {
@@ -78,6 +96,68 @@ pub trait SegmentedChangelog: Send + Sync {
         count: u64,
     ) -> Result<Vec<ChangesetId>>;
 
+    /// Get the commit graph location of a commit given its identifier.
+    ///
+    /// This is the reverse of `location_to_changeset_id`. The location is expressed relative to
+    /// a commit that clients are expected to know: either one of `master_heads` or a parent of a
+    /// merge commit. Returns `None` when `cs_id` is not an ancestor of `master_heads`.
+    async fn changeset_id_to_location(
+        &self,
+        ctx: &CoreContext,
+        master_heads: Vec<ChangesetId>,
+        cs_id: ChangesetId,
+    ) -> Result<Option<Location<ChangesetId>>>;
+
+    /// Returns whether `ancestor` is an ancestor of `descendant`. A commit is considered its own
+    /// ancestor.
+    ///
+    /// Returns `None` when either commit is not part of the graph that segmented changelog has
+    /// built, in which case the caller should fall back to another index.
+    async fn is_ancestor(
+        &self,
+        ctx: &CoreContext,
+        ancestor: ChangesetId,
+        descendant: ChangesetId,
+    ) -> Result<Option<bool>>;
+
+    /// Returns the greatest common ancestors of the given commits, that is, the heads of the set
+    /// of their common ancestors, most recent first.
+    ///
+    /// Returns `None` when any of the commits is not part of the graph that segmented changelog
+    /// has built.
+    async fn common_ancestors(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: Vec<ChangesetId>,
+    ) -> Result<Option<Vec<ChangesetId>>>;
+
+    /// Returns the commits that are both descendants of `roots` and ancestors of `heads`
+    /// (`roots::heads` in revset terms), ancestors first.
+    ///
+    /// Returns `None` when the range contains more than `limit` commits, without looking up
+    /// any of them.
+    async fn range(
+        &self,
+        ctx: &CoreContext,
+        roots: Vec<ChangesetId>,
+        heads: Vec<ChangesetId>,
+        limit: u64,
+    ) -> Result<Option<Vec<ChangesetId>>>;
+
+    /// Get a slice of the commit graph around the given heads.
+    ///
+    /// Returns the heads and their ancestors up to `depth` generations back, each paired with
+    /// its parents. With a `depth` of 0 only the heads themselves are returned. Clients that
+    /// don't have the full commit graph can use this to answer ancestry questions on demand.
+    /// Requests deeper than `GRAPH_SLICE_MAX_DEPTH` or that would return more than
+    /// `GRAPH_SLICE_MAX_ENTRIES` commits fail instead of returning a partial slice.
+    async fn graph_slice(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        depth: u64,
+    ) -> Result<Vec<(ChangesetId, Vec<ChangesetId>)>>;
+
     /// Returns data necessary for SegmentedChangelog to be initialized by a client.
     ///
     /// Note that the heads that are sent over in a clone can vary. Strictly speaking the client
@@ -116,6 +196,61 @@ impl SegmentedChangelog for DisabledSegmentedChangelog {
         ))
     }
 
+    async fn changeset_id_to_location(
+        &self,
+        _ctx: &CoreContext,
+        _master_heads: Vec<ChangesetId>,
+        _cs_id: ChangesetId,
+    ) -> Result<Option<Location<ChangesetId>>> {
+        Err(format_err!(
+            "Segmented Changelog is not enabled for this repo",
+        ))
+    }
+
+    async fn is_ancestor(
+        &self,
+        _ctx: &CoreContext,
+        _ancestor: ChangesetId,
+        _descendant: ChangesetId,
+    ) -> Result<Option<bool>> {
+        Err(format_err!(
+            "Segmented Changelog is not enabled for this repo",
+        ))
+    }
+
+    async fn common_ancestors(
+        &self,
+        _ctx: &CoreContext,
+        _cs_ids: Vec<ChangesetId>,
+    ) -> Result<Option<Vec<ChangesetId>>> {
+        Err(format_err!(
+            "Segmented Changelog is not enabled for this repo",
+        ))
+    }
+
+    async fn range(
+        &self,
+        _ctx: &CoreContext,
+        _roots: Vec<ChangesetId>,
+        _heads: Vec<ChangesetId>,
+        _limit: u64,
+    ) -> Result<Option<Vec<ChangesetId>>> {
+        Err(format_err!(
+            "Segmented Changelog is not enabled for this repo",
+        ))
+    }
+
+    async fn graph_slice(
+        &self,
+        _ctx: &CoreContext,
+        _heads: Vec<ChangesetId>,
+        _depth: u64,
+    ) -> Result<Vec<(ChangesetId, Vec<ChangesetId>)>> {
+        Err(format_err!(
+            "Segmented Changelog is not enabled for this repo",
+        ))
+    }
+
     async fn clone_data(&self, _ctx: &CoreContext) -> Result<CloneData<ChangesetId>> {
         Err(format_err!(
             "Segmented Changelog is not enabled for this repo",
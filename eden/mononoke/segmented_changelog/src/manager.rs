@@ -6,12 +6,14 @@
  */
 
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::{format_err, Context, Result};
 use async_trait::async_trait;
 use slog::{debug, info};
+use tokio::sync::RwLock;
 
-use dag::InProcessIdDag;
+use dag::{Id as Vertex, InProcessIdDag};
 
 use context::CoreContext;
 use mononoke_types::{ChangesetId, RepositoryId};
@@ -22,7 +24,16 @@ use crate::iddag::IdDagSaveStore;
 use crate::idmap::{CacheHandlers, CachedIdMap, IdMap, SqlIdMapFactory};
 use crate::logging::log_new_bundle;
 use crate::types::{DagBundle, IdMapVersion};
-use crate::{CloneData, SegmentedChangelog, StreamCloneData};
+use crate::{CloneData, Location, SegmentedChangelog, StreamCloneData};
+
+/// How long a dag loaded to answer queries is used before checking for a newer saved dag.
+const LOADED_DAG_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+struct LoadedDag {
+    bundle: DagBundle,
+    dag: Arc<Dag>,
+    checked_at: Instant,
+}
 
 pub struct SegmentedChangelogManager {
     repo_id: RepositoryId,
@@ -30,6 +41,7 @@ pub struct SegmentedChangelogManager {
     iddag_save_store: IdDagSaveStore,
     idmap_factory: SqlIdMapFactory,
     cache_handlers: Option<CacheHandlers>,
+    loaded_dag: RwLock<Option<LoadedDag>>,
 }
 
 impl SegmentedChangelogManager {
@@ -46,6 +58,7 @@ impl SegmentedChangelogManager {
             iddag_save_store,
             idmap_factory,
             cache_handlers,
+            loaded_dag: RwLock::new(None),
         }
     }
 
@@ -53,6 +66,7 @@ impl SegmentedChangelogManager {
         &self,
         ctx: &CoreContext,
         iddag: &InProcessIdDag,
+        master_head: Vertex,
         idmap_version: IdMapVersion,
     ) -> Result<DagBundle> {
         // Save the IdDag
@@ -61,6 +75,10 @@ impl SegmentedChangelogManager {
             .save(&ctx, &iddag)
             .await
             .with_context(|| format!("repo {}: error saving iddag", self.repo_id))?;
+        self.iddag_save_store
+            .save_master_head(&ctx, iddag_version, master_head)
+            .await
+            .with_context(|| format!("repo {}: error saving iddag master head", self.repo_id))?;
         // Update BundleStore
         let bundle = DagBundle::new(iddag_version, idmap_version);
         self.bundle_store
@@ -79,8 +97,45 @@ impl SegmentedChangelogManager {
     }
 
     pub async fn load_dag(&self, ctx: &CoreContext) -> Result<(DagBundle, Dag)> {
-        let bundle = self
-            .bundle_store
+        let bundle = self.load_bundle(ctx).await?;
+        let dag = self.load_dag_from_bundle(ctx, bundle).await?;
+        Ok((bundle, dag))
+    }
+
+    /// Dag used to answer queries. The saved dag is loaded once and shared by queries until
+    /// a newer one is saved, instead of being loaded from the blobstore for each query.
+    pub(crate) async fn query_dag(&self, ctx: &CoreContext) -> Result<Arc<Dag>> {
+        if let Some(loaded) = self.loaded_dag.read().await.as_ref() {
+            if loaded.checked_at.elapsed() < LOADED_DAG_REFRESH_INTERVAL {
+                return Ok(loaded.dag.clone());
+            }
+        }
+
+        let mut loaded_dag = self.loaded_dag.write().await;
+        if let Some(loaded) = loaded_dag.as_mut() {
+            // Another query may have refreshed the dag while this one waited for the lock.
+            if loaded.checked_at.elapsed() < LOADED_DAG_REFRESH_INTERVAL {
+                return Ok(loaded.dag.clone());
+            }
+        }
+        let bundle = self.load_bundle(ctx).await?;
+        if let Some(loaded) = loaded_dag.as_mut() {
+            if loaded.bundle == bundle {
+                loaded.checked_at = Instant::now();
+                return Ok(loaded.dag.clone());
+            }
+        }
+        let dag = Arc::new(self.load_dag_from_bundle(ctx, bundle).await?);
+        *loaded_dag = Some(LoadedDag {
+            bundle,
+            dag: dag.clone(),
+            checked_at: Instant::now(),
+        });
+        Ok(dag)
+    }
+
+    async fn load_bundle(&self, ctx: &CoreContext) -> Result<DagBundle> {
+        self.bundle_store
             .get(&ctx)
             .await
             .with_context(|| {
@@ -94,12 +149,20 @@ impl SegmentedChangelogManager {
                     "repo {}: segmented changelog metadata not found, maybe repo is not seeded",
                     self.repo_id
                 )
-            })?;
+            })
+    }
+
+    async fn load_dag_from_bundle(&self, ctx: &CoreContext, bundle: DagBundle) -> Result<Dag> {
         let iddag = self
             .iddag_save_store
             .load(&ctx, bundle.iddag_version)
             .await
             .with_context(|| format!("repo {}: failed to load iddag", self.repo_id))?;
+        let master_head = self
+            .iddag_save_store
+            .find_master_head(&ctx, bundle.iddag_version)
+            .await
+            .with_context(|| format!("repo {}: failed to load iddag master head", self.repo_id))?;
         let idmap = self.new_idmap(bundle.idmap_version);
         debug!(
             ctx.logger(),
@@ -109,8 +172,7 @@ impl SegmentedChangelogManager {
             bundle.idmap_version,
             bundle.iddag_version,
         );
-        let dag = Dag::new(iddag, idmap);
-        Ok((bundle, dag))
+        Ok(Dag::new(iddag, idmap).with_master_head(master_head))
     }
 
     pub fn new_idmap(&self, idmap_version: IdMapVersion) -> Arc<dyn IdMap> {
@@ -136,7 +198,7 @@ impl SegmentedChangelog for SegmentedChangelogManager {
         distance: u64,
         count: u64,
     ) -> Result<Vec<ChangesetId>> {
-        let (_, dag) = self.load_dag(&ctx).await.with_context(|| {
+        let dag = self.query_dag(&ctx).await.with_context(|| {
             format!(
                 "repo {}: error loading segmented changelog from save",
                 self.repo_id
@@ -146,8 +208,83 @@ impl SegmentedChangelog for SegmentedChangelogManager {
             .await
     }
 
+    async fn graph_slice(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        depth: u64,
+    ) -> Result<Vec<(ChangesetId, Vec<ChangesetId>)>> {
+        let dag = self.query_dag(&ctx).await.with_context(|| {
+            format!(
+                "repo {}: error loading segmented changelog from save",
+                self.repo_id
+            )
+        })?;
+        dag.graph_slice(ctx, heads, depth).await
+    }
+
+    async fn changeset_id_to_location(
+        &self,
+        ctx: &CoreContext,
+        master_heads: Vec<ChangesetId>,
+        cs_id: ChangesetId,
+    ) -> Result<Option<Location<ChangesetId>>> {
+        let dag = self.query_dag(&ctx).await.with_context(|| {
+            format!(
+                "repo {}: error loading segmented changelog from save",
+                self.repo_id
+            )
+        })?;
+        dag.changeset_id_to_location(ctx, master_heads, cs_id).await
+    }
+
+    async fn is_ancestor(
+        &self,
+        ctx: &CoreContext,
+        ancestor: ChangesetId,
+        descendant: ChangesetId,
+    ) -> Result<Option<bool>> {
+        let dag = self.query_dag(&ctx).await.with_context(|| {
+            format!(
+                "repo {}: error loading segmented changelog from save",
+                self.repo_id
+            )
+        })?;
+        dag.is_ancestor(ctx, ancestor, descendant).await
+    }
+
+    async fn common_ancestors(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: Vec<ChangesetId>,
+    ) -> Result<Option<Vec<ChangesetId>>> {
+        let dag = self.query_dag(&ctx).await.with_context(|| {
+            format!(
+                "repo {}: error loading segmented changelog from save",
+                self.repo_id
+            )
+        })?;
+        dag.common_ancestors(ctx, cs_ids).await
+    }
+
+    async fn range(
+        &self,
+        ctx: &CoreContext,
+        roots: Vec<ChangesetId>,
+        heads: Vec<ChangesetId>,
+        limit: u64,
+    ) -> Result<Option<Vec<ChangesetId>>> {
+        let dag = self.query_dag(&ctx).await.with_context(|| {
+            format!(
+                "repo {}: error loading segmented changelog from save",
+                self.repo_id
+            )
+        })?;
+        dag.range(ctx, roots, heads, limit).await
+    }
+
     async fn clone_data(&self, ctx: &CoreContext) -> Result<CloneData<ChangesetId>> {
-        let (_, dag) = self.load_dag(&ctx).await.with_context(|| {
+        let dag = self.query_dag(&ctx).await.with_context(|| {
             format!(
                 "repo {}: error loading segmented changelog from save",
                 self.repo_id
@@ -160,8 +297,8 @@ impl SegmentedChangelog for SegmentedChangelogManager {
         &self,
         ctx: &CoreContext,
     ) -> Result<StreamCloneData<ChangesetId>> {
-        let (_, dag) = self
-            .load_dag(&ctx)
+        let dag = self
+            .query_dag(&ctx)
             .await
             .context("error loading segmented changelog from save")?;
         dag.full_idmap_clone_data(ctx).await
@@ -24,12 +24,17 @@ use mononoke_types::ChangesetId;
 
 use crate::dag::{Dag, StartState};
 use crate::idmap::IdMap;
-use crate::{SegmentedChangelog, StreamCloneData};
+use crate::{Location, SegmentedChangelog, StreamCloneData};
 
 define_stats! {
     prefix = "mononoke.segmented_changelog.ondemand";
     build_incremental: timeseries(Sum),
     location_to_changeset_id: timeseries(Sum),
+    graph_slice: timeseries(Sum),
+    changeset_id_to_location: timeseries(Sum),
+    is_ancestor: timeseries(Sum),
+    common_ancestors: timeseries(Sum),
+    range: timeseries(Sum),
 }
 
 pub struct OnDemandUpdateDag {
@@ -44,6 +49,42 @@ impl OnDemandUpdateDag {
             changeset_fetcher,
         }
     }
+
+    /// Get the vertexes of the given changesets, incrementally building the dag for those that
+    /// it doesn't contain yet. The vertexes are returned in the same order as the changesets.
+    async fn get_or_build_vertexes(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+    ) -> Result<Vec<Vertex>> {
+        let mut vertexes = Vec::with_capacity(cs_ids.len());
+        {
+            let dag = self.dag.read().await;
+            for cs_id in cs_ids {
+                let vertex = match dag
+                    .idmap
+                    .find_vertex(ctx, *cs_id)
+                    .await
+                    .context("fetching vertex for csid")?
+                {
+                    Some(vertex) if dag.iddag.contains_id(vertex)? => Some(vertex),
+                    _ => None,
+                };
+                vertexes.push(vertex);
+            }
+        }
+        if vertexes.iter().any(Option::is_none) {
+            let mut dag = self.dag.write().await;
+            for (cs_id, vertex) in cs_ids.iter().zip(vertexes.iter_mut()) {
+                if vertex.is_none() {
+                    let built =
+                        build_incremental(ctx, &mut dag, &self.changeset_fetcher, *cs_id).await?;
+                    *vertex = Some(built);
+                }
+            }
+        }
+        Ok(vertexes.into_iter().flatten().collect())
+    }
 }
 
 #[async_trait]
@@ -81,6 +122,73 @@ impl SegmentedChangelog for OnDemandUpdateDag {
             .await
     }
 
+    async fn graph_slice(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        depth: u64,
+    ) -> Result<Vec<(ChangesetId, Vec<ChangesetId>)>> {
+        STATS::graph_slice.add_value(1);
+        let head_vertexes = self.get_or_build_vertexes(ctx, &heads).await?;
+        let dag = self.dag.read().await;
+        dag.known_graph_slice(ctx, head_vertexes, depth).await
+    }
+
+    async fn changeset_id_to_location(
+        &self,
+        ctx: &CoreContext,
+        mut master_heads: Vec<ChangesetId>,
+        cs_id: ChangesetId,
+    ) -> Result<Option<Location<ChangesetId>>> {
+        STATS::changeset_id_to_location.add_value(1);
+        master_heads.push(cs_id);
+        let mut head_vertexes = self.get_or_build_vertexes(ctx, &master_heads).await?;
+        let vertex = head_vertexes.pop().expect("vertex for cs_id");
+        let dag = self.dag.read().await;
+        dag.known_changeset_id_to_location(ctx, head_vertexes, vertex)
+            .await
+    }
+
+    async fn is_ancestor(
+        &self,
+        ctx: &CoreContext,
+        ancestor: ChangesetId,
+        descendant: ChangesetId,
+    ) -> Result<Option<bool>> {
+        STATS::is_ancestor.add_value(1);
+        let vertexes = self
+            .get_or_build_vertexes(ctx, &[ancestor, descendant])
+            .await?;
+        let dag = self.dag.read().await;
+        dag.known_is_ancestor(vertexes[0], vertexes[1]).map(Some)
+    }
+
+    async fn common_ancestors(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: Vec<ChangesetId>,
+    ) -> Result<Option<Vec<ChangesetId>>> {
+        STATS::common_ancestors.add_value(1);
+        let vertexes = self.get_or_build_vertexes(ctx, &cs_ids).await?;
+        let dag = self.dag.read().await;
+        dag.known_common_ancestors(ctx, vertexes).await.map(Some)
+    }
+
+    async fn range(
+        &self,
+        ctx: &CoreContext,
+        roots: Vec<ChangesetId>,
+        heads: Vec<ChangesetId>,
+        limit: u64,
+    ) -> Result<Option<Vec<ChangesetId>>> {
+        STATS::range.add_value(1);
+        let root_vertexes = self.get_or_build_vertexes(ctx, &roots).await?;
+        let head_vertexes = self.get_or_build_vertexes(ctx, &heads).await?;
+        let dag = self.dag.read().await;
+        dag.known_range(ctx, root_vertexes, head_vertexes, limit)
+            .await
+    }
+
     async fn clone_data(&self, ctx: &CoreContext) -> Result<CloneData<ChangesetId>> {
         let dag = self.dag.read().await;
         dag.clone_data(ctx).await
@@ -171,9 +279,11 @@ pub(crate) async fn build_incremental(
         }
     }
 
-    dag.build(ctx, id_map_next_id, head, start_state)
+    let head_vertexes = dag
+        .build(ctx, id_map_next_id, &[head], start_state)
         .await
-        .context("incrementally updating dag")
+        .context("incrementally updating dag")?;
+    Ok(head_vertexes[0])
 }
 
 async fn get_parents_and_vertex(
@@ -7,7 +7,7 @@
 
 use std::sync::Arc;
 
-use anyhow::{Context, Result};
+use anyhow::{format_err, Context, Result};
 use futures::stream::TryStreamExt;
 use slog::info;
 
@@ -50,21 +50,29 @@ impl SegmentedChangelogSeeder {
             manager,
         }
     }
-    pub async fn run(&self, ctx: &CoreContext, head: ChangesetId) -> Result<()> {
+
+    /// Seed the segmented changelog with the ancestors of `heads`. Heads are assigned to the
+    /// master group in the order given, so the primary head should come first.
+    pub async fn run(&self, ctx: &CoreContext, heads: Vec<ChangesetId>) -> Result<()> {
         info!(
             ctx.logger(),
             "seeding segmented changelog using idmap version: {}", self.idmap_version
         );
-        let (dag, last_vertex) = self
-            .build_dag_from_scratch(&ctx, head)
+        let (dag, head_vertexes) = self
+            .build_dag_from_scratch(&ctx, heads.clone())
             .await
             .context("building dag from scratch")?;
-        info!(
-            ctx.logger(),
-            "finished building dag, head '{}' has assigned vertex '{}'", head, last_vertex
-        );
+        for (head, vertex) in heads.iter().zip(head_vertexes.iter()) {
+            info!(
+                ctx.logger(),
+                "finished building dag, head '{}' has assigned vertex '{}'", head, vertex
+            );
+        }
+        let master_head = *head_vertexes
+            .first()
+            .ok_or_else(|| format_err!("no heads to seed the dag from"))?;
         self.manager
-            .save_dag(ctx, &dag.iddag, self.idmap_version)
+            .save_dag(ctx, &dag.iddag, master_head, self.idmap_version)
             .await
             .context("failed to save dag")?;
         // Update IdMapVersion
@@ -82,8 +90,8 @@ impl SegmentedChangelogSeeder {
     pub async fn build_dag_from_scratch(
         &self,
         ctx: &CoreContext,
-        head: ChangesetId,
-    ) -> Result<(Dag, Vertex)> {
+        heads: Vec<ChangesetId>,
+    ) -> Result<(Dag, Vec<Vertex>)> {
         STATS::build_all_graph.add_value(1);
 
         let changeset_entries: Vec<ChangesetEntry> = self
@@ -104,7 +112,7 @@ impl SegmentedChangelogSeeder {
         let low_vertex = dag::Group::MASTER.min_id();
         let idmap = self.manager.new_idmap(self.idmap_version);
         let mut dag = Dag::new(InProcessIdDag::new_in_process(), idmap);
-        let last_vertex = dag.build(ctx, low_vertex, head, start_state).await?;
-        Ok((dag, last_vertex))
+        let head_vertexes = dag.build(ctx, low_vertex, &heads, start_state).await?;
+        Ok((dag, head_vertexes))
     }
 }
@@ -10,7 +10,7 @@ use std::time::Duration;
 
 use anyhow::{format_err, Context, Result};
 use futures_stats::TimedFutureExt;
-use slog::{debug, error, info};
+use slog::{debug, error, info, warn};
 
 use dag::{Group, Id as Vertex, InProcessIdDag};
 use stats::prelude::*;
@@ -45,6 +45,7 @@ pub struct SegmentedChangelogTailer {
     changeset_fetcher: Arc<dyn ChangesetFetcher>,
     bookmarks: Arc<dyn Bookmarks>,
     bookmark_name: BookmarkName,
+    tracked_bookmarks: Vec<BookmarkName>,
     manager: SegmentedChangelogManager,
 }
 
@@ -54,6 +55,7 @@ impl SegmentedChangelogTailer {
         changeset_fetcher: Arc<dyn ChangesetFetcher>,
         bookmarks: Arc<dyn Bookmarks>,
         bookmark_name: BookmarkName,
+        tracked_bookmarks: Vec<BookmarkName>,
         manager: SegmentedChangelogManager,
     ) -> Self {
         Self {
@@ -61,6 +63,7 @@ impl SegmentedChangelogTailer {
             changeset_fetcher,
             bookmarks,
             bookmark_name,
+            tracked_bookmarks,
             manager,
         }
     }
@@ -126,22 +129,61 @@ impl SegmentedChangelogTailer {
             ctx.logger(),
             "repo {}: bookmark {} resolved to {}", self.repo_id, self.bookmark_name, head
         );
+        let mut heads = vec![head];
+        for bookmark_name in self.tracked_bookmarks.iter() {
+            match self
+                .bookmarks
+                .get(ctx.clone(), bookmark_name)
+                .await
+                .with_context(|| format!("fetching changesetid for '{}'", bookmark_name))?
+            {
+                Some(cs_id) => {
+                    info!(
+                        ctx.logger(),
+                        "repo {}: tracked bookmark {} resolved to {}",
+                        self.repo_id,
+                        bookmark_name,
+                        cs_id
+                    );
+                    heads.push(cs_id);
+                }
+                None => warn!(
+                    ctx.logger(),
+                    "repo {}: tracked bookmark {} could not be found, skipping it",
+                    self.repo_id,
+                    bookmark_name
+                ),
+            }
+        }
         let old_master_vertex = dag
             .iddag
             .next_free_id(0, Group::MASTER)
             .context("fetching next free id")?;
 
-        // This updates the IdMap common storage and also updates the dag we loaded.
-        let head_vertex = build_incremental(&ctx, &mut dag, &self.changeset_fetcher, head)
-            .await
-            .context("when incrementally building dag")?;
-
-        if old_master_vertex > head_vertex {
+        // This updates the IdMap common storage and also updates the dag we loaded. Heads are
+        // processed in order so that the main bookmark keeps the lowest vertexes.
+        let mut head_vertexes = Vec::with_capacity(heads.len());
+        for head in heads {
+            let head_vertex = build_incremental(&ctx, &mut dag, &self.changeset_fetcher, head)
+                .await
+                .context("when incrementally building dag")?;
+            head_vertexes.push(head_vertex);
+        }
+        let master_vertex = *head_vertexes
+            .first()
+            .ok_or_else(|| format_err!("no heads to build the dag from"))?;
+        let max_vertex = head_vertexes
+            .iter()
+            .copied()
+            .max()
+            .ok_or_else(|| format_err!("no heads to build the dag from"))?;
+
+        if old_master_vertex > max_vertex && dag.master_head() == Some(master_vertex) {
             info!(
                 ctx.logger(),
                 "repo {}: dag already up to date, skipping update to iddag", self.repo_id
             );
-            return Ok((dag, head_vertex));
+            return Ok((dag, master_vertex));
         } else {
             info!(
                 ctx.logger(),
@@ -152,12 +194,14 @@ impl SegmentedChangelogTailer {
         // Let's rebuild the dag to keep segment fragmentation low
         let mut new_iddag = InProcessIdDag::new_in_process();
         let get_parents = |id| dag.iddag.parent_ids(id);
-        new_iddag.build_segments_volatile(head_vertex, &get_parents)?;
+        for vertex in head_vertexes {
+            new_iddag.build_segments_volatile(vertex, &get_parents)?;
+        }
         info!(ctx.logger(), "repo {}: IdDag rebuilt", self.repo_id);
 
         // Save the Dag
         self.manager
-            .save_dag(&ctx, &new_iddag, bundle.idmap_version)
+            .save_dag(&ctx, &new_iddag, master_vertex, bundle.idmap_version)
             .await
             .context("failed to save updated dag")?;
 
@@ -166,7 +210,7 @@ impl SegmentedChangelogTailer {
             "repo {}: successful incremental update to segmented changelog", self.repo_id,
         );
 
-        let new_dag = Dag::new(new_iddag, dag.idmap);
-        Ok((new_dag, head_vertex))
+        let new_dag = Dag::new(new_iddag, dag.idmap).with_master_head(Some(master_vertex));
+        Ok((new_dag, master_vertex))
     }
 }
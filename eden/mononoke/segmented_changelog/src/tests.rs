@@ -23,7 +23,7 @@ use mononoke_types::ChangesetId;
 use phases::mark_reachable_as_public;
 use revset::AncestorsNodeStream;
 use sql_construct::SqlConstruct;
-use tests_utils::resolve_cs_id;
+use tests_utils::{bookmark, resolve_cs_id, CreateCommitContext};
 
 use crate::builder::SegmentedChangelogBuilder;
 use crate::dag::Dag;
@@ -31,7 +31,7 @@ use crate::iddag::IdDagSaveStore;
 use crate::idmap::CacheHandlers;
 use crate::on_demand::OnDemandUpdateDag;
 use crate::types::IdDagVersion;
-use crate::SegmentedChangelog;
+use crate::{Location, SegmentedChangelog, GRAPH_SLICE_MAX_DEPTH};
 
 async fn validate_build_idmap(
     ctx: CoreContext,
@@ -74,7 +74,7 @@ pub async fn new_build_all_from_blobrepo(
         .build_seeder(ctx)
         .await?;
 
-    let (dag, _) = seeder.build_dag_from_scratch(ctx, head).await?;
+    let (dag, _) = seeder.build_dag_from_scratch(ctx, vec![head]).await?;
     Ok(dag)
 }
 
@@ -100,12 +100,10 @@ async fn test_iddag_save_store(fb: FacebookInit) -> Result<()> {
     let iddag_save_store = IdDagSaveStore::new(repo_id, Arc::new(blobrepo.get_blobstore()));
     let iddag_version = iddag_save_store.save(&ctx, &dag.iddag).await?;
 
-    assert!(
-        iddag_save_store
-            .find(&ctx, IdDagVersion::from_serialized_bytes(b"random"))
-            .await?
-            .is_none()
-    );
+    assert!(iddag_save_store
+        .find(&ctx, IdDagVersion::from_serialized_bytes(b"random"))
+        .await?
+        .is_none());
     let loaded_id_dag = iddag_save_store.load(&ctx, iddag_version).await?;
     let from_save = Dag::new(loaded_id_dag, dag.idmap.clone());
     let answer = from_save
@@ -211,30 +209,181 @@ async fn test_location_to_changeset_id_invalid_req(fb: FacebookInit) -> Result<(
         resolve_cs_id(&ctx, &blobrepo, "7fe9947f101acb4acf7d945e69f0d6ce76a81113").await?;
     setup_phases(&ctx, &blobrepo, known_cs_id).await?;
     let dag = new_build_all_from_blobrepo(&ctx, &blobrepo, known_cs_id).await?;
-    assert!(
-        dag.location_to_many_changeset_ids(&ctx, known_cs_id, 1u64, 2u64)
-            .await
-            .is_err()
-    );
+    assert!(dag
+        .location_to_many_changeset_ids(&ctx, known_cs_id, 1u64, 2u64)
+        .await
+        .is_err());
     // TODO(T74320664): Ideally LocationToHash should error when asked to go over merge commit.
     // The parents order is not well defined enough for this not to be ambiguous.
-    assert!(
-        dag.location_to_many_changeset_ids(&ctx, known_cs_id, 2u64, 1u64)
-            .await
-            .is_ok()
-    );
+    assert!(dag
+        .location_to_many_changeset_ids(&ctx, known_cs_id, 2u64, 1u64)
+        .await
+        .is_ok());
     let second_commit =
         resolve_cs_id(&ctx, &blobrepo, "1700524113b1a3b1806560341009684b4378660b").await?;
-    assert!(
-        dag.location_to_many_changeset_ids(&ctx, second_commit, 1u64, 2u64)
-            .await
-            .is_err()
+    assert!(dag
+        .location_to_many_changeset_ids(&ctx, second_commit, 1u64, 2u64)
+        .await
+        .is_err());
+    assert!(dag
+        .location_to_many_changeset_ids(&ctx, second_commit, 2u64, 1u64)
+        .await
+        .is_err());
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn test_graph_slice(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let blobrepo = linear::getrepo(fb).await;
+    let head = resolve_cs_id(&ctx, &blobrepo, "79a13814c5ce7330173ec04d279bf95ab3f652fb").await?;
+    setup_phases(&ctx, &blobrepo, head).await?;
+    let dag = new_build_all_from_blobrepo(&ctx, &blobrepo, head).await?;
+
+    let ancestors = dag.location_to_many_changeset_ids(&ctx, head, 0, 4).await?;
+    let slice = dag.graph_slice(&ctx, vec![head], 2).await?;
+    assert_eq!(
+        slice,
+        vec![
+            (ancestors[0], vec![ancestors[1]]),
+            (ancestors[1], vec![ancestors[2]]),
+            (ancestors[2], vec![ancestors[3]]),
+        ]
     );
-    assert!(
-        dag.location_to_many_changeset_ids(&ctx, second_commit, 2u64, 1u64)
-            .await
-            .is_err()
+
+    let slice = dag.graph_slice(&ctx, vec![head], 0).await?;
+    assert_eq!(slice, vec![(ancestors[0], vec![ancestors[1]])]);
+
+    // Commits reachable from more than one head are only returned once.
+    let slice = dag
+        .graph_slice(&ctx, vec![ancestors[0], ancestors[1]], 1)
+        .await?;
+    assert_eq!(slice.len(), 3);
+
+    // Slices that are too deep or too large are refused rather than truncated.
+    assert!(dag
+        .graph_slice(&ctx, vec![head], GRAPH_SLICE_MAX_DEPTH + 1)
+        .await
+        .is_err());
+    let head_vertex = dag.idmap.get_vertex(&ctx, head).await?;
+    let slice = dag
+        .known_graph_slice_with_limit(&ctx, vec![head_vertex], 2, 3)
+        .await?;
+    assert_eq!(slice.len(), 3);
+    assert!(dag
+        .known_graph_slice_with_limit(&ctx, vec![head_vertex], 3, 3)
+        .await
+        .is_err());
+
+    let blobrepo = unshared_merge_even::getrepo(fb).await;
+    let head = resolve_cs_id(&ctx, &blobrepo, "7fe9947f101acb4acf7d945e69f0d6ce76a81113").await?;
+    let merge = resolve_cs_id(&ctx, &blobrepo, "d592490c4386cdb3373dd93af04d563de199b2fb").await?;
+    setup_phases(&ctx, &blobrepo, head).await?;
+    let dag = new_build_all_from_blobrepo(&ctx, &blobrepo, head).await?;
+
+    let slice = dag.graph_slice(&ctx, vec![head], 1).await?;
+    assert_eq!(slice.len(), 2);
+    assert_eq!(slice[0], (head, vec![merge]));
+    assert_eq!(slice[1].0, merge);
+    assert_eq!(slice[1].1.len(), 2);
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn test_ancestry_queries(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let blobrepo = linear::getrepo(fb).await;
+    let head = resolve_cs_id(&ctx, &blobrepo, "79a13814c5ce7330173ec04d279bf95ab3f652fb").await?;
+    setup_phases(&ctx, &blobrepo, head).await?;
+    let dag = new_build_all_from_blobrepo(&ctx, &blobrepo, head).await?;
+    let a = dag.location_to_many_changeset_ids(&ctx, head, 0, 4).await?;
+
+    assert_eq!(dag.is_ancestor(&ctx, a[3], a[0]).await?, Some(true));
+    assert_eq!(dag.is_ancestor(&ctx, a[0], a[3]).await?, Some(false));
+    assert_eq!(dag.is_ancestor(&ctx, a[1], a[1]).await?, Some(true));
+    assert_eq!(
+        dag.common_ancestors(&ctx, vec![a[1], a[3]]).await?,
+        Some(vec![a[3]])
+    );
+    assert_eq!(
+        dag.range(&ctx, vec![a[3]], vec![a[1]], 3).await?,
+        Some(vec![a[3], a[2], a[1]])
+    );
+    assert_eq!(dag.range(&ctx, vec![a[3]], vec![a[1]], 2).await?, None);
+    assert_eq!(
+        dag.changeset_id_to_location(&ctx, vec![head], a[2]).await?,
+        Some(Location::new(head, 2))
+    );
+    assert_eq!(
+        dag.changeset_id_to_location(&ctx, vec![a[2]], a[1]).await?,
+        None
+    );
+
+    // Commits that the dag doesn't know about can't be answered.
+    let partial_dag = new_build_all_from_blobrepo(&ctx, &blobrepo, a[2]).await?;
+    assert_eq!(partial_dag.is_ancestor(&ctx, a[3], head).await?, None);
+    assert_eq!(
+        partial_dag.common_ancestors(&ctx, vec![a[3], head]).await?,
+        None
+    );
+
+    let blobrepo = unshared_merge_even::getrepo(fb).await;
+    let head = resolve_cs_id(&ctx, &blobrepo, "7fe9947f101acb4acf7d945e69f0d6ce76a81113").await?;
+    setup_phases(&ctx, &blobrepo, head).await?;
+    let dag = new_build_all_from_blobrepo(&ctx, &blobrepo, head).await?;
+    let slice = dag.graph_slice(&ctx, vec![head], 1).await?;
+    let (p1, p2) = (slice[1].1[0], slice[1].1[1]);
+
+    assert_eq!(dag.is_ancestor(&ctx, p2, head).await?, Some(true));
+    assert_eq!(dag.is_ancestor(&ctx, p1, p2).await?, Some(false));
+    assert_eq!(
+        dag.common_ancestors(&ctx, vec![p1, p2]).await?,
+        Some(vec![])
     );
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn test_on_demand_update_dag_ancestry_queries(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let blobrepo = linear::getrepo(fb).await;
+    let head = resolve_cs_id(&ctx, &blobrepo, "79a13814c5ce7330173ec04d279bf95ab3f652fb").await?;
+    let ancestor =
+        resolve_cs_id(&ctx, &blobrepo, "0ed509bf086fadcb8a8a5384dc3b550729b0fc17").await?;
+
+    let dag = SegmentedChangelogBuilder::with_sqlite_in_memory()?
+        .with_blobrepo(&blobrepo)
+        .build_on_demand_update()?;
+
+    assert_eq!(dag.is_ancestor(&ctx, ancestor, head).await?, Some(true));
+    assert_eq!(
+        dag.common_ancestors(&ctx, vec![ancestor, head]).await?,
+        Some(vec![ancestor])
+    );
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn test_on_demand_update_dag_graph_slice(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let blobrepo = linear::getrepo(fb).await;
+    let head = resolve_cs_id(&ctx, &blobrepo, "79a13814c5ce7330173ec04d279bf95ab3f652fb").await?;
+
+    let dag = SegmentedChangelogBuilder::with_sqlite_in_memory()?
+        .with_blobrepo(&blobrepo)
+        .build_on_demand_update()?;
+
+    let slice = dag.graph_slice(&ctx, vec![head], 3).await?;
+    let ancestors = dag.location_to_many_changeset_ids(&ctx, head, 0, 5).await?;
+    let expected = ancestors
+        .windows(2)
+        .map(|w| (w[0], vec![w[1]]))
+        .collect::<Vec<_>>();
+    assert_eq!(slice, expected);
+
     Ok(())
 }
 
@@ -384,7 +533,7 @@ async fn test_incremental_update_with_desync_iddag(fb: FacebookInit) -> Result<(
 
     let builder = SegmentedChangelogBuilder::with_sqlite_in_memory()?.with_blobrepo(&blobrepo);
     let seeder = builder.clone().build_seeder(&ctx).await?;
-    let (dag, _) = seeder.build_dag_from_scratch(&ctx, master_cs).await?;
+    let (dag, _) = seeder.build_dag_from_scratch(&ctx, vec![master_cs]).await?;
 
     let cs7 = resolve_cs_id(&ctx, &blobrepo, "0ed509bf086fadcb8a8a5384dc3b550729b0fc17").await?;
     let distance: u64 = 4;
@@ -437,6 +586,129 @@ async fn test_clone_data(fb: FacebookInit) -> Result<()> {
     Ok(())
 }
 
+#[fbinit::compat_test]
+async fn test_clone_data_multiple_heads(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let blobrepo = unshared_merge_even::getrepo(fb).await;
+
+    let merge = resolve_cs_id(&ctx, &blobrepo, "7fe9947f101acb4acf7d945e69f0d6ce76a81113").await?;
+    setup_phases(&ctx, &blobrepo, merge).await?;
+    let parents = blobrepo
+        .get_changeset_parents_by_bonsai(ctx.clone(), merge)
+        .await?;
+    let (p1, p2) = (parents[0], parents[1]);
+
+    let mut seeded_head_vertexes = vec![];
+    for _ in 0..2 {
+        let seeder = SegmentedChangelogBuilder::with_sqlite_in_memory()?
+            .with_blobrepo(&blobrepo)
+            .build_seeder(&ctx)
+            .await?;
+        let (dag, head_vertexes) = seeder.build_dag_from_scratch(&ctx, vec![p1, p2]).await?;
+        // The ancestors of the first head are assigned first.
+        assert!(head_vertexes[0] < head_vertexes[1]);
+        assert_eq!(dag.is_ancestor(&ctx, p1, p2).await?, Some(false));
+
+        let clone_data = dag.clone_data(&ctx).await?;
+        assert_eq!(clone_data.idmap.get(&head_vertexes[0]), Some(&p1));
+        assert_eq!(clone_data.idmap.get(&head_vertexes[1]), Some(&p2));
+        seeded_head_vertexes.push(head_vertexes);
+    }
+    // Assignments are stable across seedings.
+    assert_eq!(seeded_head_vertexes[0], seeded_head_vertexes[1]);
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn test_clone_data_head_is_master_after_tracked_bookmark_moves(
+    fb: FacebookInit,
+) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let blobrepo = linear::getrepo(fb).await;
+
+    let master_cs =
+        resolve_cs_id(&ctx, &blobrepo, "79a13814c5ce7330173ec04d279bf95ab3f652fb").await?;
+    let master = bookmark(&ctx, &blobrepo, "master")
+        .set_to(master_cs)
+        .await?;
+    let release_cs = CreateCommitContext::new(&ctx, &blobrepo, vec![master_cs])
+        .add_file("release", "1")
+        .commit()
+        .await?;
+    let release = bookmark(&ctx, &blobrepo, "release")
+        .set_to(release_cs)
+        .await?;
+    setup_phases(&ctx, &blobrepo, release_cs).await?;
+
+    let builder = SegmentedChangelogBuilder::with_sqlite_in_memory()?
+        .with_blobrepo(&blobrepo)
+        .with_bookmark_name(master)
+        .with_tracked_bookmarks(vec![release.clone()]);
+    let seeder = builder.clone().build_seeder(&ctx).await?;
+    seeder.run(&ctx, vec![master_cs, release_cs]).await?;
+
+    // The tracked bookmark moves while master stays put, so the last assigned vertex is no
+    // longer the master head.
+    let release_cs = CreateCommitContext::new(&ctx, &blobrepo, vec![release_cs])
+        .add_file("release", "2")
+        .commit()
+        .await?;
+    bookmark(&ctx, &blobrepo, release)
+        .set_to(release_cs)
+        .await?;
+
+    let tailer = builder.clone().build_tailer()?;
+    let (dag, master_vertex) = tailer.once(&ctx).await?;
+    assert_eq!(dag.idmap.get_vertex(&ctx, master_cs).await?, master_vertex);
+    assert!(dag.idmap.get_vertex(&ctx, release_cs).await? > master_vertex);
+
+    let clone_data = dag.clone_data(&ctx).await?;
+    assert_eq!(clone_data.head_id, master_vertex);
+    assert_eq!(clone_data.idmap.get(&clone_data.head_id), Some(&master_cs));
+
+    // The saved dag keeps track of the master head as well.
+    let manager = builder.build_manager()?;
+    let clone_data = manager.clone_data(&ctx).await?;
+    assert_eq!(clone_data.head_id, master_vertex);
+
+    let clone_data = manager.full_idmap_clone_data(&ctx).await?;
+    assert_eq!(clone_data.head_id, master_vertex);
+    let idmap = clone_data.idmap_stream.try_collect::<Vec<_>>().await?;
+    assert!(idmap.contains(&(master_vertex, master_cs)));
+    assert!(idmap.iter().any(|(_, cs_id)| *cs_id == release_cs));
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn test_manager_queries_reuse_loaded_dag(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let blobrepo = linear::getrepo(fb).await;
+    let head = resolve_cs_id(&ctx, &blobrepo, "79a13814c5ce7330173ec04d279bf95ab3f652fb").await?;
+    let ancestor =
+        resolve_cs_id(&ctx, &blobrepo, "0ed509bf086fadcb8a8a5384dc3b550729b0fc17").await?;
+    setup_phases(&ctx, &blobrepo, head).await?;
+
+    let builder = SegmentedChangelogBuilder::with_sqlite_in_memory()?.with_blobrepo(&blobrepo);
+    let seeder = builder.clone().build_seeder(&ctx).await?;
+    seeder.run(&ctx, vec![head]).await?;
+
+    let manager = builder.build_manager()?;
+    assert_eq!(manager.is_ancestor(&ctx, ancestor, head).await?, Some(true));
+    assert_eq!(
+        manager.common_ancestors(&ctx, vec![ancestor, head]).await?,
+        Some(vec![ancestor])
+    );
+    // Queries share the saved dag instead of loading it again.
+    assert!(Arc::ptr_eq(
+        &manager.query_dag(&ctx).await?,
+        &manager.query_dag(&ctx).await?
+    ));
+
+    Ok(())
+}
+
 #[fbinit::compat_test]
 async fn test_full_idmap_clone_data(fb: FacebookInit) -> Result<()> {
     // In this test we first build a dag from scratch and then we reuse the idmap in an ondemand
@@ -478,7 +750,7 @@ async fn test_caching(fb: FacebookInit) -> Result<()> {
         .with_cache_handlers(cache_handlers)
         .build_seeder(&ctx)
         .await?;
-    let (dag, _) = seeder.build_dag_from_scratch(&ctx, head).await?;
+    let (dag, _) = seeder.build_dag_from_scratch(&ctx, vec![head]).await?;
 
     let distance: u64 = 1;
     let _ = dag.location_to_changeset_id(&ctx, head, distance).await?;
@@ -82,6 +82,9 @@ pub struct MononokeTunables {
     filenodes_disabled: AtomicBool,
     filenodes_master_fallback_ratio: AtomicI64,
     skiplist_max_skips_without_yield: AtomicI64,
+    // How often servers check for a new skiplist published by the skiplist
+    // tailer. Reloading is disabled if not positive.
+    skiplist_reload_interval_secs: AtomicI64,
     deduplicated_put_sampling_rate: AtomicI64,
     disable_repo_client_warm_bookmarks_cache: AtomicBool,
     remotefilelog_file_history_limit: AtomicI64,
@@ -557,6 +557,7 @@ class remotefileslog(filelog.fileslog):
                 memcachestore,
                 edenapistore,
                 correlator=correlator,
+                ui=repo.ui,
             )
             sharedonlymetadatastore = revisionstore.metadatastore(
                 None,
@@ -587,6 +588,7 @@ class remotefileslog(filelog.fileslog):
                 memcachestore,
                 edenapistore,
                 correlator=correlator,
+                ui=repo.ui,
             )
             self.metadatastore = revisionstore.metadatastore(
                 repo.svfs.vfs.base,
@@ -0,0 +1,84 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::dagalgo::dagalgo;
+use crate::Names;
+use async_runtime::block_on_exclusive as block_on;
+use cpython::*;
+use cpython_ext::convert::Serde;
+use cpython_ext::PyNone;
+use cpython_ext::ResultPyErrExt;
+use dag::bisect::Bisect;
+use dag::bisect::BisectStep;
+use dag::Set;
+use std::cell::RefCell;
+
+// A wrapper around [`Bisect`].
+py_class!(pub class bisect |py| {
+    data inner: RefCell<Bisect>;
+
+    def __new__(_cls, good: Names, bad: Names, skip: Option<Names> = None) -> PyResult<Self> {
+        let skip = skip.map(|s| s.0).unwrap_or_else(Set::empty);
+        let inner = block_on(Bisect::from_sets(&good.0, &bad.0, &skip)).map_pyerr(py)?;
+        Self::create_instance(py, RefCell::new(inner))
+    }
+
+    /// Resume a bisection from a state returned by `state()`.
+    @staticmethod
+    def fromstate(state: Serde<Bisect>) -> PyResult<Self> {
+        Self::create_instance(py, RefCell::new(state.0))
+    }
+
+    /// Serializable state of the bisection.
+    def state(&self) -> PyResult<Serde<Bisect>> {
+        Ok(Serde(self.inner(py).borrow().clone()))
+    }
+
+    def markgood(&self, set: Names) -> PyResult<PyNone> {
+        block_on(self.inner(py).borrow_mut().mark_good(&set.0)).map_pyerr(py)?;
+        Ok(PyNone)
+    }
+
+    def markbad(&self, set: Names) -> PyResult<PyNone> {
+        block_on(self.inner(py).borrow_mut().mark_bad(&set.0)).map_pyerr(py)?;
+        Ok(PyNone)
+    }
+
+    def markskip(&self, set: Names) -> PyResult<PyNone> {
+        block_on(self.inner(py).borrow_mut().mark_skip(&set.0)).map_pyerr(py)?;
+        Ok(PyNone)
+    }
+
+    def good(&self) -> PyResult<Names> {
+        Ok(Names(self.inner(py).borrow().good()))
+    }
+
+    def bad(&self) -> PyResult<Names> {
+        Ok(Names(self.inner(py).borrow().bad()))
+    }
+
+    def skip(&self) -> PyResult<Names> {
+        Ok(Names(self.inner(py).borrow().skip()))
+    }
+
+    /// Decide the next step of the bisection.
+    ///
+    /// Returns `("test", node, candidatecount)` if `node` should be tested
+    /// next, or `("found", nameset)` if the first bad commit is in `nameset`.
+    def next(&self, dag: dagalgo) -> PyResult<PyTuple> {
+        let dag = dag.inner_dag(py);
+        let inner = self.inner(py).borrow().clone();
+        let step = block_on(inner.next(dag.as_ref())).map_pyerr(py)?;
+        let result = match step {
+            BisectStep::Test { vertex, candidates } => {
+                ("test", PyBytes::new(py, vertex.as_ref()), candidates).to_py_object(py)
+            }
+            BisectStep::Found(set) => ("found", Names(set)).to_py_object(py),
+        };
+        Ok(result)
+    }
+});
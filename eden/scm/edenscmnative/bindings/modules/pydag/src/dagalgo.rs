@@ -164,4 +164,8 @@ impl dagalgo {
     pub fn from_arc_dag(py: Python, dag: Arc<dyn DagAlgorithm + Send + Sync>) -> PyResult<Self> {
         Self::create_instance(py, dag)
     }
+
+    pub(crate) fn inner_dag(&self, py: Python) -> Arc<dyn DagAlgorithm + Send + Sync> {
+        self.dag(py).clone()
+    }
 }
@@ -9,6 +9,7 @@
 
 use cpython::*;
 
+pub mod bisect;
 pub mod commits;
 pub mod dagalgo;
 pub mod idmap;
@@ -34,6 +35,9 @@ pub fn init_module(py: Python, package: &str) -> PyResult<PyModule> {
     m.add_class::<nameset::nameset>(py)?;
     m.add_class::<spanset::spans>(py)?;
 
+    // bisection
+    m.add_class::<bisect::bisect>(py)?;
+
     // maximum Id
     m.add(py, "MAX_ID", dag::Id::MAX.0)?;
 
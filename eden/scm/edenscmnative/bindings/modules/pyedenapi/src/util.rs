@@ -95,5 +95,7 @@ pub fn meta_to_dict(py: Python, meta: &ResponseMeta) -> PyResult<PyDict> {
     dict.set_item(py, "tw_task_version", &meta.tw_task_version)?;
     dict.set_item(py, "tw_canary_id", &meta.tw_canary_id)?;
     dict.set_item(py, "server_load", &meta.server_load)?;
+    dict.set_item(py, "retries", meta.retries)?;
+    dict.set_item(py, "content_encoding", &meta.content_encoding)?;
     Ok(dict)
 }
@@ -907,12 +907,16 @@ py_class!(pub class contentstore |py| {
         memcache: Option<memcachestore>,
         edenapi: Option<edenapifilestore> = None,
         suffix: Option<String> = None,
-        correlator: Option<String> = None
+        correlator: Option<String> = None,
+        ui: Option<PyObject> = None
     ) -> PyResult<contentstore> {
         let remotestore = remote.extract_inner(py);
         let config = config.get_cfg(py);
+        let progress = ui.map_or_else(|| Ok(NullProgressFactory::arc()), |ui| PyProgressFactory::arc(py, ui))?;
 
-        let mut builder = ContentStoreBuilder::new(&config).correlator(correlator);
+        let mut builder = ContentStoreBuilder::new(&config)
+            .correlator(correlator)
+            .progress(progress);
 
         builder = if let Some(edenapi) = edenapi {
             builder.remotestore(edenapi.extract_inner(py))
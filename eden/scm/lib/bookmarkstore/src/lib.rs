@@ -67,7 +67,7 @@ impl BookmarkStore {
         let mut iter = self.log.lookup(0, bookmark).unwrap();
         iter.next().and_then(|data| {
             let data = data.unwrap();
-            match BookmarkEntry::unpack(data) {
+            match BookmarkEntry::unpack(&data) {
                 BookmarkEntry::Remove {
                     bookmark: found_bookmark,
                 } => {
@@ -91,7 +91,7 @@ impl BookmarkStore {
             .filter_map(|data| {
                 let data = data.unwrap();
 
-                match BookmarkEntry::unpack(data) {
+                match BookmarkEntry::unpack(&data) {
                     BookmarkEntry::Remove { bookmark: _ } => {
                         panic!("unreachable code");
                     }
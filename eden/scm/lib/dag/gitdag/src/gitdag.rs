@@ -8,14 +8,22 @@
 use crate::errors::MapDagError;
 use dag::ops::DagAlgorithm;
 use dag::ops::DagPersistent;
+use dag::ops::IdConvert;
 use dag::Dag;
 use dag::Set;
 use dag::Vertex;
 use nonblocking::non_blocking_result;
 use parking_lot::Mutex;
 use std::collections::BTreeMap;
+use std::fs;
+use std::io;
 use std::ops::Deref;
 use std::path::Path;
+use std::time::Duration;
+use std::time::Instant;
+
+/// File in the dag directory tracking the git references imported so far.
+const REFERENCES_FILE: &str = "git-references";
 
 /// `GitDag` maintains segmented changelog as an index on the Git commit graph.
 ///
@@ -23,13 +31,39 @@ use std::path::Path;
 /// parts of the git repo, or make changes to the Git commit graph, use a
 /// separate `git2::Repository` object.
 ///
-/// The `dag` part is append-only. It might include vertexes no longer referred
-/// by the git repo. Use `ancestors(git_heads())` to get commits referred by
-/// the git repo, and use `&` to filter them.
+/// The MASTER group of the `dag` is append-only. It might include vertexes no
+/// longer referred by the git repo. Use `ancestors(git_heads())` to get
+/// commits referred by the git repo, and use `&` to filter them.
+///
+/// The NON_MASTER group is rebuilt when a non-main reference is deleted or
+/// force-pushed, so it does not accumulate unreferenced commits.
 pub struct GitDag {
     dag: Dag,
     heads: Set,
     references: BTreeMap<String, Vertex>,
+    stats: SyncStats,
+}
+
+/// Statistics about the last sync from git.
+#[derive(Clone, Debug, Default)]
+pub struct SyncStats {
+    /// Time spent listing and resolving git references.
+    pub list_references: Duration,
+
+    /// Time spent importing commits into the dag.
+    pub import: Duration,
+
+    /// Time spent calculating heads.
+    pub heads: Duration,
+
+    /// Number of references that are new or moved since the last sync.
+    pub changed_references: usize,
+
+    /// Number of references that are deleted since the last sync.
+    pub deleted_references: usize,
+
+    /// Whether the NON_MASTER group was rebuilt.
+    pub reset_non_master: bool,
 }
 
 impl GitDag {
@@ -41,13 +75,16 @@ impl GitDag {
     }
 
     /// For an git repo, build index at `dag_dir` with specified `main_branch`.
+    ///
+    /// References imported by a previous `open` are remembered in `dag_dir`,
+    /// so only commits reachable from new or moved references are imported.
     pub fn open_git_repo(
         git_repo: &git2::Repository,
         dag_dir: &Path,
         main_branch: &str,
     ) -> dag::Result<Self> {
         let dag = Dag::open(dag_dir)?;
-        Ok(sync_from_git(dag, git_repo, main_branch)?)
+        Ok(sync_from_git(dag, dag_dir, git_repo, main_branch)?)
     }
 
     /// Get "snapshotted" references.
@@ -59,6 +96,11 @@ impl GitDag {
     pub fn git_heads(&self) -> Set {
         self.heads.clone()
     }
+
+    /// Get statistics about the sync from git that happened on open.
+    pub fn sync_stats(&self) -> &SyncStats {
+        &self.stats
+    }
 }
 
 impl Deref for GitDag {
@@ -69,18 +111,58 @@ impl Deref for GitDag {
     }
 }
 
+/// Read references imported by a previous sync.
+fn load_references(dag_dir: &Path) -> dag::Result<BTreeMap<String, Vertex>> {
+    let path = dag_dir.join(REFERENCES_FILE);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Default::default()),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+    };
+    let mut references = BTreeMap::new();
+    for line in content.lines() {
+        // Each line is "<hex> <name>". Ignore malformed lines, the affected
+        // references are treated as new.
+        let mut parts = line.splitn(2, ' ');
+        if let (Some(hex), Some(name)) = (parts.next(), parts.next()) {
+            if let Ok(vertex) = Vertex::from_hex(hex.as_bytes()) {
+                references.insert(name.to_string(), vertex);
+            }
+        }
+    }
+    Ok(references)
+}
+
+/// Remember references imported by this sync.
+fn save_references(dag_dir: &Path, references: &BTreeMap<String, Vertex>) -> dag::Result<()> {
+    let mut content = String::new();
+    for (name, vertex) in references {
+        content.push_str(&format!("{} {}\n", vertex.to_hex(), name));
+    }
+    // Write to a temporary file and rename, so a crash does not leave a
+    // truncated file behind.
+    let path = dag_dir.join(REFERENCES_FILE);
+    let tmp_path = dag_dir.join(format!("{}.tmp", REFERENCES_FILE));
+    fs::write(&tmp_path, content).with_context(|| format!("writing {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, &path).with_context(|| format!("writing {}", path.display()))?;
+    Ok(())
+}
+
 /// Read references from git, build segments for new heads.
 ///
 /// Useful when the git repo is changed by other processes or threads.
 fn sync_from_git(
     mut dag: Dag,
+    dag_dir: &Path,
     git_repo: &git2::Repository,
     main_branch: &str,
 ) -> dag::Result<GitDag> {
+    let mut stats = SyncStats::default();
     let mut master_heads = Vec::new();
     let mut non_master_heads = Vec::new();
     let mut references = BTreeMap::new();
 
+    let start = Instant::now();
     let git_refs = git_repo.references().context("listing git references")?;
     for git_ref in git_refs {
         let git_ref = git_ref.context("resolving git reference")?;
@@ -108,6 +190,35 @@ fn sync_from_git(
             non_master_heads.push(vertex);
         }
     }
+    stats.list_references = start.elapsed();
+
+    // Compare with the references imported last time. A non-main reference
+    // that was deleted, or moved to a commit that is not a descendant of its
+    // old position, might leave commits in the NON_MASTER group that are no
+    // longer referred. Rebuild the group in that case.
+    let old_references = load_references(dag_dir)?;
+    for (name, old_vertex) in old_references.iter() {
+        match references.get(name) {
+            None => {
+                tracing::debug!("git ref {} was deleted", name);
+                stats.deleted_references += 1;
+                if name != main_branch {
+                    stats.reset_non_master = true;
+                }
+            }
+            Some(vertex) if vertex != old_vertex => {
+                if name != main_branch && !is_descendant(git_repo, vertex, old_vertex)? {
+                    tracing::debug!("git ref {} was force-pushed", name);
+                    stats.reset_non_master = true;
+                }
+            }
+            Some(_) => {}
+        }
+    }
+    stats.changed_references = references
+        .iter()
+        .filter(|(name, vertex)| old_references.get(*name) != Some(*vertex))
+        .count();
 
     struct ForceSend<T>(T);
 
@@ -134,15 +245,256 @@ fn sync_from_git(
     };
     let parents: Box<dyn Fn(Vertex) -> dag::Result<Vec<Vertex>> + Send + Sync> =
         Box::new(parent_func);
-    non_blocking_result(dag.add_heads_and_flush(&parents, &master_heads, &non_master_heads))?;
 
+    let start = Instant::now();
+    if stats.reset_non_master {
+        non_blocking_result(dag.reset_non_master_and_flush(
+            &parents,
+            &master_heads,
+            &non_master_heads,
+        ))?;
+    } else if stats.changed_references > 0 {
+        // Only import heads that are not known yet. Main branch heads are
+        // always passed so they get promoted to the MASTER group if they
+        // were imported as part of another reference.
+        let mut new_heads = Vec::new();
+        for head in non_master_heads.iter() {
+            if !non_blocking_result(dag.contains_vertex_name(head))? {
+                new_heads.push(head.clone());
+            }
+        }
+        non_blocking_result(dag.add_heads_and_flush(&parents, &master_heads, &new_heads))?;
+    }
+    stats.import = start.elapsed();
+
+    let start = Instant::now();
     let possible_heads =
         Set::from_static_names(master_heads.into_iter().chain(non_master_heads.into_iter()));
     let heads = non_blocking_result(dag.heads_ancestors(possible_heads))?;
+    stats.heads = start.elapsed();
+
+    if references != old_references {
+        save_references(dag_dir, &references)?;
+    }
+
+    tracing::info!(
+        "synced {} git refs ({} changed, {} deleted{}) in {:?} (list: {:?}, import: {:?}, heads: {:?})",
+        references.len(),
+        stats.changed_references,
+        stats.deleted_references,
+        if stats.reset_non_master {
+            ", non-master reset"
+        } else {
+            ""
+        },
+        stats.list_references + stats.import + stats.heads,
+        stats.list_references,
+        stats.import,
+        stats.heads,
+    );
 
     Ok(GitDag {
         dag,
         heads,
         references,
+        stats,
     })
 }
+
+/// Test if `vertex` is `ancestor`, or a descendant of it, in git.
+fn is_descendant(
+    git_repo: &git2::Repository,
+    vertex: &Vertex,
+    ancestor: &Vertex,
+) -> dag::Result<bool> {
+    let oid = git2::Oid::from_bytes(vertex.as_ref())
+        .with_context(|| format!("converting to git oid for {:?}", vertex))?;
+    let ancestor_oid = git2::Oid::from_bytes(ancestor.as_ref())
+        .with_context(|| format!("converting to git oid for {:?}", ancestor))?;
+    if oid == ancestor_oid {
+        return Ok(true);
+    }
+    match git_repo.graph_descendant_of(oid, ancestor_oid) {
+        Ok(result) => Ok(result),
+        // The old commit might have been garbage collected.
+        Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(false),
+        Err(e) => Err(e).context("checking git ancestry"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dag::nameset::SyncNameSetQuery;
+
+    const MASTER: &str = "refs/heads/master";
+    const FEATURE: &str = "refs/heads/feature";
+
+    struct TestRepo {
+        _dir: tempfile::TempDir,
+        git_repo: git2::Repository,
+        dag_dir: std::path::PathBuf,
+    }
+
+    impl TestRepo {
+        fn new() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let git_repo = git2::Repository::init_bare(dir.path().join("git")).unwrap();
+            let dag_dir = dir.path().join("dag");
+            Self {
+                _dir: dir,
+                git_repo,
+                dag_dir,
+            }
+        }
+
+        /// Create a commit with an empty tree.
+        fn commit(&self, message: &str, parents: &[git2::Oid]) -> git2::Oid {
+            let tree_id = self.git_repo.treebuilder(None).unwrap().write().unwrap();
+            let tree = self.git_repo.find_tree(tree_id).unwrap();
+            let parents: Vec<git2::Commit> = parents
+                .iter()
+                .map(|oid| self.git_repo.find_commit(*oid).unwrap())
+                .collect();
+            let parents: Vec<&git2::Commit> = parents.iter().collect();
+            let sig =
+                git2::Signature::new("test", "test@example.com", &git2::Time::new(0, 0)).unwrap();
+            self.git_repo
+                .commit(None, &sig, &sig, message, &tree, &parents)
+                .unwrap()
+        }
+
+        fn set_ref(&self, name: &str, oid: git2::Oid) {
+            self.git_repo.reference(name, oid, true, "test").unwrap();
+        }
+
+        fn delete_ref(&self, name: &str) {
+            self.git_repo
+                .find_reference(name)
+                .unwrap()
+                .delete()
+                .unwrap();
+        }
+
+        fn open(&self) -> GitDag {
+            GitDag::open_git_repo(&self.git_repo, &self.dag_dir, MASTER).unwrap()
+        }
+    }
+
+    fn v(oid: git2::Oid) -> Vertex {
+        Vertex::copy_from(oid.as_bytes())
+    }
+
+    fn contains(dag: &GitDag, oid: git2::Oid) -> bool {
+        non_blocking_result(dag.contains_vertex_name(&v(oid))).unwrap()
+    }
+
+    fn heads(dag: &GitDag) -> Vec<Vertex> {
+        let heads = dag
+            .git_heads()
+            .iter()
+            .unwrap()
+            .map(|v| v.unwrap())
+            .collect();
+        sorted(heads)
+    }
+
+    fn sorted(mut vertexes: Vec<Vertex>) -> Vec<Vertex> {
+        vertexes.sort();
+        vertexes
+    }
+
+    #[test]
+    fn test_incremental_sync() {
+        let repo = TestRepo::new();
+        let a = repo.commit("A", &[]);
+        let b = repo.commit("B", &[a]);
+        repo.set_ref(MASTER, b);
+
+        let dag = repo.open();
+        assert_eq!(dag.sync_stats().changed_references, 1);
+        assert_eq!(heads(&dag), vec![v(b)]);
+        assert_eq!(dag.git_references().get(MASTER), Some(&v(b)));
+        drop(dag);
+
+        // Nothing changed. Nothing is imported.
+        let dag = repo.open();
+        assert_eq!(dag.sync_stats().changed_references, 0);
+        assert!(!dag.sync_stats().reset_non_master);
+        assert_eq!(heads(&dag), vec![v(b)]);
+        drop(dag);
+
+        // Only new and moved references are imported.
+        let c = repo.commit("C", &[b]);
+        let d = repo.commit("D", &[b]);
+        repo.set_ref(MASTER, c);
+        repo.set_ref(FEATURE, d);
+        let dag = repo.open();
+        assert_eq!(dag.sync_stats().changed_references, 2);
+        assert_eq!(dag.sync_stats().deleted_references, 0);
+        assert!(!dag.sync_stats().reset_non_master);
+        assert!(contains(&dag, c));
+        assert!(contains(&dag, d));
+        assert_eq!(heads(&dag), sorted(vec![v(c), v(d)]));
+        drop(dag);
+
+        // Fast-forwarding a non-main reference does not rebuild NON_MASTER.
+        let e = repo.commit("E", &[d]);
+        repo.set_ref(FEATURE, e);
+        let dag = repo.open();
+        assert_eq!(dag.sync_stats().changed_references, 1);
+        assert!(!dag.sync_stats().reset_non_master);
+        assert_eq!(heads(&dag), sorted(vec![v(c), v(e)]));
+    }
+
+    #[test]
+    fn test_deleted_reference() {
+        let repo = TestRepo::new();
+        let a = repo.commit("A", &[]);
+        let b = repo.commit("B", &[a]);
+        repo.set_ref(MASTER, a);
+        repo.set_ref(FEATURE, b);
+        let dag = repo.open();
+        assert!(contains(&dag, b));
+        drop(dag);
+
+        // Commits only referred by the deleted reference are dropped.
+        repo.delete_ref(FEATURE);
+        let dag = repo.open();
+        assert_eq!(dag.sync_stats().deleted_references, 1);
+        assert!(dag.sync_stats().reset_non_master);
+        assert!(!contains(&dag, b));
+        assert!(contains(&dag, a));
+        assert_eq!(heads(&dag), vec![v(a)]);
+        assert_eq!(dag.git_references().get(FEATURE), None);
+        drop(dag);
+
+        // The deletion is remembered.
+        let dag = repo.open();
+        assert_eq!(dag.sync_stats().deleted_references, 0);
+        assert!(!dag.sync_stats().reset_non_master);
+    }
+
+    #[test]
+    fn test_force_pushed_reference() {
+        let repo = TestRepo::new();
+        let a = repo.commit("A", &[]);
+        let b1 = repo.commit("B1", &[a]);
+        repo.set_ref(MASTER, a);
+        repo.set_ref(FEATURE, b1);
+        let dag = repo.open();
+        assert!(contains(&dag, b1));
+        drop(dag);
+
+        // Commits no longer referred after the force-push are dropped.
+        let b2 = repo.commit("B2", &[a]);
+        repo.set_ref(FEATURE, b2);
+        let dag = repo.open();
+        assert_eq!(dag.sync_stats().changed_references, 1);
+        assert!(dag.sync_stats().reset_non_master);
+        assert!(!contains(&dag, b1));
+        assert!(contains(&dag, b2));
+        assert_eq!(heads(&dag), vec![v(b2)]);
+        assert_eq!(dag.git_references().get(FEATURE), Some(&v(b2)));
+    }
+}
@@ -0,0 +1,357 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! # bisect
+//!
+//! Bisection over a DAG to find the first "bad" vertex.
+
+use crate::errors::DagError;
+use crate::DagAlgorithm;
+use crate::NameSet;
+use crate::Result;
+use crate::VertexName;
+use futures::TryStreamExt;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// State of a bisection.
+///
+/// The state only contains the vertexes marked as good, bad, or skipped, so
+/// it can be serialized and resumed later, potentially against a DAG that
+/// has grown since.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bisect {
+    good: Vec<VertexName>,
+    bad: Vec<VertexName>,
+    skip: Vec<VertexName>,
+}
+
+/// What to do next in a bisection.
+#[derive(Debug)]
+pub enum BisectStep {
+    /// `vertex` should be tested next. `candidates` is the number of
+    /// vertexes that might still be the first bad vertex.
+    Test {
+        vertex: VertexName,
+        candidates: usize,
+    },
+
+    /// The first bad vertex is in the set. The set contains more than one
+    /// vertex if skipped vertexes make the answer ambiguous.
+    Found(NameSet),
+}
+
+#[derive(Copy, Clone)]
+enum Mark {
+    Good,
+    Bad,
+    Skip,
+}
+
+impl Bisect {
+    /// Start a bisection from the given good, bad and skipped vertexes.
+    pub async fn from_sets(good: &NameSet, bad: &NameSet, skip: &NameSet) -> Result<Self> {
+        let mut bisect = Self::default();
+        bisect.mark_good(good).await?;
+        bisect.mark_bad(bad).await?;
+        bisect.mark_skip(skip).await?;
+        Ok(bisect)
+    }
+
+    /// Mark vertexes as good. Overrides previous marks of those vertexes.
+    pub async fn mark_good(&mut self, set: &NameSet) -> Result<()> {
+        self.mark(set, Mark::Good).await
+    }
+
+    /// Mark vertexes as bad. Overrides previous marks of those vertexes.
+    pub async fn mark_bad(&mut self, set: &NameSet) -> Result<()> {
+        self.mark(set, Mark::Bad).await
+    }
+
+    /// Mark vertexes as skipped. Skipped vertexes are never picked for
+    /// testing. Overrides previous marks of those vertexes.
+    pub async fn mark_skip(&mut self, set: &NameSet) -> Result<()> {
+        self.mark(set, Mark::Skip).await
+    }
+
+    /// Vertexes marked as good.
+    pub fn good(&self) -> NameSet {
+        NameSet::from_static_names(self.good.clone())
+    }
+
+    /// Vertexes marked as bad.
+    pub fn bad(&self) -> NameSet {
+        NameSet::from_static_names(self.bad.clone())
+    }
+
+    /// Vertexes marked as skipped.
+    pub fn skip(&self) -> NameSet {
+        NameSet::from_static_names(self.skip.clone())
+    }
+
+    /// Decide the next step of the bisection.
+    ///
+    /// The first bad vertex is a common ancestor of all bad vertexes that
+    /// is not an ancestor of any good vertex. Among those candidates, pick
+    /// an untested vertex whose ancestors split the candidates about evenly,
+    /// so either answer eliminates as many candidates as possible. Counting
+    /// ancestors instead of using revision numbers keeps the split balanced
+    /// across merges.
+    pub async fn next(&self, dag: &(impl DagAlgorithm + ?Sized)) -> Result<BisectStep> {
+        if self.good.is_empty() || self.bad.is_empty() {
+            return Err(DagError::InvalidBisect(
+                "bisect requires at least one good and one bad vertex".to_string(),
+            ));
+        }
+        let common_ancestors = dag.common_ancestors(self.bad()).await?;
+        let good_ancestors = dag.ancestors(self.good()).await?;
+        let candidates = dag
+            .sort(&(common_ancestors.clone() - good_ancestors))
+            .await?;
+        let total = candidates.count().await?;
+        if total == 0 {
+            let reason = if common_ancestors.is_empty().await? {
+                "the bad vertexes have no common ancestor"
+            } else {
+                "every common ancestor of the bad vertexes is an ancestor of a good vertex"
+            };
+            return Err(DagError::InvalidBisect(format!(
+                "inconsistent bisect state: {}",
+                reason
+            )));
+        }
+
+        // Sorted sets are backed by id spans if the dag assigns ids, so the
+        // set operations below work on segments instead of vertexes.
+        let untested = dag.sort(&self.bad().union(&self.skip())).await?;
+        let testable = candidates.clone() - untested;
+        let testable_count = testable.count().await?;
+
+        // Number of candidates that are ancestors of the `index`-th testable
+        // vertex, counting from the roots.
+        let below = |index: usize| {
+            let vertex = testable.skip((testable_count - 1 - index) as u64).take(1);
+            let candidates = candidates.clone();
+            async move {
+                let below = (dag.ancestors(vertex.clone()).await? & candidates)
+                    .count()
+                    .await?;
+                let vertex = vertex.first().await?.expect("index is in range");
+                Result::Ok((vertex, below))
+            }
+        };
+
+        // The number of candidate ancestors mostly grows with the topological
+        // order, so binary search the first vertex that has at least half of
+        // the candidates as ancestors. It or the vertex before it gives the
+        // most even split.
+        let (mut low, mut high) = (0, testable_count);
+        while low < high {
+            let mid = (low + high) / 2;
+            if below(mid).await?.1 * 2 >= total {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        let mut best: Option<(usize, VertexName)> = None;
+        for index in low.saturating_sub(1)..(low + 1).min(testable_count) {
+            let (vertex, below) = below(index).await?;
+            // If `vertex` is bad, `below` candidates remain. Otherwise,
+            // `total - below` candidates remain.
+            let score = below.min(total - below);
+            if score > 0 && best.as_ref().map_or(true, |(s, _)| score > *s) {
+                best = Some((score, vertex));
+            }
+        }
+
+        match best {
+            Some((_, vertex)) => Ok(BisectStep::Test {
+                vertex,
+                candidates: total,
+            }),
+            None => Ok(BisectStep::Found(candidates)),
+        }
+    }
+
+    async fn mark(&mut self, set: &NameSet, mark: Mark) -> Result<()> {
+        let names: Vec<VertexName> = set.iter().await?.try_collect().await?;
+        for name in names {
+            self.good.retain(|v| v != &name);
+            self.bad.retain(|v| v != &name);
+            self.skip.retain(|v| v != &name);
+            match mark {
+                Mark::Good => self.good.push(name),
+                Mark::Bad => self.bad.push(name),
+                Mark::Skip => self.skip.push(name),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::ImportAscii;
+    use crate::MemDag;
+    use nonblocking::non_blocking_result as r;
+
+    fn v(name: &str) -> VertexName {
+        VertexName::copy_from(name.as_bytes())
+    }
+
+    fn set(names: &str) -> NameSet {
+        NameSet::from_static_names(names.split_whitespace().map(v))
+    }
+
+    fn names(set: NameSet) -> String {
+        let names: Vec<VertexName> =
+            r(async { set.iter().await?.try_collect::<Vec<_>>().await }).unwrap();
+        let mut names: Vec<String> = names
+            .into_iter()
+            .map(|v| String::from_utf8_lossy(v.as_ref()).to_string())
+            .collect();
+        names.sort();
+        names.join(" ")
+    }
+
+    fn bisect_until_found(dag: &MemDag, mut bisect: Bisect, first_bad: &str) -> Result<usize> {
+        let first_bad = v(first_bad);
+        let mut steps = 0;
+        loop {
+            match r(bisect.next(dag))? {
+                BisectStep::Test { vertex, .. } => {
+                    steps += 1;
+                    let is_bad = r(dag.is_ancestor(first_bad.clone(), vertex.clone()))?;
+                    let vertex = NameSet::from_static_names(vec![vertex]);
+                    if is_bad {
+                        r(bisect.mark_bad(&vertex))?;
+                    } else {
+                        r(bisect.mark_good(&vertex))?;
+                    }
+                }
+                BisectStep::Found(set) => {
+                    assert_eq!(names(set), String::from_utf8_lossy(first_bad.as_ref()));
+                    return Ok(steps);
+                }
+            }
+        }
+    }
+
+    fn dag(ascii: &str, heads: &[&str]) -> MemDag {
+        let mut dag = MemDag::new();
+        dag.import_ascii_with_heads(ascii, Some(heads)).unwrap();
+        dag
+    }
+
+    #[test]
+    fn test_bisect_linear() -> Result<()> {
+        let dag = dag("A-B-C-D-E-F-G-H-I-J-K-L-M-N-O-P", &["P"]);
+        let bisect = r(Bisect::from_sets(&set("A"), &set("P"), &set("")))?;
+        match r(bisect.next(&dag))? {
+            BisectStep::Test { vertex, candidates } => {
+                assert_eq!(candidates, 15);
+                assert_eq!(vertex, v("H"));
+            }
+            BisectStep::Found(_) => panic!("bisect should not be done"),
+        }
+
+        for first_bad in "B C D E F G H I J K L M N O P".split_whitespace() {
+            let steps = bisect_until_found(&dag, bisect.clone(), first_bad)?;
+            assert!(steps <= 4, "{} took {} steps", first_bad, steps);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_bisect_merges() -> Result<()> {
+        let dag = dag(
+            r#"
+            A-B-C-D-E-F-G-M-N
+               \         /
+                H-I-J-K-L"#,
+            &["N"],
+        );
+        let bisect = r(Bisect::from_sets(&set("A"), &set("N"), &set("")))?;
+        for first_bad in "B C D E F G H I J K L M N".split_whitespace() {
+            bisect_until_found(&dag, bisect.clone(), first_bad)?;
+        }
+
+        // Vertexes on the other side of the merge are not candidates once a
+        // vertex on one side is known to be bad.
+        let mut bisect = bisect;
+        r(bisect.mark_bad(&set("F")))?;
+        r(bisect.mark_good(&set("D")))?;
+        match r(bisect.next(&dag))? {
+            BisectStep::Test { vertex, candidates } => {
+                assert_eq!(candidates, 2);
+                assert_eq!(vertex, v("E"));
+            }
+            BisectStep::Found(_) => panic!("bisect should not be done"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_bisect_skip() -> Result<()> {
+        let dag = dag("A-B-C-D-E", &["E"]);
+        let mut bisect = r(Bisect::from_sets(&set("A"), &set("E"), &set("B C")))?;
+        match r(bisect.next(&dag))? {
+            BisectStep::Test { vertex, .. } => assert_eq!(vertex, v("D")),
+            BisectStep::Found(_) => panic!("bisect should not be done"),
+        }
+        r(bisect.mark_bad(&set("D")))?;
+        match r(bisect.next(&dag))? {
+            BisectStep::Found(set) => assert_eq!(names(set), "B C D"),
+            BisectStep::Test { vertex, .. } => panic!("unexpected test of {:?}", vertex),
+        }
+
+        // Marking a skipped vertex as good removes it from the skip list.
+        r(bisect.mark_good(&set("C")))?;
+        assert_eq!(names(bisect.skip()), "B");
+        assert_eq!(names(bisect.good()), "A C");
+        match r(bisect.next(&dag))? {
+            BisectStep::Found(set) => assert_eq!(names(set), "D"),
+            BisectStep::Test { vertex, .. } => panic!("unexpected test of {:?}", vertex),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_bisect_invalid_state() {
+        let dag = dag("A-B-C", &["C"]);
+        let bisect = r(Bisect::from_sets(&set("A"), &set(""), &set(""))).unwrap();
+        assert!(matches!(
+            r(bisect.next(&dag)),
+            Err(DagError::InvalidBisect(_))
+        ));
+        let bisect = r(Bisect::from_sets(&set("C"), &set("A"), &set(""))).unwrap();
+        assert!(matches!(
+            r(bisect.next(&dag)),
+            Err(DagError::InvalidBisect(_))
+        ));
+    }
+
+    #[test]
+    fn test_bisect_no_common_ancestor() {
+        let dag = dag(
+            r#"
+            A-B-C
+            D-E-F"#,
+            &["C", "F"],
+        );
+        let bisect = r(Bisect::from_sets(&set("A"), &set("C F"), &set(""))).unwrap();
+        match r(bisect.next(&dag)) {
+            Err(DagError::InvalidBisect(message)) => assert_eq!(
+                message,
+                "inconsistent bisect state: the bad vertexes have no common ancestor"
+            ),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
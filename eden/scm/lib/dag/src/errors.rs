@@ -26,6 +26,11 @@ pub enum DagError {
     #[error("ProgrammingError: {0}")]
     Programming(String),
 
+    /// The vertexes marked during a bisection are inconsistent. For example,
+    /// a vertex marked as good is a descendant of a vertex marked as bad.
+    #[error("{0}")]
+    InvalidBisect(String),
+
     /// Logic error in this crate. A bug in this crate or the backend data.
     #[error("bug: {0}")]
     Bug(String),
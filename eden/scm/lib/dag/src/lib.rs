@@ -12,6 +12,7 @@
 //!
 //! Building blocks for the commit graph used by source control.
 
+pub mod bisect;
 mod bsearch;
 mod default_impl;
 mod delegate;
@@ -36,7 +37,7 @@ pub use dag_wire_types::id;
 
 pub use clone::CloneData;
 pub use id::{Group, Id, VertexName};
-pub use iddag::IdDag;
+pub use iddag::{FirstAncestorConstraint, IdDag};
 #[cfg(any(test, feature = "indexedlog-backend"))]
 pub use idmap::IdMap;
 #[cfg(any(test, feature = "indexedlog-backend"))]
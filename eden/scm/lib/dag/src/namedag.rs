@@ -142,6 +142,52 @@ where
         Ok(())
     }
 
+    /// Drop the NON_MASTER group, then add vertexes and their ancestors to
+    /// the on-disk DAG.
+    ///
+    /// Useful when non-master heads are removed or rewritten, so vertexes
+    /// only reachable from the old heads are no longer part of the DAG.
+    async fn reset_non_master_and_flush(
+        &mut self,
+        parent_names_func: &dyn Parents,
+        master_names: &[VertexName],
+        non_master_names: &[VertexName],
+    ) -> Result<()> {
+        if !self.pending_heads.is_empty() {
+            return programming(format!(
+                "ProgrammingError: reset_non_master_and_flush called with pending heads ({:?})",
+                &self.pending_heads,
+            ));
+        }
+
+        self.invalidate_snapshot();
+
+        let locked = self.state.prepare_filesystem_sync()?;
+        let mut map = self.map.prepare_filesystem_sync()?;
+        let mut dag = self.dag.prepare_filesystem_sync()?;
+
+        // Remove existing non-master data.
+        dag.remove_non_master()?;
+        map.remove_non_master()?;
+
+        // Build.
+        build(
+            &mut map,
+            &mut dag,
+            parent_names_func,
+            master_names,
+            non_master_names,
+        )
+        .await?;
+
+        // Write to disk.
+        map.sync()?;
+        dag.sync()?;
+        locked.sync()?;
+
+        Ok(())
+    }
+
     /// Write in-memory DAG to disk. This will also pick up changes to
     /// the DAG by other processes.
     async fn flush(&mut self, master_heads: &[VertexName]) -> Result<()> {
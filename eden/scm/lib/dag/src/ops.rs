@@ -249,6 +249,16 @@ pub trait DagPersistent {
         non_master_names: &[VertexName],
     ) -> Result<()>;
 
+    /// Similar to `add_heads_and_flush`, but drops the existing NON_MASTER
+    /// group first. Vertexes in the NON_MASTER group that are not ancestors
+    /// of the given heads are removed. The MASTER group is not affected.
+    async fn reset_non_master_and_flush(
+        &mut self,
+        parent_names_func: &dyn Parents,
+        master_names: &[VertexName],
+        non_master_names: &[VertexName],
+    ) -> Result<()>;
+
     /// Import from another (potentially large) DAG. Write to disk immediately.
     async fn import_and_flush(
         &mut self,
@@ -13,6 +13,7 @@ mod output;
 #[allow(clippy::module_inception)]
 mod render;
 mod render_utils;
+mod svg;
 
 #[cfg(test)]
 mod test_fixtures;
@@ -25,3 +26,4 @@ pub use self::ascii_large::AsciiLargeRenderer;
 pub use self::box_drawing::BoxDrawingRenderer;
 pub use self::render::{Ancestor, GraphRowRenderer, LinkLine, NodeLine, PadLine, Renderer};
 pub use self::render_utils::render_namedag;
+pub use self::svg::{HtmlRenderer, SvgRenderer};
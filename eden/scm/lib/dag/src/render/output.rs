@@ -11,6 +11,7 @@ use super::ascii::AsciiRenderer;
 use super::ascii_large::AsciiLargeRenderer;
 use super::box_drawing::BoxDrawingRenderer;
 use super::render::{GraphRow, Renderer};
+use super::svg::{HtmlRenderer, SvgRenderer};
 
 pub(crate) struct OutputRendererOptions {
     pub(crate) min_row_height: usize,
@@ -53,4 +54,12 @@ where
     pub fn build_box_drawing(self) -> BoxDrawingRenderer<N, R> {
         BoxDrawingRenderer::new(self.inner, self.options)
     }
+
+    pub fn build_svg(self) -> SvgRenderer<N, R> {
+        SvgRenderer::new(self.inner)
+    }
+
+    pub fn build_html(self) -> HtmlRenderer<N, R> {
+        HtmlRenderer::new(self.inner)
+    }
 }
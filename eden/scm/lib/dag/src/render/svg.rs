@@ -0,0 +1,699 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! SVG and HTML output for graph rows.
+//!
+//! The layout comes from the wrapped `GraphRowRenderer`, so the columns match
+//! the text renderers exactly.  Each branch is drawn in its own color, which
+//! keeps branches visually distinct.  The color follows the branch if it is
+//! moved to another column.
+
+use std::fmt::Write;
+use std::marker::PhantomData;
+
+use super::render::{Ancestor, GraphRow, LinkLine, NodeLine, PadLine, Renderer};
+
+/// Width of a graph column, in pixels.
+const COLUMN_WIDTH: usize = 16;
+
+/// Height of the part of a row containing the node, in pixels.
+const NODE_HEIGHT: usize = 24;
+
+/// Height of link and termination lines, in pixels.
+const LINK_HEIGHT: usize = 16;
+
+/// Radius of a node.
+const NODE_RADIUS: usize = 4;
+
+/// Approximate width of a character of node text, used to size documents.
+const CHAR_WIDTH: usize = 8;
+
+const DEFAULT_COLORS: &[&str] = &[
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#17becf",
+];
+
+/// Options shared by the SVG and HTML renderers.
+struct SvgOptions<N> {
+    colors: Vec<String>,
+    node_text: Box<dyn Fn(&N, &str) -> String>,
+}
+
+impl<N> SvgOptions<N> {
+    fn new() -> Self {
+        SvgOptions {
+            colors: DEFAULT_COLORS.iter().map(|c| c.to_string()).collect(),
+            node_text: Box::new(|_, message| message.lines().next().unwrap_or("").to_string()),
+        }
+    }
+
+    fn set_colors(&mut self, colors: &[&str]) {
+        if !colors.is_empty() {
+            self.colors = colors.iter().map(|c| c.to_string()).collect();
+        }
+    }
+
+    fn color(&self, index: usize) -> &str {
+        &self.colors[index % self.colors.len()]
+    }
+
+    /// Returns the text to display next to the node, and the metadata to show
+    /// when hovering over it.
+    fn text_and_metadata(&self, line: &GraphRow<N>) -> (String, String) {
+        let text = (self.node_text)(&line.node, &line.message);
+        let metadata = line.message.lines().skip(1).collect::<Vec<_>>().join("\n");
+        (text, metadata.trim().to_string())
+    }
+}
+
+/// The colors of the branches that pass through each column.
+///
+/// Colors are indexes into `SvgOptions::colors`.  A branch keeps its color for
+/// as long as it occupies a column, and new branches take the next color.
+#[derive(Default)]
+struct Lanes {
+    /// The color of the branch in each column after the previous row, if the
+    /// column is occupied.
+    colors: Vec<Option<usize>>,
+    next_color: usize,
+}
+
+impl Lanes {
+    fn color_at(&self, column: usize) -> Option<usize> {
+        self.colors.get(column).copied().flatten()
+    }
+
+    fn new_color(&mut self) -> usize {
+        let color = self.next_color;
+        self.next_color += 1;
+        color
+    }
+
+    /// Assign colors to the branches leaving `line`, whose node is in
+    /// `node_column` and has the color `node_color`.  Returns the color of each
+    /// column in the link and term lines of `line`.
+    fn advance<N>(
+        &mut self,
+        line: &GraphRow<N>,
+        node_column: usize,
+        node_color: usize,
+    ) -> Vec<usize> {
+        // A node with a single parent that is already in a column to its right
+        // moves that parent into its own column.  The parent's branch is
+        // moved, so it keeps its color.
+        let moved_from = line.link_line.as_ref().and_then(|link_line| {
+            if link_line[node_column].contains(LinkLine::RIGHT_FORK) {
+                (node_column + 1..link_line.len())
+                    .find(|&j| link_line[j].contains(LinkLine::LEFT_MERGE))
+            } else {
+                None
+            }
+        });
+
+        let mut row_colors = Vec::with_capacity(line.pad_lines.len());
+        let mut colors = Vec::with_capacity(line.pad_lines.len());
+        for (i, pad) in line.pad_lines.iter().enumerate() {
+            let terminated = line.term_line.as_ref().map_or(false, |term| term[i]);
+            let color = if *pad == PadLine::Blank && !terminated {
+                None
+            } else if i == node_column {
+                Some(
+                    moved_from
+                        .and_then(|j| self.color_at(j))
+                        .unwrap_or(node_color),
+                )
+            } else {
+                Some(self.color_at(i).unwrap_or_else(|| self.new_color()))
+            };
+            row_colors.push(color.unwrap_or(node_color));
+            colors.push(color.filter(|_| *pad != PadLine::Blank));
+        }
+        self.colors = colors;
+        row_colors
+    }
+}
+
+fn column_x(column: usize) -> usize {
+    column * COLUMN_WIDTH + COLUMN_WIDTH / 2
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn draw_line(
+    out: &mut String,
+    x1: usize,
+    y1: usize,
+    x2: usize,
+    y2: usize,
+    color: &str,
+    dashed: bool,
+) {
+    let dash = if dashed {
+        r#" stroke-dasharray="2 2""#
+    } else {
+        ""
+    };
+    let _ = writeln!(
+        out,
+        r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}"{}/>"#,
+        x1, y1, x2, y2, color, dash
+    );
+}
+
+fn draw_curve(
+    out: &mut String,
+    x1: usize,
+    y1: usize,
+    x2: usize,
+    y2: usize,
+    color: &str,
+    dashed: bool,
+) {
+    let dash = if dashed {
+        r#" stroke-dasharray="2 2""#
+    } else {
+        ""
+    };
+    let mid = (y1 + y2) / 2;
+    let _ = writeln!(
+        out,
+        r#"<path d="M{} {}C{} {} {} {} {} {}" stroke="{}"{}/>"#,
+        x1, y1, x1, mid, x2, mid, x2, y2, color, dash
+    );
+}
+
+fn draw_node(out: &mut String, x: usize, y: usize, glyph: &str, color: &str) {
+    // The working copy is drawn hollow, as "@" stands out in text output.
+    let fill = if glyph == "@" { "#fff" } else { color };
+    let _ = writeln!(
+        out,
+        r#"<circle cx="{}" cy="{}" r="{}" fill="{}" stroke="{}" stroke-width="2"/>"#,
+        x, y, NODE_RADIUS, fill, color
+    );
+}
+
+/// Draw the edges of a graph row with its top at `top`.
+///
+/// `prev_pad_line` is the pad line of the previous row, which determines
+/// whether the node is connected to a child above it.  Returns the height of
+/// the row, and the column and color of the node.
+fn draw_edges<N>(
+    out: &mut String,
+    line: &GraphRow<N>,
+    prev_pad_line: &[PadLine],
+    lanes: &mut Lanes,
+    options: &SvgOptions<N>,
+    top: usize,
+) -> (usize, usize, usize) {
+    let _ = writeln!(out, r#"<g fill="none" stroke-width="2">"#);
+
+    let node_column = line
+        .node_line
+        .iter()
+        .position(|entry| *entry == NodeLine::Node)
+        .unwrap_or(0);
+    let node_color = lanes
+        .color_at(node_column)
+        .unwrap_or_else(|| lanes.new_color());
+    let node_line_colors: Vec<_> = (0..line.node_line.len())
+        .map(|i| lanes.color_at(i).unwrap_or(node_color))
+        .collect();
+    let row_colors = lanes.advance(line, node_column, node_color);
+
+    // Draw the node line.
+    let mid = top + NODE_HEIGHT / 2;
+    let mut bottom = top + NODE_HEIGHT;
+    for (i, entry) in line.node_line.iter().enumerate() {
+        let x = column_x(i);
+        let color = options.color(node_line_colors[i]);
+        match entry {
+            NodeLine::Node => {
+                match prev_pad_line.get(i) {
+                    Some(PadLine::Parent) => draw_line(out, x, top, x, mid, color, false),
+                    Some(PadLine::Ancestor) => draw_line(out, x, top, x, mid, color, true),
+                    _ => {}
+                }
+                let has_parents = match &line.link_line {
+                    Some(link_line) => !link_line[i].is_empty(),
+                    None => {
+                        line.pad_lines[i] != PadLine::Blank
+                            || line.term_line.as_ref().map_or(false, |term| term[i])
+                    }
+                };
+                if has_parents {
+                    let dashed = line.link_line.is_none() && line.pad_lines[i] == PadLine::Ancestor;
+                    draw_line(out, x, mid, x, bottom, color, dashed);
+                }
+            }
+            NodeLine::Parent => draw_line(out, x, top, x, bottom, color, false),
+            NodeLine::Ancestor => draw_line(out, x, top, x, bottom, color, true),
+            NodeLine::Blank => {}
+        }
+    }
+
+    // Draw the link line.  Forks are connected to the nearest merge on the
+    // side of the child.
+    if let Some(link_line) = &line.link_line {
+        let top = bottom;
+        bottom = top + LINK_HEIGHT;
+        for (i, cur) in link_line.iter().enumerate() {
+            let x = column_x(i);
+            let color = options.color(row_colors[i]);
+            let dashed = line.pad_lines[i] == PadLine::Ancestor;
+            if cur.intersects(LinkLine::PARENT | LinkLine::ANCESTOR) {
+                draw_line(
+                    out,
+                    x,
+                    top,
+                    x,
+                    bottom,
+                    color,
+                    !cur.contains(LinkLine::PARENT),
+                );
+            }
+            if cur.contains(LinkLine::LEFT_FORK) {
+                if let Some(child) = (0..i)
+                    .rev()
+                    .find(|&j| link_line[j].contains(LinkLine::RIGHT_MERGE))
+                {
+                    draw_curve(out, column_x(child), top, x, bottom, color, dashed);
+                }
+            }
+            if cur.contains(LinkLine::RIGHT_FORK) {
+                if let Some(child) =
+                    (i + 1..link_line.len()).find(|&j| link_line[j].contains(LinkLine::LEFT_MERGE))
+                {
+                    draw_curve(out, column_x(child), top, x, bottom, color, dashed);
+                }
+            }
+        }
+    }
+
+    // Draw the term line.
+    if let Some(term_line) = &line.term_line {
+        let top = bottom;
+        bottom = top + LINK_HEIGHT;
+        for (i, term) in term_line.iter().enumerate() {
+            let x = column_x(i);
+            let color = options.color(row_colors[i]);
+            if *term {
+                let end = top + LINK_HEIGHT / 2;
+                draw_line(out, x, top, x, end, color, false);
+                draw_line(
+                    out,
+                    x - NODE_RADIUS,
+                    end,
+                    x + NODE_RADIUS,
+                    end,
+                    color,
+                    false,
+                );
+            } else {
+                match line.pad_lines[i] {
+                    PadLine::Parent => draw_line(out, x, top, x, bottom, color, false),
+                    PadLine::Ancestor => draw_line(out, x, top, x, bottom, color, true),
+                    PadLine::Blank => {}
+                }
+            }
+        }
+    }
+
+    let _ = writeln!(out, "</g>");
+    (bottom - top, node_column, node_color)
+}
+
+/// Renders a graph as a standalone SVG image.
+///
+/// Each call to `next_row` returns the SVG elements for that row.  The
+/// concatenated rows should be passed to `document` to produce the image.
+pub struct SvgRenderer<N, R>
+where
+    R: Renderer<N, Output = GraphRow<N>> + Sized,
+{
+    inner: R,
+    options: SvgOptions<N>,
+    prev_pad_line: Vec<PadLine>,
+    lanes: Lanes,
+    width: usize,
+    height: usize,
+    _phantom: PhantomData<N>,
+}
+
+impl<N, R> SvgRenderer<N, R>
+where
+    R: Renderer<N, Output = GraphRow<N>> + Sized,
+{
+    pub(crate) fn new(inner: R) -> Self {
+        SvgRenderer {
+            inner,
+            options: SvgOptions::new(),
+            prev_pad_line: Vec::new(),
+            lanes: Lanes::default(),
+            width: 0,
+            height: 0,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Use the given colors for the branches of the graph, cycling through
+    /// them if there are more branches than colors.
+    pub fn with_colors(mut self, colors: &[&str]) -> Self {
+        self.options.set_colors(colors);
+        self
+    }
+
+    /// Use a custom function to produce the text displayed next to each node
+    /// from the node and its message.  By default, the first line of the
+    /// message is displayed.  The remaining lines are shown on hover.
+    pub fn with_node_text(mut self, node_text: impl Fn(&N, &str) -> String + 'static) -> Self {
+        self.options.node_text = Box::new(node_text);
+        self
+    }
+
+    /// Wrap the rendered rows in an SVG document sized to fit them.
+    pub fn document(&self, rows: &str) -> String {
+        format!(
+            concat!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" "#,
+                r#"font-family="monospace" font-size="12">"#,
+                "\n{}</svg>\n",
+            ),
+            self.width, self.height, rows
+        )
+    }
+}
+
+impl<N, R> Renderer<N> for SvgRenderer<N, R>
+where
+    N: Clone + Eq,
+    R: Renderer<N, Output = GraphRow<N>> + Sized,
+{
+    type Output = String;
+
+    fn width(&self, node: Option<&N>, parents: Option<&Vec<Ancestor<N>>>) -> u64 {
+        self.inner
+            .width(node, parents)
+            .saturating_mul(COLUMN_WIDTH as u64)
+    }
+
+    fn reserve(&mut self, node: N) {
+        self.inner.reserve(node);
+    }
+
+    fn next_row(
+        &mut self,
+        node: N,
+        parents: Vec<Ancestor<N>>,
+        glyph: String,
+        message: String,
+    ) -> String {
+        let line = self.inner.next_row(node, parents, glyph, message);
+        let (text, metadata) = self.options.text_and_metadata(&line);
+        let mut out = String::new();
+
+        let top = self.height;
+        let (height, node_column, node_color) = draw_edges(
+            &mut out,
+            &line,
+            &self.prev_pad_line,
+            &mut self.lanes,
+            &self.options,
+            top,
+        );
+
+        let x = column_x(node_column);
+        let y = top + NODE_HEIGHT / 2;
+        let text_x = line.node_line.len() * COLUMN_WIDTH + COLUMN_WIDTH / 2;
+        let _ = writeln!(out, "<g>");
+        if !metadata.is_empty() {
+            let _ = writeln!(out, "<title>{}</title>", escape(&metadata));
+        }
+        draw_node(&mut out, x, y, &line.glyph, self.options.color(node_color));
+        let _ = writeln!(
+            out,
+            r#"<text x="{}" y="{}" dominant-baseline="middle">{}</text>"#,
+            text_x,
+            y,
+            escape(&text)
+        );
+        let _ = writeln!(out, "</g>");
+
+        self.width = self.width.max(text_x + text.chars().count() * CHAR_WIDTH);
+        self.height += height;
+        self.prev_pad_line = line.pad_lines;
+        out
+    }
+}
+
+/// Renders a graph as HTML, one element per row.
+///
+/// Each row contains an inline SVG image of its part of the graph, followed
+/// by the node text.  Hovering over a row shows the rest of its message.
+/// Rows can be emitted as they are rendered, and line up when concatenated.
+pub struct HtmlRenderer<N, R>
+where
+    R: Renderer<N, Output = GraphRow<N>> + Sized,
+{
+    inner: R,
+    options: SvgOptions<N>,
+    prev_pad_line: Vec<PadLine>,
+    lanes: Lanes,
+    _phantom: PhantomData<N>,
+}
+
+impl<N, R> HtmlRenderer<N, R>
+where
+    R: Renderer<N, Output = GraphRow<N>> + Sized,
+{
+    pub(crate) fn new(inner: R) -> Self {
+        HtmlRenderer {
+            inner,
+            options: SvgOptions::new(),
+            prev_pad_line: Vec::new(),
+            lanes: Lanes::default(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Use the given colors for the branches of the graph, cycling through
+    /// them if there are more branches than colors.
+    pub fn with_colors(mut self, colors: &[&str]) -> Self {
+        self.options.set_colors(colors);
+        self
+    }
+
+    /// Use a custom function to produce the text displayed next to each node
+    /// from the node and its message.  By default, the first line of the
+    /// message is displayed.  The remaining lines are shown on hover.
+    pub fn with_node_text(mut self, node_text: impl Fn(&N, &str) -> String + 'static) -> Self {
+        self.options.node_text = Box::new(node_text);
+        self
+    }
+}
+
+impl<N, R> Renderer<N> for HtmlRenderer<N, R>
+where
+    N: Clone + Eq,
+    R: Renderer<N, Output = GraphRow<N>> + Sized,
+{
+    type Output = String;
+
+    fn width(&self, node: Option<&N>, parents: Option<&Vec<Ancestor<N>>>) -> u64 {
+        self.inner
+            .width(node, parents)
+            .saturating_mul(COLUMN_WIDTH as u64)
+    }
+
+    fn reserve(&mut self, node: N) {
+        self.inner.reserve(node);
+    }
+
+    fn next_row(
+        &mut self,
+        node: N,
+        parents: Vec<Ancestor<N>>,
+        glyph: String,
+        message: String,
+    ) -> String {
+        let line = self.inner.next_row(node, parents, glyph, message);
+        let (text, metadata) = self.options.text_and_metadata(&line);
+
+        let mut graph = String::new();
+        let (height, node_column, node_color) = draw_edges(
+            &mut graph,
+            &line,
+            &self.prev_pad_line,
+            &mut self.lanes,
+            &self.options,
+            0,
+        );
+        draw_node(
+            &mut graph,
+            column_x(node_column),
+            NODE_HEIGHT / 2,
+            &line.glyph,
+            self.options.color(node_color),
+        );
+        let width = line.node_line.len() * COLUMN_WIDTH;
+        self.prev_pad_line = line.pad_lines;
+
+        let title = if metadata.is_empty() {
+            String::new()
+        } else {
+            format!(r#" title="{}""#, escape(&metadata))
+        };
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            r#"<div class="dag-row"{} style="display:flex;height:{}px">"#,
+            title, height
+        );
+        let _ = writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}">"#,
+            width, height
+        );
+        out.push_str(&graph);
+        let _ = writeln!(out, "</svg>");
+        let _ = writeln!(
+            out,
+            r#"<span class="dag-text" style="line-height:{}px;padding-left:{}px;white-space:pre">{}</span>"#,
+            NODE_HEIGHT,
+            COLUMN_WIDTH / 2,
+            escape(&text)
+        );
+        let _ = writeln!(out, "</div>");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_fixtures::{self, TestFixture};
+    use super::super::test_utils::render_rows_with_order;
+    use crate::render::{Ancestor, GraphRowRenderer, Renderer};
+    use crate::VertexName;
+
+    fn render_svg(fixture: &TestFixture) -> String {
+        let mut renderer = GraphRowRenderer::new().output().build_svg();
+        let rows: String = render_rows_with_order(fixture, &mut renderer, None)
+            .into_iter()
+            .map(|(_, _, row)| row)
+            .collect();
+        renderer.document(&rows)
+    }
+
+    #[test]
+    fn basic() {
+        let svg = render_svg(&test_fixtures::BASIC);
+        assert!(
+            svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="32" height="72" "#)
+        );
+        assert_eq!(svg.matches("<circle").count(), 3);
+        for (name, y) in &[("C", 12), ("B", 36), ("A", 60)] {
+            assert!(svg.contains(&format!(
+                r##"<circle cx="8" cy="{}" r="4" fill="#1f77b4" stroke="#1f77b4" stroke-width="2"/>"##,
+                y
+            )));
+            assert!(svg.contains(&format!(
+                r#"<text x="24" y="{}" dominant-baseline="middle">{}</text>"#,
+                y, name
+            )));
+        }
+        // The edges between the nodes, without anything above C or below A.
+        assert!(svg.contains(r##"<line x1="8" y1="12" x2="8" y2="24" stroke="#1f77b4"/>"##));
+        assert!(svg.contains(r##"<line x1="8" y1="24" x2="8" y2="36" stroke="#1f77b4"/>"##));
+        assert!(svg.contains(r##"<line x1="8" y1="48" x2="8" y2="60" stroke="#1f77b4"/>"##));
+        assert!(!svg.contains(r#"y1="0""#));
+        assert!(!svg.contains(r#"y2="72""#));
+    }
+
+    #[test]
+    fn branches_are_colored() {
+        let mut renderer = GraphRowRenderer::new()
+            .output()
+            .build_svg()
+            .with_colors(&["red", "blue"]);
+        let rows: String =
+            render_rows_with_order(&test_fixtures::BRANCHES_AND_MERGES, &mut renderer, None)
+                .into_iter()
+                .map(|(_, _, row)| row)
+                .collect();
+        assert!(rows.contains(r#"fill="red""#));
+        assert!(rows.contains(r#"fill="blue""#));
+        // Merges are drawn as curves into the parent columns.
+        assert!(rows.contains("<path"));
+    }
+
+    #[test]
+    fn moved_branches_keep_their_color() {
+        let mut renderer = GraphRowRenderer::new()
+            .output()
+            .build_svg()
+            .with_colors(&["red", "blue"]);
+        let mut row = |node: &str, parent: &str| {
+            renderer.next_row(
+                node.to_string(),
+                vec![Ancestor::Parent(parent.to_string())],
+                "o".to_string(),
+                node.to_string(),
+            )
+        };
+        // X and Z start branches in the first and second columns.  Y moves
+        // Z's branch into the first column, as they share the parent A.
+        assert!(row("X", "Y").contains(r#"fill="red""#));
+        assert!(row("Z", "A").contains(r#"fill="blue""#));
+        let y = row("Y", "A");
+        assert!(y.contains(r#"fill="red""#));
+        assert!(y.contains(r#"<path d="M24 72C24 80 8 80 8 88" stroke="blue"/>"#));
+        let a = row("A", "B");
+        assert!(a.contains(r#"<circle cx="8" cy="100" r="4" fill="blue""#));
+    }
+
+    #[test]
+    fn ancestors_and_terminations() {
+        let svg = render_svg(&test_fixtures::ANCESTORS);
+        assert!(svg.contains(r#"stroke-dasharray="2 2""#));
+        let svg = render_svg(&test_fixtures::TERMINATIONS);
+        // Each termination is a short line ending in a bar.
+        assert!(svg.contains(r#"<line x1="4" "#));
+    }
+
+    #[test]
+    fn html_hover_metadata() {
+        let mut renderer = GraphRowRenderer::new()
+            .output()
+            .build_html()
+            .with_node_text(|node: &VertexName, _| format!("<{:?}>", node));
+        let rows = render_rows_with_order(&test_fixtures::LONG_MESSAGES, &mut renderer, None);
+        let (_, _, row) = rows
+            .iter()
+            .find(|(name, _, _)| name == "C")
+            .expect("C should be rendered");
+        assert!(row.starts_with(
+            "<div class=\"dag-row\" title=\"long message 1\nlong message 2\nlong message 3\""
+        ));
+        assert!(row.contains(">&lt;C&gt;</span>"));
+        let (_, _, row) = rows
+            .iter()
+            .find(|(name, _, _)| name == "B")
+            .expect("B should be rendered");
+        assert!(row.starts_with(r#"<div class="dag-row" style="#));
+    }
+}
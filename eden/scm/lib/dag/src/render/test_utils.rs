@@ -30,6 +30,41 @@ pub(crate) fn render_string_with_order(
     renderer: &mut dyn Renderer<VertexName, Output = String>,
     order: Option<&[&str]>,
 ) -> String {
+    let mut out = String::new();
+    for (name, width, row) in render_rows_with_order(fixture, renderer, order) {
+        let row_indent = row
+            .lines()
+            .filter_map(|line| line.find(&name).map(|offset| &line[..offset]))
+            .next()
+            .expect("name should be in the output");
+        assert_eq!(
+            row_indent.width() as u64,
+            width,
+            "indent '{}' for row for {} is the wrong width",
+            row_indent,
+            name
+        );
+
+        out.push_str(&row);
+    }
+
+    format!(
+        "\n{}",
+        out.trim_end()
+            .lines()
+            .map(|l| format!("            {}", l))
+            .collect::<Vec<_>>()
+            .join("\n")
+    )
+}
+
+/// Render each row of the fixture, returning the node name, the width
+/// reported by the renderer before the row was rendered, and the row.
+pub(crate) fn render_rows_with_order<R: Renderer<VertexName> + ?Sized>(
+    fixture: &TestFixture,
+    renderer: &mut R,
+    order: Option<&[&str]>,
+) -> Vec<(String, u64, R::Output)> {
     let TestFixture {
         dag: ascii,
         messages,
@@ -67,7 +102,7 @@ pub(crate) fn render_string_with_order(
         Some(order) => order.iter().map(|name| v(name)).collect(),
     };
 
-    let mut out = String::new();
+    let mut rows = Vec::new();
     for node in iter {
         if missing.contains(&node) {
             continue;
@@ -92,28 +127,7 @@ pub(crate) fn render_string_with_order(
         };
         let width = renderer.width(Some(&node), Some(&parents));
         let row = renderer.next_row(node, parents, String::from("o"), message);
-        let row_indent = row
-            .lines()
-            .filter_map(|line| line.find(&name).map(|offset| &line[..offset]))
-            .next()
-            .expect("name should be in the output");
-        assert_eq!(
-            row_indent.width() as u64,
-            width,
-            "indent '{}' for row for {} is the wrong width",
-            row_indent,
-            name
-        );
-
-        out.push_str(&row);
+        rows.push((name, width, row));
     }
-
-    format!(
-        "\n{}",
-        out.trim_end()
-            .lines()
-            .map(|l| format!("            {}", l))
-            .collect::<Vec<_>>()
-            .join("\n")
-    )
+    rows
 }
@@ -36,6 +36,8 @@ use crate::ops::IdConvert;
 use crate::protocol::{Process, RequestLocationToName, RequestNameToLocation};
 #[cfg(test)]
 use crate::Id;
+#[cfg(test)]
+use std::collections::HashMap;
 
 // Example from segmented-changelog.pdf
 // - DAG1: page 10
@@ -731,12 +733,24 @@ Lv1: R0-6[]"#
         Id(8)
     );
     assert_eq!(
-        built.name_dag.map.find_name_by_id(Id(8)).unwrap().unwrap(),
+        built
+            .name_dag
+            .map
+            .find_name_by_id(Id(8))
+            .unwrap()
+            .unwrap()
+            .as_ref(),
         b"m"
     );
     let id = Group::NON_MASTER.min_id() + 5;
     assert_eq!(
-        built.name_dag.map.find_name_by_id(id).unwrap().unwrap(),
+        built
+            .name_dag
+            .map
+            .find_name_by_id(id)
+            .unwrap()
+            .unwrap()
+            .as_ref(),
         b"q"
     );
 
@@ -806,6 +820,48 @@ fn test_namedag_reassign_non_master() {
     assert_eq!(format!("{:?}", z_vertex), "Z");
 }
 
+#[test]
+fn test_namedag_reset_non_master() {
+    let v = |s: &str| VertexName::copy_from(s.as_bytes());
+    let parents: HashMap<VertexName, Vec<VertexName>> = vec![
+        (v("A"), vec![]),
+        (v("B"), vec![v("A")]),
+        (v("C"), vec![v("B")]),
+        (v("D"), vec![v("B")]),
+        (v("E"), vec![v("D")]),
+    ]
+    .into_iter()
+    .collect();
+
+    // B: master; C, D, E: non-master.
+    let mut t = TestDag::new();
+    r(t.dag
+        .add_heads_and_flush(&parents, &[v("B")], &[v("C"), v("E")]))
+    .unwrap();
+    assert!(r(t.dag.contains_vertex_name(&v("C"))).unwrap());
+
+    // Drop C and E, keep D.
+    r(t.dag
+        .reset_non_master_and_flush(&parents, &[v("B")], &[v("D")]))
+    .unwrap();
+    assert!(!r(t.dag.contains_vertex_name(&v("C"))).unwrap());
+    assert!(!r(t.dag.contains_vertex_name(&v("E"))).unwrap());
+    assert_eq!(
+        t.render_graph(),
+        r#"
+            D  N0
+            │
+            B  1
+            │
+            A  0"#
+    );
+
+    // The change is persisted.
+    let dag = NameDag::open(t.dir.path().join("n")).unwrap();
+    assert!(!r(dag.contains_vertex_name(&v("C"))).unwrap());
+    assert!(r(dag.contains_vertex_name(&v("D"))).unwrap());
+}
+
 #[test]
 fn test_segment_ancestors_example1() {
     // DAG from segmented-changelog.pdf
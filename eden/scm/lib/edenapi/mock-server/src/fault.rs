@@ -0,0 +1,83 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::time::Duration;
+
+use http::StatusCode;
+
+/// A failure that the mock server can inject into its responses.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Fault {
+    /// Wait for the given duration before responding.
+    Latency(Duration),
+    /// Respond to the next `count` requests with the given status code
+    /// instead of serving them.
+    Error { status: StatusCode, count: usize },
+    /// Abort the response body after the given number of entries have
+    /// been sent, as if the connection had been dropped mid-stream.
+    Truncate { entries: usize },
+}
+
+/// A fault, optionally limited to a single endpoint.
+struct Rule {
+    endpoint: Option<String>,
+    fault: Fault,
+}
+
+/// The faults that apply to a single request.
+#[derive(Debug, Default)]
+pub(crate) struct Injected {
+    pub(crate) latency: Duration,
+    pub(crate) error: Option<StatusCode>,
+    pub(crate) truncate: Option<usize>,
+}
+
+#[derive(Default)]
+pub(crate) struct Faults {
+    rules: Vec<Rule>,
+}
+
+impl Faults {
+    pub(crate) fn add(&mut self, endpoint: Option<String>, fault: Fault) {
+        self.rules.push(Rule { endpoint, fault });
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.rules.clear();
+    }
+
+    /// Determine which faults to inject into a request to the given
+    /// endpoint. Error faults are used up by the requests they fail.
+    pub(crate) fn take(&mut self, endpoint: &str) -> Injected {
+        let mut injected = Injected::default();
+
+        for rule in &mut self.rules {
+            match &rule.endpoint {
+                Some(e) if e != endpoint => continue,
+                _ => {}
+            }
+            match &mut rule.fault {
+                Fault::Latency(latency) => injected.latency += *latency,
+                Fault::Error { status, count } => {
+                    if injected.error.is_none() && *count > 0 {
+                        injected.error = Some(*status);
+                        *count -= 1;
+                    }
+                }
+                Fault::Truncate { entries } => {
+                    injected.truncate =
+                        Some(injected.truncate.map_or(*entries, |n| n.min(*entries)));
+                }
+            }
+        }
+
+        self.rules
+            .retain(|rule| !matches!(rule.fault, Fault::Error { count: 0, .. }));
+
+        injected
+    }
+}
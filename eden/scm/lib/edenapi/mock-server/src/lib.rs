@@ -0,0 +1,419 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! In-process EdenAPI server for client-side integration tests.
+//!
+//! Testing code that talks to EdenAPI (such as revisionstore or the Python
+//! bindings) against a real Mononoke server is heavyweight. This crate
+//! provides a `MockServer` that serves in-memory repos over HTTP using the
+//! same CBOR wire types as the real server, so that clients can be tested
+//! end-to-end on a single machine.
+//!
+//! The server can also inject faults, such as added latency, error
+//! responses, and truncated response streams, to exercise the client's
+//! error handling and retry logic.
+
+#![deny(warnings)]
+
+mod fault;
+mod repo;
+mod server;
+
+pub use crate::fault::Fault;
+pub use crate::repo::{MockCommit, MockRepo, MASTER_BOOKMARK};
+pub use crate::server::MockServer;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, Instant};
+
+    use anyhow::Result;
+    use futures::prelude::*;
+    use http::StatusCode;
+
+    use dag::{
+        ops::{DagAlgorithm, DagImportCloneData, IdConvert},
+        CloneData, MemDag, VertexName,
+    };
+    use edenapi::{Builder, Client, EdenApi, EdenApiError};
+    use edenapi_types::{AnyId, BookmarkEntry, CommitGraphEntry, HistoryEntry, TreeAttributes};
+    use types::{testutil::*, HgId, Key, NodeInfo, Parents, RepoPathBuf};
+
+    const REPO: &str = "repo";
+
+    fn client(server: &MockServer) -> Result<Client> {
+        Ok(Builder::new()
+            .server_url(server.url())
+            .max_retries(2)
+            .retry_delay(Duration::from_millis(1))
+            .build()?)
+    }
+
+    #[tokio::test]
+    async fn test_files() -> Result<()> {
+        let server = MockServer::start()?;
+        let repo = server.add_repo(REPO);
+
+        let present = key("a", "1");
+        let missing = key("b", "2");
+        repo.add_file(present.clone(), "content", Parents::None);
+
+        let client = client(&server)?;
+        let fetch = client
+            .files(REPO.into(), vec![present.clone(), missing], None)
+            .await?;
+        let entries = fetch.entries.try_collect::<Vec<_>>().await?;
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].key, present);
+        assert_eq!(&entries[0].data_unchecked()[..], b"content");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_history() -> Result<()> {
+        let server = MockServer::start()?;
+        let repo = server.add_repo(REPO);
+
+        let k1 = key("a", "1");
+        let k2 = key("a", "2");
+        let k3 = key("a", "3");
+        for (key, p1) in vec![(&k1, Key::default()), (&k2, k1.clone()), (&k3, k2.clone())] {
+            repo.add_history(HistoryEntry {
+                key: key.clone(),
+                nodeinfo: NodeInfo {
+                    parents: [p1, Key::default()],
+                    linknode: hgid("f"),
+                },
+            });
+        }
+
+        let client = client(&server)?;
+        let fetch = client
+            .history(REPO.into(), vec![k3.clone()], Some(2), None)
+            .await?;
+        let entries = fetch.entries.try_collect::<Vec<_>>().await?;
+        let keys = entries.into_iter().map(|e| e.key).collect::<Vec<_>>();
+
+        assert_eq!(keys, vec![k3, k2]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_trees() -> Result<()> {
+        let server = MockServer::start()?;
+        let repo = server.add_repo(REPO);
+
+        let present = Key::new(RepoPathBuf::new(), hgid("1"));
+        let missing = Key::new(RepoPathBuf::new(), hgid("2"));
+        repo.add_tree(present.clone(), "tree", Parents::None);
+
+        let client = client(&server)?;
+        let attributes = TreeAttributes {
+            manifest_blob: true,
+            parents: false,
+            child_metadata: false,
+        };
+        let fetch = client
+            .trees(
+                REPO.into(),
+                vec![present.clone(), missing.clone()],
+                Some(attributes),
+                None,
+            )
+            .await?;
+        let entries = fetch.entries.try_collect::<Vec<_>>().await?;
+
+        assert_eq!(entries.len(), 2);
+        let tree = entries[0].as_ref().expect("tree should be present");
+        assert_eq!(tree.key, present);
+        assert_eq!(tree.data.as_deref(), Some(&b"tree"[..]));
+        assert_eq!(tree.parents, None);
+
+        let err = entries[1].as_ref().expect_err("tree should be missing");
+        assert_eq!(err.key.as_ref(), Some(&missing));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bookmarks() -> Result<()> {
+        let server = MockServer::start()?;
+        let repo = server.add_repo(REPO);
+
+        repo.set_bookmark("master", hgid("1"));
+        repo.set_bookmark("release/1", hgid("2"));
+        repo.set_bookmark("release/2", hgid("3"));
+
+        let client = client(&server)?;
+        let fetch = client
+            .bookmarks(
+                REPO.into(),
+                vec!["master".into(), "missing".into()],
+                vec!["release/".into()],
+                None,
+            )
+            .await?;
+        let entries = fetch.entries.try_collect::<Vec<_>>().await?;
+
+        let expected = vec![
+            BookmarkEntry::new("master".into(), Some(hgid("1"))),
+            BookmarkEntry::new("missing".into(), None),
+            BookmarkEntry::new("release/1".into(), Some(hgid("2"))),
+            BookmarkEntry::new("release/2".into(), Some(hgid("3"))),
+        ];
+        assert_eq!(entries, expected);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_and_land() -> Result<()> {
+        let server = MockServer::start()?;
+        let repo = server.add_repo(REPO);
+
+        let base = hgid("1");
+        let head = hgid("2");
+        repo.add_commit(base, Parents::None, "base");
+        repo.set_bookmark("master", base);
+
+        let client = client(&server)?;
+        let lookup = client
+            .lookup(REPO.into(), vec![AnyId::HgChangesetId(head)], None)
+            .await?
+            .entries
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert!(!lookup[0].present);
+
+        let uploaded = client
+            .upload_file_contents(REPO.into(), vec!["data".into()], None)
+            .await?
+            .entries
+            .try_collect::<Vec<_>>()
+            .await?;
+        let content_id = uploaded[0].id.clone();
+        let lookup = client
+            .lookup(REPO.into(), vec![content_id], None)
+            .await?
+            .entries
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert!(lookup[0].present);
+
+        repo.add_commit(head, Parents::One(base), "head");
+        let landed = client
+            .land_stack(REPO.into(), "master".into(), head, base)
+            .await?;
+
+        assert_eq!(landed.new_head, head);
+        assert_eq!(landed.old_to_new_hgids, vec![(head, head)]);
+        assert_eq!(repo.bookmark("master"), Some(head));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_commit_graph() -> Result<()> {
+        let server = MockServer::start()?;
+        let repo = server.add_repo(REPO);
+
+        // 1 - 2 - 4
+        //   \     /
+        //     3
+        let (c1, c2, c3, c4) = (hgid("1"), hgid("2"), hgid("3"), hgid("4"));
+        repo.add_commit(c1, Parents::None, "1");
+        repo.add_commit(c2, Parents::One(c1), "2");
+        repo.add_commit(c3, Parents::One(c1), "3");
+        repo.add_commit(c4, Parents::Two(c2, c3), "4");
+
+        let client = client(&server)?;
+        let entries = client
+            .commit_graph(REPO.into(), vec![c4], 1, None)
+            .await?
+            .entries
+            .try_collect::<Vec<_>>()
+            .await?;
+        let expected = vec![
+            CommitGraphEntry::new(c4, vec![c2, c3]),
+            CommitGraphEntry::new(c2, vec![c1]),
+            CommitGraphEntry::new(c3, vec![c1]),
+        ];
+        assert_eq!(entries, expected);
+
+        let answers = client
+            .commit_is_ancestor(REPO.into(), vec![(c1, c4), (c3, c2), (c4, c4)], None)
+            .await?
+            .entries
+            .try_collect::<Vec<_>>()
+            .await?;
+        let answers = answers.iter().map(|a| a.is_ancestor).collect::<Vec<_>>();
+        assert_eq!(answers, vec![true, false, true]);
+
+        Ok(())
+    }
+
+    fn vertex(hgid: HgId) -> VertexName {
+        VertexName::copy_from(hgid.as_ref())
+    }
+
+    /// Set up a repo where master is at 3 and 4 is not an ancestor of it:
+    ///
+    /// 1 - 2 - 3
+    ///   \
+    ///     4
+    fn clone_repo(server: &MockServer) -> Vec<HgId> {
+        let repo = server.add_repo(REPO);
+        let commits = vec![hgid("1"), hgid("2"), hgid("3"), hgid("4")];
+        repo.add_commit(commits[0], Parents::None, "1");
+        repo.add_commit(commits[1], Parents::One(commits[0]), "2");
+        repo.add_commit(commits[2], Parents::One(commits[1]), "3");
+        repo.add_commit(commits[3], Parents::One(commits[0]), "4");
+        repo.set_bookmark(MASTER_BOOKMARK, commits[2]);
+        commits
+    }
+
+    #[tokio::test]
+    async fn test_clone_data() -> Result<()> {
+        let server = MockServer::start()?;
+        let commits = clone_repo(&server);
+
+        let client = client(&server)?;
+        let clone_data = client.clone_data(REPO.into(), None).await?;
+        let full = client.full_idmap_clone_data(REPO.into(), None).await?;
+
+        // Both endpoints describe the same graph, headed by master.
+        assert_eq!(clone_data.head_id, full.head_id);
+        assert_eq!(clone_data.flat_segments, full.flat_segments);
+        assert_eq!(full.idmap.get(&full.head_id), Some(&commits[2]));
+        for (id, hgid) in &clone_data.idmap {
+            assert_eq!(full.idmap.get(id), Some(hgid));
+        }
+
+        // The full idmap covers the ancestors of master and nothing else.
+        let mut hgids = full.idmap.values().copied().collect::<Vec<_>>();
+        hgids.sort();
+        assert_eq!(hgids, commits[..3].to_vec());
+
+        // The client can build its dag from the clone data.
+        let mut dag = MemDag::new();
+        dag.import_clone_data(CloneData {
+            head_id: full.head_id,
+            flat_segments: full.flat_segments,
+            idmap: full
+                .idmap
+                .into_iter()
+                .map(|(id, hgid)| (id, vertex(hgid)))
+                .collect(),
+        })?;
+        assert_eq!(
+            dag.parent_names(vertex(commits[2])).await?,
+            vec![vertex(commits[1])]
+        );
+        assert!(!dag.contains_vertex_name(&vertex(commits[3])).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_clone_data_without_master() -> Result<()> {
+        let server = MockServer::start()?;
+        let repo = server.add_repo(REPO);
+        repo.add_commit(hgid("1"), Parents::None, "1");
+
+        let client = client(&server)?;
+        match client.clone_data(REPO.into(), None).await {
+            Err(EdenApiError::HttpError { status, .. }) => {
+                assert_eq!(status, StatusCode::BAD_REQUEST)
+            }
+            _ => panic!("expected HTTP error"),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_injected_errors_are_retried() -> Result<()> {
+        let server = MockServer::start()?;
+        let repo = server.add_repo(REPO);
+        repo.set_bookmark("master", hgid("1"));
+
+        server.inject_for(
+            "bookmarks",
+            Fault::Error {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                count: 2,
+            },
+        );
+
+        let client = client(&server)?;
+        let fetch = client
+            .bookmarks(REPO.into(), vec!["master".into()], vec![], None)
+            .await?;
+        assert_eq!(fetch.meta[0].retries, 2);
+        assert_eq!(fetch.entries.try_collect::<Vec<_>>().await?.len(), 1);
+        assert_eq!(server.requests(), vec!["bookmarks"; 3]);
+
+        server.inject(Fault::Error {
+            status: StatusCode::BAD_REQUEST,
+            count: 1,
+        });
+        let res = client
+            .bookmarks(REPO.into(), vec!["master".into()], vec![], None)
+            .await;
+        match res {
+            Err(EdenApiError::HttpError { status, .. }) => {
+                assert_eq!(status, StatusCode::BAD_REQUEST)
+            }
+            _ => panic!("expected HTTP error"),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_truncate() -> Result<()> {
+        let server = MockServer::start()?;
+        let repo = server.add_repo(REPO);
+
+        let keys = vec![key("a", "1"), key("b", "2"), key("c", "3")];
+        for key in &keys {
+            repo.add_file(key.clone(), "content", Parents::None);
+        }
+
+        server.inject(Fault::Truncate { entries: 1 });
+
+        // The response headers have been sent by the time the stream is cut
+        // off, so the failure surfaces while reading the entries.
+        let client = client(&server)?;
+        let entries = client.files(REPO.into(), keys.clone(), None).await?.entries;
+        assert!(entries.try_collect::<Vec<_>>().await.is_err());
+
+        server.clear_faults();
+        let entries = client.files(REPO.into(), keys, None).await?.entries;
+        assert_eq!(entries.try_collect::<Vec<_>>().await?.len(), 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_latency() -> Result<()> {
+        let server = MockServer::start()?;
+        server.inject(Fault::Latency(Duration::from_millis(100)));
+
+        let client = client(&server)?;
+        let start = Instant::now();
+        client.health().await?;
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        Ok(())
+    }
+}
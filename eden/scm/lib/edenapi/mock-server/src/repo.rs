@@ -0,0 +1,446 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+use anyhow::Error;
+use bytes::Bytes;
+use dag::{
+    nonblocking::non_blocking_result,
+    ops::{DagPersistent, IdConvert},
+    CloneData, Group, MemDag, VertexName,
+};
+use parking_lot::RwLock;
+use sha1::Sha1 as Sha1Hasher;
+use sha2::{Digest, Sha256 as Sha256Hasher};
+
+use edenapi_types::{
+    AnyId, BookmarkEntry, CommitGraphEntry, CommitIsAncestor, CommitRevlogData, EdenApiServerError,
+    FileContentEntry, FileContentId, FileEntry, HistoryEntry, HistoryResponseChunk, Sha1, Sha256,
+    TreeAttributes, TreeEntry, WireHistoryEntry,
+};
+use types::{HgId, Key, Parents};
+
+/// The bookmark whose ancestors form the master group of the repo's
+/// segmented changelog, which is served by the clone endpoints.
+pub const MASTER_BOOKMARK: &str = "master";
+
+/// An in-memory repository served by a `MockServer`.
+///
+/// The repository is shared between all clones of a `MockRepo`, so tests
+/// can keep a handle to a repo after adding it to a server in order to
+/// populate it further or to inspect objects uploaded by the client.
+#[derive(Clone, Default)]
+pub struct MockRepo {
+    inner: Arc<RwLock<RepoData>>,
+}
+
+#[derive(Default)]
+struct RepoData {
+    files: HashMap<Key, FileEntry>,
+    content_ids: HashMap<[u8; 32], Bytes>,
+    sha1s: HashMap<[u8; 20], Bytes>,
+    sha256s: HashMap<[u8; 32], Bytes>,
+    history: HashMap<Key, HistoryEntry>,
+    trees: HashMap<Key, (Bytes, Parents)>,
+    commits: HashMap<HgId, MockCommit>,
+    bookmarks: BTreeMap<String, HgId>,
+}
+
+/// A commit in the repo's commit graph.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MockCommit {
+    pub parents: Parents,
+    pub revlog_data: Bytes,
+}
+
+impl MockRepo {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add a file with the given content. The content is also made
+    /// available by its SHA-1 and SHA-256 hashes.
+    pub fn add_file(&self, key: Key, data: impl Into<Bytes>, parents: Parents) {
+        self.add_file_entry(FileEntry::new(
+            key,
+            data.into(),
+            parents,
+            Default::default(),
+        ));
+    }
+
+    /// Add a file entry as-is, which allows adding entries with metadata
+    /// or with content that doesn't match the entry's filenode hash.
+    pub fn add_file_entry(&self, entry: FileEntry) {
+        self.store_content(entry.data.clone());
+        self.inner.write().files.insert(entry.key.clone(), entry);
+    }
+
+    /// Make content available under the given content id, in addition to
+    /// its SHA-1 and SHA-256 hashes. Mononoke's canonical content ids can't
+    /// be computed on the client, so they must be assigned explicitly.
+    pub fn add_file_content(&self, id: FileContentId, data: impl Into<Bytes>) {
+        let data = data.into();
+        self.store_content(data.clone());
+        let mut inner = self.inner.write();
+        match id {
+            FileContentId::ContentId(id) => inner.content_ids.insert(id.0, data),
+            FileContentId::Sha1(sha1) => inner.sha1s.insert(sha1.0, data),
+            FileContentId::Sha256(sha256) => inner.sha256s.insert(sha256.0, data),
+        };
+    }
+
+    pub fn add_history(&self, entry: HistoryEntry) {
+        self.inner.write().history.insert(entry.key.clone(), entry);
+    }
+
+    pub fn add_tree(&self, key: Key, data: impl Into<Bytes>, parents: Parents) {
+        self.inner.write().trees.insert(key, (data.into(), parents));
+    }
+
+    pub fn add_commit(&self, hgid: HgId, parents: Parents, revlog_data: impl Into<Bytes>) {
+        let commit = MockCommit {
+            parents,
+            revlog_data: revlog_data.into(),
+        };
+        self.inner.write().commits.insert(hgid, commit);
+    }
+
+    pub fn set_bookmark(&self, bookmark: impl ToString, hgid: HgId) {
+        self.inner
+            .write()
+            .bookmarks
+            .insert(bookmark.to_string(), hgid);
+    }
+
+    pub fn file(&self, key: &Key) -> Option<FileEntry> {
+        self.inner.read().files.get(key).cloned()
+    }
+
+    pub fn tree(&self, key: &Key) -> Option<(Bytes, Parents)> {
+        self.inner.read().trees.get(key).cloned()
+    }
+
+    pub fn commit(&self, hgid: &HgId) -> Option<MockCommit> {
+        self.inner.read().commits.get(hgid).cloned()
+    }
+
+    pub fn bookmark(&self, bookmark: &str) -> Option<HgId> {
+        self.inner.read().bookmarks.get(bookmark).copied()
+    }
+
+    pub(crate) fn files(&self, keys: Vec<Key>) -> Vec<FileEntry> {
+        let inner = self.inner.read();
+        keys.iter()
+            .filter_map(|key| inner.files.get(key).cloned())
+            .collect()
+    }
+
+    pub(crate) fn file_contents(&self, ids: Vec<FileContentId>) -> Vec<FileContentEntry> {
+        let inner = self.inner.read();
+        ids.into_iter()
+            .filter_map(|id| {
+                let data = match &id {
+                    FileContentId::ContentId(id) => inner.content_ids.get(&id.0),
+                    FileContentId::Sha1(sha1) => inner.sha1s.get(&sha1.0),
+                    FileContentId::Sha256(sha256) => inner.sha256s.get(&sha256.0),
+                };
+                Some(FileContentEntry::new(id, data?.clone()))
+            })
+            .collect()
+    }
+
+    /// Walk the history of each of the given files, following parents
+    /// breadth-first, returning at most `length` entries per file.
+    pub(crate) fn history(&self, keys: Vec<Key>, length: Option<u32>) -> Vec<HistoryResponseChunk> {
+        let inner = self.inner.read();
+        let max = length.map_or(usize::MAX, |n| n as usize);
+
+        keys.into_iter()
+            .map(|key| {
+                let path = key.path.clone();
+                let mut entries = Vec::new();
+                let mut seen = HashSet::new();
+                let mut queue = VecDeque::from(vec![key]);
+
+                while let Some(key) = queue.pop_front() {
+                    if entries.len() >= max {
+                        break;
+                    }
+                    if key.hgid.is_null() || !seen.insert(key.clone()) {
+                        continue;
+                    }
+                    if let Some(entry) = inner.history.get(&key) {
+                        queue.extend(entry.nodeinfo.parents.iter().cloned());
+                        entries.push(WireHistoryEntry::from(entry.clone()));
+                    }
+                }
+
+                HistoryResponseChunk { path, entries }
+            })
+            .collect()
+    }
+
+    /// Look up the requested trees. Like the real server, missing trees
+    /// are reported as error entries rather than failing the request.
+    pub(crate) fn trees(
+        &self,
+        keys: Vec<Key>,
+        attributes: &TreeAttributes,
+    ) -> Vec<Result<TreeEntry, EdenApiServerError>> {
+        let inner = self.inner.read();
+        keys.into_iter()
+            .map(|key| {
+                let (data, parents) = match inner.trees.get(&key) {
+                    Some(tree) => tree.clone(),
+                    None => return Err(EdenApiServerError::with_key(key, "tree not found")),
+                };
+                Ok(TreeEntry {
+                    key,
+                    data: Some(data).filter(|_| attributes.manifest_blob),
+                    parents: Some(parents).filter(|_| attributes.parents),
+                    children: None,
+                })
+            })
+            .collect()
+    }
+
+    pub(crate) fn commit_revlog_data(&self, hgids: Vec<HgId>) -> Vec<CommitRevlogData> {
+        let inner = self.inner.read();
+        hgids
+            .into_iter()
+            .filter_map(|hgid| {
+                let commit = inner.commits.get(&hgid)?;
+                Some(CommitRevlogData::new(hgid, commit.revlog_data.clone()))
+            })
+            .collect()
+    }
+
+    /// Walk the commit graph from the given heads, following all parents,
+    /// returning each commit up to `depth` generations back along with its
+    /// parents. Fails if any of the heads is unknown.
+    pub(crate) fn commit_graph(
+        &self,
+        heads: Vec<HgId>,
+        depth: u64,
+    ) -> Result<Vec<CommitGraphEntry>, String> {
+        let inner = self.inner.read();
+        let mut entries = Vec::new();
+        let mut seen = HashSet::new();
+        let mut queue = heads
+            .into_iter()
+            .map(|hgid| (hgid, 0))
+            .collect::<VecDeque<_>>();
+
+        while let Some((hgid, generation)) = queue.pop_front() {
+            if !seen.insert(hgid) {
+                continue;
+            }
+            let commit = inner
+                .commits
+                .get(&hgid)
+                .ok_or_else(|| format!("commit {} not found", hgid))?;
+            let parents = commit.parents.into_iter().collect::<Vec<_>>();
+            if generation < depth {
+                queue.extend(parents.iter().map(|p| (*p, generation + 1)));
+            }
+            entries.push(CommitGraphEntry::new(hgid, parents));
+        }
+
+        Ok(entries)
+    }
+
+    /// Check whether the first commit of each pair is an ancestor of the
+    /// second. Fails if any of the commits is unknown.
+    pub(crate) fn is_ancestor(
+        &self,
+        pairs: Vec<(HgId, HgId)>,
+    ) -> Result<Vec<CommitIsAncestor>, String> {
+        let inner = self.inner.read();
+        pairs
+            .into_iter()
+            .map(|(ancestor, descendant)| {
+                for hgid in &[ancestor, descendant] {
+                    if !inner.commits.contains_key(hgid) {
+                        return Err(format!("commit {} not found", hgid));
+                    }
+                }
+
+                let mut seen = HashSet::new();
+                let mut queue = VecDeque::from(vec![descendant]);
+                let mut found = false;
+                while let Some(hgid) = queue.pop_front() {
+                    if hgid == ancestor {
+                        found = true;
+                        break;
+                    }
+                    if !seen.insert(hgid) {
+                        continue;
+                    }
+                    if let Some(commit) = inner.commits.get(&hgid) {
+                        queue.extend(commit.parents);
+                    }
+                }
+
+                Ok(CommitIsAncestor::new(ancestor, descendant, found))
+            })
+            .collect()
+    }
+
+    /// Build a segmented changelog of the ancestors of the master bookmark,
+    /// like Mononoke does for the clone endpoints, and export it as clone
+    /// data. The idmap covers only the universally known commits unless
+    /// `full_idmap` is set, in which case it covers every commit.
+    pub(crate) fn clone_data(&self, full_idmap: bool) -> Result<CloneData<HgId>, String> {
+        let (parents, master) = {
+            let inner = self.inner.read();
+            let master = inner
+                .bookmarks
+                .get(MASTER_BOOKMARK)
+                .copied()
+                .ok_or_else(|| format!("bookmark '{}' not found", MASTER_BOOKMARK))?;
+            let parents = inner
+                .commits
+                .iter()
+                .map(|(hgid, commit)| {
+                    let parents = commit.parents.into_iter().map(vertex).collect();
+                    (vertex(*hgid), parents)
+                })
+                .collect::<HashMap<_, Vec<_>>>();
+            (parents, vertex(master))
+        };
+
+        // The in-memory dag never blocks.
+        non_blocking_result(async move {
+            let mut dag = MemDag::new();
+            dag.add_heads_and_flush(&parents, std::slice::from_ref(&master), &[])
+                .await?;
+
+            let head_id = dag.vertex_id(master).await?;
+            let flat_segments = dag.dag().flat_segments(Group::MASTER)?;
+            let ids = if full_idmap {
+                dag.dag().all()?.iter().collect::<Vec<_>>()
+            } else {
+                dag.dag().universal_ids()?.into_iter().collect()
+            };
+
+            let mut idmap = HashMap::new();
+            for id in ids {
+                let name = dag.vertex_name(id).await?;
+                idmap.insert(id, HgId::from_slice(name.as_ref())?);
+            }
+
+            Ok(CloneData {
+                head_id,
+                flat_segments,
+                idmap,
+            })
+        })
+        .map_err(|e: Error| e.to_string())
+    }
+
+    /// Resolve bookmarks by name, and list the bookmarks matching any of
+    /// the given prefixes.
+    pub(crate) fn bookmarks(
+        &self,
+        bookmarks: Vec<String>,
+        prefixes: Vec<String>,
+    ) -> Vec<BookmarkEntry> {
+        let inner = self.inner.read();
+        let mut entries = bookmarks
+            .into_iter()
+            .map(|name| {
+                let hgid = inner.bookmarks.get(&name).copied();
+                BookmarkEntry::new(name, hgid)
+            })
+            .collect::<Vec<_>>();
+
+        for prefix in prefixes {
+            entries.extend(
+                inner
+                    .bookmarks
+                    .range(prefix.clone()..)
+                    .take_while(|(name, _)| name.starts_with(&prefix))
+                    .map(|(name, hgid)| BookmarkEntry::new(name.clone(), Some(*hgid))),
+            );
+        }
+
+        entries
+    }
+
+    pub(crate) fn contains(&self, id: &AnyId) -> bool {
+        let inner = self.inner.read();
+        match id {
+            AnyId::FileContentSha256(sha256) => inner.sha256s.contains_key(&sha256.0),
+            AnyId::HgFilenodeId(hgid) => inner.files.keys().any(|key| &key.hgid == hgid),
+            AnyId::HgTreeId(hgid) => inner.trees.keys().any(|key| &key.hgid == hgid),
+            AnyId::HgChangesetId(hgid) => inner.commits.contains_key(hgid),
+        }
+    }
+
+    /// Store content under its hashes, returning its SHA-256 hash.
+    pub(crate) fn store_content(&self, data: Bytes) -> Sha256 {
+        let sha1 = Sha1(Sha1Hasher::digest(&data).into());
+        let sha256 = Sha256(Sha256Hasher::digest(&data).into());
+
+        let mut inner = self.inner.write();
+        inner.sha1s.insert(sha1.0, data.clone());
+        inner.sha256s.insert(sha256.0, data);
+
+        sha256
+    }
+
+    pub(crate) fn content_by_sha256(&self, sha256: &Sha256) -> Option<Bytes> {
+        self.inner.read().sha256s.get(&sha256.0).cloned()
+    }
+
+    /// Land the commits between `base` (exclusive) and `head` (inclusive)
+    /// onto `bookmark`. Unlike pushrebase, this only supports fast-forward
+    /// moves, so the commits are never rewritten.
+    pub(crate) fn land_stack(
+        &self,
+        bookmark: &str,
+        head: HgId,
+        base: HgId,
+    ) -> Result<Vec<HgId>, String> {
+        let mut inner = self.inner.write();
+
+        match inner.bookmarks.get(bookmark) {
+            Some(current) if *current != base => {
+                return Err(format!(
+                    "bookmark '{}' is at {}, not at stack base {}",
+                    bookmark, current, base
+                ));
+            }
+            _ => {}
+        }
+
+        let mut stack = Vec::new();
+        let mut next = head;
+        while next != base && !next.is_null() {
+            let commit = inner
+                .commits
+                .get(&next)
+                .ok_or_else(|| format!("commit {} has not been uploaded", next))?;
+            stack.push(next);
+            next = commit.parents.p1().copied().unwrap_or(*HgId::null_id());
+        }
+
+        if next != base {
+            return Err(format!("{} is not an ancestor of {}", base, head));
+        }
+
+        inner.bookmarks.insert(bookmark.to_string(), head);
+        Ok(stack)
+    }
+}
+
+fn vertex(hgid: HgId) -> VertexName {
+    VertexName::copy_from(hgid.as_ref())
+}
@@ -0,0 +1,504 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::io::{self, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Result;
+use futures::{channel::oneshot, prelude::*};
+use http::{header, Method, StatusCode};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
+use parking_lot::{Mutex, RwLock};
+use percent_encoding::percent_decode_str;
+use serde::{de::DeserializeOwned, Serialize};
+use url::Url;
+
+use edenapi_types::{
+    wire::{
+        WireBookmarkRequest, WireFileRequest, WireHistoryRequest, WireIdMapEntry, WireTreeRequest,
+    },
+    AnyId, CommitGraphRequest, CommitIsAncestorRequest, CommitRevlogDataRequest,
+    FileContentRequest, LandStackRequest, LandStackResponse, LookupRequest, LookupResponse, ToApi,
+    ToWire, UploadFileContentsRequest, UploadHgChangesetsRequest, UploadHgFilenodesRequest,
+    UploadResponse, UploadTreesRequest,
+};
+
+use crate::fault::{Fault, Faults};
+use crate::repo::MockRepo;
+
+/// Endpoint paths, relative to the repo name. These must match the paths
+/// used by the EdenAPI client.
+mod paths {
+    pub const HEALTH_CHECK: &str = "health_check";
+    pub const FILES: &str = "files";
+    pub const FILES_BY_CONTENT: &str = "files/content";
+    pub const HISTORY: &str = "history";
+    pub const TREES: &str = "trees";
+    pub const COMPLETE_TREES: &str = "trees/complete";
+    pub const COMMIT_REVLOG_DATA: &str = "commit/revlog_data";
+    pub const COMMIT_GRAPH: &str = "commit/graph";
+    pub const COMMIT_IS_ANCESTOR: &str = "commit/is_ancestor";
+    pub const BOOKMARKS: &str = "bookmarks";
+    pub const CLONE_DATA: &str = "clone";
+    pub const FULL_IDMAP_CLONE_DATA: &str = "full_idmap_clone";
+    pub const LOOKUP: &str = "lookup";
+    pub const UPLOAD_FILE_CONTENTS: &str = "upload/file_contents";
+    pub const UPLOAD_FILENODES: &str = "upload/filenodes";
+    pub const UPLOAD_TREES: &str = "upload/trees";
+    pub const UPLOAD_CHANGESETS: &str = "upload/changesets";
+    pub const LAND_STACK: &str = "land_stack";
+}
+
+/// A lightweight EdenAPI server for client-side integration tests.
+///
+/// The server listens on an arbitrary port on localhost and serves
+/// in-memory `MockRepo`s using the same CBOR wire format as the real
+/// server. It runs on a dedicated thread with its own runtime, so it can
+/// be used from both synchronous and asynchronous tests. The server is
+/// shut down when the `MockServer` is dropped.
+///
+/// ```rust,ignore
+/// let server = MockServer::start()?;
+/// let repo = server.add_repo("repo");
+/// repo.add_file(key, "content", Parents::None);
+///
+/// let client = Builder::new().server_url(server.url()).build()?;
+/// ```
+pub struct MockServer {
+    url: Url,
+    state: Arc<ServerState>,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+#[derive(Default)]
+struct ServerState {
+    repos: RwLock<HashMap<String, MockRepo>>,
+    faults: Mutex<Faults>,
+    requests: Mutex<Vec<String>>,
+}
+
+impl MockServer {
+    pub fn start() -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let url = Url::parse(&format!("http://{}/", listener.local_addr()?))?;
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let builder = {
+            let _guard = runtime.enter();
+            Server::from_tcp(listener)?
+        };
+
+        let state = Arc::new(ServerState::default());
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+
+        let thread = thread::spawn({
+            let state = state.clone();
+            move || {
+                let make_service = make_service_fn(move |_| {
+                    let state = state.clone();
+                    async move { Ok::<_, Infallible>(service_fn(move |req| handle(state.clone(), req))) }
+                });
+                let server = builder.serve(make_service);
+
+                // Stop serving immediately on shutdown rather than waiting
+                // for open connections to finish, since a test may drop the
+                // server while a client is still waiting on a response.
+                if let Err(e) = runtime.block_on(async move {
+                    tokio::select! {
+                        res = server => res,
+                        _ = shutdown_rx => Ok(()),
+                    }
+                }) {
+                    tracing::error!("Mock EdenAPI server failed: {}", e);
+                }
+            }
+        });
+
+        Ok(Self {
+            url,
+            state,
+            shutdown: Some(shutdown_tx),
+            thread: Some(thread),
+        })
+    }
+
+    /// The base URL of the server, suitable for use as the client's
+    /// `edenapi.url`.
+    pub fn url(&self) -> Url {
+        self.url.clone()
+    }
+
+    /// Serve the given repo under the given name, replacing any repo that
+    /// was previously served under that name.
+    pub fn with_repo(&self, name: impl ToString, repo: MockRepo) {
+        self.state.repos.write().insert(name.to_string(), repo);
+    }
+
+    /// Serve a new empty repo under the given name, returning a handle
+    /// that can be used to populate it.
+    pub fn add_repo(&self, name: impl ToString) -> MockRepo {
+        let repo = MockRepo::new();
+        self.with_repo(name, repo.clone());
+        repo
+    }
+
+    /// Inject a fault into responses from all endpoints.
+    pub fn inject(&self, fault: Fault) {
+        self.state.faults.lock().add(None, fault);
+    }
+
+    /// Inject a fault into responses from a single endpoint, specified by
+    /// its path relative to the repo (e.g., "files" or "trees/complete").
+    pub fn inject_for(&self, endpoint: impl ToString, fault: Fault) {
+        self.state
+            .faults
+            .lock()
+            .add(Some(endpoint.to_string()), fault);
+    }
+
+    /// Remove all injected faults.
+    pub fn clear_faults(&self) {
+        self.state.faults.lock().clear();
+    }
+
+    /// The endpoints that have been requested so far, in order. Requests
+    /// that failed due to injected faults are included.
+    pub fn requests(&self) -> Vec<String> {
+        self.state.requests.lock().clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// An error response. The message is sent as the response body.
+struct HandlerError {
+    status: StatusCode,
+    message: String,
+}
+
+impl HandlerError {
+    fn new(status: StatusCode, message: impl ToString) -> Self {
+        Self {
+            status,
+            message: message.to_string(),
+        }
+    }
+
+    fn bad_request(message: impl ToString) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    fn into_response(self) -> Response<Body> {
+        Response::builder()
+            .status(self.status)
+            .body(Body::from(self.message))
+            .expect("failed to build error response")
+    }
+}
+
+async fn handle(state: Arc<ServerState>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    Ok(serve(state, req)
+        .await
+        .unwrap_or_else(HandlerError::into_response))
+}
+
+async fn serve(
+    state: Arc<ServerState>,
+    req: Request<Body>,
+) -> Result<Response<Body>, HandlerError> {
+    let path = percent_decode_str(req.uri().path().trim_start_matches('/'))
+        .decode_utf8_lossy()
+        .into_owned();
+    let (repo, endpoint) = if path == paths::HEALTH_CHECK {
+        (None, path.as_str())
+    } else {
+        let mut parts = path.splitn(2, '/');
+        let repo = parts.next().unwrap_or_default();
+        (Some(repo), parts.next().unwrap_or_default())
+    };
+
+    state.requests.lock().push(endpoint.to_string());
+    let faults = state.faults.lock().take(endpoint);
+
+    if faults.latency > Duration::default() {
+        tokio::time::sleep(faults.latency).await;
+    }
+    if let Some(status) = faults.error {
+        return Err(HandlerError::new(status, "injected error"));
+    }
+
+    let repo = match repo {
+        Some(repo) => repo,
+        None => return Ok(Response::new(Body::from("I_AM_ALIVE"))),
+    };
+
+    if req.method() != Method::POST {
+        return Err(HandlerError::new(
+            StatusCode::METHOD_NOT_ALLOWED,
+            format!("{} is not supported", req.method()),
+        ));
+    }
+
+    let repo =
+        state.repos.read().get(repo).cloned().ok_or_else(|| {
+            HandlerError::new(StatusCode::NOT_FOUND, format!("no repo '{}'", repo))
+        })?;
+
+    let zstd = accepts_zstd(&req);
+    let body = hyper::body::to_bytes(req.into_body())
+        .await
+        .map_err(HandlerError::bad_request)?;
+    let entries = dispatch(&repo, endpoint, &body)?;
+
+    if zstd {
+        let entries = zstd_encode(entries)
+            .map_err(|e| HandlerError::new(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        let mut res = cbor_response(entries, faults.truncate);
+        res.headers_mut()
+            .insert(header::CONTENT_ENCODING, "zstd".parse().unwrap());
+        Ok(res)
+    } else {
+        Ok(cbor_response(entries, faults.truncate))
+    }
+}
+
+fn dispatch(repo: &MockRepo, endpoint: &str, body: &[u8]) -> Result<Vec<Vec<u8>>, HandlerError> {
+    match endpoint {
+        paths::FILES => {
+            let req = parse_wire::<WireFileRequest>(body)?;
+            encode(repo.files(req.keys).into_iter().map(ToWire::to_wire))
+        }
+        paths::FILES_BY_CONTENT => {
+            let req: FileContentRequest = parse(body)?;
+            encode(repo.file_contents(req.ids))
+        }
+        paths::HISTORY => {
+            let req = parse_wire::<WireHistoryRequest>(body)?;
+            let chunks = repo.history(req.keys, req.length);
+            encode(chunks.into_iter().map(ToWire::to_wire))
+        }
+        paths::TREES => {
+            let req = parse_wire::<WireTreeRequest>(body)?;
+            let trees = repo.trees(req.keys, &req.attributes);
+            encode(trees.into_iter().map(ToWire::to_wire))
+        }
+        paths::COMMIT_REVLOG_DATA => {
+            let req: CommitRevlogDataRequest = parse(body)?;
+            encode(repo.commit_revlog_data(req.hgids))
+        }
+        paths::COMMIT_GRAPH => {
+            let req: CommitGraphRequest = parse(body)?;
+            let entries = repo
+                .commit_graph(req.hgids, req.depth)
+                .map_err(HandlerError::bad_request)?;
+            encode(entries)
+        }
+        paths::COMMIT_IS_ANCESTOR => {
+            let req: CommitIsAncestorRequest = parse(body)?;
+            let entries = repo
+                .is_ancestor(req.pairs)
+                .map_err(HandlerError::bad_request)?;
+            encode(entries)
+        }
+        paths::BOOKMARKS => {
+            let req = parse_wire::<WireBookmarkRequest>(body)?;
+            let entries = repo.bookmarks(req.bookmarks, req.prefixes);
+            encode(entries.into_iter().map(ToWire::to_wire))
+        }
+        paths::LOOKUP => {
+            let req: LookupRequest = parse(body)?;
+            encode(req.ids.into_iter().map(|id| LookupResponse {
+                present: repo.contains(&id),
+                id,
+            }))
+        }
+        paths::UPLOAD_FILE_CONTENTS => {
+            let req: UploadFileContentsRequest = parse(body)?;
+            encode(req.contents.into_iter().map(|data| UploadResponse {
+                id: AnyId::FileContentSha256(repo.store_content(data)),
+            }))
+        }
+        paths::UPLOAD_FILENODES => {
+            let req: UploadHgFilenodesRequest = parse(body)?;
+            let mut responses = Vec::new();
+            for entry in req.filenodes {
+                let content = repo
+                    .content_by_sha256(&entry.content_sha256)
+                    .ok_or_else(|| {
+                        HandlerError::bad_request(format!(
+                            "content {} has not been uploaded",
+                            entry.content_sha256
+                        ))
+                    })?;
+                // Copy information is stored in a metadata header at the
+                // start of the filenode's content, as in a revlog.
+                let data = match &entry.copy_from {
+                    Some(copy_from) => {
+                        let mut data = format!(
+                            "\x01\ncopy: {}\ncopyrev: {}\n\x01\n",
+                            copy_from.path,
+                            copy_from.hgid.to_hex()
+                        )
+                        .into_bytes();
+                        data.extend_from_slice(&content);
+                        data.into()
+                    }
+                    None => content,
+                };
+                repo.add_file(entry.key.clone(), data, entry.parents);
+                responses.push(UploadResponse {
+                    id: AnyId::HgFilenodeId(entry.key.hgid),
+                });
+            }
+            encode(responses)
+        }
+        paths::UPLOAD_TREES => {
+            let req: UploadTreesRequest = parse(body)?;
+            encode(req.trees.into_iter().map(|entry| {
+                let id = AnyId::HgTreeId(entry.key.hgid);
+                repo.add_tree(entry.key, entry.data, entry.parents);
+                UploadResponse { id }
+            }))
+        }
+        paths::UPLOAD_CHANGESETS => {
+            let req: UploadHgChangesetsRequest = parse(body)?;
+            encode(req.changesets.into_iter().map(|entry| {
+                repo.add_commit(entry.hgid, entry.parents, entry.revlog_data);
+                UploadResponse {
+                    id: AnyId::HgChangesetId(entry.hgid),
+                }
+            }))
+        }
+        paths::LAND_STACK => {
+            let req: LandStackRequest = parse(body)?;
+            let stack = repo
+                .land_stack(&req.bookmark, req.head, req.base)
+                .map_err(|e| HandlerError::new(StatusCode::CONFLICT, e))?;
+            encode(vec![LandStackResponse {
+                new_head: req.head,
+                old_to_new_hgids: stack.into_iter().map(|id| (id, id)).collect(),
+            }])
+        }
+        paths::CLONE_DATA => {
+            let clone_data = repo.clone_data(false).map_err(HandlerError::bad_request)?;
+            encode(vec![clone_data.to_wire()])
+        }
+        paths::FULL_IDMAP_CLONE_DATA => {
+            // Like the real server, send the clone data without its idmap,
+            // followed by a stream of idmap entries.
+            let mut clone_data = repo.clone_data(true).map_err(HandlerError::bad_request)?;
+            let mut idmap = clone_data.idmap.drain().collect::<Vec<_>>();
+            idmap.sort();
+            let mut entries = encode(vec![clone_data.to_wire()])?;
+            entries.extend(encode(idmap.into_iter().map(|(dag_id, hg_id)| {
+                WireIdMapEntry {
+                    dag_id: dag_id.to_wire(),
+                    hg_id: hg_id.to_wire(),
+                }
+            }))?);
+            Ok(entries)
+        }
+        paths::COMPLETE_TREES => Err(HandlerError::new(
+            StatusCode::NOT_IMPLEMENTED,
+            "not supported by mock server",
+        )),
+        _ => Err(HandlerError::new(
+            StatusCode::NOT_FOUND,
+            format!("unknown endpoint '{}'", endpoint),
+        )),
+    }
+}
+
+fn parse<T: DeserializeOwned>(body: &[u8]) -> Result<T, HandlerError> {
+    serde_cbor::from_slice(body).map_err(HandlerError::bad_request)
+}
+
+fn parse_wire<T: DeserializeOwned + ToApi>(body: &[u8]) -> Result<T::Api, HandlerError> {
+    parse::<T>(body)?
+        .to_api()
+        .map_err(|e| HandlerError::bad_request(format!("{:?}", e)))
+}
+
+/// Serialize each entry of a response separately, so that the response
+/// can be streamed (and truncated) one entry at a time.
+fn encode<T: Serialize>(
+    entries: impl IntoIterator<Item = T>,
+) -> Result<Vec<Vec<u8>>, HandlerError> {
+    entries
+        .into_iter()
+        .map(|entry| serde_cbor::to_vec(&entry))
+        .collect::<Result<_, _>>()
+        .map_err(|e| HandlerError::new(StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+/// Whether the client accepts zstd-compressed responses. Like the real
+/// server, only zstd is supported; other encodings are ignored.
+fn accepts_zstd(req: &Request<Body>) -> bool {
+    req.headers()
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|encoding| encoding.trim() == "zstd")
+}
+
+/// Compress the entries of a response as a single zstd frame, flushing the
+/// encoder after each entry so that every chunk can be decoded as soon as
+/// it arrives. The trailing chunk ends the frame.
+fn zstd_encode(entries: Vec<Vec<u8>>) -> io::Result<Vec<Vec<u8>>> {
+    let mut encoder = zstd::stream::write::Encoder::new(Vec::new(), 0)?;
+    let mut chunks = Vec::with_capacity(entries.len() + 1);
+    for entry in entries {
+        encoder.write_all(&entry)?;
+        encoder.flush()?;
+        chunks.push(std::mem::take(encoder.get_mut()));
+    }
+    chunks.push(encoder.finish()?);
+    Ok(chunks)
+}
+
+/// Build a streaming response from CBOR-encoded entries. If `truncate` is
+/// set, the response is aborted after that many entries.
+fn cbor_response(entries: Vec<Vec<u8>>, truncate: Option<usize>) -> Response<Body> {
+    let n = truncate.unwrap_or(entries.len());
+    let body = stream::iter(entries.into_iter().take(n).map(Ok));
+    let body = match truncate {
+        Some(_) => body
+            .chain(stream::once(async {
+                // Yield first so that the entries before the truncation point
+                // are flushed to the client before the connection is dropped.
+                tokio::task::yield_now().await;
+                Err(io::Error::new(io::ErrorKind::Other, "injected truncation"))
+            }))
+            .left_stream(),
+        None => body.right_stream(),
+    };
+
+    Response::builder()
+        .header("Content-Type", "application/cbor")
+        .body(Body::wrap_stream(body))
+        .expect("failed to build response")
+}
@@ -6,10 +6,13 @@
  */
 
 use async_trait::async_trait;
+use bytes::Bytes;
 
 use edenapi_types::{
-    CloneData, CommitRevlogData, EdenApiServerError, FileEntry, HistoryEntry, TreeAttributes,
-    TreeEntry,
+    AnyId, BookmarkEntry, CloneData, CommitGraphEntry, CommitIsAncestor, CommitRevlogData,
+    EdenApiServerError, FileContentEntry, FileContentId, FileEntry, HistoryEntry,
+    LandStackResponse, LookupResponse, TreeAttributes, TreeEntry, UploadHgChangesetEntry,
+    UploadHgFilenodeEntry, UploadResponse, UploadTreeEntry,
 };
 use http_client::Progress;
 use types::{HgId, Key, RepoPathBuf};
@@ -30,6 +33,13 @@ pub trait EdenApi: Send + Sync + 'static {
         progress: Option<ProgressCallback>,
     ) -> Result<Fetch<FileEntry>, EdenApiError>;
 
+    async fn files_by_content(
+        &self,
+        repo: String,
+        ids: Vec<FileContentId>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<FileContentEntry>, EdenApiError>;
+
     async fn history(
         &self,
         repo: String,
@@ -63,6 +73,33 @@ pub trait EdenApi: Send + Sync + 'static {
         progress: Option<ProgressCallback>,
     ) -> Result<Fetch<CommitRevlogData>, EdenApiError>;
 
+    /// Fetch a slice of the commit graph: the given commits and their
+    /// ancestors up to `depth` generations back, each with its parents.
+    async fn commit_graph(
+        &self,
+        repo: String,
+        hgids: Vec<HgId>,
+        depth: u64,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<CommitGraphEntry>, EdenApiError>;
+
+    /// Check whether the first commit of each `(ancestor, descendant)`
+    /// pair is an ancestor of the second.
+    async fn commit_is_ancestor(
+        &self,
+        repo: String,
+        pairs: Vec<(HgId, HgId)>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<CommitIsAncestor>, EdenApiError>;
+
+    async fn bookmarks(
+        &self,
+        repo: String,
+        bookmarks: Vec<String>,
+        prefixes: Vec<String>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<BookmarkEntry>, EdenApiError>;
+
     async fn clone_data(
         &self,
         repo: String,
@@ -74,4 +111,47 @@ pub trait EdenApi: Send + Sync + 'static {
         repo: String,
         progress: Option<ProgressCallback>,
     ) -> Result<CloneData<HgId>, EdenApiError>;
+
+    async fn lookup(
+        &self,
+        repo: String,
+        ids: Vec<AnyId>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<LookupResponse>, EdenApiError>;
+
+    async fn upload_file_contents(
+        &self,
+        repo: String,
+        contents: Vec<Bytes>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<UploadResponse>, EdenApiError>;
+
+    async fn upload_filenodes(
+        &self,
+        repo: String,
+        filenodes: Vec<UploadHgFilenodeEntry>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<UploadResponse>, EdenApiError>;
+
+    async fn upload_trees(
+        &self,
+        repo: String,
+        trees: Vec<UploadTreeEntry>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<UploadResponse>, EdenApiError>;
+
+    async fn upload_changesets(
+        &self,
+        repo: String,
+        changesets: Vec<UploadHgChangesetEntry>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<UploadResponse>, EdenApiError>;
+
+    async fn land_stack(
+        &self,
+        repo: String,
+        bookmark: String,
+        head: HgId,
+        base: HgId,
+    ) -> Result<LandStackResponse, EdenApiError>;
 }
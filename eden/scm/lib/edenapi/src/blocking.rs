@@ -6,9 +6,12 @@
  */
 
 use async_runtime::block_on_exclusive as block_on_future;
+use bytes::Bytes;
 use edenapi_types::{
-    CloneData, CommitRevlogData, EdenApiServerError, FileEntry, HistoryEntry, TreeAttributes,
-    TreeEntry,
+    AnyId, BookmarkEntry, CloneData, CommitGraphEntry, CommitIsAncestor, CommitRevlogData,
+    EdenApiServerError, FileContentEntry, FileContentId, FileEntry, HistoryEntry,
+    LandStackResponse, LookupResponse, TreeAttributes, TreeEntry, UploadHgChangesetEntry,
+    UploadHgFilenodeEntry, UploadResponse, UploadTreeEntry,
 };
 use types::{HgId, Key, RepoPathBuf};
 
@@ -30,6 +33,15 @@ pub trait EdenApiBlocking: EdenApi {
         BlockingFetch::from_async(self.files(repo, keys, progress))
     }
 
+    fn files_by_content_blocking(
+        &self,
+        repo: String,
+        ids: Vec<FileContentId>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<BlockingFetch<FileContentEntry>, EdenApiError> {
+        BlockingFetch::from_async(self.files_by_content(repo, ids, progress))
+    }
+
     fn history_blocking(
         &self,
         repo: String,
@@ -78,6 +90,35 @@ pub trait EdenApiBlocking: EdenApi {
         BlockingFetch::from_async(self.commit_revlog_data(repo, hgids, progress))
     }
 
+    fn commit_graph_blocking(
+        &self,
+        repo: String,
+        hgids: Vec<HgId>,
+        depth: u64,
+        progress: Option<ProgressCallback>,
+    ) -> Result<BlockingFetch<CommitGraphEntry>, EdenApiError> {
+        BlockingFetch::from_async(self.commit_graph(repo, hgids, depth, progress))
+    }
+
+    fn commit_is_ancestor_blocking(
+        &self,
+        repo: String,
+        pairs: Vec<(HgId, HgId)>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<BlockingFetch<CommitIsAncestor>, EdenApiError> {
+        BlockingFetch::from_async(self.commit_is_ancestor(repo, pairs, progress))
+    }
+
+    fn bookmarks_blocking(
+        &self,
+        repo: String,
+        bookmarks: Vec<String>,
+        prefixes: Vec<String>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<BlockingFetch<BookmarkEntry>, EdenApiError> {
+        BlockingFetch::from_async(self.bookmarks(repo, bookmarks, prefixes, progress))
+    }
+
     fn clone_data_blocking(
         &self,
         repo: String,
@@ -93,6 +134,61 @@ pub trait EdenApiBlocking: EdenApi {
     ) -> Result<CloneData<HgId>, EdenApiError> {
         block_on_future(self.full_idmap_clone_data(repo, progress))
     }
+
+    fn lookup_blocking(
+        &self,
+        repo: String,
+        ids: Vec<AnyId>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<BlockingFetch<LookupResponse>, EdenApiError> {
+        BlockingFetch::from_async(self.lookup(repo, ids, progress))
+    }
+
+    fn upload_file_contents_blocking(
+        &self,
+        repo: String,
+        contents: Vec<Bytes>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<BlockingFetch<UploadResponse>, EdenApiError> {
+        BlockingFetch::from_async(self.upload_file_contents(repo, contents, progress))
+    }
+
+    fn upload_filenodes_blocking(
+        &self,
+        repo: String,
+        filenodes: Vec<UploadHgFilenodeEntry>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<BlockingFetch<UploadResponse>, EdenApiError> {
+        BlockingFetch::from_async(self.upload_filenodes(repo, filenodes, progress))
+    }
+
+    fn upload_trees_blocking(
+        &self,
+        repo: String,
+        trees: Vec<UploadTreeEntry>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<BlockingFetch<UploadResponse>, EdenApiError> {
+        BlockingFetch::from_async(self.upload_trees(repo, trees, progress))
+    }
+
+    fn upload_changesets_blocking(
+        &self,
+        repo: String,
+        changesets: Vec<UploadHgChangesetEntry>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<BlockingFetch<UploadResponse>, EdenApiError> {
+        BlockingFetch::from_async(self.upload_changesets(repo, changesets, progress))
+    }
+
+    fn land_stack_blocking(
+        &self,
+        repo: String,
+        bookmark: String,
+        head: HgId,
+        base: HgId,
+    ) -> Result<LandStackResponse, EdenApiError> {
+        block_on_future(self.land_stack(repo, bookmark, head, base))
+    }
 }
 
 impl<T: EdenApi + ?Sized> EdenApiBlocking for T {}
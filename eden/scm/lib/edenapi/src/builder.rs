@@ -16,10 +16,16 @@ use url::Url;
 use anyhow::anyhow;
 use auth::AuthConfig;
 use configparser::{config::ConfigSet, hg::ConfigSetHgExt};
-use http_client::HttpVersion;
+use http_client::{Encoding, HttpVersion};
 
 use crate::client::Client;
 use crate::errors::{ConfigError, EdenApiError};
+use crate::retry::{DEFAULT_MAX_RETRIES, DEFAULT_RETRY_DELAY};
+
+/// Encodings that the client accepts for compressed responses by default.
+/// zstd compresses CBOR-encoded file and tree data considerably better and
+/// faster than gzip, which is only offered as a fallback.
+const DEFAULT_COMPRESSION: &[Encoding] = &[Encoding::Zstd, Encoding::Gzip];
 
 /// Builder for creating new EdenAPI clients.
 #[derive(Debug, Default)]
@@ -33,6 +39,9 @@ pub struct Builder {
     max_trees: Option<usize>,
     max_history: Option<usize>,
     timeout: Option<Duration>,
+    max_retries: Option<usize>,
+    retry_delay: Option<Duration>,
+    compression: Option<Vec<Encoding>>,
     debug: bool,
     correlator: Option<String>,
     http_version: Option<HttpVersion>,
@@ -94,6 +103,27 @@ impl Builder {
             .map_err(|e| ConfigError::Malformed("edenapi.timeout".into(), e))?
             .map(Duration::from_secs);
 
+        let max_retries = config
+            .get_opt("edenapi", "max-retries")
+            .map_err(|e| ConfigError::Malformed("edenapi.max-retries".into(), e))?;
+
+        let retry_delay = config
+            .get_opt("edenapi", "retry-delay-ms")
+            .map_err(|e| ConfigError::Malformed("edenapi.retry-delay-ms".into(), e))?
+            .map(Duration::from_millis);
+
+        let compression = config
+            .get_opt::<Vec<String>>("edenapi", "compression")
+            .map_err(|e| ConfigError::Malformed("edenapi.compression".into(), e))?
+            .map(|encodings| {
+                encodings
+                    .iter()
+                    .map(|e| e.parse::<Encoding>())
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()
+            .map_err(|e| ConfigError::Malformed("edenapi.compression".into(), e.into()))?;
+
         let debug = config
             .get_opt("edenapi", "debug")
             .map_err(|e| ConfigError::Malformed("edenapi.timeout".into(), e))?
@@ -124,6 +154,9 @@ impl Builder {
             max_trees,
             max_history,
             timeout,
+            max_retries,
+            retry_delay,
+            compression,
             debug,
             correlator: None,
             http_version,
@@ -207,6 +240,29 @@ impl Builder {
         self
     }
 
+    /// Maximum number of times a failed request will be retried. Only
+    /// idempotent requests that failed due to a connection error or a
+    /// transient server error (e.g., 429 or 503) are retried.
+    pub fn max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = Some(max_retries);
+        self
+    }
+
+    /// Delay before the first retry of a failed request. The delay grows
+    /// exponentially with each subsequent retry, unless the server specifies
+    /// a delay using the `Retry-After` header.
+    pub fn retry_delay(mut self, retry_delay: Duration) -> Self {
+        self.retry_delay = Some(retry_delay);
+        self
+    }
+
+    /// Content encodings that the server may use to compress responses, in
+    /// order of preference. Pass an empty list to disable compression.
+    pub fn compression(mut self, encodings: Vec<Encoding>) -> Self {
+        self.compression = Some(encodings);
+        self
+    }
+
     /// Unique identifier that will be logged by both the client and server for
     /// every request, allowing log entries on both sides to be correlated. Also
     /// allows correlating multiple requests that were made by the same instance
@@ -231,6 +287,9 @@ pub(crate) struct Config {
     pub(crate) max_trees: Option<usize>,
     pub(crate) max_history: Option<usize>,
     pub(crate) timeout: Option<Duration>,
+    pub(crate) max_retries: usize,
+    pub(crate) retry_delay: Duration,
+    pub(crate) compression: Vec<Encoding>,
     pub(crate) debug: bool,
     pub(crate) correlator: Option<String>,
     pub(crate) http_version: Option<HttpVersion>,
@@ -251,6 +310,9 @@ impl TryFrom<Builder> for Config {
             max_trees,
             max_history,
             timeout,
+            max_retries,
+            retry_delay,
+            compression,
             debug,
             correlator,
             http_version,
@@ -265,6 +327,10 @@ impl TryFrom<Builder> for Config {
         let max_trees = max_trees.filter(|n| *n > 0);
         let max_history = max_history.filter(|n| *n > 0);
 
+        let max_retries = max_retries.unwrap_or(DEFAULT_MAX_RETRIES);
+        let retry_delay = retry_delay.unwrap_or(DEFAULT_RETRY_DELAY);
+        let compression = compression.unwrap_or_else(|| DEFAULT_COMPRESSION.to_vec());
+
         Ok(Config {
             server_url,
             cert,
@@ -275,6 +341,9 @@ impl TryFrom<Builder> for Config {
             max_trees,
             max_history,
             timeout,
+            max_retries,
+            retry_delay,
+            compression,
             debug,
             correlator,
             http_version,
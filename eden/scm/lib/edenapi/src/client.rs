@@ -7,11 +7,15 @@
 
 use std::collections::HashMap;
 use std::iter::FromIterator;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 use anyhow::format_err;
 use async_trait::async_trait;
+use bytes::Bytes;
 use futures::prelude::*;
 use itertools::Itertools;
+use parking_lot::Mutex;
 use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_cbor::Deserializer;
@@ -20,21 +24,30 @@ use url::Url;
 use auth::check_certs;
 use edenapi_types::{
     wire::{
-        WireCloneData, WireFileEntry, WireHistoryResponseChunk, WireIdMapEntry,
+        WireBookmarkEntry, WireCloneData, WireFileEntry, WireHistoryResponseChunk, WireIdMapEntry,
         WireToApiConversionError, WireTreeEntry,
     },
-    CloneData, CommitRevlogData, CommitRevlogDataRequest, CompleteTreeRequest, EdenApiServerError,
-    FileEntry, FileRequest, HistoryEntry, HistoryRequest, ToApi, ToWire, TreeAttributes, TreeEntry,
-    TreeRequest,
+    AnyId, BookmarkEntry, BookmarkRequest, CloneData, CommitGraphEntry, CommitGraphRequest,
+    CommitIsAncestor, CommitIsAncestorRequest, CommitRevlogData, CommitRevlogDataRequest,
+    CompleteTreeRequest, EdenApiServerError, FileContentEntry, FileContentId, FileContentRequest,
+    FileEntry, FileRequest, HistoryEntry, HistoryRequest, LandStackRequest, LandStackResponse,
+    LookupRequest, LookupResponse, ToApi, ToWire, TreeAttributes, TreeEntry, TreeRequest,
+    UploadFileContentsRequest, UploadHgChangesetEntry, UploadHgChangesetsRequest,
+    UploadHgFilenodeEntry, UploadHgFilenodesRequest, UploadResponse, UploadTreeEntry,
+    UploadTreesRequest,
 };
 use hg_http::http_client;
-use http_client::{AsyncResponse, HttpClient, HttpClientError, Progress, Request};
+use hg_metrics::increment_counter;
+use http_client::{
+    AsyncResponse, Encoding, HttpClient, HttpClientError, Progress, Request, Stats, StatsFuture,
+};
 use types::{HgId, Key, RepoPathBuf};
 
 use crate::api::{EdenApi, ProgressCallback};
 use crate::builder::Config;
 use crate::errors::EdenApiError;
 use crate::response::{Fetch, ResponseMeta};
+use crate::retry::{retry_after, RetryPolicy};
 
 /// All non-alphanumeric characters (except hypens, underscores, and periods)
 /// found in the repo's name will be percent-encoded before being used in URLs.
@@ -43,12 +56,22 @@ const RESERVED_CHARS: &AsciiSet = &NON_ALPHANUMERIC.remove(b'_').remove(b'-').re
 mod paths {
     pub const HEALTH_CHECK: &str = "health_check";
     pub const FILES: &str = "files";
+    pub const FILES_BY_CONTENT: &str = "files/content";
     pub const HISTORY: &str = "history";
     pub const TREES: &str = "trees";
     pub const COMPLETE_TREES: &str = "trees/complete";
     pub const COMMIT_REVLOG_DATA: &str = "commit/revlog_data";
+    pub const COMMIT_GRAPH: &str = "commit/graph";
+    pub const COMMIT_IS_ANCESTOR: &str = "commit/is_ancestor";
+    pub const BOOKMARKS: &str = "bookmarks";
     pub const CLONE_DATA: &str = "clone";
     pub const FULL_IDMAP_CLONE_DATA: &str = "full_idmap_clone";
+    pub const LOOKUP: &str = "lookup";
+    pub const UPLOAD_FILE_CONTENTS: &str = "upload/file_contents";
+    pub const UPLOAD_FILENODES: &str = "upload/filenodes";
+    pub const UPLOAD_TREES: &str = "upload/trees";
+    pub const UPLOAD_CHANGESETS: &str = "upload/changesets";
+    pub const LAND_STACK: &str = "land_stack";
 }
 
 pub struct Client {
@@ -108,6 +131,8 @@ impl Client {
             req = req.http_version(http_version);
         }
 
+        req = req.accept_encoding(&self.config.compression);
+
         Ok(req)
     }
 
@@ -115,7 +140,7 @@ impl Client {
     /// The keys will be grouped into batches of the specified size and
     /// passed to the `make_req` callback, which should insert them into
     /// a struct that will be CBOR-encoded and used as the request body.
-    fn prepare<K, F, R>(
+    fn prepare<T, K, F, R>(
         &self,
         url: &Url,
         keys: K,
@@ -123,8 +148,8 @@ impl Client {
         mut make_req: F,
     ) -> Result<Vec<Request>, EdenApiError>
     where
-        K: IntoIterator<Item = Key>,
-        F: FnMut(Vec<Key>) -> R,
+        K: IntoIterator<Item = T>,
+        F: FnMut(Vec<T>) -> R,
         R: Serialize,
     {
         split_into_batches(keys, batch_size)
@@ -146,33 +171,47 @@ impl Client {
     /// the order the responses arrive. The response streams will be
     /// combined into a single stream, in which the returned entries
     /// from different HTTP responses may be arbitrarily interleaved.
+    ///
+    /// Requests that fail are retried individually according to the
+    /// client's retry configuration, so the requests must be idempotent.
     async fn fetch_raw<T: DeserializeOwned + Send + 'static>(
         &self,
         requests: Vec<Request>,
         progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<T>, EdenApiError> {
+        let retry = RetryPolicy::new(self.config.max_retries, self.config.retry_delay);
+        self.fetch_raw_with_retry(requests, progress, retry).await
+    }
+
+    /// Same as `fetch_raw`, but with an explicit retry policy.
+    async fn fetch_raw_with_retry<T: DeserializeOwned + Send + 'static>(
+        &self,
+        requests: Vec<Request>,
+        progress: Option<ProgressCallback>,
+        retry: RetryPolicy,
     ) -> Result<Fetch<T>, EdenApiError> {
         let progress = progress.unwrap_or_else(|| Box::new(|_| ()));
-        let requests = requests.into_iter().collect::<Vec<_>>();
         let n_requests = requests.len();
-
-        let (mut responses, stats) = self.client.send_async_with_progress(requests, progress)?;
+        let (responses, stats) = self.send_with_retry(requests, progress, retry).await?;
 
         let mut meta = Vec::with_capacity(n_requests);
         let mut streams = Vec::with_capacity(n_requests);
 
-        while let Some(res) = responses.try_next().await? {
-            let res = raise_for_status(res).await?;
-
-            let response_meta = ResponseMeta::from(&res);
+        for (res, retries) in responses {
+            let mut response_meta = ResponseMeta::from(&res);
+            response_meta.retries = retries;
             tracing::debug!("{:?}", &response_meta);
             meta.push(response_meta);
 
-            let entries = res.into_cbor_stream::<T>().err_into().boxed();
+            let entries = decompress(res)?.into_cbor_stream::<T>().err_into().boxed();
             streams.push(entries);
         }
 
         let entries = stream::select_all(streams).boxed();
-        let stats = stats.err_into().boxed();
+        let stats = future::try_join_all(stats)
+            .map_ok(combine_stats)
+            .err_into()
+            .boxed();
 
         Ok(Fetch {
             meta,
@@ -181,6 +220,92 @@ impl Client {
         })
     }
 
+    /// Send HTTP requests concurrently as a single batch. Requests that
+    /// fail before the server starts sending a successful response are
+    /// retried together in a new batch; requests that succeeded are not
+    /// sent again. Returns the responses, in the order of the requests,
+    /// along with the number of retries each needed, and futures for the
+    /// transfer stats of each batch.
+    async fn send_with_retry(
+        &self,
+        requests: Vec<Request>,
+        progress: ProgressCallback,
+        retry: RetryPolicy,
+    ) -> Result<(Vec<(AsyncResponse, usize)>, Vec<StatsFuture>), EdenApiError> {
+        let n_requests = requests.len();
+        let progress = CombinedProgress::new(progress, retry.max_retries + 1);
+        let mut responses: Vec<Option<(AsyncResponse, usize)>> =
+            (0..n_requests).map(|_| None).collect();
+        let mut stats = Vec::new();
+
+        // Indexes of the requests to send in the next batch.
+        let mut pending: Vec<usize> = (0..n_requests).collect();
+        let mut attempt = 0;
+        loop {
+            let batch = pending.iter().map(|&i| requests[i].clone());
+            let (batch_responses, batch_stats) = self
+                .client
+                .send_async_ordered_with_progress(batch, progress.updater(attempt))?;
+            stats.push(batch_stats);
+
+            let mut failed = Vec::new();
+            let mut last_error = None;
+            let mut max_retry_after = None;
+            for (i, res) in pending
+                .into_iter()
+                .zip(future::join_all(batch_responses).await)
+            {
+                let (error, retry_after) = match res {
+                    Ok(res) if retry.should_retry(res.status) => {
+                        let retry_after = retry_after(&res.headers);
+                        (http_error(res).await, retry_after)
+                    }
+                    Ok(res) => {
+                        responses[i] = Some((raise_for_status(res).await?, attempt));
+                        continue;
+                    }
+                    Err(e) => (e.into(), None),
+                };
+                if attempt >= retry.max_retries {
+                    return Err(error);
+                }
+                failed.push(i);
+                last_error = Some(error);
+                max_retry_after = max_retry_after.max(retry_after);
+            }
+
+            let error = match last_error {
+                Some(error) => error,
+                None => break,
+            };
+
+            let delay = retry.delay(attempt, max_retry_after);
+            let msg = format!(
+                "Retrying {} of {} request(s) in {:?} (attempt {} of {}): {}",
+                failed.len(),
+                n_requests,
+                delay,
+                attempt + 1,
+                retry.max_retries,
+                &error
+            );
+            tracing::warn!("{}", &msg);
+            if self.config.debug {
+                eprintln!("{}", &msg);
+            }
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+            pending = failed;
+        }
+
+        let responses = responses
+            .into_iter()
+            .map(|res| res.expect("all requests succeeded"))
+            .collect();
+        Ok((responses, stats))
+    }
+
     /// Fetch data from the server.
     ///
     /// Concurrently performs all of the given HTTP requests, each of
@@ -257,6 +382,30 @@ impl EdenApi for Client {
         Ok(self.fetch::<WireFileEntry>(requests, progress).await?)
     }
 
+    async fn files_by_content(
+        &self,
+        repo: String,
+        ids: Vec<FileContentId>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<FileContentEntry>, EdenApiError> {
+        let msg = format!("Requesting content for {} file(s) by hash", ids.len());
+        tracing::info!("{}", &msg);
+        if self.config.debug {
+            eprintln!("{}", &msg);
+        }
+
+        if ids.is_empty() {
+            return Ok(Fetch::empty());
+        }
+
+        let url = self.url(paths::FILES_BY_CONTENT, Some(&repo))?;
+        let requests = self.prepare(&url, ids, self.config.max_files, |ids| {
+            FileContentRequest { ids }
+        })?;
+
+        self.fetch_raw::<FileContentEntry>(requests, progress).await
+    }
+
     async fn history(
         &self,
         repo: String,
@@ -389,6 +538,95 @@ impl EdenApi for Client {
             .await
     }
 
+    async fn commit_graph(
+        &self,
+        repo: String,
+        hgids: Vec<HgId>,
+        depth: u64,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<CommitGraphEntry>, EdenApiError> {
+        let msg = format!(
+            "Requesting commit graph for {} commit(s) with depth {}",
+            hgids.len(),
+            depth
+        );
+        tracing::info!("{}", &msg);
+        if self.config.debug {
+            eprintln!("{}", &msg);
+        }
+
+        let url = self.url(paths::COMMIT_GRAPH, Some(&repo))?;
+        let commit_graph_req = CommitGraphRequest { hgids, depth };
+
+        let req = self
+            .configure(Request::post(url))?
+            .cbor(&commit_graph_req)
+            .map_err(EdenApiError::RequestSerializationFailed)?;
+
+        self.fetch_raw::<CommitGraphEntry>(vec![req], progress)
+            .await
+    }
+
+    async fn commit_is_ancestor(
+        &self,
+        repo: String,
+        pairs: Vec<(HgId, HgId)>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<CommitIsAncestor>, EdenApiError> {
+        let msg = format!("Requesting ancestry of {} commit pair(s)", pairs.len());
+        tracing::info!("{}", &msg);
+        if self.config.debug {
+            eprintln!("{}", &msg);
+        }
+
+        let url = self.url(paths::COMMIT_IS_ANCESTOR, Some(&repo))?;
+        let is_ancestor_req = CommitIsAncestorRequest { pairs };
+
+        let req = self
+            .configure(Request::post(url))?
+            .cbor(&is_ancestor_req)
+            .map_err(EdenApiError::RequestSerializationFailed)?;
+
+        self.fetch_raw::<CommitIsAncestor>(vec![req], progress)
+            .await
+    }
+
+    async fn bookmarks(
+        &self,
+        repo: String,
+        bookmarks: Vec<String>,
+        prefixes: Vec<String>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<BookmarkEntry>, EdenApiError> {
+        let msg = format!(
+            "Requesting {} bookmark(s) and {} bookmark prefix(es)",
+            bookmarks.len(),
+            prefixes.len()
+        );
+        tracing::info!("{}", &msg);
+        if self.config.debug {
+            eprintln!("{}", &msg);
+        }
+
+        if bookmarks.is_empty() && prefixes.is_empty() {
+            return Ok(Fetch::empty());
+        }
+
+        let url = self.url(paths::BOOKMARKS, Some(&repo))?;
+        let bookmark_req = BookmarkRequest {
+            bookmarks,
+            prefixes,
+        }
+        .to_wire();
+
+        let req = self
+            .configure(Request::post(url))?
+            .cbor(&bookmark_req)
+            .map_err(EdenApiError::RequestSerializationFailed)?;
+
+        Ok(self.fetch::<WireBookmarkEntry>(vec![req], progress).await?)
+    }
+
     async fn clone_data(
         &self,
         repo: String,
@@ -425,7 +663,7 @@ impl EdenApi for Client {
 
         let url = self.url(paths::FULL_IDMAP_CLONE_DATA, Some(&repo))?;
         let req = self.configure(Request::post(url))?;
-        let async_response = req.send_async().await?;
+        let async_response = decompress(req.send_async().await?)?;
         let response_bytes = async_response
             .body
             .try_fold(Vec::new(), |mut acc, v| {
@@ -455,13 +693,188 @@ impl EdenApi for Client {
         clone_data.idmap = idmap;
         Ok(clone_data)
     }
+
+    async fn lookup(
+        &self,
+        repo: String,
+        ids: Vec<AnyId>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<LookupResponse>, EdenApiError> {
+        let msg = format!("Looking up {} object(s)", ids.len());
+        tracing::info!("{}", &msg);
+        if self.config.debug {
+            eprintln!("{}", &msg);
+        }
+
+        if ids.is_empty() {
+            return Ok(Fetch::empty());
+        }
+
+        let url = self.url(paths::LOOKUP, Some(&repo))?;
+        let lookup_req = LookupRequest { ids };
+
+        let req = self
+            .configure(Request::post(url))?
+            .cbor(&lookup_req)
+            .map_err(EdenApiError::RequestSerializationFailed)?;
+
+        self.fetch_raw::<LookupResponse>(vec![req], progress).await
+    }
+
+    async fn upload_file_contents(
+        &self,
+        repo: String,
+        contents: Vec<Bytes>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<UploadResponse>, EdenApiError> {
+        let msg = format!("Uploading content for {} file(s)", contents.len());
+        tracing::info!("{}", &msg);
+        if self.config.debug {
+            eprintln!("{}", &msg);
+        }
+
+        if contents.is_empty() {
+            return Ok(Fetch::empty());
+        }
+
+        let url = self.url(paths::UPLOAD_FILE_CONTENTS, Some(&repo))?;
+        let upload_req = UploadFileContentsRequest { contents };
+
+        let req = self
+            .configure(Request::post(url))?
+            .cbor(&upload_req)
+            .map_err(EdenApiError::RequestSerializationFailed)?;
+
+        self.fetch_raw::<UploadResponse>(vec![req], progress).await
+    }
+
+    async fn upload_filenodes(
+        &self,
+        repo: String,
+        filenodes: Vec<UploadHgFilenodeEntry>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<UploadResponse>, EdenApiError> {
+        let msg = format!("Uploading {} filenode(s)", filenodes.len());
+        tracing::info!("{}", &msg);
+        if self.config.debug {
+            eprintln!("{}", &msg);
+        }
+
+        if filenodes.is_empty() {
+            return Ok(Fetch::empty());
+        }
+
+        let url = self.url(paths::UPLOAD_FILENODES, Some(&repo))?;
+        let upload_req = UploadHgFilenodesRequest { filenodes };
+
+        let req = self
+            .configure(Request::post(url))?
+            .cbor(&upload_req)
+            .map_err(EdenApiError::RequestSerializationFailed)?;
+
+        self.fetch_raw::<UploadResponse>(vec![req], progress).await
+    }
+
+    async fn upload_trees(
+        &self,
+        repo: String,
+        trees: Vec<UploadTreeEntry>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<UploadResponse>, EdenApiError> {
+        let msg = format!("Uploading {} tree(s)", trees.len());
+        tracing::info!("{}", &msg);
+        if self.config.debug {
+            eprintln!("{}", &msg);
+        }
+
+        if trees.is_empty() {
+            return Ok(Fetch::empty());
+        }
+
+        let url = self.url(paths::UPLOAD_TREES, Some(&repo))?;
+        let upload_req = UploadTreesRequest { trees };
+
+        let req = self
+            .configure(Request::post(url))?
+            .cbor(&upload_req)
+            .map_err(EdenApiError::RequestSerializationFailed)?;
+
+        self.fetch_raw::<UploadResponse>(vec![req], progress).await
+    }
+
+    async fn upload_changesets(
+        &self,
+        repo: String,
+        changesets: Vec<UploadHgChangesetEntry>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<UploadResponse>, EdenApiError> {
+        let msg = format!("Uploading {} changeset(s)", changesets.len());
+        tracing::info!("{}", &msg);
+        if self.config.debug {
+            eprintln!("{}", &msg);
+        }
+
+        if changesets.is_empty() {
+            return Ok(Fetch::empty());
+        }
+
+        // Changesets are sent in a single request because the server
+        // needs each changeset's parents to be created before it.
+        let url = self.url(paths::UPLOAD_CHANGESETS, Some(&repo))?;
+        let upload_req = UploadHgChangesetsRequest { changesets };
+
+        let req = self
+            .configure(Request::post(url))?
+            .cbor(&upload_req)
+            .map_err(EdenApiError::RequestSerializationFailed)?;
+
+        self.fetch_raw::<UploadResponse>(vec![req], progress).await
+    }
+
+    async fn land_stack(
+        &self,
+        repo: String,
+        bookmark: String,
+        head: HgId,
+        base: HgId,
+    ) -> Result<LandStackResponse, EdenApiError> {
+        let msg = format!(
+            "Landing stack {}::{} onto bookmark '{}'",
+            base, head, &bookmark
+        );
+        tracing::info!("{}", &msg);
+        if self.config.debug {
+            eprintln!("{}", &msg);
+        }
+
+        let url = self.url(paths::LAND_STACK, Some(&repo))?;
+        let land_req = LandStackRequest {
+            bookmark,
+            head,
+            base,
+        };
+
+        let req = self
+            .configure(Request::post(url))?
+            .cbor(&land_req)
+            .map_err(EdenApiError::RequestSerializationFailed)?;
+
+        // Landing is not idempotent, so a failed request must not be retried.
+        let mut fetch = self
+            .fetch_raw_with_retry::<LandStackResponse>(vec![req], None, RetryPolicy::none())
+            .await?;
+        let response = fetch.entries.next().await.ok_or_else(|| {
+            EdenApiError::Other(format_err!("land stack result missing from response body"))
+        })??;
+        Ok(response)
+    }
 }
 
 /// Split up a collection of keys into batches of at most `batch_size`.
-fn split_into_batches(
-    keys: impl IntoIterator<Item = Key>,
+fn split_into_batches<T>(
+    keys: impl IntoIterator<Item = T>,
     batch_size: Option<usize>,
-) -> Vec<Vec<Key>> {
+) -> Vec<Vec<T>> {
     match batch_size {
         Some(n) => keys
             .into_iter()
@@ -477,20 +890,143 @@ async fn raise_for_status(res: AsyncResponse) -> Result<AsyncResponse, EdenApiEr
     if res.status.as_u16() < 400 {
         return Ok(res);
     }
+    Err(http_error(res).await)
+}
 
-    let body = res.body.try_concat().await?;
+/// Construct an error from an unsuccessful response, using the response
+/// body as the error message.
+async fn http_error(res: AsyncResponse) -> EdenApiError {
+    let body = match res.body.try_concat().await {
+        Ok(body) => body,
+        Err(e) => return e.into(),
+    };
     let message = String::from_utf8_lossy(&body).into_owned();
-    Err(EdenApiError::HttpError {
+    EdenApiError::HttpError {
         status: res.status,
         message,
+    }
+}
+
+/// Decompress the body of a response if the server compressed it. Once
+/// the body has been fully received, the number of bytes received over
+/// the network and the decompressed size are added to the client's
+/// metrics so that the savings from compression can be tracked.
+fn decompress(mut res: AsyncResponse) -> Result<AsyncResponse, EdenApiError> {
+    let encoding = Encoding::from_headers(&res.headers)?;
+    if encoding == Encoding::Identity {
+        return Ok(res);
+    }
+
+    let received = Arc::new(AtomicUsize::new(0));
+    let decompressed = Arc::new(AtomicUsize::new(0));
+
+    res.body = res
+        .body
+        .inspect_ok({
+            let received = received.clone();
+            move |chunk| {
+                received.fetch_add(chunk.len(), Ordering::Relaxed);
+            }
+        })
+        .boxed();
+
+    let mut res = res.decoded()?;
+
+    let report = {
+        let decompressed = decompressed.clone();
+        stream::once(async move {
+            let received = received.load(Ordering::Relaxed);
+            let decompressed = decompressed.load(Ordering::Relaxed);
+            tracing::debug!(
+                "Received {} bytes of {}-compressed data ({} bytes decompressed)",
+                received,
+                encoding,
+                decompressed
+            );
+            increment_counter("edenapi.compressed_rx_bytes", received);
+            increment_counter("edenapi.decompressed_rx_bytes", decompressed);
+            Ok(Vec::new())
+        })
+    };
+
+    res.body = res
+        .body
+        .inspect_ok(move |chunk| {
+            decompressed.fetch_add(chunk.len(), Ordering::Relaxed);
+        })
+        .chain(report)
+        .try_filter(|chunk| future::ready(!chunk.is_empty()))
+        .boxed();
+
+    Ok(res)
+}
+
+/// Combine the transfer stats of several concurrently-sent requests.
+fn combine_stats(stats: Vec<Stats>) -> Stats {
+    stats.into_iter().fold(Stats::default(), |acc, s| Stats {
+        downloaded: acc.downloaded + s.downloaded,
+        uploaded: acc.uploaded + s.uploaded,
+        requests: acc.requests + s.requests,
+        time: acc.time.max(s.time),
+        latency: if acc.requests == 0 {
+            s.latency
+        } else {
+            acc.latency.min(s.latency)
+        },
     })
 }
 
+/// Combines the progress of several batches of requests, such as the
+/// initial batch and its retries, and reports it to a single progress
+/// callback.
+#[derive(Clone)]
+struct CombinedProgress {
+    inner: Arc<Mutex<(ProgressCallback, Vec<Progress>)>>,
+}
+
+impl CombinedProgress {
+    fn new(callback: ProgressCallback, n_batches: usize) -> Self {
+        let progress = vec![Progress::default(); n_batches];
+        Self {
+            inner: Arc::new(Mutex::new((callback, progress))),
+        }
+    }
+
+    /// Progress callback for the batch at the given index.
+    fn updater(&self, index: usize) -> impl FnMut(Progress) + Clone + Send + 'static {
+        let inner = self.inner.clone();
+        move |progress| {
+            let (callback, all_progress) = &mut *inner.lock();
+            all_progress[index] = progress;
+            callback(all_progress.iter().copied().sum());
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use anyhow::Result;
+    use edenapi_mock_server::{Fault, MockServer};
+    use futures::prelude::*;
+    use http::StatusCode;
+    use types::{testutil::key, Parents};
 
+    use crate::api::EdenApi;
     use crate::builder::Builder;
+    use crate::client::Client;
+    use crate::errors::EdenApiError;
+
+    const REPO: &str = "repo";
+
+    fn client(server: &MockServer) -> Result<Client> {
+        Ok(Builder::new()
+            .server_url(server.url())
+            .max_retries(2)
+            .retry_delay(Duration::from_millis(1))
+            .build()?)
+    }
 
     #[test]
     fn test_url_escaping() -> Result<()> {
@@ -507,4 +1043,118 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_retry_transient_errors() -> Result<()> {
+        let server = MockServer::start()?;
+        server.add_repo(REPO);
+        server.inject_for(
+            "bookmarks",
+            Fault::Error {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                count: 3,
+            },
+        );
+
+        let res = client(&server)?
+            .bookmarks(REPO.into(), vec!["master".into()], vec![], None)
+            .await;
+
+        match res {
+            Err(EdenApiError::HttpError { status, message }) => {
+                assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+                assert_eq!(&message, "injected error");
+            }
+            _ => panic!("expected request to fail with HTTP 503"),
+        }
+        assert_eq!(server.requests(), vec!["bookmarks"; 3]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_failed_requests_only() -> Result<()> {
+        let server = MockServer::start()?;
+        let repo = server.add_repo(REPO);
+
+        let keys = vec![key("a", "1"), key("b", "2")];
+        for key in &keys {
+            repo.add_file(key.clone(), "content", Parents::None);
+        }
+
+        // Only the first request to arrive fails.
+        server.inject_for(
+            "files",
+            Fault::Error {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                count: 1,
+            },
+        );
+
+        let client = Builder::new()
+            .server_url(server.url())
+            .max_files(Some(1))
+            .max_retries(2)
+            .retry_delay(Duration::from_millis(1))
+            .build()?;
+
+        let fetch = client.files(REPO.into(), keys, None).await?;
+        let mut retries = fetch
+            .meta
+            .iter()
+            .map(|meta| meta.retries)
+            .collect::<Vec<_>>();
+        retries.sort();
+        assert_eq!(retries, vec![0, 1]);
+        assert_eq!(fetch.entries.try_collect::<Vec<_>>().await?.len(), 2);
+
+        // The request that succeeded was not sent again.
+        assert_eq!(server.requests(), vec!["files"; 3]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_client_errors() -> Result<()> {
+        let server = MockServer::start()?;
+        server.add_repo(REPO);
+        server.inject_for(
+            "bookmarks",
+            Fault::Error {
+                status: StatusCode::BAD_REQUEST,
+                count: 1,
+            },
+        );
+
+        let res = client(&server)?
+            .bookmarks(REPO.into(), vec!["master".into()], vec![], None)
+            .await;
+
+        assert!(res.is_err());
+        assert_eq!(server.requests(), vec!["bookmarks"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compressed_response() -> Result<()> {
+        let server = MockServer::start()?;
+        let repo = server.add_repo(REPO);
+
+        let keys = vec![key("a", "1"), key("b", "2")];
+        for key in &keys {
+            repo.add_file(key.clone(), "content", Parents::None);
+        }
+
+        let fetch = client(&server)?.files(REPO.into(), keys, None).await?;
+        assert_eq!(fetch.meta[0].content_encoding.as_deref(), Some("zstd"));
+
+        let entries = fetch.entries.try_collect::<Vec<_>>().await?;
+        assert_eq!(entries.len(), 2);
+        for entry in entries {
+            assert_eq!(&entry.data_unchecked()[..], b"content");
+        }
+
+        Ok(())
+    }
 }
@@ -22,6 +22,8 @@ pub enum EdenApiError {
     BadCertificate(#[from] X509Error),
     #[error(transparent)]
     Http(#[from] HttpClientError),
+    #[error("Request was cancelled")]
+    Cancelled,
     #[error("Server reported an error ({status}): {message}")]
     HttpError { status: StatusCode, message: String },
     #[error(transparent)]
@@ -12,13 +12,16 @@ mod blocking;
 mod builder;
 mod client;
 mod errors;
+mod monitor;
 mod response;
+mod retry;
 
 pub use crate::api::{EdenApi, ProgressCallback};
 pub use crate::blocking::EdenApiBlocking;
 pub use crate::builder::Builder;
 pub use crate::client::Client;
 pub use crate::errors::{ConfigError, EdenApiError};
+pub use crate::monitor::{CancelHandle, FetchMonitor, FetchProgress, FetchProgressCallback};
 pub use crate::response::{BlockingFetch, Entries, Fetch, ResponseMeta};
 
 // Re-export for convenience.
@@ -0,0 +1,366 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+use futures::prelude::*;
+use parking_lot::Mutex;
+
+use http_client::Progress;
+use progress::ProgressBar;
+
+use crate::api::ProgressCallback;
+use crate::errors::EdenApiError;
+use crate::response::{Entries, Fetch};
+
+pub type FetchProgressCallback = Box<dyn FnMut(FetchProgress) + Send + 'static>;
+
+/// Combined transfer and decoding progress of one or more fetches.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+pub struct FetchProgress {
+    /// Progress of the underlying HTTP transfers.
+    pub transfer: Progress,
+    /// Number of entries decoded from the responses so far.
+    pub entries: usize,
+}
+
+/// Monitors streaming fetches, reporting progress as response data
+/// arrives and as entries are decoded, and allowing the fetches to be
+/// cancelled from another task or thread.
+///
+/// A monitor is attached to a fetch in two steps: the callback returned
+/// by `transfer_callback` is passed as the `progress` argument of an
+/// `EdenApi` method, and the resulting `Fetch` is then passed through
+/// `monitor`. A single monitor may be used for several fetches, in which
+/// case their progress is combined.
+///
+/// ```rust,ignore
+/// let monitor = FetchMonitor::new(|p| println!("{} entries", p.entries));
+/// let fetch = client.files(repo, keys, Some(monitor.transfer_callback())).await?;
+/// let fetch = monitor.monitor(fetch);
+/// ```
+#[derive(Clone)]
+pub struct FetchMonitor {
+    state: Arc<Mutex<MonitorState>>,
+    cancel: CancelHandle,
+}
+
+struct MonitorState {
+    transfers: Vec<Progress>,
+    entries: usize,
+    callback: FetchProgressCallback,
+}
+
+impl MonitorState {
+    fn progress(&self) -> FetchProgress {
+        FetchProgress {
+            transfer: self.transfers.iter().copied().sum(),
+            entries: self.entries,
+        }
+    }
+
+    fn report(&mut self) {
+        let progress = self.progress();
+        (self.callback)(progress);
+    }
+}
+
+impl FetchMonitor {
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: FnMut(FetchProgress) + Send + 'static,
+    {
+        Self {
+            state: Arc::new(Mutex::new(MonitorState {
+                transfers: Vec::new(),
+                entries: 0,
+                callback: Box::new(callback),
+            })),
+            cancel: CancelHandle::new(),
+        }
+    }
+
+    /// Create a monitor that displays the number of decoded entries and,
+    /// optionally, the number of downloaded bytes using progress bars.
+    pub fn with_progress_bars(
+        entries_bar: Box<dyn ProgressBar>,
+        bytes_bar: Option<Box<dyn ProgressBar>>,
+    ) -> Self {
+        Self::new(move |progress| {
+            // Progress bars are purely informational, so failing to
+            // update them should not interfere with the fetch.
+            let _ = entries_bar.set(progress.entries as u64);
+            if let Some(bytes_bar) = &bytes_bar {
+                let _ = bytes_bar.set(progress.transfer.downloaded as u64);
+                if progress.transfer.total_downloaded > 0 {
+                    let _ = bytes_bar.set_total(Some(progress.transfer.total_downloaded as u64));
+                }
+            }
+        })
+    }
+
+    /// The current progress of all monitored fetches.
+    pub fn progress(&self) -> FetchProgress {
+        self.state.lock().progress()
+    }
+
+    /// A progress callback that can be passed to any `EdenApi` method to
+    /// report the transfer progress of the resulting fetch to this monitor.
+    pub fn transfer_callback(&self) -> ProgressCallback {
+        let state = self.state.clone();
+        let index = {
+            let mut state = state.lock();
+            state.transfers.push(Progress::default());
+            state.transfers.len() - 1
+        };
+        Box::new(move |progress| {
+            let mut state = state.lock();
+            state.transfers[index] = progress;
+            state.report();
+        })
+    }
+
+    /// Wrap the entry stream of the given fetch so that decoded entries are
+    /// reported to this monitor, and so that the fetch can be cancelled
+    /// with this monitor's `CancelHandle`.
+    pub fn monitor<T: Send + 'static>(&self, fetch: Fetch<T>) -> Fetch<T> {
+        let Fetch {
+            meta,
+            entries,
+            stats,
+        } = fetch;
+
+        let state = self.state.clone();
+        let entries = self
+            .cancel
+            .cancellable(entries)
+            .inspect_ok(move |_| {
+                let mut state = state.lock();
+                state.entries += 1;
+                state.report();
+            })
+            .boxed();
+
+        Fetch {
+            meta,
+            entries,
+            stats,
+        }
+    }
+
+    /// A handle that can be used to cancel all fetches monitored by this
+    /// monitor, including those that are monitored after cancellation.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+}
+
+/// Handle for cancelling in-progress fetches.
+///
+/// Cancelling a fetch drops its response streams, which aborts the
+/// underlying HTTP transfers. Consumers of the fetch's entries will
+/// receive an `EdenApiError::Cancelled` error, after which the entry
+/// stream will end.
+#[derive(Clone)]
+pub struct CancelHandle {
+    inner: Arc<Mutex<CancelState>>,
+}
+
+#[derive(Default)]
+struct CancelState {
+    cancelled: bool,
+    cancellers: Vec<Box<dyn FnOnce() + Send + 'static>>,
+}
+
+impl CancelHandle {
+    fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(CancelState::default())),
+        }
+    }
+
+    pub fn cancel(&self) {
+        let cancellers = {
+            let mut state = self.inner.lock();
+            state.cancelled = true;
+            std::mem::take(&mut state.cancellers)
+        };
+        for cancel in cancellers {
+            cancel();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.lock().cancelled
+    }
+
+    fn cancellable<T: Send + 'static>(&self, entries: Entries<T>) -> Cancellable<T> {
+        let shared = Arc::new(Mutex::new(CancellableState {
+            entries: Some(entries),
+            waker: None,
+        }));
+
+        let mut state = self.inner.lock();
+        if state.cancelled {
+            shared.lock().entries = None;
+        } else {
+            let shared = shared.clone();
+            state.cancellers.push(Box::new(move || {
+                let (entries, waker) = {
+                    let mut shared = shared.lock();
+                    (shared.entries.take(), shared.waker.take())
+                };
+                // Drop the entries outside of the lock, since doing so
+                // may run arbitrary destructors.
+                drop(entries);
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+            }));
+        }
+
+        Cancellable {
+            shared,
+            done: false,
+        }
+    }
+}
+
+struct CancellableState<T> {
+    entries: Option<Entries<T>>,
+    waker: Option<Waker>,
+}
+
+/// Entry stream that can be cancelled via a `CancelHandle`.
+struct Cancellable<T> {
+    shared: Arc<Mutex<CancellableState<T>>>,
+    done: bool,
+}
+
+impl<T> Stream for Cancellable<T> {
+    type Item = Result<T, EdenApiError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+
+        let poll = {
+            let mut shared = self.shared.lock();
+            match shared.entries.as_mut() {
+                Some(entries) => {
+                    let poll = entries.poll_next_unpin(cx);
+                    if poll.is_pending() {
+                        shared.waker = Some(cx.waker().clone());
+                    }
+                    Some(poll)
+                }
+                None => None,
+            }
+        };
+
+        match poll {
+            Some(poll) => poll,
+            None => {
+                self.done = true;
+                Poll::Ready(Some(Err(EdenApiError::Cancelled)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use http_client::Stats;
+
+    fn fetch_from<S>(entries: S) -> Fetch<u32>
+    where
+        S: Stream<Item = Result<u32, EdenApiError>> + Send + 'static,
+    {
+        Fetch {
+            meta: Vec::new(),
+            entries: entries.boxed(),
+            stats: future::ok(Stats::default()).boxed(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_progress() -> Result<(), EdenApiError> {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let monitor = FetchMonitor::new({
+            let reported = reported.clone();
+            move |p| reported.lock().push(p)
+        });
+
+        let mut transfer = monitor.transfer_callback();
+        transfer(Progress::new(10, 100, 0, 0));
+
+        let entries = stream::iter(vec![Ok(1), Ok(2), Ok(3)]);
+        let fetch = monitor.monitor(fetch_from(entries));
+        let entries = fetch.entries.try_collect::<Vec<_>>().await?;
+        assert_eq!(entries, vec![1, 2, 3]);
+
+        // Transfer progress from several fetches is combined.
+        let mut transfer2 = monitor.transfer_callback();
+        transfer2(Progress::new(5, 50, 0, 0));
+
+        let expected = FetchProgress {
+            transfer: Progress::new(15, 150, 0, 0),
+            entries: 3,
+        };
+        assert_eq!(monitor.progress(), expected);
+        assert_eq!(reported.lock().len(), 5);
+        assert_eq!(reported.lock().last(), Some(&expected));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancel() {
+        let monitor = FetchMonitor::new(|_| ());
+        let handle = monitor.cancel_handle();
+
+        // Use a reference count to check when the stream is dropped.
+        let token = Arc::new(());
+        let entries = stream::iter(vec![Ok(1)]).chain(stream::pending()).map({
+            let token = token.clone();
+            move |v| {
+                let _ = &token;
+                v
+            }
+        });
+        let mut fetch = monitor.monitor(fetch_from(entries));
+
+        assert_eq!(fetch.entries.next().await.unwrap().unwrap(), 1);
+        assert_eq!(Arc::strong_count(&token), 2);
+
+        let next = tokio::spawn(async move {
+            let res = fetch.entries.next().await;
+            (res, fetch.entries.next().await)
+        });
+        handle.cancel();
+
+        // Cancellation drops the underlying stream right away.
+        assert_eq!(Arc::strong_count(&token), 1);
+        assert!(handle.is_cancelled());
+
+        let (res, end) = next.await.unwrap();
+        assert!(matches!(res, Some(Err(EdenApiError::Cancelled))));
+        assert!(end.is_none());
+
+        // Fetches monitored after cancellation are cancelled immediately.
+        let mut fetch = monitor.monitor(fetch_from(stream::iter(vec![Ok(1)])));
+        assert!(matches!(
+            fetch.entries.next().await,
+            Some(Err(EdenApiError::Cancelled))
+        ));
+    }
+}
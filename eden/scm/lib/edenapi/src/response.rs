@@ -21,6 +21,7 @@ const TW_TASK_HEADER: &str = "x-tw-task";
 const TW_VERSION_HEADER: &str = "x-tw-task-version";
 const TW_CANARY_HEADER: &str = "x-tw-canary-id";
 const SERVER_LOAD_HEADER: &str = "x-load";
+const CONTENT_ENCODING_HEADER: &str = "content-encoding";
 
 /// A generic `Stream` of "entries" representing the deserialized content
 /// of a streaming response from the server.
@@ -95,6 +96,11 @@ pub struct ResponseMeta {
     pub tw_task_version: Option<String>,
     pub tw_canary_id: Option<String>,
     pub server_load: Option<usize>,
+    /// Encoding used by the server to compress the response body.
+    pub content_encoding: Option<String>,
+    /// Number of times the request was retried before this response
+    /// was received.
+    pub retries: usize,
 }
 
 impl ResponseMeta {
@@ -108,6 +114,8 @@ impl ResponseMeta {
             tw_task_version: get_header(headers, TW_VERSION_HEADER),
             tw_canary_id: get_header(headers, TW_CANARY_HEADER),
             server_load: get_header(headers, SERVER_LOAD_HEADER).and_then(|l| l.parse().ok()),
+            content_encoding: get_header(headers, CONTENT_ENCODING_HEADER),
+            retries: 0,
         }
     }
 }
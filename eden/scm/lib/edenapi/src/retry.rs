@@ -0,0 +1,142 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::time::Duration;
+
+use http::{header::RETRY_AFTER, HeaderMap, StatusCode};
+use rand::Rng;
+
+pub(crate) const DEFAULT_MAX_RETRIES: usize = 3;
+pub(crate) const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Upper bound on the time to wait between attempts, including delays
+/// requested by the server via the `Retry-After` header.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Determines whether and when a failed request should be retried.
+///
+/// Only requests that failed before the server started sending a response
+/// body are retried, since a partially consumed response stream cannot be
+/// restarted transparently.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct RetryPolicy {
+    pub(crate) max_retries: usize,
+    pub(crate) base_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub(crate) fn new(max_retries: usize, base_delay: Duration) -> Self {
+        Self {
+            max_retries,
+            base_delay,
+        }
+    }
+
+    /// Policy for requests that are not idempotent and must never be
+    /// sent more than once.
+    pub(crate) fn none() -> Self {
+        Self::new(0, Duration::default())
+    }
+
+    /// Whether a response with the given status indicates a transient
+    /// failure that is likely to succeed if retried.
+    pub(crate) fn should_retry(&self, status: StatusCode) -> bool {
+        matches!(
+            status,
+            StatusCode::TOO_MANY_REQUESTS
+                | StatusCode::BAD_GATEWAY
+                | StatusCode::SERVICE_UNAVAILABLE
+                | StatusCode::GATEWAY_TIMEOUT
+        )
+    }
+
+    /// How long to wait before making the given retry (numbered from 0).
+    ///
+    /// A delay requested by the server takes precedence. Otherwise, the
+    /// delay grows exponentially with each attempt, with half of it chosen
+    /// at random so that clients failing at the same time don't all retry
+    /// in lockstep.
+    pub(crate) fn delay(&self, retry: usize, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(MAX_RETRY_DELAY);
+        }
+
+        let backoff = self
+            .base_delay
+            .checked_mul(1 << retry.min(16))
+            .unwrap_or(MAX_RETRY_DELAY)
+            .min(MAX_RETRY_DELAY);
+        let half = backoff / 2;
+        let jitter = rand::thread_rng().gen_range(0, half.as_millis() as u64 + 1);
+
+        half + Duration::from_millis(jitter)
+    }
+}
+
+/// Parse the `Retry-After` header of a response. Only the delay-seconds
+/// form is supported; HTTP dates are ignored.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?;
+    value.trim().parse().ok().map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use http::HeaderValue;
+
+    #[test]
+    fn test_delay_backoff() {
+        let policy = RetryPolicy::new(5, Duration::from_millis(100));
+
+        for retry in 0..5 {
+            let max = Duration::from_millis(100 << retry);
+            let delay = policy.delay(retry, None);
+            assert!(delay >= max / 2, "{:?} < {:?}", delay, max / 2);
+            assert!(delay <= max, "{:?} > {:?}", delay, max);
+        }
+
+        assert!(policy.delay(100, None) <= MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_delay_retry_after() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(100));
+
+        let retry_after = Some(Duration::from_secs(2));
+        assert_eq!(policy.delay(0, retry_after), Duration::from_secs(2));
+
+        let retry_after = Some(Duration::from_secs(3600));
+        assert_eq!(policy.delay(0, retry_after), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_should_retry() {
+        let policy = RetryPolicy::new(3, DEFAULT_RETRY_DELAY);
+
+        assert!(policy.should_retry(StatusCode::TOO_MANY_REQUESTS));
+        assert!(policy.should_retry(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!policy.should_retry(StatusCode::BAD_REQUEST));
+        assert!(!policy.should_retry(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("5"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(5)));
+
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), None);
+    }
+}
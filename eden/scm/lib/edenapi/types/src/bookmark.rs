@@ -0,0 +1,58 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use serde_derive::{Deserialize, Serialize};
+
+use types::hgid::HgId;
+
+/// Resolve bookmarks to the Mercurial commits they point to. Bookmarks can
+/// be requested by name, or listed by prefix. Prefix listings only include
+/// publishing bookmarks; scratch bookmarks must be requested by name.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct BookmarkRequest {
+    pub bookmarks: Vec<String>,
+    pub prefixes: Vec<String>,
+}
+
+/// The commit a bookmark points to. One entry is returned for each bookmark
+/// requested by name, with `hgid` set to `None` if the bookmark doesn't exist.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Serialize, Deserialize)]
+pub struct BookmarkEntry {
+    pub bookmark: String,
+    pub hgid: Option<HgId>,
+}
+
+impl BookmarkEntry {
+    pub fn new(bookmark: String, hgid: Option<HgId>) -> Self {
+        Self { bookmark, hgid }
+    }
+}
+
+#[cfg(any(test, feature = "for-tests"))]
+use quickcheck::Arbitrary;
+
+#[cfg(any(test, feature = "for-tests"))]
+impl Arbitrary for BookmarkRequest {
+    fn arbitrary<G: quickcheck::Gen>(g: &mut G) -> Self {
+        Self {
+            bookmarks: Arbitrary::arbitrary(g),
+            prefixes: Arbitrary::arbitrary(g),
+        }
+    }
+}
+
+#[cfg(any(test, feature = "for-tests"))]
+impl Arbitrary for BookmarkEntry {
+    fn arbitrary<G: quickcheck::Gen>(g: &mut G) -> Self {
+        Self {
+            bookmark: Arbitrary::arbitrary(g),
+            hgid: Arbitrary::arbitrary(g),
+        }
+    }
+}
@@ -103,3 +103,57 @@ impl CommitRevlogData {
         Self { hgid, revlog_data }
     }
 }
+
+/// Request a slice of the commit graph: the given commits and their ancestors, following all
+/// parents, up to `depth` generations back. A depth of 0 returns only the requested commits,
+/// along with their parents.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Serialize, Deserialize)]
+pub struct CommitGraphRequest {
+    pub hgids: Vec<HgId>,
+    pub depth: u64,
+}
+
+/// A commit in a slice of the commit graph, along with its parents.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Serialize, Deserialize)]
+pub struct CommitGraphEntry {
+    #[serde(with = "types::serde_with::hgid::bytes")]
+    pub hgid: HgId,
+    pub parents: Vec<HgId>,
+}
+
+impl CommitGraphEntry {
+    pub fn new(hgid: HgId, parents: Vec<HgId>) -> Self {
+        Self { hgid, parents }
+    }
+}
+
+/// A set of `(ancestor, descendant)` pairs of Mercurial commit ids for which we want to know
+/// whether the first commit is an ancestor of the second.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Serialize, Deserialize)]
+pub struct CommitIsAncestorRequest {
+    pub pairs: Vec<(HgId, HgId)>,
+}
+
+/// The answer to an ancestry query. A commit is considered to be its own ancestor.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Serialize, Deserialize)]
+pub struct CommitIsAncestor {
+    #[serde(with = "types::serde_with::hgid::bytes")]
+    pub ancestor: HgId,
+    #[serde(with = "types::serde_with::hgid::bytes")]
+    pub descendant: HgId,
+    pub is_ancestor: bool,
+}
+
+impl CommitIsAncestor {
+    pub fn new(ancestor: HgId, descendant: HgId, is_ancestor: bool) -> Self {
+        Self {
+            ancestor,
+            descendant,
+            is_ancestor,
+        }
+    }
+}
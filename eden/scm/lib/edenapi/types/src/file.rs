@@ -15,6 +15,7 @@ use quickcheck::Arbitrary;
 use revisionstore_types::Metadata;
 use types::{hgid::HgId, key::Key, parents::Parents};
 
+use crate::metadata::{ContentId, Sha1, Sha256};
 use crate::InvalidHgId;
 
 /// Tombstone string that replaces the content of redacted files.
@@ -158,3 +159,63 @@ impl Arbitrary for FileRequest {
         }
     }
 }
+
+/// Content-addressed identifier of a file's content, as used by Mononoke's
+/// filestore. Unlike a filenode id, it does not depend on the file's path
+/// or history, so identical content only needs to be fetched once.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub enum FileContentId {
+    ContentId(ContentId),
+    Sha1(Sha1),
+    Sha256(Sha256),
+}
+
+/// Request file content by content hash. The returned data is the raw
+/// file content, without any Mercurial copy metadata header.
+#[derive(Clone, Default, Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct FileContentRequest {
+    pub ids: Vec<FileContentId>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct FileContentEntry {
+    pub id: FileContentId,
+    pub data: Bytes,
+}
+
+impl FileContentEntry {
+    pub fn new(id: FileContentId, data: Bytes) -> Self {
+        Self { id, data }
+    }
+}
+
+#[cfg(any(test, feature = "for-tests"))]
+impl Arbitrary for FileContentId {
+    fn arbitrary<G: quickcheck::Gen>(g: &mut G) -> Self {
+        match u8::arbitrary(g) % 3 {
+            0 => FileContentId::ContentId(Arbitrary::arbitrary(g)),
+            1 => FileContentId::Sha1(Arbitrary::arbitrary(g)),
+            _ => FileContentId::Sha256(Arbitrary::arbitrary(g)),
+        }
+    }
+}
+
+#[cfg(any(test, feature = "for-tests"))]
+impl Arbitrary for FileContentRequest {
+    fn arbitrary<G: quickcheck::Gen>(g: &mut G) -> Self {
+        Self {
+            ids: Arbitrary::arbitrary(g),
+        }
+    }
+}
+
+#[cfg(any(test, feature = "for-tests"))]
+impl Arbitrary for FileContentEntry {
+    fn arbitrary<G: quickcheck::Gen>(g: &mut G) -> Self {
+        let bytes: Vec<u8> = Arbitrary::arbitrary(g);
+        Self {
+            id: Arbitrary::arbitrary(g),
+            data: Bytes::from(bytes),
+        }
+    }
+}
@@ -27,6 +27,7 @@ use serde_json::{json, Map, Value};
 
 use types::{HgId, Key, RepoPathBuf};
 
+use crate::bookmark::BookmarkRequest;
 use crate::commit::{CommitLocation, CommitLocationToHashRequest, CommitRevlogDataRequest};
 use crate::complete_tree::CompleteTreeRequest;
 use crate::file::FileRequest;
@@ -34,6 +35,25 @@ use crate::history::HistoryRequest;
 use crate::metadata::{DirectoryMetadataRequest, FileMetadataRequest};
 use crate::tree::{TreeAttributes, TreeRequest};
 
+/// Parse a `BookmarkRequest` from JSON.
+///
+/// Example request:
+/// ```json
+/// {
+///   "bookmarks": ["master", "stable"],
+///   "prefixes": ["release/"]
+/// }
+/// ```
+pub fn parse_bookmark_req(json: &Value) -> Result<BookmarkRequest> {
+    let json = json.as_object().context("input must be a JSON object")?;
+    let bookmarks = optional_string_list_field(json, "bookmarks")?;
+    let prefixes = optional_string_list_field(json, "prefixes")?;
+    Ok(BookmarkRequest {
+        bookmarks,
+        prefixes,
+    })
+}
+
 /// Parse a `CommitRevlogDataRequest` from JSON.
 ///
 /// Example request:
@@ -368,6 +388,23 @@ fn optional_bool_field(json: &Map<String, Value>, field: &str) -> Result<bool> {
         .unwrap_or_default())
 }
 
+fn optional_string_list_field(json: &Map<String, Value>, field: &str) -> Result<Vec<String>> {
+    let array = match json.get(field) {
+        Some(value) => value
+            .as_array()
+            .context(format!("{} {}", field, "field must be an array"))?,
+        None => return Ok(Vec::new()),
+    };
+    array
+        .iter()
+        .map(|v| {
+            v.as_str()
+                .map(ToString::to_string)
+                .context(format!("{} {}", field, "items must be strings"))
+        })
+        .collect()
+}
+
 fn optional_default_field<T: Default + FromJson>(
     json: &Map<String, Value>,
     field: &str,
@@ -419,6 +456,12 @@ impl FromJson for CommitLocationToHashRequest {
     }
 }
 
+impl FromJson for BookmarkRequest {
+    fn from_json(json: &Value) -> Result<Self> {
+        parse_bookmark_req(json)
+    }
+}
+
 impl FromJson for CommitRevlogDataRequest {
     fn from_json(json: &Value) -> Result<Self> {
         parse_commit_revlog_data_req(json)
@@ -489,6 +532,15 @@ impl ToJson for CompleteTreeRequest {
     }
 }
 
+impl ToJson for BookmarkRequest {
+    fn to_json(&self) -> Value {
+        json!({
+            "bookmarks": self.bookmarks,
+            "prefixes": self.prefixes,
+        })
+    }
+}
+
 impl ToJson for FileMetadataRequest {
     fn to_json(&self) -> Value {
         json!({
@@ -564,4 +616,10 @@ mod tests {
         let json = req.to_json();
         req == CompleteTreeRequest::from_json(&json).unwrap()
     }
+
+    #[quickcheck]
+    fn test_bookmark_req_roundtrip(req: BookmarkRequest) -> bool {
+        let json = req.to_json();
+        req == BookmarkRequest::from_json(&json).unwrap()
+    }
 }
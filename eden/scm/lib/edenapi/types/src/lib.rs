@@ -25,6 +25,7 @@
 
 #![deny(warnings)]
 
+pub mod bookmark;
 pub mod commit;
 pub mod complete_tree;
 pub mod file;
@@ -32,14 +33,19 @@ pub mod history;
 pub mod json;
 pub mod metadata;
 pub mod tree;
+pub mod upload;
 pub mod wire;
 
+pub use crate::bookmark::{BookmarkEntry, BookmarkRequest};
 pub use crate::commit::{
+    CommitGraphEntry, CommitGraphRequest, CommitIsAncestor, CommitIsAncestorRequest,
     CommitLocation, CommitLocationToHash, CommitLocationToHashRequest, CommitRevlogData,
     CommitRevlogDataRequest,
 };
 pub use crate::complete_tree::CompleteTreeRequest;
-pub use crate::file::{FileEntry, FileError, FileRequest};
+pub use crate::file::{
+    FileContentEntry, FileContentId, FileContentRequest, FileEntry, FileError, FileRequest,
+};
 pub use crate::history::{
     HistoryEntry, HistoryRequest, HistoryResponse, HistoryResponseChunk, WireHistoryEntry,
 };
@@ -51,6 +57,12 @@ pub use crate::tree::{
     TreeAttributes, TreeChildDirectoryEntry, TreeChildEntry, TreeChildFileEntry, TreeEntry,
     TreeError, TreeRequest,
 };
+pub use crate::upload::{
+    AnyId, LandStackRequest, LandStackResponse, LookupRequest, LookupResponse,
+    UploadFileContentsRequest, UploadHgChangesetEntry, UploadHgChangesetsRequest,
+    UploadHgFilenodeEntry, UploadHgFilenodesRequest, UploadResponse, UploadTreeEntry,
+    UploadTreesRequest,
+};
 pub use crate::wire::{ToApi, ToWire, WireToApiConversionError};
 
 // re-export CloneData
@@ -0,0 +1,132 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Types for pushing commits over EdenAPI.
+//!
+//! A commit stack is pushed bottom-up: the client first asks the server
+//! which objects it already has (`LookupRequest`), then uploads the missing
+//! file contents, filenodes, trees and changesets, in that order. Finally
+//! the stack is landed onto a bookmark with a `LandStackRequest`, which
+//! goes through pushrebase on the server.
+
+use bytes::Bytes;
+use serde_derive::{Deserialize, Serialize};
+
+use types::{hgid::HgId, key::Key, parents::Parents};
+
+use crate::metadata::Sha256;
+
+/// Identifier of an object that can be uploaded to the server.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum AnyId {
+    FileContentSha256(Sha256),
+    HgFilenodeId(HgId),
+    HgTreeId(HgId),
+    HgChangesetId(HgId),
+}
+
+/// Ask the server which of the given objects it already has, so that
+/// only the missing ones need to be uploaded.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct LookupRequest {
+    pub ids: Vec<AnyId>,
+}
+
+/// Whether the server already has the object identified by `id`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct LookupResponse {
+    pub id: AnyId,
+    pub present: bool,
+}
+
+/// Raw file contents to store on the server. Contents are identified by
+/// their SHA-256 hash, which the response reports for each stored file.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct UploadFileContentsRequest {
+    pub contents: Vec<Bytes>,
+}
+
+/// A Mercurial filenode whose contents have already been uploaded.
+/// `key` holds the path and filenode id, and `copy_from` the source of
+/// a copy or rename, if any.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct UploadHgFilenodeEntry {
+    pub key: Key,
+    pub parents: Parents,
+    pub content_sha256: Sha256,
+    pub copy_from: Option<Key>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct UploadHgFilenodesRequest {
+    pub filenodes: Vec<UploadHgFilenodeEntry>,
+}
+
+/// A Mercurial tree manifest, as it is stored in the revlog.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct UploadTreeEntry {
+    pub key: Key,
+    pub parents: Parents,
+    pub data: Bytes,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct UploadTreesRequest {
+    pub trees: Vec<UploadTreeEntry>,
+}
+
+/// A Mercurial changeset, as it is stored in the revlog. All trees and
+/// filenodes the changeset refers to must have been uploaded already.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct UploadHgChangesetEntry {
+    pub hgid: HgId,
+    pub parents: Parents,
+    pub revlog_data: Bytes,
+}
+
+/// Changesets to create on the server. Changesets must be ordered so that
+/// parents come before their children.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct UploadHgChangesetsRequest {
+    pub changesets: Vec<UploadHgChangesetEntry>,
+}
+
+/// An object that the server stored in response to an upload request.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct UploadResponse {
+    pub id: AnyId,
+}
+
+/// Land the uploaded commits between `base` (exclusive) and `head`
+/// (inclusive) onto `bookmark` via pushrebase.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct LandStackRequest {
+    pub bookmark: String,
+    pub head: HgId,
+    pub base: HgId,
+}
+
+/// The new position of the bookmark, and the ids of the landed commits
+/// before and after they were rebased.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct LandStackResponse {
+    pub new_head: HgId,
+    pub old_to_new_hgids: Vec<(HgId, HgId)>,
+}
@@ -0,0 +1,127 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    wire::{is_default, ToApi, ToWire, WireHgId, WireToApiConversionError},
+    BookmarkEntry, BookmarkRequest,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct WireBookmarkRequest {
+    #[serde(rename = "0", default, skip_serializing_if = "is_default")]
+    pub bookmarks: Vec<String>,
+
+    #[serde(rename = "1", default, skip_serializing_if = "is_default")]
+    pub prefixes: Vec<String>,
+}
+
+impl ToWire for BookmarkRequest {
+    type Wire = WireBookmarkRequest;
+
+    fn to_wire(self) -> Self::Wire {
+        WireBookmarkRequest {
+            bookmarks: self.bookmarks,
+            prefixes: self.prefixes,
+        }
+    }
+}
+
+impl ToApi for WireBookmarkRequest {
+    type Api = BookmarkRequest;
+    type Error = WireToApiConversionError;
+
+    fn to_api(self) -> Result<Self::Api, Self::Error> {
+        Ok(BookmarkRequest {
+            bookmarks: self.bookmarks,
+            prefixes: self.prefixes,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct WireBookmarkEntry {
+    #[serde(rename = "0", default, skip_serializing_if = "is_default")]
+    pub bookmark: String,
+
+    #[serde(rename = "1", default, skip_serializing_if = "is_default")]
+    pub hgid: Option<WireHgId>,
+}
+
+impl ToWire for BookmarkEntry {
+    type Wire = WireBookmarkEntry;
+
+    fn to_wire(self) -> Self::Wire {
+        WireBookmarkEntry {
+            bookmark: self.bookmark,
+            hgid: self.hgid.to_wire(),
+        }
+    }
+}
+
+impl ToApi for WireBookmarkEntry {
+    type Api = BookmarkEntry;
+    type Error = WireToApiConversionError;
+
+    fn to_api(self) -> Result<Self::Api, Self::Error> {
+        Ok(BookmarkEntry {
+            bookmark: self.bookmark,
+            hgid: self.hgid.to_api()?,
+        })
+    }
+}
+
+#[cfg(any(test, feature = "for-tests"))]
+use quickcheck::Arbitrary;
+
+#[cfg(any(test, feature = "for-tests"))]
+impl Arbitrary for WireBookmarkRequest {
+    fn arbitrary<G: quickcheck::Gen>(g: &mut G) -> Self {
+        Self {
+            bookmarks: Arbitrary::arbitrary(g),
+            prefixes: Arbitrary::arbitrary(g),
+        }
+    }
+}
+
+#[cfg(any(test, feature = "for-tests"))]
+impl Arbitrary for WireBookmarkEntry {
+    fn arbitrary<G: quickcheck::Gen>(g: &mut G) -> Self {
+        Self {
+            bookmark: Arbitrary::arbitrary(g),
+            hgid: Arbitrary::arbitrary(g),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::wire::tests::{check_serialize_roundtrip, check_wire_roundtrip};
+
+    use quickcheck::quickcheck;
+
+    quickcheck! {
+        fn test_request_roundtrip_serialize(v: WireBookmarkRequest) -> bool {
+            check_serialize_roundtrip(v)
+        }
+
+        fn test_request_roundtrip_wire(v: BookmarkRequest) -> bool {
+            check_wire_roundtrip(v)
+        }
+
+        fn test_entry_roundtrip_serialize(v: WireBookmarkEntry) -> bool {
+            check_serialize_roundtrip(v)
+        }
+
+        fn test_entry_roundtrip_wire(v: BookmarkEntry) -> bool {
+            check_wire_roundtrip(v)
+        }
+    }
+}
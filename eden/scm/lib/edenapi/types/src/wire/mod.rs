@@ -45,6 +45,7 @@
 //! 7. If the type has a corresponding API type, add a quickcheck wire-API round
 //! trip test.
 
+pub mod bookmark;
 pub mod clone;
 pub mod complete_tree;
 pub mod file;
@@ -55,6 +56,7 @@ pub mod tree;
 use dag_wire_types::id::Id as DagId;
 
 pub use crate::wire::{
+    bookmark::{WireBookmarkEntry, WireBookmarkRequest},
     clone::{WireCloneData, WireIdMapEntry},
     complete_tree::WireCompleteTreeRequest,
     file::{WireFileEntry, WireFileRequest},
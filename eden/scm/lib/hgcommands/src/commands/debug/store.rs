@@ -11,9 +11,11 @@ use super::Result;
 use super::IO;
 use clidispatch::errors;
 use revisionstore::{
-    CorruptionPolicy, DataPackStore, ExtStoredPolicy, HgIdDataStore, IndexedLogDataStoreType,
-    IndexedLogHgIdDataStore, StoreKey, StoreResult, UnionHgIdDataStore,
+    ContentStore, CorruptionPolicy, DataPackStore, ExtStoredPolicy, HgIdDataStore,
+    IndexedLogDataStoreType, IndexedLogHgIdDataStore, MetadataStore, StoreKey, StoreResult,
+    UnionHgIdDataStore,
 };
+use std::path::Path;
 use std::str::FromStr;
 use types::{HgId, Key, RepoPathBuf};
 
@@ -22,17 +24,18 @@ define_flags! {
         /// print blob contents
         content: bool,
 
-        #[arg]
-        path: String,
+        /// verify that the cached data matches its hashes
+        verify: bool,
 
-        #[arg]
-        hgid: String,
+        /// with --verify, remove corrupt data from the cache so it is fetched again
+        delete_corrupt: bool,
+
+        #[args]
+        args: Vec<String>,
     }
 }
 
 pub fn run(opts: DebugstoreOpts, io: &mut IO, repo: Repo) -> Result<u8> {
-    let path = RepoPathBuf::from_string(opts.path)?;
-    let hgid = HgId::from_str(&opts.hgid)?;
     let config = repo.config();
     let cachepath = match config.get("remotefilelog", "cachepath") {
         Some(c) => c.to_string(),
@@ -42,6 +45,20 @@ pub fn run(opts: DebugstoreOpts, io: &mut IO, repo: Repo) -> Result<u8> {
         Some(c) => c.to_string(),
         None => return Err(errors::Abort("remotefilelog.reponame is not set".into()).into()),
     };
+    if opts.verify {
+        let sharedpath = Path::new(&cachepath).join(&reponame);
+        return verify(io, &repo, &sharedpath, opts.delete_corrupt);
+    }
+
+    let (path, hgid) = match opts.args.as_slice() {
+        [path, hgid] => (path, hgid),
+        _ => {
+            let msg = "PATH and HGID are required unless --verify is set";
+            return Err(errors::Abort(msg.into()).into());
+        }
+    };
+    let path = RepoPathBuf::from_string(path.to_string())?;
+    let hgid = HgId::from_str(hgid)?;
     let fullpath = format!("{}/{}/packs", cachepath, reponame);
     let packstore = Box::new(DataPackStore::new(
         fullpath,
@@ -69,10 +86,27 @@ pub fn run(opts: DebugstoreOpts, io: &mut IO, repo: Repo) -> Result<u8> {
     Ok(0)
 }
 
+/// Verify both the file and the tree stores, as `hg doctor` repairs them.
+fn verify(io: &mut IO, repo: &Repo, sharedpath: &Path, delete_corrupt: bool) -> Result<u8> {
+    let config = repo.config();
+    let localpath = Some(repo.store_path());
+    let mut ok = true;
+    for suffix in [None, Some("manifests")].iter() {
+        let content = ContentStore::verify(sharedpath, localpath, *suffix, config, delete_corrupt)?;
+        let history =
+            MetadataStore::verify(sharedpath, localpath, *suffix, config, delete_corrupt)?;
+        let name = if suffix.is_some() { "trees" } else { "files" };
+        write!(io.output, "checking {} data\n{}", name, content)?;
+        write!(io.output, "checking {} history\n{}", name, history)?;
+        ok &= content.is_ok() && history.is_ok();
+    }
+    Ok(if ok || delete_corrupt { 0 } else { 1 })
+}
+
 pub fn name() -> &'static str {
     "debugstore"
 }
 
 pub fn doc() -> &'static str {
-    "print information about blobstore"
+    "print information about blobstore, or verify its integrity with --verify"
 }
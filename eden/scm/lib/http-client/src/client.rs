@@ -28,6 +28,8 @@ pub type ResponseStream =
     Pin<Box<dyn Stream<Item = Result<AsyncResponse, HttpClientError>> + Send + 'static>>;
 pub type StatsFuture =
     Pin<Box<dyn Future<Output = Result<Stats, HttpClientError>> + Send + 'static>>;
+pub type ResponseFuture =
+    Pin<Box<dyn Future<Output = Result<AsyncResponse, HttpClientError>> + Send + 'static>>;
 
 /// An async-compatible HTTP client powered by libcurl.
 ///
@@ -151,6 +153,27 @@ impl HttpClient {
         requests: I,
         progress_cb: P,
     ) -> Result<(ResponseStream, StatsFuture), HttpClientError>
+    where
+        I: IntoIterator<Item = Request>,
+        P: FnMut(Progress) + Send + 'static,
+    {
+        let (responses, stats) = self.send_async_ordered_with_progress(requests, progress_cb)?;
+        let response_stream = responses
+            .into_iter()
+            .collect::<stream::FuturesUnordered<_>>()
+            .boxed();
+        Ok((response_stream, stats))
+    }
+
+    /// Same as `send_async_with_progress()`, but returns a future for
+    /// each response, in the same order as the requests, rather than
+    /// a stream of responses in the order they arrive. This is useful
+    /// for telling which of the requests failed.
+    pub fn send_async_ordered_with_progress<I, P>(
+        &self,
+        requests: I,
+        progress_cb: P,
+    ) -> Result<(Vec<ResponseFuture>, StatsFuture), HttpClientError>
     where
         I: IntoIterator<Item = Request>,
         P: FnMut(Progress) + Send + 'static,
@@ -158,15 +181,15 @@ impl HttpClient {
         let client = self.clone();
 
         let mut stream_requests = Vec::new();
-        let response_stream = stream::FuturesUnordered::new();
+        let mut responses = Vec::new();
         for req in requests {
             let (receiver, streams) = ChannelReceiver::new();
 
             let req = req.into_streaming(receiver);
             stream_requests.push(req);
 
-            let res = AsyncResponse::new(streams);
-            response_stream.push(res);
+            let res: ResponseFuture = AsyncResponse::new(streams).boxed();
+            responses.push(res);
         }
 
         let task = tokio::task::spawn_blocking(move || {
@@ -178,7 +201,7 @@ impl HttpClient {
             .map(|res| Ok(res??))
             .boxed();
 
-        Ok((response_stream.boxed(), stats))
+        Ok((responses, stats))
     }
 
     /// Perform the given requests, but stream the responses to the
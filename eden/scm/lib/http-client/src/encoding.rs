@@ -0,0 +1,213 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use async_compression::futures::bufread::{GzipDecoder, ZstdDecoder};
+use futures::prelude::*;
+use http::header::{HeaderMap, CONTENT_ENCODING};
+
+use crate::errors::HttpClientError;
+use crate::response::AsyncBody;
+
+const IDENTITY: &str = "identity";
+const ZSTD: &str = "zstd";
+const GZIP: &str = "gzip";
+
+/// Size of the chunks of a decoded body.
+const DECODED_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Content encodings that the client is able to decode.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Encoding {
+    Identity,
+    Zstd,
+    Gzip,
+}
+
+impl Encoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Identity => IDENTITY,
+            Self::Zstd => ZSTD,
+            Self::Gzip => GZIP,
+        }
+    }
+
+    /// Determine the encoding of a response body from the response's
+    /// `Content-Encoding` header. A missing header indicates that the
+    /// body is not encoded.
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, HttpClientError> {
+        match headers.get(CONTENT_ENCODING) {
+            Some(value) => value
+                .to_str()
+                .map_err(|_| HttpClientError::UnsupportedEncoding(format!("{:?}", value)))?
+                .parse(),
+            None => Ok(Self::Identity),
+        }
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = HttpClientError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            IDENTITY | "" => Ok(Self::Identity),
+            ZSTD => Ok(Self::Zstd),
+            GZIP => Ok(Self::Gzip),
+            other => Err(HttpClientError::UnsupportedEncoding(other.into())),
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Format a list of encodings as the value of an `Accept-Encoding` header.
+/// The encodings should be given in order of preference.
+pub(crate) fn accept_encoding_header(encodings: &[Encoding]) -> String {
+    encodings
+        .iter()
+        .map(Encoding::as_str)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Decompress a response body that was encoded with the given encoding.
+pub(crate) fn decode(body: AsyncBody, encoding: Encoding) -> AsyncBody {
+    if encoding == Encoding::Identity {
+        return body;
+    }
+
+    // The decoders read from an `AsyncBufRead`, which operates on `io::Result`s.
+    let reader = body
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+        .into_async_read();
+
+    match encoding {
+        Encoding::Identity => unreachable!(),
+        Encoding::Zstd => into_stream(ZstdDecoder::new(reader)),
+        Encoding::Gzip => into_stream(GzipDecoder::new(reader)),
+    }
+}
+
+/// Read a decoder's output as a stream of chunks.
+fn into_stream(reader: impl AsyncRead + Send + Unpin + 'static) -> AsyncBody {
+    stream::try_unfold(reader, |mut reader| async move {
+        let mut chunk = vec![0; DECODED_CHUNK_SIZE];
+        let len = reader.read(&mut chunk).await?;
+        if len == 0 {
+            return Ok(None);
+        }
+        chunk.truncate(len);
+        Ok(Some((chunk, reader)))
+    })
+    .map_err(decode_error)
+    .boxed()
+}
+
+/// Convert an I/O error from a decoder back into an `HttpClientError`,
+/// unwrapping any errors that came from the underlying response body.
+fn decode_error(e: io::Error) -> HttpClientError {
+    let from_body = e
+        .get_ref()
+        .map_or(false, |inner| inner.is::<HttpClientError>());
+    if from_body {
+        // The type of the inner error was checked above, so neither of
+        // these unwraps can fail.
+        return *e.into_inner().unwrap().downcast().unwrap();
+    }
+    HttpClientError::DecodingFailed(e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anyhow::Result;
+    use async_compression::futures::bufread::{GzipEncoder, ZstdEncoder};
+    use bytes::Bytes;
+    use http::header::HeaderValue;
+
+    fn body_from(chunks: Vec<Bytes>) -> AsyncBody {
+        stream::iter(chunks.into_iter().map(|c| Ok(c.to_vec()))).boxed()
+    }
+
+    /// Encode `data`, and split the result in small chunks.
+    async fn encode(data: &'static [u8], encoding: Encoding) -> Result<Vec<Bytes>> {
+        let mut encoded = Vec::new();
+        match encoding {
+            Encoding::Identity => encoded.extend_from_slice(data),
+            Encoding::Zstd => {
+                ZstdEncoder::new(data).read_to_end(&mut encoded).await?;
+            }
+            Encoding::Gzip => {
+                GzipEncoder::new(data).read_to_end(&mut encoded).await?;
+            }
+        };
+        Ok(encoded.chunks(7).map(Bytes::copy_from_slice).collect())
+    }
+
+    #[tokio::test]
+    async fn test_decode() -> Result<()> {
+        let data = b"The quick brown fox jumps over the lazy dog. \
+            The quick brown fox jumps over the lazy dog.";
+
+        for encoding in &[Encoding::Identity, Encoding::Zstd, Encoding::Gzip] {
+            let encoded = encode(data, *encoding).await?;
+            let decoded = decode(body_from(encoded), *encoding).try_concat().await?;
+            assert_eq!(&decoded[..], &data[..], "encoding: {}", encoding);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_decode_corrupt() -> Result<()> {
+        let body = body_from(vec![Bytes::from_static(b"not zstd")]);
+        let res = decode(body, Encoding::Zstd).try_concat().await;
+        assert!(matches!(res, Err(HttpClientError::DecodingFailed(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_decode_forwards_body_errors() -> Result<()> {
+        let body = stream::once(async { Err(HttpClientError::BadResponse) }).boxed();
+        let res = decode(body, Encoding::Gzip).try_concat().await;
+        assert!(matches!(res, Err(HttpClientError::BadResponse)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_headers() -> Result<()> {
+        let mut headers = HeaderMap::new();
+        assert_eq!(Encoding::from_headers(&headers)?, Encoding::Identity);
+
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("zstd"));
+        assert_eq!(Encoding::from_headers(&headers)?, Encoding::Zstd);
+
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("br"));
+        assert!(Encoding::from_headers(&headers).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_accept_encoding_header() {
+        assert_eq!(
+            accept_encoding_header(&[Encoding::Zstd, Encoding::Gzip]),
+            "zstd, gzip"
+        );
+        assert_eq!(accept_encoding_header(&[]), "");
+    }
+}
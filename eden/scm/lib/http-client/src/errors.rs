@@ -26,6 +26,10 @@ pub enum HttpClientError {
     CborError(#[from] serde_cbor::Error),
     #[error(transparent)]
     CborStreamError(#[from] crate::stream::CborStreamError),
+    #[error("Unsupported content encoding: {0}")]
+    UnsupportedEncoding(String),
+    #[error("Failed to decode response body: {0}")]
+    DecodingFailed(#[source] std::io::Error),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -40,6 +40,11 @@ impl<R> Streaming<R> {
 impl<R: Receiver> Handler for Streaming<R> {
     fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
         if let Some(ref mut receiver) = self.receiver {
+            if receiver.is_cancelled() {
+                // Reporting fewer bytes than were received tells
+                // libcurl to abort the transfer.
+                return Ok(0);
+            }
             receiver.chunk(data.into());
         }
         Ok(data.len())
@@ -82,7 +87,10 @@ impl<R: Receiver> Handler for Streaming<R> {
             updater.update(progress);
         }
 
-        true
+        // Returning false aborts the transfer. Checking here (rather than
+        // only in `write`) ensures that cancelled transfers are aborted
+        // even if the server isn't currently sending any data.
+        !self.receiver.as_ref().map_or(false, |r| r.is_cancelled())
     }
 }
 
@@ -104,6 +112,7 @@ mod tests {
 
     use crate::progress::ProgressReporter;
     use crate::receiver::testutil::{NullReceiver, TestReceiver};
+    use crate::receiver::ChannelReceiver;
 
     #[test]
     fn test_read() {
@@ -179,4 +188,19 @@ mod tests {
         // Check that ProgressReporter also got the value.
         assert_eq!(reporter.aggregate(), expected);
     }
+
+    #[test]
+    fn test_cancel() {
+        let (receiver, streams) = ChannelReceiver::new();
+        let mut handler = Streaming::with_receiver(receiver);
+
+        assert_eq!(handler.write(&[1, 2, 3]).unwrap(), 3);
+        assert!(handler.progress(0.0, 3.0, 0.0, 0.0));
+
+        // Dropping the response body should abort the transfer.
+        drop(streams);
+
+        assert_eq!(handler.write(&[4, 5, 6]).unwrap(), 0);
+        assert!(!handler.progress(0.0, 6.0, 0.0, 0.0));
+    }
 }
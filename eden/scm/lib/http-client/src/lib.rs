@@ -11,6 +11,7 @@
 
 mod client;
 mod driver;
+mod encoding;
 mod errors;
 mod handler;
 mod header;
@@ -22,8 +23,9 @@ mod response;
 mod stats;
 mod stream;
 
-pub use client::{HttpClient, ResponseStream, StatsFuture};
+pub use client::{HttpClient, ResponseFuture, ResponseStream, StatsFuture};
 pub use curl::easy::HttpVersion;
+pub use encoding::Encoding;
 pub use errors::{Abort, HttpClientError};
 pub use header::Header;
 pub use progress::Progress;
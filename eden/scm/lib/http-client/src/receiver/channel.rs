@@ -60,6 +60,12 @@ impl Receiver for ChannelReceiver {
         let _ = self.headers_tx.unbounded_send(header);
     }
 
+    /// The transfer is cancelled once the body stream has been dropped,
+    /// since there is nothing left to deliver the response body to.
+    fn is_cancelled(&self) -> bool {
+        self.body_tx.is_closed()
+    }
+
     fn done(self, res: Result<(), HttpClientError>) -> Result<(), Abort> {
         let _ = self.done_tx.send(res);
         Ok(())
@@ -26,6 +26,13 @@ pub trait Receiver: Sized {
     /// transfer makes progress.
     fn progress(&mut self, _progress: Progress) {}
 
+    /// Whether the consumer of the response is no longer interested in it.
+    /// If this returns `true`, the transfer will be aborted at the next
+    /// opportunity, and `done` will be called with the resulting error.
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+
     /// Called when the transfer has completed (successfully or not).
     ///
     /// If a fatal error occured while performing the transfer, the error
@@ -18,6 +18,7 @@ use serde::Serialize;
 use url::Url;
 
 use crate::{
+    encoding::{accept_encoding_header, Encoding},
     errors::HttpClientError,
     handler::{Buffered, Configure, Streaming},
     receiver::{ChannelReceiver, Receiver},
@@ -147,6 +148,16 @@ impl Request {
             .body(serde_cbor::to_vec(value)?))
     }
 
+    /// Advertise the given content encodings to the server via the
+    /// `Accept-Encoding` header, in order of preference. Responses must
+    /// be decoded by calling `AsyncResponse::decoded`.
+    pub fn accept_encoding(self, encodings: &[Encoding]) -> Self {
+        if encodings.is_empty() {
+            return self;
+        }
+        self.header("Accept-Encoding", accept_encoding_header(encodings))
+    }
+
     /// Set a request header.
     pub fn header(mut self, name: impl ToString, value: impl ToString) -> Self {
         self.headers.push((name.to_string(), value.to_string()));
@@ -12,6 +12,7 @@ use futures::prelude::*;
 use http::{header::HeaderMap, status::StatusCode, version::Version};
 use serde::de::DeserializeOwned;
 
+use crate::encoding::{decode, Encoding};
 use crate::errors::HttpClientError;
 use crate::handler::Buffered;
 use crate::header::Header;
@@ -130,6 +131,17 @@ impl AsyncResponse {
         })
     }
 
+    /// Decompress the response body according to the response's
+    /// `Content-Encoding` header. The header itself is left intact
+    /// so that the original encoding can still be inspected.
+    pub fn decoded(self) -> Result<Self, HttpClientError> {
+        let encoding = Encoding::from_headers(&self.headers)?;
+        Ok(Self {
+            body: decode(self.body, encoding),
+            ..self
+        })
+    }
+
     /// Consume the response and attempt to deserialize the
     /// incoming data as a stream of CBOR-serialized values.
     pub fn into_cbor_stream<T: DeserializeOwned>(self) -> CborStreamBody<T> {
@@ -0,0 +1,102 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Transparent compression of `Log` entries.
+
+use super::{Compression, EntryResult, LogMetadata};
+use crate::errors::IoResultExt;
+use std::borrow::Cow;
+use std::sync::Mutex;
+use vlqencoding::{VLQDecodeAt, VLQEncode};
+
+/// Compress and decompress entry content.
+///
+/// A compressed entry has the content:
+///
+/// ```plain,ignore
+/// CONTENT := LEN(RAW) + ZSTD(RAW)
+/// ```
+///
+/// `ZSTD` uses the dictionary stored in [`LogMetadata`], if present.
+pub(crate) struct EntryCodec {
+    dict: Option<Vec<u8>>,
+    compressor: Mutex<Option<zstd::block::Compressor>>,
+    decompressor: Mutex<Option<zstd::block::Decompressor>>,
+}
+
+impl EntryCodec {
+    pub(crate) fn new(meta: &LogMetadata) -> Self {
+        Self {
+            dict: meta.zstd_dict.clone(),
+            compressor: Default::default(),
+            decompressor: Default::default(),
+        }
+    }
+
+    /// Compress `data`. Return `None` if it should be stored as-is.
+    pub(crate) fn encode(
+        &self,
+        compression: Compression,
+        data: &[u8],
+    ) -> crate::Result<Option<Vec<u8>>> {
+        let level = match compression {
+            Compression::None => return Ok(None),
+            Compression::Zstd { level } => level,
+        };
+        let mut compressor = self.compressor.lock().unwrap();
+        let compressor = compressor.get_or_insert_with(|| match &self.dict {
+            Some(dict) => zstd::block::Compressor::with_dict(dict.clone()),
+            None => zstd::block::Compressor::new(),
+        });
+        let compressed = compressor
+            .compress(data, level)
+            .map_err(|e| crate::Error::wrap(Box::new(e), "cannot compress entry"))?;
+        let mut content = Vec::with_capacity(compressed.len() + 4);
+        content.write_vlq(data.len()).infallible()?;
+        content.extend_from_slice(&compressed);
+        if content.len() < data.len() {
+            Ok(Some(content))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Decompress content written by `encode`.
+    pub(crate) fn decode(&self, content: &[u8]) -> crate::Result<Vec<u8>> {
+        let (raw_len, vlq_len): (usize, _) = content.read_vlq_at(0).map_err(|e| {
+            crate::Error::wrap(Box::new(e), "cannot read uncompressed length").mark_corruption()
+        })?;
+        let mut decompressor = self.decompressor.lock().unwrap();
+        let decompressor = decompressor.get_or_insert_with(|| match &self.dict {
+            Some(dict) => zstd::block::Decompressor::with_dict(dict.clone()),
+            None => zstd::block::Decompressor::new(),
+        });
+        let data = decompressor
+            .decompress(&content[vlq_len..], raw_len)
+            .map_err(|e| {
+                crate::Error::wrap(Box::new(e), "cannot decompress entry").mark_corruption()
+            })?;
+        if data.len() != raw_len {
+            let msg = format!(
+                "decompressed entry has {} bytes, expected {} bytes",
+                data.len(),
+                raw_len
+            );
+            return Err(crate::Error::blank().message(msg).mark_corruption());
+        }
+        Ok(data)
+    }
+
+    /// Get the uncompressed data of an entry.
+    pub(super) fn decode_entry<'a>(&self, entry: &EntryResult<'a>) -> crate::Result<Cow<'a, [u8]>> {
+        if entry.compressed {
+            Ok(Cow::Owned(self.decode(entry.data)?))
+        } else {
+            Ok(Cow::Borrowed(entry.data))
+        }
+    }
+}
@@ -5,11 +5,12 @@
  * GNU General Public License version 2.
  */
 
+use super::PRIMARY_FILE;
 use crate::errors::IoResultExt;
 use crate::utils::{self, atomic_read, atomic_write, xxhash};
 use std::collections::BTreeMap;
 use std::io::{self, Cursor, Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use vlqencoding::{VLQDecode, VLQEncode};
 
 /// Metadata about index names, logical [`Log`] and [`Index`] file lengths.
@@ -24,19 +25,43 @@ pub struct LogMetadata {
     /// Used to detect non-append-only changes.
     /// Conceptually similar to "create time".
     pub(crate) epoch: u64,
+
+    /// Whether the primary log might contain compressed entries.
+    ///
+    /// Older readers do not understand compressed entries. Metadata of such
+    /// logs uses a header they reject.
+    pub(crate) compressed: bool,
+
+    /// Dictionary used to compress entries.
+    /// Changing it requires rewriting the log, and a new epoch.
+    pub(crate) zstd_dict: Option<Vec<u8>>,
+
+    /// File name of the primary log, if it is not [`PRIMARY_FILE`].
+    /// Rewrites write the new primary log under a fresh name, so replacing
+    /// the log is a single metadata write. Older readers would map
+    /// [`PRIMARY_FILE`] instead. Metadata with a name uses a header they
+    /// reject.
+    pub(crate) primary_name: Option<String>,
 }
 
 impl LogMetadata {
     const HEADER: &'static [u8] = b"meta\0";
+    const HEADER_V1: &'static [u8] = b"meta\x01";
+
+    const FLAG_COMPRESSED: u64 = 1;
 
     /// Read metadata from a reader.
     pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
         let mut header = vec![0; Self::HEADER.len()];
         reader.read_exact(&mut header)?;
-        if header != Self::HEADER {
+        let is_v1 = if header == Self::HEADER {
+            false
+        } else if header == Self::HEADER_V1 {
+            true
+        } else {
             let msg = "invalid metadata header";
             return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
-        }
+        };
 
         let hash: u64 = reader.read_vlq()?;
         let buf_len = reader.read_vlq()?;
@@ -65,6 +90,21 @@ impl LogMetadata {
             indexes.insert(name, len);
         }
 
+        if is_v1 {
+            let epoch = reader.read_vlq()?;
+            let flags: u64 = reader.read_vlq()?;
+            let zstd_dict = read_optional_bytes(&mut reader)?;
+            let primary_name = read_optional_name(&mut reader)?;
+            return Ok(Self {
+                primary_len,
+                indexes,
+                epoch,
+                compressed: flags & Self::FLAG_COMPRESSED != 0,
+                zstd_dict,
+                primary_name,
+            });
+        }
+
         // 'epoch' is optional - it does not exist in a previous serialization
         // format. So not being able to read it (because EOF) is not fatal.
         let epoch = reader.read_vlq().unwrap_or_default();
@@ -73,6 +113,9 @@ impl LogMetadata {
             primary_len,
             indexes,
             epoch,
+            compressed: false,
+            zstd_dict: None,
+            primary_name: None,
         })
     }
 
@@ -88,7 +131,23 @@ impl LogMetadata {
             buf.write_vlq(*len)?;
         }
         buf.write_vlq(self.epoch)?;
-        writer.write_all(Self::HEADER)?;
+        let header = if self.is_v1() {
+            let flags = if self.compressed {
+                Self::FLAG_COMPRESSED
+            } else {
+                0
+            };
+            buf.write_vlq(flags)?;
+            write_bytes(&mut buf, self.zstd_dict.as_deref().unwrap_or_default())?;
+            write_bytes(
+                &mut buf,
+                self.primary_name.as_deref().unwrap_or_default().as_bytes(),
+            )?;
+            Self::HEADER_V1
+        } else {
+            Self::HEADER
+        };
+        writer.write_all(header)?;
         writer.write_vlq(xxhash(&buf))?;
         writer.write_vlq(buf.len())?;
         writer.write_all(&buf)?;
@@ -96,6 +155,12 @@ impl LogMetadata {
         Ok(())
     }
 
+    /// Whether the metadata uses features older readers do not understand.
+    /// Such metadata is written with a header they reject.
+    fn is_v1(&self) -> bool {
+        self.compressed || self.zstd_dict.is_some() || self.primary_name.is_some()
+    }
+
     /// Read metadata from a file.
     pub fn read_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
         let buf = atomic_read(path.as_ref())?;
@@ -119,9 +184,17 @@ impl LogMetadata {
             primary_len: len,
             indexes: BTreeMap::new(),
             epoch: utils::epoch(),
+            compressed: false,
+            zstd_dict: None,
+            primary_name: None,
         }
     }
 
+    /// Path of the primary log file in `dir`.
+    pub(crate) fn primary_path(&self, dir: &Path) -> PathBuf {
+        dir.join(self.primary_name.as_deref().unwrap_or(PRIMARY_FILE))
+    }
+
     /// Test if two Metadata is compatible, aka. having the same length
     /// and epoch.
     pub(crate) fn is_compatible_with(&self, other: &Self) -> bool {
@@ -129,6 +202,37 @@ impl LogMetadata {
     }
 }
 
+/// Write `LEN(DATA) + DATA`.
+fn write_bytes(buf: &mut Vec<u8>, data: &[u8]) -> io::Result<()> {
+    buf.write_vlq(data.len())?;
+    buf.write_all(data)
+}
+
+/// Read `LEN(DATA) + DATA`. Empty data is `None`.
+fn read_optional_bytes(reader: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let len: usize = reader.read_vlq()?;
+    if len == 0 {
+        return Ok(None);
+    }
+    let mut data = vec![0; len];
+    reader.read_exact(&mut data)?;
+    Ok(Some(data))
+}
+
+/// Read a primary log name written by `write_bytes`.
+fn read_optional_name(reader: &mut impl Read) -> io::Result<Option<String>> {
+    match read_optional_bytes(reader)? {
+        Some(name) => {
+            let name = String::from_utf8(name).map_err(|_e| {
+                let msg = "non-utf8 primary log name";
+                io::Error::new(io::ErrorKind::InvalidData, msg)
+            })?;
+            Ok(Some(name))
+        }
+        None => Ok(None),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -136,18 +240,22 @@ mod tests {
     use tempfile::tempdir;
 
     quickcheck! {
-        fn test_roundtrip_meta(primary_len: u64, indexes: BTreeMap<String, u64>, epoch: u64) -> bool {
+        fn test_roundtrip_meta(primary_len: u64, indexes: BTreeMap<String, u64>, epoch: u64, compressed: bool, zstd_dict: Option<Vec<u8>>, primary_name: Option<String>) -> bool {
             let mut buf = Vec::new();
-            let meta = LogMetadata { primary_len, indexes, epoch,  };
+            let zstd_dict = zstd_dict.filter(|dict| !dict.is_empty());
+            let primary_name = primary_name.filter(|name| !name.is_empty());
+            let meta = LogMetadata { primary_len, indexes, epoch, compressed, zstd_dict, primary_name };
             meta.write(&mut buf).expect("write");
             let mut cur = Cursor::new(buf);
             let meta_read = LogMetadata::read(&mut cur).expect("read");
             meta_read == meta
         }
 
-        fn test_roundtrip_meta_file(primary_len: u64, indexes: BTreeMap<String, u64>, epoch: u64) -> bool {
+        fn test_roundtrip_meta_file(primary_len: u64, indexes: BTreeMap<String, u64>, epoch: u64, compressed: bool, zstd_dict: Option<Vec<u8>>, primary_name: Option<String>) -> bool {
             let dir = tempdir().unwrap();
-            let meta = LogMetadata { primary_len, indexes, epoch,  };
+            let zstd_dict = zstd_dict.filter(|dict| !dict.is_empty());
+            let primary_name = primary_name.filter(|name| !name.is_empty());
+            let meta = LogMetadata { primary_len, indexes, epoch, compressed, zstd_dict, primary_name };
             let path = dir.path().join("meta");
             meta.write_file(&path, false).expect("write_file");
             let meta_read = LogMetadata::read_file(&path).expect("read_file");
@@ -155,4 +263,22 @@ mod tests {
         }
 
     }
+
+    #[test]
+    fn test_header_version() {
+        let header = |meta: &LogMetadata| {
+            let mut buf = Vec::new();
+            meta.write(&mut buf).unwrap();
+            buf.truncate(LogMetadata::HEADER.len());
+            buf
+        };
+
+        // Plain logs stay readable by older readers.
+        let mut meta = LogMetadata::new_with_primary_len(10);
+        assert_eq!(header(&meta), LogMetadata::HEADER);
+
+        // Rewritten logs are not. Older readers would map the stale 'log'.
+        meta.primary_name = Some("log.1".to_string());
+        assert_eq!(header(&meta), LogMetadata::HEADER_V1);
+    }
 }
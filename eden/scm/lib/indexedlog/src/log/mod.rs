@@ -19,14 +19,21 @@
 //   ENTRY_LIST := '' | ENTRY_LIST + ENTRY
 //   ENTRY := ENTRY_FLAGS + LEN(CONTENT) + CHECKSUM + CONTENT
 //   CHECKSUM := '' | XXHASH64(CONTENT) | XXHASH32(CONTENT)
+//   CONTENT := RAW | LEN(RAW) + ZSTD(RAW) (if ENTRY_FLAGS has ENTRY_FLAG_ZSTD)
 //
 // Metadata:
 //   META := HEADER + XXHASH64(DATA) + LEN(DATA) + DATA
-//   HEADER := 'meta\0'
-//   DATA := LEN(LOG) + LEN(INDEXES) + INDEXES
+//   HEADER := 'meta\0' | 'meta\1' (if DATA has FEATURES)
+//   DATA := LEN(LOG) + LEN(INDEXES) + INDEXES + EPOCH + FEATURES
+//   FEATURES := '' | FLAGS + LEN(ZSTD_DICT) + ZSTD_DICT + LEN(PRIMARY_NAME) + PRIMARY_NAME
+//   FLAGS := 1 (if LOG might have ENTRY_FLAG_ZSTD entries) | 0
 //   INDEXES := '' | INDEXES + INDEX
 //   INDEX := LEN(NAME) + NAME + INDEX_LOGIC_LEN
 //
+// Readers that predate FEATURES reject the 'meta\1' header. Therefore they
+// do not return compressed entries as-is, or map 'log' after it was replaced
+// by a rewrite.
+//
 // Indexes:
 //   See `index.rs`.
 //
@@ -38,6 +45,7 @@ use crate::index::{self, Index, InsertKey, InsertValue, LeafValueIter, RangeIter
 use crate::lock::ScopedDirLock;
 use crate::utils::{self, mmap_path, xxhash, xxhash32};
 use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use codec::EntryCodec;
 use minibytes::Bytes;
 use std::borrow::Cow;
 use std::fmt::{self, Debug, Formatter};
@@ -51,19 +59,22 @@ use tracing::debug_span;
 use tracing::trace;
 use vlqencoding::{VLQDecodeAt, VLQEncode};
 
+mod codec;
 mod meta;
 mod open_options;
 mod path;
 mod repair;
+mod rewrite;
 #[cfg(test)]
 mod tests;
 
 pub use self::meta::LogMetadata;
 pub use open_options::{
-    ChecksumType, FlushFilterContext, FlushFilterFunc, FlushFilterOutput, IndexDef, IndexOutput,
-    OpenOptions,
+    ChecksumType, Compression, FlushFilterContext, FlushFilterFunc, FlushFilterOutput, IndexDef,
+    IndexOutput, OpenOptions,
 };
 pub use path::GenericPath;
+pub use rewrite::CompactContext;
 
 // Constants about file names
 pub(crate) const PRIMARY_FILE: &str = "log";
@@ -73,6 +84,7 @@ pub(crate) const META_FILE: &str = "meta";
 
 const ENTRY_FLAG_HAS_XXHASH64: u32 = 1;
 const ENTRY_FLAG_HAS_XXHASH32: u32 = 2;
+const ENTRY_FLAG_ZSTD: u32 = 4;
 
 // 1MB index checksum. This makes checksum file within one block (4KB) for 512MB index.
 const INDEX_CHECKSUM_CHUNK_SIZE_LOGARITHM: u32 = 20;
@@ -106,6 +118,8 @@ pub struct Log {
     // probably fine considering index corruptions are rare.
     index_corrupted: bool,
     open_options: OpenOptions,
+    // Compresses new entries and decompresses existing ones. Matches `meta`.
+    codec: EntryCodec,
 }
 
 /// Iterator over all entries in a [`Log`].
@@ -210,6 +224,8 @@ impl Log {
     pub fn append<T: AsRef<[u8]>>(&mut self, data: T) -> crate::Result<()> {
         let result: crate::Result<_> = (|| {
             let data = data.as_ref();
+            let compressed = self.codec.encode(self.open_options.compression, data)?;
+            let content = compressed.as_deref().unwrap_or(data);
 
             let checksum_type = if self.open_options.checksum_type == ChecksumType::Auto {
                 // xxhash64 is slower for smaller data. A quick benchmark on x64 platform shows:
@@ -229,7 +245,7 @@ impl Log {
                 //  120       3000      3428
                 //  128       3459      4266
                 const XXHASH64_THRESHOLD: usize = 88;
-                if data.len() >= XXHASH64_THRESHOLD {
+                if content.len() >= XXHASH64_THRESHOLD {
                     ChecksumType::Xxhash64
                 } else {
                     ChecksumType::Xxhash32
@@ -240,39 +256,48 @@ impl Log {
 
             let offset = self.meta.primary_len + self.mem_buf.len() as u64;
 
-            // Design note: Currently checksum_type is the only thing that decides
-            // entry_flags.  Entry flags is not designed to just cover different
-            // checksum types.  For example, if we'd like to introduce transparent
-            // compression (maybe not a good idea since it can be more cleanly built
-            // at an upper layer), or some other ways to store data (ex. reference
-            // to other data, or fixed length data), they can probably be done by
-            // extending the entry type.
+            // Design note: checksum_type and compression decide entry_flags.
+            // Entry flags is not designed to just cover those. For example,
+            // some other ways to store data (ex. reference to other data, or
+            // fixed length data) can probably be done by extending the entry
+            // type.
             let mut entry_flags = 0;
             entry_flags |= match checksum_type {
                 ChecksumType::Xxhash64 => ENTRY_FLAG_HAS_XXHASH64,
                 ChecksumType::Xxhash32 => ENTRY_FLAG_HAS_XXHASH32,
                 ChecksumType::Auto => unreachable!(),
             };
+            if compressed.is_some() {
+                entry_flags |= ENTRY_FLAG_ZSTD;
+            }
 
             self.mem_buf.write_vlq(entry_flags).infallible()?;
-            self.mem_buf.write_vlq(data.len()).infallible()?;
+            self.mem_buf.write_vlq(content.len()).infallible()?;
 
+            // The checksum covers the stored (compressed) content so integrity
+            // can be verified without decompressing.
             match checksum_type {
                 ChecksumType::Xxhash64 => {
                     self.mem_buf
-                        .write_u64::<LittleEndian>(xxhash(data))
+                        .write_u64::<LittleEndian>(xxhash(content))
                         .infallible()?;
                 }
                 ChecksumType::Xxhash32 => {
                     self.mem_buf
-                        .write_u32::<LittleEndian>(xxhash32(data))
+                        .write_u32::<LittleEndian>(xxhash32(content))
                         .infallible()?;
                 }
                 ChecksumType::Auto => unreachable!(),
             };
             let data_offset = self.meta.primary_len + self.mem_buf.len() as u64;
 
-            self.mem_buf.write_all(data).infallible()?;
+            self.mem_buf.write_all(content).infallible()?;
+            // Raw data of compressed entries is not in the buffer. Index keys
+            // cannot reference it.
+            let data_offset = match compressed {
+                Some(_) => None,
+                None => Some(data_offset),
+            };
             self.update_indexes_for_in_memory_entry(data, offset, data_offset)?;
 
             if let Some(threshold) = self.open_options.auto_sync_threshold {
@@ -369,6 +394,7 @@ impl Log {
             indexes,
             index_corrupted: false,
             open_options: self.open_options.clone(),
+            codec: EntryCodec::new(&self.meta),
         };
 
         if !copy_dirty {
@@ -483,7 +509,7 @@ impl Log {
                     let content = entry?;
                     let context = FlushFilterContext { log: &log };
                     // Re-insert entries to that clean log.
-                    match filter(&context, content)
+                    match filter(&context, &content)
                         .map_err(|err| crate::Error::wrap(err, "failed to run filter function"))?
                     {
                         FlushFilterOutput::Drop => {}
@@ -517,7 +543,7 @@ impl Log {
             }
 
             // Step 2: Append to the primary log.
-            let primary_path = self.dir.as_opt_path().unwrap().join(PRIMARY_FILE);
+            let primary_path = meta.primary_path(&dir);
             let mut primary_file = fs::OpenOptions::new()
                 .read(true)
                 .write(true)
@@ -567,6 +593,11 @@ impl Log {
             }
 
             meta.primary_len += self.mem_buf.len() as u64;
+            if self.open_options.compression != Compression::None {
+                // Older readers would return compressed entries as-is.
+                // Make them reject the log.
+                meta.compressed = true;
+            }
             self.mem_buf.clear();
 
             // Step 3: Reload primary log and indexes to get the latest view.
@@ -672,6 +703,12 @@ impl Log {
         }
     }
 
+    /// Size of the primary log in bytes, including entries that are not
+    /// yet written to disk. This is the size that [`Log::sync`] will return.
+    pub fn size(&self) -> u64 {
+        self.meta.primary_len + self.mem_buf.len() as u64
+    }
+
     /// Renamed. Use [`Log::sync`] instead.
     pub fn flush(&mut self) -> crate::Result<u64> {
         self.sync()
@@ -821,6 +858,7 @@ impl Log {
                             def,
                             &self.disk_buf,
                             self.meta.primary_len,
+                            &self.codec,
                         )?;
                         index.flush()?
                     };
@@ -857,7 +895,7 @@ impl Log {
     /// Look up an entry using the given index. The `index_id` is the index of
     /// `index_defs` passed to [`Log::open`].
     ///
-    /// Return an iterator of `Result<&[u8]>`, in reverse insertion order.
+    /// Return an iterator of `Result<Cow<[u8]>>`, in reverse insertion order.
     pub fn lookup<K: AsRef<[u8]>>(&self, index_id: usize, key: K) -> crate::Result<LogLookupIter> {
         let result: crate::Result<_> = (|| {
             self.maybe_return_index_error()?;
@@ -1017,12 +1055,13 @@ impl Log {
     ///
     /// `offset` is the logical start offset of the entry.
     /// `data_offset` is the logical start offset of the real data (skips
-    /// length, and checksum header in the entry).
+    /// length, and checksum header in the entry), or `None` if the entry is
+    /// compressed.
     fn update_indexes_for_in_memory_entry(
         &mut self,
         data: &[u8],
         offset: u64,
-        data_offset: u64,
+        data_offset: Option<u64>,
     ) -> crate::Result<()> {
         let result = self.update_indexes_for_in_memory_entry_unchecked(data, offset, data_offset);
         self.maybe_set_index_error(result)
@@ -1032,29 +1071,49 @@ impl Log {
         &mut self,
         data: &[u8],
         offset: u64,
-        data_offset: u64,
+        data_offset: Option<u64>,
     ) -> crate::Result<()> {
         for (index, def) in self.indexes.iter_mut().zip(&self.open_options.index_defs) {
             for index_output in (def.func)(data) {
-                match index_output {
-                    IndexOutput::Reference(range) => {
-                        assert!(range.start <= range.end && range.end <= data.len() as u64);
+                Self::insert_index_output(index, index_output, data, offset, data_offset)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply an [`IndexOutput`] of the entry at `offset` to `index`.
+    ///
+    /// `data` is the raw data of the entry. See
+    /// [`Log::update_indexes_for_in_memory_entry`] for `data_offset`.
+    fn insert_index_output(
+        index: &mut Index,
+        index_output: IndexOutput,
+        data: &[u8],
+        offset: u64,
+        data_offset: Option<u64>,
+    ) -> crate::Result<()> {
+        match index_output {
+            IndexOutput::Reference(range) => {
+                assert!(range.start <= range.end && range.end <= data.len() as u64);
+                let key = match data_offset {
+                    Some(data_offset) => {
                         let start = range.start + data_offset;
                         let end = range.end + data_offset;
-                        let key = InsertKey::Reference((start, end - start));
-                        index.insert_advanced(key, InsertValue::Prepend(offset))?;
-                    }
-                    IndexOutput::Owned(key) => {
-                        let key = InsertKey::Embed(&key);
-                        index.insert_advanced(key, InsertValue::Prepend(offset))?;
-                    }
-                    IndexOutput::Remove(key) => {
-                        index.remove(key)?;
+                        InsertKey::Reference((start, end - start))
                     }
-                    IndexOutput::RemovePrefix(key) => {
-                        index.remove_prefix(key)?;
-                    }
-                }
+                    None => InsertKey::Embed(&data[range.start as usize..range.end as usize]),
+                };
+                index.insert_advanced(key, InsertValue::Prepend(offset))?;
+            }
+            IndexOutput::Owned(key) => {
+                let key = InsertKey::Embed(&key);
+                index.insert_advanced(key, InsertValue::Prepend(offset))?;
+            }
+            IndexOutput::Remove(key) => {
+                index.remove(key)?;
+            }
+            IndexOutput::RemovePrefix(key) => {
+                index.remove_prefix(key)?;
             }
         }
         Ok(())
@@ -1077,6 +1136,7 @@ impl Log {
                 def,
                 &self.disk_buf,
                 self.meta.primary_len,
+                &self.codec,
             )?;
         }
         Ok(())
@@ -1088,6 +1148,7 @@ impl Log {
         def: &IndexDef,
         disk_buf: &Bytes,
         primary_len: u64,
+        codec: &EntryCodec,
     ) -> crate::Result<usize> {
         // The index meta is used to store the next offset the index should be built.
         let mut offset = Self::get_index_log_len(index, true)?;
@@ -1103,28 +1164,14 @@ impl Log {
             })?
         {
             count += 1;
-            let data = entry_result.data;
-            for index_output in (def.func)(data) {
-                match index_output {
-                    IndexOutput::Reference(range) => {
-                        assert!(range.start <= range.end && range.end <= data.len() as u64);
-                        let start = range.start + entry_result.data_offset;
-                        let end = range.end + entry_result.data_offset;
-                        let key = InsertKey::Reference((start, end - start));
-
-                        index.insert_advanced(key, InsertValue::Prepend(offset))?;
-                    }
-                    IndexOutput::Owned(key) => {
-                        let key = InsertKey::Embed(&key);
-                        index.insert_advanced(key, InsertValue::Prepend(offset))?;
-                    }
-                    IndexOutput::Remove(key) => {
-                        index.remove(key)?;
-                    }
-                    IndexOutput::RemovePrefix(key) => {
-                        index.remove_prefix(key)?;
-                    }
-                }
+            let data = codec.decode_entry(&entry_result)?;
+            let data_offset = if entry_result.compressed {
+                None
+            } else {
+                Some(entry_result.data_offset)
+            };
+            for index_output in (def.func)(&data) {
+                Self::insert_index_output(index, index_output, &data, offset, data_offset)?;
             }
             offset = entry_result.next_offset;
         }
@@ -1192,7 +1239,7 @@ impl Log {
         fsync: bool,
     ) -> crate::Result<(Bytes, Vec<Index>)> {
         let primary_buf = match dir.as_opt_path() {
-            Some(dir) => mmap_path(&dir.join(PRIMARY_FILE), meta.primary_len)?,
+            Some(dir) => mmap_path(&meta.primary_path(dir), meta.primary_len)?,
             None => Bytes::new(),
         };
 
@@ -1273,10 +1320,10 @@ impl Log {
         }
     }
 
-    /// Read the entry at the given offset. Return `None` if offset is out of bound, or the content
-    /// of the data, the real offset of the data, and the next offset. Raise errors if
-    /// integrity-check failed.
-    fn read_entry(&self, offset: u64) -> crate::Result<Option<EntryResult>> {
+    /// Read the entry at the given offset. Return `None` if offset is out of bound, or the
+    /// uncompressed content of the data, and the next offset. Raise errors if integrity-check
+    /// failed.
+    fn read_entry(&self, offset: u64) -> crate::Result<Option<(Cow<[u8]>, u64)>> {
         let result = if offset < self.meta.primary_len {
             Self::read_entry_from_buf(&self.dir, &self.disk_buf, offset)?
         } else {
@@ -1287,7 +1334,16 @@ impl Log {
             Self::read_entry_from_buf(&self.dir, &self.mem_buf, offset)?
                 .map(|entry_result| entry_result.offset(self.meta.primary_len))
         };
-        Ok(result)
+        match result {
+            Some(entry_result) => {
+                let data = self
+                    .codec
+                    .decode_entry(&entry_result)
+                    .map_err(|e| e.message(format!("cannot decompress entry at {}", offset)))?;
+                Ok(Some((data, entry_result.next_offset)))
+            }
+            None => Ok(None),
+        }
     }
 
     /// Read an entry at the given offset of the given buffer. Verify its integrity. Return the
@@ -1380,6 +1436,7 @@ impl Log {
                 data,
                 data_offset: offset,
                 next_offset: end,
+                compressed: entry_flags & ENTRY_FLAG_ZSTD != 0,
             }))
         } else {
             Err(data_error(format!("integrity check failed at {}", offset)))
@@ -1494,6 +1551,8 @@ struct EntryResult<'a> {
     data: &'a [u8],
     data_offset: u64,
     next_offset: u64,
+    // Whether `data` is compressed.
+    compressed: bool,
 }
 
 impl<'a> EntryResult<'a> {
@@ -1505,12 +1564,13 @@ impl<'a> EntryResult<'a> {
             // So it does not need to be changed.
             data_offset: self.data_offset,
             next_offset: self.next_offset + offset,
+            compressed: self.compressed,
         }
     }
 }
 
 impl<'a> Iterator for LogLookupIter<'a> {
-    type Item = crate::Result<&'a [u8]>;
+    type Item = crate::Result<Cow<'a, [u8]>>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.errored {
@@ -1527,7 +1587,7 @@ impl<'a> Iterator for LogLookupIter<'a> {
                 .read_entry(offset)
                 .context("in LogLookupIter::next")
             {
-                Ok(Some(entry)) => Some(Ok(entry.data)),
+                Ok(Some((data, _next_offset))) => Some(Ok(data)),
                 Ok(None) => None,
                 Err(err) => {
                     // Do not set this iterator to an error state. It's possible
@@ -1546,13 +1606,13 @@ impl<'a> Iterator for LogLookupIter<'a> {
 
 impl<'a> LogLookupIter<'a> {
     /// A convenient way to get data.
-    pub fn into_vec(self) -> crate::Result<Vec<&'a [u8]>> {
+    pub fn into_vec(self) -> crate::Result<Vec<Cow<'a, [u8]>>> {
         self.collect()
     }
 }
 
 impl<'a> Iterator for LogIter<'a> {
-    type Item = crate::Result<&'a [u8]>;
+    type Item = crate::Result<Cow<'a, [u8]>>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.errored {
@@ -1567,10 +1627,10 @@ impl<'a> Iterator for LogIter<'a> {
                 self.errored = true;
                 Some(Err(e))
             }
-            Ok(Some(entry_result)) => {
-                assert!(entry_result.next_offset > self.next_offset);
-                self.next_offset = entry_result.next_offset;
-                Some(Ok(entry_result.data))
+            Ok(Some((data, next_offset))) => {
+                assert!(next_offset > self.next_offset);
+                self.next_offset = next_offset;
+                Some(Ok(data))
             }
             Ok(None) => None,
         }
@@ -8,6 +8,7 @@
 use crate::errors::ResultExt;
 use crate::index::Index;
 use crate::lock::ScopedDirLock;
+use crate::log::codec::EntryCodec;
 use crate::log::{GenericPath, Log, LogMetadata, PRIMARY_START_OFFSET};
 use std::borrow::Cow;
 use std::fmt::{self, Debug};
@@ -106,6 +107,20 @@ pub enum ChecksumType {
     Xxhash32,
 }
 
+/// How to compress new entries.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Compression {
+    /// Store entries as-is.
+    None,
+
+    /// Compress entries using zstd at the given level. If the log has a
+    /// dictionary (see [`OpenOptions::rewrite_compression`]), it is used.
+    ///
+    /// Entries are still stored as-is if compression does not make them
+    /// smaller. Index functions and readers always see uncompressed data.
+    Zstd { level: i32 },
+}
+
 /// Options used to configured how an [`Log`] is opened.
 #[derive(Clone)]
 pub struct OpenOptions {
@@ -115,6 +130,7 @@ pub struct OpenOptions {
     pub(crate) flush_filter: Option<FlushFilterFunc>,
     pub(crate) fsync: bool,
     pub(crate) auto_sync_threshold: Option<u64>,
+    pub(crate) compression: Compression,
 }
 
 pub type FlushFilterFunc =
@@ -230,6 +246,7 @@ impl OpenOptions {
             flush_filter: None,
             fsync: false,
             auto_sync_threshold: None,
+            compression: Compression::None,
         }
     }
 
@@ -287,6 +304,17 @@ impl OpenOptions {
         self
     }
 
+    /// Sets how new entries are compressed.
+    ///
+    /// Existing entries are readable regardless of this option. Use
+    /// [`OpenOptions::rewrite_compression`] to compress existing entries.
+    ///
+    /// See [`Compression`] for details.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
     /// Sets the flush filter function.
     ///
     /// The function will be called at [`Log::sync`] time, if there are
@@ -359,6 +387,7 @@ impl OpenOptions {
                 None,
                 self.fsync,
             )?;
+            let codec = EntryCodec::new(&meta);
             Ok(Log {
                 dir,
                 disk_buf,
@@ -367,6 +396,7 @@ impl OpenOptions {
                 indexes,
                 index_corrupted: false,
                 open_options: self.clone(),
+                codec,
             })
         })();
 
@@ -419,6 +449,7 @@ impl OpenOptions {
             reuse_indexes,
             self.fsync,
         )?;
+        let codec = EntryCodec::new(&meta);
         let mut log = Log {
             dir: dir.clone(),
             disk_buf,
@@ -427,6 +458,7 @@ impl OpenOptions {
             indexes,
             index_corrupted: false,
             open_options: self.clone(),
+            codec,
         };
         log.update_indexes_for_on_disk_entries()?;
         let lagging_index_ids = log.lagging_index_ids();
@@ -489,6 +521,7 @@ impl fmt::Debug for OpenOptions {
         write!(f, "create: {}, ", self.create)?;
         write!(f, "checksum_type: {:?}, ", self.checksum_type)?;
         write!(f, "auto_sync_threshold: {:?}, ", self.auto_sync_threshold)?;
+        write!(f, "compression: {:?}, ", self.compression)?;
         let flush_filter_desc = match self.flush_filter {
             Some(ref _buf) => "Some(_)",
             None => "None",
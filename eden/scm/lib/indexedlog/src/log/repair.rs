@@ -8,7 +8,7 @@
 use crate::errors::{IoResultExt, ResultExt};
 use crate::lock::ScopedDirLock;
 use crate::log::{
-    GenericPath, LogMetadata, OpenOptions, META_FILE, PRIMARY_FILE, PRIMARY_HEADER,
+    GenericPath, Log, LogMetadata, OpenOptions, META_FILE, PRIMARY_FILE, PRIMARY_HEADER,
     PRIMARY_START_OFFSET,
 };
 use crate::repair::OpenOptionsRepair;
@@ -43,8 +43,12 @@ impl OpenOptions {
 
             let lock = ScopedDirLock::new(dir)?;
 
-            let primary_path = dir.join(PRIMARY_FILE);
             let meta_path = dir.join(META_FILE);
+            // Rewritten logs record the name of their primary log in meta.
+            let primary_path = match LogMetadata::read_file(&meta_path) {
+                Ok(meta) => meta.primary_path(dir),
+                Err(_) => dir.join(PRIMARY_FILE),
+            };
 
             // Make sure the header of the primary log file is okay.
             (|| -> crate::Result<()> {
@@ -137,15 +141,18 @@ impl OpenOptions {
                 })
                 .context("cannot open log for repair")?;
 
-            let mut iter = log.iter();
-
-            // Read entries until hitting a checksum error.
+            // Read entries until hitting a checksum error. Compressed entries
+            // are not decompressed. Their checksums cover the compressed data.
             let mut entry_count = 0;
-            while let Some(Ok(_)) = iter.next() {
+            let mut valid_len = PRIMARY_START_OFFSET;
+            let mut has_compressed = false;
+            while let Ok(Some(entry)) = Log::read_entry_from_buf(&log.dir, &log.disk_buf, valid_len)
+            {
                 entry_count += 1;
+                valid_len = entry.next_offset;
+                has_compressed |= entry.compressed;
             }
 
-            let valid_len = iter.next_offset;
             assert!(valid_len >= PRIMARY_START_OFFSET);
             assert!(valid_len <= log.meta.primary_len);
 
@@ -213,6 +220,15 @@ impl OpenOptions {
                 message += &format!("Reset log size to {}\n", valid_len);
             }
 
+            // Rebuilt metadata does not know about compressed entries.
+            if has_compressed && !log.meta.compressed {
+                log.meta.compressed = true;
+                log.meta
+                    .write_file(&meta_path, log.open_options.fsync)
+                    .context("while trying to mark log as compressed")?;
+                message += "Marked log as compressed\n";
+            }
+
             // Also rebuild corrupted indexes.
             // Without this, indexes are empty until the next `sync`, which
             // can lead to bad performance.
@@ -0,0 +1,368 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Non-append-only rewrites of [`Log`]: compaction, and changing compression.
+//!
+//! A rewrite writes kept entries to a new primary log file under a fresh
+//! name, then atomically replaces the metadata to point to it and rebuilds
+//! indexes, while holding the directory lock. The epoch is bumped so other
+//! [`Log`]s reload on `sync`. On POSIX systems, readers holding mmaps of the
+//! old files keep working.
+
+use super::{
+    Compression, GenericPath, IndexOutput, Log, LogMetadata, OpenOptions, PRIMARY_FILE,
+    PRIMARY_START_OFFSET,
+};
+use crate::errors::{IoResultExt, ResultExt};
+use crate::lock::ScopedDirLock;
+use std::borrow::Cow;
+use std::fs;
+use std::path::Path;
+
+/// Flush the rewritten log when its in-memory buffer exceeds this size.
+const REWRITE_SYNC_THRESHOLD: u64 = 64 << 20;
+
+/// Stop collecting dictionary training samples once they reach
+/// `dictionary_size * TRAINING_SAMPLE_RATIO` bytes. zstd recommends about
+/// 100x of the dictionary size.
+const TRAINING_SAMPLE_RATIO: usize = 100;
+
+/// Potentially useful context for the compaction filter function.
+pub struct CompactContext<'a> {
+    /// The [`Log`] being compacted. Its indexes are up-to-date.
+    pub log: &'a Log,
+
+    /// Offset of the entry being filtered.
+    offset: u64,
+}
+
+impl<'a> CompactContext<'a> {
+    /// Test if the entry is the most recent entry for at least one of the
+    /// keys it inserted into the given index.
+    ///
+    /// Entries that are superseded by newer entries with the same keys, or
+    /// whose keys were removed, return `false`.
+    pub fn is_latest_in_index(&self, index_id: usize, data: &[u8]) -> crate::Result<bool> {
+        let index = match self.log.indexes.get(index_id) {
+            Some(index) => index,
+            None => {
+                let msg = format!(
+                    "invalid index_id {} (len={})",
+                    index_id,
+                    self.log.indexes.len()
+                );
+                return Err(crate::Error::programming(msg));
+            }
+        };
+        let def = &self.log.open_options.index_defs[index_id];
+        for output in (def.func)(data) {
+            if let IndexOutput::Remove(_) | IndexOutput::RemovePrefix(_) = output {
+                continue;
+            }
+            let key = output.into_cow(data)?;
+            if let Some(offset) = index.get(&key)?.values(index).next() {
+                if offset? == self.offset {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl Log {
+    /// Iterate through on-disk entries, with their offsets.
+    fn iter_on_disk_with_offsets(
+        &self,
+    ) -> impl Iterator<Item = crate::Result<(u64, Cow<'_, [u8]>)>> + '_ {
+        let mut offset = PRIMARY_START_OFFSET;
+        std::iter::from_fn(move || {
+            let entry = match Log::read_entry_from_buf(&self.dir, &self.disk_buf, offset) {
+                Ok(Some(entry)) => entry,
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            };
+            let entry_offset = offset;
+            offset = entry.next_offset;
+            Some(
+                self.codec
+                    .decode_entry(&entry)
+                    .map(|data| (entry_offset, data)),
+            )
+        })
+    }
+}
+
+impl OpenOptions {
+    /// Rewrite the log at `dir`, keeping only entries selected by `filter`.
+    ///
+    /// `filter` receives the uncompressed entries in insertion order, and
+    /// returns `true` to keep an entry. Use
+    /// [`CompactContext::is_latest_in_index`] to keep entries still
+    /// reachable from indexes.
+    ///
+    /// New entries are written using the current [`Compression`] option.
+    /// Indexes are rebuilt.
+    ///
+    /// To compact a [`Log`] of a [`MultiLog`](crate::multi::MultiLog), pass
+    /// [`Log::path`] while holding [`MultiLog::lock`](crate::multi::MultiLog::lock),
+    /// then call [`MultiLog::write_meta`](crate::multi::MultiLog::write_meta).
+    ///
+    /// Return message useful for human consumption.
+    pub fn compact<F>(&self, dir: impl Into<GenericPath>, mut filter: F) -> crate::Result<String>
+    where
+        F: FnMut(&CompactContext, &[u8]) -> Result<bool, Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let generic_dir = dir.into();
+        let dir = match generic_dir.as_opt_path() {
+            Some(dir) => dir,
+            None => {
+                return Ok(format!(
+                    "{:?} is not on disk. Nothing to compact.\n",
+                    &generic_dir
+                ));
+            }
+        };
+
+        let result: crate::Result<_> = (|| {
+            if !dir.exists() {
+                return Ok(format!("{:?} does not exist. Nothing to compact.\n", dir));
+            }
+
+            let lock = ScopedDirLock::new(dir)?;
+            let log = self
+                .open_with_lock(&generic_dir, &lock)
+                .context("cannot open log for compaction")?;
+
+            let entries = log.iter_on_disk_with_offsets().filter_map(|entry| {
+                let (offset, data) = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => return Some(Err(e)),
+                };
+                let context = CompactContext { log: &log, offset };
+                match filter(&context, &data) {
+                    Ok(true) => Some(Ok(data)),
+                    Ok(false) => None,
+                    Err(err) => Some(Err(crate::Error::wrap(
+                        err,
+                        "failed to run compaction filter function",
+                    ))),
+                }
+            });
+            let zstd_dict = log.meta.zstd_dict.clone();
+            self.replace_entries_with_lock(&generic_dir, &lock, &log.meta, zstd_dict, entries)
+        })();
+
+        result.context(|| format!("in log::OpenOptions::compact({:?})", dir))
+    }
+
+    /// Rewrite all entries of the log at `dir` using the current
+    /// [`Compression`] option.
+    ///
+    /// If `dictionary_size` is set, and compression is enabled, a zstd
+    /// dictionary of at most that size is trained from existing entries and
+    /// stored in the log metadata.
+    ///
+    /// Indexes are rebuilt. [`Log`]s of a [`MultiLog`](crate::multi::MultiLog)
+    /// are rewritten like [`OpenOptions::compact`] does.
+    ///
+    /// Return message useful for human consumption.
+    pub fn rewrite_compression(
+        &self,
+        dir: impl Into<GenericPath>,
+        dictionary_size: Option<usize>,
+    ) -> crate::Result<String> {
+        let generic_dir = dir.into();
+        let dir = match generic_dir.as_opt_path() {
+            Some(dir) => dir,
+            None => {
+                return Ok(format!(
+                    "{:?} is not on disk. Nothing to rewrite.\n",
+                    &generic_dir
+                ));
+            }
+        };
+
+        let result: crate::Result<_> = (|| {
+            if !dir.exists() {
+                return Ok(format!("{:?} does not exist. Nothing to rewrite.\n", dir));
+            }
+
+            let mut message = String::new();
+            let lock = ScopedDirLock::new(dir)?;
+            let log = self
+                .clone()
+                .index_defs(Vec::new())
+                .open_with_lock(&generic_dir, &lock)
+                .context("cannot open log for rewrite")?;
+
+            // Train a dictionary from existing entries.
+            let zstd_dict = match (self.compression, dictionary_size) {
+                (Compression::Zstd { .. }, Some(dictionary_size)) => {
+                    let mut samples = Vec::new();
+                    let mut sample_size = 0;
+                    for entry in log.iter_on_disk_with_offsets() {
+                        let (_offset, data) = entry?;
+                        sample_size += data.len();
+                        samples.push(data.into_owned());
+                        if sample_size >= dictionary_size * TRAINING_SAMPLE_RATIO {
+                            break;
+                        }
+                    }
+                    // Training fails if there are too few samples. That is
+                    // not fatal. Entries are compressed without a dictionary.
+                    match zstd::dict::from_samples(&samples, dictionary_size) {
+                        Ok(dict) => {
+                            message += &format!(
+                                "Trained {}-byte dictionary from {} entries\n",
+                                dict.len(),
+                                samples.len()
+                            );
+                            Some(dict)
+                        }
+                        Err(e) => {
+                            message += &format!("Skipped dictionary training: {}\n", e);
+                            None
+                        }
+                    }
+                }
+                _ => None,
+            };
+
+            let entries = log
+                .iter_on_disk_with_offsets()
+                .map(|entry| entry.map(|(_offset, data)| data));
+            message += &self.replace_entries_with_lock(
+                &generic_dir,
+                &lock,
+                &log.meta,
+                zstd_dict,
+                entries,
+            )?;
+            Ok(message)
+        })();
+
+        result.context(|| format!("in log::OpenOptions::rewrite_compression({:?})", dir))
+    }
+
+    /// Replace entries of the log at `dir` with `entries`, and rebuild
+    /// indexes. `old_meta` is the current metadata of the log.
+    ///
+    /// Writing the metadata pointing to the new primary log is the only
+    /// step that changes the log. If anything fails before that, the log
+    /// keeps its old entries. Shared metadata is updated in memory. The
+    /// owner of it is responsible for writing it.
+    fn replace_entries_with_lock<'a>(
+        &self,
+        generic_dir: &GenericPath,
+        lock: &ScopedDirLock,
+        old_meta: &LogMetadata,
+        zstd_dict: Option<Vec<u8>>,
+        entries: impl Iterator<Item = crate::Result<Cow<'a, [u8]>>>,
+    ) -> crate::Result<String> {
+        let dir = lock.path();
+        let (meta, entry_count) =
+            self.write_rewritten_primary_with_lock(dir, lock, old_meta, zstd_dict, entries)?;
+        generic_dir.write_meta(&meta, self.fsync)?;
+        let mut message = format!(
+            "Rewrote {} entries, {} bytes to {} bytes\n",
+            entry_count, old_meta.primary_len, meta.primary_len
+        );
+
+        // The replaced primary log is kept, since readers might have loaded
+        // the old metadata without the lock. Older ones are removed.
+        remove_stale_primary_files(dir, &[&meta, old_meta]);
+
+        // Rebuild indexes. Without this, indexes are empty until the next
+        // `sync`, which can lead to bad performance.
+        let mut log = self
+            .clone()
+            .index_defs(Vec::new())
+            .open_with_lock(generic_dir, lock)?;
+        log.open_options.index_defs = self.index_defs.clone();
+        message += &log
+            .rebuild_indexes_with_lock(true, lock)
+            .context("while trying to rebuild indexes for rewritten log")?;
+
+        Ok(message)
+    }
+
+    /// Write `entries` to a new primary log file in `dir`, under a name not
+    /// used by the current log. Return the metadata pointing to it, with a
+    /// bumped epoch, and the number of entries written.
+    ///
+    /// The log at `dir` does not change until the metadata is written.
+    pub(super) fn write_rewritten_primary_with_lock<'a>(
+        &self,
+        dir: &Path,
+        _lock: &ScopedDirLock,
+        old_meta: &LogMetadata,
+        zstd_dict: Option<Vec<u8>>,
+        entries: impl Iterator<Item = crate::Result<Cow<'a, [u8]>>>,
+    ) -> crate::Result<(LogMetadata, usize)> {
+        // Write entries to a new log in a temporary directory.
+        let tmp = tempfile::tempdir_in(dir).context(dir, "cannot create tempdir")?;
+        let tmp_dir = GenericPath::from(tmp.path());
+        let mut tmp_meta = Log::load_or_create_meta(&tmp_dir, true)?;
+        tmp_meta.zstd_dict = zstd_dict.clone();
+        tmp_dir.write_meta(&tmp_meta, false)?;
+        let mut new_log = OpenOptions::new()
+            .checksum_type(self.checksum_type)
+            .compression(self.compression)
+            .fsync(self.fsync)
+            .auto_sync_threshold(REWRITE_SYNC_THRESHOLD)
+            .open(tmp_dir)?;
+        let mut entry_count = 0;
+        for data in entries {
+            new_log.append(data?)?;
+            entry_count += 1;
+        }
+        let new_len = new_log.sync()?;
+        let compressed = new_log.meta.compressed;
+        drop(new_log);
+
+        let mut meta = LogMetadata::new_with_primary_len(new_len);
+        meta.epoch = old_meta.epoch.wrapping_add(1);
+        meta.compressed = compressed;
+        meta.zstd_dict = zstd_dict;
+        meta.primary_name = Some(format!("{}.{}", PRIMARY_FILE, meta.epoch));
+        let primary_path = meta.primary_path(dir);
+        fs::rename(tmp.path().join(PRIMARY_FILE), &primary_path)
+            .context(&primary_path, "cannot move rewritten log")?;
+
+        Ok((meta, entry_count))
+    }
+}
+
+/// Remove primary log files in `dir` that are not used by any of `metas`:
+/// logs replaced by earlier rewrites, and logs left by interrupted rewrites.
+///
+/// Errors are ignored. For example, files that are still mmapped cannot be
+/// removed on Windows. They will be removed by a later rewrite.
+fn remove_stale_primary_files(dir: &Path, metas: &[&LogMetadata]) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        let is_primary = match entry.file_name().to_str() {
+            Some(name) if name == PRIMARY_FILE => true,
+            Some(name) => match name
+                .strip_prefix(PRIMARY_FILE)
+                .and_then(|suffix| suffix.strip_prefix('.'))
+            {
+                Some(epoch) => epoch.parse::<u64>().is_ok(),
+                None => false,
+            },
+            None => false,
+        };
+        if is_primary && metas.iter().all(|meta| meta.primary_path(dir) != path) {
+            let _ = fs::remove_file(&path);
+        }
+    }
+}
@@ -160,7 +160,7 @@ fn test_iter_and_iter_dirty() {
 
     assert_eq!(
         log.iter().collect::<crate::Result<Vec<_>>>().unwrap(),
-        vec![b"2", b"4", b"3"]
+        vec![&b"2"[..], &b"4"[..], &b"3"[..]]
     );
     assert_eq!(
         log.iter().collect::<crate::Result<Vec<_>>>().unwrap(),
@@ -177,18 +177,18 @@ fn test_iter_and_iter_dirty() {
     );
     assert_eq!(
         log.iter().collect::<crate::Result<Vec<_>>>().unwrap(),
-        vec![b"2", b"4", b"3"]
+        vec![&b"2"[..], &b"4"[..], &b"3"[..]]
     );
 
     log.append(b"5").unwrap();
     log.append(b"1").unwrap();
     assert_eq!(
         log.iter_dirty().collect::<crate::Result<Vec<_>>>().unwrap(),
-        vec![b"5", b"1"]
+        vec![&b"5"[..], &b"1"[..]]
     );
     assert_eq!(
         log.iter().collect::<crate::Result<Vec<_>>>().unwrap(),
-        vec![b"2", b"4", b"3", b"5", b"1"]
+        vec![&b"2"[..], &b"4"[..], &b"3"[..], &b"5"[..], &b"1"[..]]
     );
 }
 
@@ -248,7 +248,8 @@ fn test_slice_to_bytes() {
     log.append(b"1231516").unwrap();
     log.sync().unwrap();
 
-    let slice = log.lookup(0, b"23").unwrap().into_vec().unwrap()[0];
+    let entries = log.lookup(0, b"23").unwrap().into_vec().unwrap();
+    let slice: &[u8] = &entries[0];
     assert_eq!(slice, b"1231516");
 
     // The bytes are zero-copy from the Log buffer.
@@ -342,16 +343,22 @@ fn test_index_manual() {
         // Lookups via index 0
         assert_eq!(
             log.lookup(0, b"34").unwrap().into_vec().unwrap(),
-            [b"3456", b"2345"]
+            [&b"3456"[..], &b"2345"[..]]
+        );
+        assert_eq!(
+            log.lookup(0, b"56").unwrap().into_vec().unwrap(),
+            [&b"3456"[..]]
+        );
+        assert_eq!(
+            log.lookup(0, b"78").unwrap().into_vec().unwrap(),
+            [&b"78"[..]]
         );
-        assert_eq!(log.lookup(0, b"56").unwrap().into_vec().unwrap(), [b"3456"]);
-        assert_eq!(log.lookup(0, b"78").unwrap().into_vec().unwrap(), [b"78"]);
         assert!(log.lookup(0, b"89").unwrap().into_vec().unwrap().is_empty());
 
         // Lookups via index 1
         assert_eq!(
             log.lookup(1, b"345").unwrap().into_vec().unwrap(),
-            [b"3456", b"2345"]
+            [&b"3456"[..], &b"2345"[..]]
         );
 
         log.sync().unwrap();
@@ -361,7 +368,10 @@ fn test_index_manual() {
         for key in [b"34", b"35"].iter() {
             assert!(log.lookup(0, key).unwrap().into_vec().unwrap().is_empty());
         }
-        assert_eq!(log.lookup(0, b"56").unwrap().into_vec().unwrap(), [b"3456"]);
+        assert_eq!(
+            log.lookup(0, b"56").unwrap().into_vec().unwrap(),
+            [&b"3456"[..]]
+        );
 
         // Delete keys.
         let mut log = Log::open(dir.path(), get_index_defs(lag)).unwrap();
@@ -397,7 +407,7 @@ fn test_index_reorder() {
     log = Log::open(dir.path(), indexes).unwrap();
     assert_eq!(
         log.lookup(1, b"23").unwrap().into_vec().unwrap(),
-        [b"234", b"123"]
+        [&b"234"[..], &b"123"[..]]
     );
 }
 
@@ -464,7 +474,7 @@ fn test_lookup_prefix_and_range() {
             .1
             .collect::<Result<Vec<_>, _>>()
             .unwrap(),
-        vec![b"bb", b"bb"]
+        vec![&b"bb"[..], &b"bb"[..]]
     );
     assert_eq!(iter.next().unwrap().unwrap().0.as_ref(), b"aa");
     assert!(iter.next().is_none());
@@ -686,7 +696,7 @@ fn test_flush_filter() {
         .create(true)
         .flush_filter(Some(|ctx: &FlushFilterContext, bytes: &[u8]| {
             // "new" changes by log2 are visible.
-            assert_eq!(ctx.log.iter().nth(0).unwrap().unwrap(), b"log2");
+            assert_eq!(ctx.log.iter().nth(0).unwrap().unwrap().as_ref(), b"log2");
             Ok(match bytes.len() {
                 1 => FlushFilterOutput::Drop,
                 2 => FlushFilterOutput::Replace(b"cc".to_vec()),
@@ -945,7 +955,7 @@ fn test_repair() {
     let mut log = Log::open(dir.path(), Vec::new()).unwrap();
     assert_eq!(
         log.iter().collect::<Result<Vec<_>, _>>().unwrap(),
-        vec![b"abc", b"def"]
+        vec![&b"abc"[..], &b"def"[..]]
     );
 
     // Writing is recovered.
@@ -955,7 +965,7 @@ fn test_repair() {
     let log = Log::open(dir.path(), Vec::new()).unwrap();
     assert_eq!(
         log.iter().collect::<Result<Vec<_>, _>>().unwrap(),
-        vec![b"abc", b"def", b"pqr"]
+        vec![&b"abc"[..], &b"def"[..], &b"pqr"[..]]
     );
 }
 
@@ -1407,7 +1417,7 @@ fn test_clear_dirty() {
         log.clear_dirty().unwrap();
         assert_eq!(
             log.iter().collect::<Result<Vec<_>, _>>().unwrap(),
-            vec![[b'a'; 10]],
+            vec![&[b'a'; 10][..]],
         );
         assert_eq!(log.lookup_range(0, ..).unwrap().count(), 1);
     }
@@ -1437,6 +1447,305 @@ fn test_clone() {
     }
 }
 
+#[cfg(unix)]
+#[test]
+fn test_compact() {
+    // This test requires replacing the log file while mmap is present. That
+    // cannot be done in Windows.
+    //
+    // Entries are "key=value", or "-key" to remove a key.
+    fn index_kv(data: &[u8]) -> Vec<IndexOutput> {
+        match data.iter().position(|&b| b == b'=') {
+            Some(pos) => vec![IndexOutput::Reference(0..pos as u64)],
+            None => vec![IndexOutput::Remove(data[1..].to_vec().into_boxed_slice())],
+        }
+    }
+
+    let dir = tempdir().unwrap();
+    let open_opts = OpenOptions::new()
+        .create(true)
+        .index_defs(vec![IndexDef::new("kv", index_kv).lag_threshold(0)]);
+    let mut log = open_opts.open(dir.path()).unwrap();
+    for entry in &["a=1", "b=1", "a=2", "-b", "c=1"] {
+        log.append(entry).unwrap();
+    }
+    log.sync().unwrap();
+
+    // Drop removed and superseded entries.
+    let message = open_opts
+        .compact(dir.path(), |ctx, data| Ok(ctx.is_latest_in_index(0, data)?))
+        .unwrap();
+    assert!(message.contains("Rewrote 2 entries"), "{}", message);
+    let meta = LogMetadata::read_file(dir.path().join(META_FILE)).unwrap();
+    assert_eq!(
+        meta.primary_name,
+        Some(format!("{}.{}", PRIMARY_FILE, meta.epoch))
+    );
+
+    // Readers holding the old log keep working until sync.
+    assert_eq!(log.iter().count(), 5);
+    assert_eq!(
+        log.lookup(0, "a")
+            .unwrap()
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap(),
+        vec![&b"a=2"[..], &b"a=1"[..]],
+    );
+    log.sync().unwrap();
+    assert_eq!(
+        log.iter().collect::<crate::Result<Vec<_>>>().unwrap(),
+        vec![&b"a=2"[..], &b"c=1"[..]],
+    );
+    assert_eq!(
+        log.lookup(0, "a")
+            .unwrap()
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap(),
+        vec![&b"a=2"[..]],
+    );
+    assert_eq!(log.lookup(0, "b").unwrap().count(), 0);
+
+    // Entries can be selected by content. Errors abort the compaction.
+    let err = open_opts
+        .compact(dir.path(), |_ctx, _data| Err("x".into()))
+        .unwrap_err();
+    assert!(err.to_string().contains("compaction filter"), "{}", err);
+    open_opts
+        .compact(dir.path(), |_ctx, data| Ok(data != b"c=1"))
+        .unwrap();
+    let log = open_opts.open(dir.path()).unwrap();
+    assert_eq!(
+        log.iter().collect::<crate::Result<Vec<_>>>().unwrap(),
+        vec![&b"a=2"[..]],
+    );
+    assert_eq!(log.lookup(0, "c").unwrap().count(), 0);
+}
+
+#[test]
+fn test_rewrite_interrupted() {
+    let dir = tempdir().unwrap();
+    let index_func = |data: &[u8]| vec![IndexOutput::Reference(0..data.len() as u64)];
+    let open_opts = OpenOptions::new()
+        .create(true)
+        .index_defs(vec![IndexDef::new("i", index_func).lag_threshold(0)]);
+    let mut log = open_opts.open(dir.path()).unwrap();
+    for entry in &["a", "b", "c"] {
+        log.append(entry).unwrap();
+    }
+    log.sync().unwrap();
+    drop(log);
+
+    let primary_files = || {
+        let mut names = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.starts_with(PRIMARY_FILE))
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    };
+
+    // Fail after writing the new primary log, before writing metadata.
+    let lock = ScopedDirLock::new(dir.path()).unwrap();
+    let meta = Log::load_or_create_meta(&dir.path().into(), false).unwrap();
+    let entries = vec![Ok(Cow::Borrowed(&b"a"[..]))].into_iter();
+    let (new_meta, entry_count) = open_opts
+        .write_rewritten_primary_with_lock(dir.path(), &lock, &meta, None, entries)
+        .unwrap();
+    assert_eq!(entry_count, 1);
+    assert!(new_meta.primary_path(dir.path()).exists());
+    drop(lock);
+
+    // The log is unchanged.
+    let log = open_opts.open(dir.path()).unwrap();
+    assert_eq!(log.meta, meta);
+    assert_eq!(
+        log.iter().collect::<crate::Result<Vec<_>>>().unwrap(),
+        vec![&b"a"[..], &b"b"[..], &b"c"[..]],
+    );
+    assert_eq!(log.lookup(0, "b").unwrap().count(), 1);
+    drop(log);
+
+    // Later rewrites succeed. The replaced primary log is kept for readers
+    // that loaded the old metadata. Older ones are removed.
+    open_opts
+        .compact(dir.path(), |_ctx, data| Ok(data != b"b"))
+        .unwrap();
+    assert_eq!(primary_files().len(), 2);
+    open_opts
+        .compact(dir.path(), |_ctx, data| Ok(data != b"c"))
+        .unwrap();
+    let log = open_opts.open(dir.path()).unwrap();
+    let mut expected = vec![
+        format!("{}.{}", PRIMARY_FILE, meta.epoch.wrapping_add(1)),
+        format!("{}.{}", PRIMARY_FILE, log.meta.epoch),
+    ];
+    expected.sort();
+    assert_eq!(primary_files(), expected);
+    assert_eq!(
+        log.iter().collect::<crate::Result<Vec<_>>>().unwrap(),
+        vec![&b"a"[..]],
+    );
+    assert_eq!(log.lookup(0, "b").unwrap().count(), 0);
+}
+
+fn compressible_entry(i: usize) -> Vec<u8> {
+    format!(
+        "{:08}: {}",
+        i,
+        "the quick brown fox jumps over the lazy dog ".repeat(4)
+    )
+    .into_bytes()
+}
+
+fn check_compressible_entries(log: &Log, n: usize) {
+    assert_eq!(
+        log.iter().collect::<crate::Result<Vec<_>>>().unwrap(),
+        (0..n).map(compressible_entry).collect::<Vec<_>>(),
+    );
+    for i in 0..n {
+        let key = format!("{:08}", i);
+        let found = log
+            .lookup(0, key.as_bytes())
+            .unwrap()
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(found, vec![&compressible_entry(i)[..]]);
+    }
+}
+
+#[test]
+fn test_compression() {
+    let dir = tempdir().unwrap();
+    let index_func = |_data: &[u8]| vec![IndexOutput::Reference(0..8)];
+    let open_opts = OpenOptions::new()
+        .create(true)
+        .compression(Compression::Zstd { level: 3 })
+        .index_defs(vec![IndexDef::new("i", index_func).lag_threshold(0)]);
+    let mut log = open_opts.open(dir.path()).unwrap();
+
+    // Both in-memory and on-disk entries are readable via iter and indexes.
+    for i in 0..10 {
+        log.append(compressible_entry(i)).unwrap();
+    }
+    check_compressible_entries(&log, 10);
+    log.sync().unwrap();
+    for i in 10..20 {
+        log.append(compressible_entry(i)).unwrap();
+    }
+    check_compressible_entries(&log, 20);
+    log.clear_dirty().unwrap();
+    check_compressible_entries(&log, 10);
+
+    // Entries are compressed on disk. Indexes built from on-disk entries
+    // see raw data.
+    let len = dir.path().join(PRIMARY_FILE).metadata().unwrap().len();
+    assert!(len < 10 * compressible_entry(0).len() as u64);
+    let mut log = open_opts
+        .clone()
+        .index_defs(vec![IndexDef::new("j", index_func).lag_threshold(0)])
+        .open(dir.path())
+        .unwrap();
+    check_compressible_entries(&log, 10);
+
+    // Short entries that do not compress are stored as-is.
+    log.append(b"abcdefgh").unwrap();
+    assert_eq!(log.iter().last().unwrap().unwrap().as_ref(), b"abcdefgh");
+    log.sync().unwrap();
+
+    // A Log without the compression option can read compressed entries.
+    let log = Log::open(dir.path(), Vec::new()).unwrap();
+    assert_eq!(log.iter().count(), 11);
+}
+
+#[test]
+fn test_compression_meta_header() {
+    let read_header = |dir: &Path| {
+        let mut header = utils::atomic_read(&dir.join(META_FILE)).unwrap();
+        header.truncate(5);
+        header
+    };
+
+    // Logs without compressed entries stay readable by older readers.
+    let dir = tempdir().unwrap();
+    let mut log = Log::open(dir.path(), Vec::new()).unwrap();
+    log.append(compressible_entry(0)).unwrap();
+    log.sync().unwrap();
+    assert_eq!(read_header(dir.path()), b"meta\0");
+
+    // Logs with compressed entries use a header older readers reject.
+    let mut log = OpenOptions::new()
+        .compression(Compression::Zstd { level: 3 })
+        .open(dir.path())
+        .unwrap();
+    log.append(compressible_entry(1)).unwrap();
+    log.sync().unwrap();
+    assert_eq!(read_header(dir.path()), b"meta\x01");
+
+    // The flag is kept by writers without the compression option.
+    let mut log = Log::open(dir.path(), Vec::new()).unwrap();
+    log.append(compressible_entry(2)).unwrap();
+    log.sync().unwrap();
+    assert_eq!(read_header(dir.path()), b"meta\x01");
+    assert!(log.meta.compressed);
+
+    // Repair restores the flag if metadata is rebuilt.
+    fs::remove_file(dir.path().join(META_FILE)).unwrap();
+    let message = OpenOptions::new().repair(dir.path()).unwrap();
+    assert!(message.contains("Marked log as compressed"), "{}", message);
+    assert_eq!(read_header(dir.path()), b"meta\x01");
+    let log = Log::open(dir.path(), Vec::new()).unwrap();
+    assert_eq!(log.iter().count(), 3);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_rewrite_compression() {
+    // This test requires replacing the log file while mmap is present. That
+    // cannot be done in Windows.
+    let dir = tempdir().unwrap();
+    let index_func = |_data: &[u8]| vec![IndexOutput::Reference(0..8)];
+    let open_opts = OpenOptions::new()
+        .create(true)
+        .index_defs(vec![IndexDef::new("i", index_func).lag_threshold(0)]);
+    let mut log = open_opts.open(dir.path()).unwrap();
+    for i in 0..200 {
+        log.append(compressible_entry(i)).unwrap();
+    }
+    log.sync().unwrap();
+    let len_before = dir.path().join(PRIMARY_FILE).metadata().unwrap().len();
+
+    // Rewrite with a trained dictionary.
+    let zstd_opts = open_opts
+        .clone()
+        .compression(Compression::Zstd { level: 3 });
+    let message = zstd_opts
+        .rewrite_compression(dir.path(), Some(1024))
+        .unwrap();
+    assert!(message.contains("Rewrote 200 entries"), "{}", message);
+    let meta = LogMetadata::read_file(dir.path().join(META_FILE)).unwrap();
+    let len_after = meta.primary_path(dir.path()).metadata().unwrap().len();
+    assert!(len_after < len_before);
+
+    // The existing Log picks up the rewritten log on sync. Its pending
+    // entries are kept.
+    log.append(compressible_entry(200)).unwrap();
+    log.sync().unwrap();
+    check_compressible_entries(&log, 201);
+
+    let mut log = zstd_opts.open(dir.path()).unwrap();
+    check_compressible_entries(&log, 201);
+    log.append(compressible_entry(201)).unwrap();
+    log.sync().unwrap();
+    check_compressible_entries(&log, 202);
+
+    // Rewrite without compression.
+    open_opts.rewrite_compression(dir.path(), None).unwrap();
+    let log = open_opts.open(dir.path()).unwrap();
+    check_compressible_entries(&log, 202);
+    assert!(log.meta.zstd_dict.is_none());
+}
+
 #[test]
 fn test_multithread_sync() {
     let dir = tempdir().unwrap();
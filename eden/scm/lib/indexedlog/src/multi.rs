@@ -439,6 +439,47 @@ mod tests {
         assert_eq!(index_size(), 71);
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_compact_log() {
+        // This test requires replacing the log file while mmap is present.
+        // That cannot be done in Windows.
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path();
+        let mut mlog = simple_multilog(path);
+        for data in &["1", "2", "3"] {
+            mlog[0].append(data).unwrap();
+        }
+        mlog[1].append(b"x").unwrap();
+        mlog.sync().unwrap();
+
+        // Compact via the shared metadata, then write the multimeta.
+        let lock = mlog.lock().unwrap();
+        let log_path = mlog[0].path().clone();
+        log::OpenOptions::new()
+            .compact(log_path, |_ctx, data| Ok(data != b"2"))
+            .unwrap();
+        mlog.write_meta(&lock).unwrap();
+        drop(lock);
+
+        // The multimeta points to the rewritten log.
+        let mlog2 = simple_multilog(path);
+        assert_eq!(
+            mlog2[0].iter().collect::<crate::Result<Vec<_>>>().unwrap(),
+            vec![&b"1"[..], &b"3"[..]],
+        );
+        assert_eq!(mlog2[1].iter().count(), 1);
+
+        // The MultiLog used for compaction picks up the rewritten log on sync.
+        mlog[0].append(b"4").unwrap();
+        mlog.sync().unwrap();
+        let mlog2 = simple_multilog(path);
+        assert_eq!(
+            mlog2[0].iter().collect::<crate::Result<Vec<_>>>().unwrap(),
+            vec![&b"1"[..], &b"3"[..], &b"4"[..]],
+        );
+    }
+
     #[test]
     fn test_wrong_locks_cause_errors() {
         let dir = tempfile::tempdir().unwrap();
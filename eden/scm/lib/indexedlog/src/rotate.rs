@@ -14,6 +14,7 @@ use crate::repair::OpenOptionsRepair;
 use crate::utils;
 use minibytes::Bytes;
 use once_cell::sync::OnceCell;
+use std::borrow::Cow;
 use std::fmt;
 use std::fs;
 use std::io;
@@ -96,6 +97,14 @@ impl OpenOptions {
         self
     }
 
+    /// Sets how new entries are compressed.
+    ///
+    /// See [log::Compression] for details.
+    pub fn compression(mut self, compression: log::Compression) -> Self {
+        self.log_open_options = self.log_open_options.compression(compression);
+        self
+    }
+
     /// Set whether create the [`RotateLog`] structure if it does not exist.
     pub fn create(mut self, create: bool) -> Self {
         self.log_open_options = self.log_open_options.create(create);
@@ -440,7 +449,7 @@ impl RotateLog {
                         for entry in self.writable_log().iter_dirty() {
                             let content = entry?;
                             let context = FlushFilterContext { log };
-                            match filter(&context, content).map_err(|err| {
+                            match filter(&context, &content).map_err(|err| {
                                 crate::Error::wrap(err, "failed to run filter function")
                             })? {
                                 FlushFilterOutput::Drop => {}
@@ -611,13 +620,13 @@ impl RotateLog {
     /// Iterate over all the entries.
     ///
     /// The entries are returned in FIFO order.
-    pub fn iter(&self) -> impl Iterator<Item = crate::Result<&[u8]>> {
+    pub fn iter(&self) -> impl Iterator<Item = crate::Result<Cow<[u8]>>> {
         let logs = self.logs();
         logs.into_iter().rev().flat_map(|log| log.iter())
     }
 
     /// Iterate over all dirty entries.
-    pub fn iter_dirty(&mut self) -> impl Iterator<Item = crate::Result<&[u8]>> {
+    pub fn iter_dirty(&mut self) -> impl Iterator<Item = crate::Result<Cow<[u8]>>> {
         self.writable_log().iter_dirty()
     }
 }
@@ -695,7 +704,7 @@ pub struct RotateLogLookupIter<'a> {
 }
 
 impl<'a> Iterator for RotateLogLookupIter<'a> {
-    type Item = crate::Result<&'a [u8]>;
+    type Item = crate::Result<Cow<'a, [u8]>>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.end {
@@ -884,18 +893,20 @@ mod tests {
     }
 
     // lookup via index 0
-    fn lookup<'a>(rotate: &'a RotateLog, key: &[u8]) -> Vec<&'a [u8]> {
+    fn lookup(rotate: &RotateLog, key: &[u8]) -> Vec<Vec<u8>> {
         rotate
             .lookup(0, key.to_vec())
             .unwrap()
-            .collect::<crate::Result<Vec<&[u8]>>>()
+            .map(|e| e.map(|data| data.into_owned()))
+            .collect::<crate::Result<Vec<_>>>()
             .unwrap()
     }
 
-    fn iter(rotate: &RotateLog) -> Vec<&[u8]> {
+    fn iter(rotate: &RotateLog) -> Vec<Vec<u8>> {
         rotate
             .iter()
-            .collect::<crate::Result<Vec<&[u8]>>>()
+            .map(|e| e.map(|data| data.into_owned()))
+            .collect::<crate::Result<Vec<_>>>()
             .unwrap()
     }
 
@@ -1201,7 +1212,7 @@ mod tests {
             .max_bytes_per_log(100)
             .flush_filter(Some(|ctx, bytes| {
                 // 'aa' is not inserted yet. It should not exist in the log.
-                assert!(!ctx.log.iter().any(|x| x.unwrap() == b"aa"));
+                assert!(!ctx.log.iter().any(|x| x.unwrap().as_ref() == b"aa"));
                 Ok(match bytes.len() {
                     1 => FlushFilterOutput::Replace(b"xx".to_vec()),
                     _ => FlushFilterOutput::Keep,
@@ -1307,13 +1318,13 @@ mod tests {
         );
 
         assert_eq!(
-            rotate.iter().map(|e| e.unwrap()).collect::<Vec<&[u8]>>(),
+            rotate.iter().map(|e| e.unwrap()).collect::<Vec<_>>(),
             vec![&a[..], &b, &a, &a],
         );
 
         rotate.sync().unwrap(); // trigger rotate
         assert_eq!(
-            rotate.iter().map(|e| e.unwrap()).collect::<Vec<&[u8]>>(),
+            rotate.iter().map(|e| e.unwrap()).collect::<Vec<_>>(),
             vec![&b[..], &a, &a],
         );
     }
@@ -175,7 +175,7 @@ impl MetaLog {
         let result = std::iter::once(EMPTY_ROOT_ID.clone())
             .chain(
                 log.iter()
-                    .map(|e| e.ok().and_then(|e| Id20::from_slice(e).ok()))
+                    .map(|e| e.ok().and_then(|e| Id20::from_slice(&e).ok()))
                     .take_while(|s| s.is_some())
                     .map(|s| s.unwrap()),
             )
@@ -415,7 +415,7 @@ fn find_last_root_id(log: &ilog::Log) -> Result<Id20> {
     for entry in log.lookup(INDEX_REVERSE, INDEX_REVERSE_KEY)? {
         // The linked list in the index is in the reversed order.
         // So the first entry contains the last root id.
-        return Ok(Id20::from_slice(entry?)?);
+        return Ok(Id20::from_slice(&entry?)?);
     }
     Ok(EMPTY_ROOT_ID.clone())
 }
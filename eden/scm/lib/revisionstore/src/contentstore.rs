@@ -22,6 +22,7 @@ use configparser::{
     hg::{ByteCount, ConfigSetHgExt},
 };
 use hgtime::HgTime;
+use progress::{null::NullProgressFactory, ProgressFactory};
 use types::{Key, RepoPathBuf};
 
 use crate::{
@@ -29,20 +30,24 @@ use crate::{
         strip_metadata, ContentDataStore, ContentMetadata, Delta, HgIdDataStore,
         HgIdMutableDeltaStore, Metadata, RemoteDataStore, ReportingRemoteDataStore, StoreResult,
     },
+    historystore::HgIdHistoryStore,
     indexedlogdatastore::{IndexedLogDataStoreType, IndexedLogHgIdDataStore},
+    indexedloghistorystore::{IndexedLogHgIdHistoryStore, IndexedLogHistoryStoreType},
     lfs::{LfsFallbackRemoteStore, LfsMultiplexer, LfsRemote, LfsStore},
     localstore::{ExtStoredPolicy, LocalStore},
     memcache::MemcacheStore,
     multiplexstore::MultiplexDeltaStore,
-    packstore::{CorruptionPolicy, MutableDataPackStore},
+    packstore::{CorruptionPolicy, HistoryPackStore, MutableDataPackStore},
     remotestore::HgIdRemoteStore,
     repack::RepackLocation,
     types::StoreKey,
     uniondatastore::{UnionContentDataStore, UnionHgIdDataStore},
+    unionhistorystore::UnionHgIdHistoryStore,
     util::{
         check_run_once, get_cache_packs_path, get_cache_path, get_indexedlogdatastore_path,
-        get_local_path, get_packs_path, RUN_ONCE_FILENAME,
+        get_indexedloghistorystore_path, get_local_path, get_packs_path, RUN_ONCE_FILENAME,
     },
+    verify::{verify_datapacks, VerifyReport},
 };
 
 /// A `ContentStore` aggregate all the local and remote stores and expose them as one. Both local and
@@ -102,6 +107,89 @@ impl ContentStore {
         Ok(repair_str)
     }
 
+    /// Verify that the data of the underlying stores that the `ContentStore` is comprised of
+    /// matches the keys it is stored under. Hashes are checked using the parents found in the
+    /// history stores at the same location.
+    ///
+    /// When `delete_corrupt` is set, corrupt data is removed from the shared stores so it can be
+    /// fetched again. Local data is never removed. As for `repair`, care must be taken to call
+    /// this only when no other `ContentStore` have been created for the `shared_path`.
+    pub fn verify(
+        shared_path: impl AsRef<Path>,
+        local_path: Option<impl AsRef<Path>>,
+        suffix: Option<impl AsRef<Path>>,
+        config: &ConfigSet,
+        delete_corrupt: bool,
+    ) -> Result<VerifyReport> {
+        let suffix = suffix.map(|p| p.as_ref().to_path_buf());
+        let unsuffixed_shared_path = shared_path.as_ref().to_path_buf();
+        let mut shared_path = unsuffixed_shared_path.clone();
+        if let Some(suffix) = suffix.as_ref() {
+            shared_path.push(suffix);
+        }
+        let unsuffixed_local_path = local_path.map(|l| l.as_ref().to_path_buf());
+        let local_path = get_local_path(&unsuffixed_local_path, &suffix)?;
+
+        let mut history: UnionHgIdHistoryStore<Box<dyn HgIdHistoryStore>> =
+            UnionHgIdHistoryStore::new();
+        history.add(Box::new(IndexedLogHgIdHistoryStore::new(
+            get_indexedloghistorystore_path(&shared_path)?,
+            config,
+            IndexedLogHistoryStoreType::Shared,
+        )?));
+        history.add(Box::new(HistoryPackStore::new(
+            get_packs_path(&unsuffixed_shared_path, &suffix)?,
+            CorruptionPolicy::IGNORE,
+            None,
+        )));
+        if let (Some(local_path), Some(unsuffixed_local_path)) =
+            (local_path.as_ref(), unsuffixed_local_path.as_ref())
+        {
+            history.add(Box::new(IndexedLogHgIdHistoryStore::new(
+                get_indexedloghistorystore_path(local_path)?,
+                config,
+                IndexedLogHistoryStoreType::Local,
+            )?));
+            history.add(Box::new(HistoryPackStore::new(
+                get_packs_path(unsuffixed_local_path, &suffix)?,
+                CorruptionPolicy::IGNORE,
+                None,
+            )));
+        }
+
+        let mut report = IndexedLogHgIdDataStore::verify(
+            get_indexedlogdatastore_path(&shared_path)?,
+            config,
+            IndexedLogDataStoreType::Shared,
+            &history,
+            delete_corrupt,
+        )?;
+        report.merge(verify_datapacks(
+            &get_packs_path(&unsuffixed_shared_path, &suffix)?,
+            &history,
+            delete_corrupt,
+        )?);
+        report.merge(LfsStore::verify(&shared_path, config, delete_corrupt)?);
+        if let (Some(local_path), Some(unsuffixed_local_path)) =
+            (local_path.as_ref(), unsuffixed_local_path.as_ref())
+        {
+            report.merge(IndexedLogHgIdDataStore::verify(
+                get_indexedlogdatastore_path(local_path)?,
+                config,
+                IndexedLogDataStoreType::Local,
+                &history,
+                false,
+            )?);
+            report.merge(verify_datapacks(
+                &get_packs_path(unsuffixed_local_path, &suffix)?,
+                &history,
+                false,
+            )?);
+        }
+
+        Ok(report)
+    }
+
     /// Some blobs may contain copy-from metadata, let's strip it. For more details about the
     /// copy-from metadata, see `datastore::strip_metadata`.
     ///
@@ -258,6 +346,7 @@ pub struct ContentStoreBuilder<'a> {
     suffix: Option<PathBuf>,
     memcachestore: Option<Arc<MemcacheStore>>,
     correlator: Option<String>,
+    progress: Arc<dyn ProgressFactory>,
 }
 
 impl<'a> ContentStoreBuilder<'a> {
@@ -270,6 +359,7 @@ impl<'a> ContentStoreBuilder<'a> {
             memcachestore: None,
             suffix: None,
             correlator: None,
+            progress: NullProgressFactory::arc(),
         }
     }
 
@@ -308,6 +398,12 @@ impl<'a> ContentStoreBuilder<'a> {
         self
     }
 
+    /// Progress bars to report the upload of LFS blobs.
+    pub fn progress(mut self, progress: Arc<dyn ProgressFactory>) -> Self {
+        self.progress = progress;
+        self
+    }
+
     pub fn build(self) -> Result<ContentStore> {
         let local_path = get_local_path(&self.local_path, &self.suffix)?;
         let cache_path = get_cache_path(self.config, &self.suffix)?;
@@ -505,6 +601,7 @@ impl<'a> ContentStoreBuilder<'a> {
                     local_lfs_store,
                     self.config,
                     self.correlator,
+                    self.progress,
                 )?);
                 remotestores.add(lfs_remote_store.datastore(shared_store.clone()));
 
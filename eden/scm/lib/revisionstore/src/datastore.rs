@@ -67,6 +67,12 @@ pub trait HgIdMutableDeltaStore: HgIdDataStore + Send + Sync {
     fn add(&self, delta: &Delta, metadata: &Metadata) -> Result<()>;
     fn flush(&self) -> Result<Option<Vec<PathBuf>>>;
 
+    /// Return the copy-from information for a file that the store knows about without holding its
+    /// content, e.g. an LFS pointer whose blob has not been fetched yet.
+    fn get_copy_from(&self, key: StoreKey) -> Result<StoreResult<Option<Key>>> {
+        Ok(StoreResult::NotFound(key))
+    }
+
     fn add_file(&self, entry: &FileEntry) -> Result<()> {
         let delta = Delta {
             data: entry.data()?.into(),
@@ -154,6 +160,10 @@ impl<T: HgIdMutableDeltaStore + ?Sized, U: Deref<Target = T> + Send + Sync> HgId
     fn flush(&self) -> Result<Option<Vec<PathBuf>>> {
         T::flush(self)
     }
+
+    fn get_copy_from(&self, key: StoreKey) -> Result<StoreResult<Option<Key>>> {
+        T::get_copy_from(self, key)
+    }
 }
 
 /// Implement `ContentDataStore` for all types that can be `Deref` into a `ContentDataStore`.
@@ -218,6 +228,35 @@ pub fn strip_metadata(data: &Bytes) -> Result<(Bytes, Option<Key>)> {
     }
 }
 
+/// When a file was copied, Mercurial expects the blob that the store returns to contain this copy
+/// information. This is the inverse of `strip_metadata`.
+pub fn rebuild_metadata(data: Bytes, copy_from: Option<&Key>) -> Bytes {
+    if let Some(copy_from) = copy_from {
+        let copy_from_path: &[u8] = copy_from.path.as_ref();
+        let mut ret = Vec::with_capacity(data.len() + copy_from_path.len() + 128);
+
+        ret.extend_from_slice(&b"\x01\n"[..]);
+        ret.extend_from_slice(&b"copy: "[..]);
+        ret.extend_from_slice(copy_from_path);
+        ret.extend_from_slice(&b"\n"[..]);
+        ret.extend_from_slice(&b"copyrev: "[..]);
+        ret.extend_from_slice(copy_from.hgid.to_hex().as_bytes());
+        ret.extend_from_slice(&b"\n"[..]);
+        ret.extend_from_slice(&b"\x01\n"[..]);
+        ret.extend_from_slice(data.as_ref());
+        ret.into()
+    } else {
+        if data.as_ref().starts_with(b"\x01\n") {
+            let mut ret = Vec::with_capacity(data.len() + 4);
+            ret.extend_from_slice(&b"\x01\n\x01\n"[..]);
+            ret.extend_from_slice(data.as_ref());
+            ret.into()
+        } else {
+            data
+        }
+    }
+}
+
 pub struct ReportingRemoteDataStore {
     store: Box<dyn RemoteDataStore>,
     filter: Option<Regex>,
@@ -5,21 +5,28 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use anyhow::Result;
 use futures::prelude::*;
 
 use async_runtime::block_on_exclusive as block_on_future;
+use edenapi::FetchMonitor;
+use edenapi_types::{FileContentId, Sha256 as EdenApiSha256};
 use progress::Unit;
+use types::{Key, Sha256};
 
 use crate::{
-    datastore::{HgIdDataStore, HgIdMutableDeltaStore, Metadata, RemoteDataStore, StoreResult},
+    datastore::{
+        rebuild_metadata, Delta, HgIdDataStore, HgIdMutableDeltaStore, Metadata, RemoteDataStore,
+        StoreResult,
+    },
     localstore::LocalStore,
-    types::StoreKey,
+    types::{ContentHash, StoreKey},
 };
 
-use super::{hgid_keys, EdenApiRemoteStore, EdenApiStoreKind, File, Tree};
+use super::{content_keys, hgid_keys, EdenApiRemoteStore, EdenApiStoreKind, File, Tree};
 
 /// A data store backed by an `EdenApiRemoteStore` and a mutable store.
 ///
@@ -45,21 +52,98 @@ impl RemoteDataStore for EdenApiDataStore<File> {
         let client = self.remote.client.clone();
         let repo = self.remote.repo.clone();
         let progress = self.remote.progress.clone();
-        let hgidkeys = hgid_keys(keys);
+        let mut hgidkeys = hgid_keys(keys);
+
+        // Identical content may be requested for several keys, so group the
+        // content-addressed keys by hash to fetch each distinct blob once.
+        // Content fetched by hash carries no copy metadata, so only keys for
+        // which the store already knows it can be fetched that way; the rest
+        // are fetched by Mercurial key instead.
+        let mut contentkeys: HashMap<Sha256, Vec<(Key, Option<Key>)>> = HashMap::new();
+        for (sha256, key) in content_keys(keys) {
+            let lookup = StoreKey::Content(ContentHash::Sha256(sha256), Some(key.clone()));
+            match self.store.get_copy_from(lookup)? {
+                StoreResult::Found(copy_from) => contentkeys
+                    .entry(sha256)
+                    .or_default()
+                    .push((key, copy_from)),
+                StoreResult::NotFound(_) => hgidkeys.push(key),
+            }
+        }
 
         let fetch = async move {
             let prog = progress.bar(
                 "Downloading files over HTTP",
-                Some(hgidkeys.len() as u64),
+                Some((hgidkeys.len() + contentkeys.len()) as u64),
                 Unit::Named("files"),
             )?;
-
-            let mut response = File::prefetch_files(client, repo, hgidkeys, None).await?;
-            while let Some(entry) = response.entries.try_next().await? {
+            let monitor = FetchMonitor::with_progress_bars(prog, None);
+
+            let response = File::prefetch_files(
+                client.clone(),
+                repo.clone(),
+                hgidkeys,
+                Some(monitor.transfer_callback()),
+            )
+            .await?;
+            let mut entries = monitor.monitor(response).entries;
+            while let Some(entry) = entries.try_next().await? {
                 self.store.add_file(&entry)?;
-                prog.increment(1)?;
             }
-            self.store.get_missing(keys)
+
+            if !contentkeys.is_empty() {
+                let ids = contentkeys
+                    .keys()
+                    .map(|sha256| FileContentId::Sha256(EdenApiSha256(sha256.into_inner())))
+                    .collect();
+                let response = File::prefetch_files_by_content(
+                    client,
+                    repo,
+                    ids,
+                    Some(monitor.transfer_callback()),
+                )
+                .await?;
+                let mut entries = monitor.monitor(response).entries;
+                while let Some(entry) = entries.try_next().await? {
+                    let sha256 = match entry.id {
+                        FileContentId::Sha256(sha256) => Sha256::from_byte_array(sha256.0),
+                        _ => continue,
+                    };
+                    let metadata = Metadata {
+                        size: Some(entry.data.len() as u64),
+                        flags: None,
+                    };
+                    for (key, copy_from) in contentkeys.remove(&sha256).unwrap_or_default() {
+                        let delta = Delta {
+                            data: rebuild_metadata(entry.data.clone().into(), copy_from.as_ref()),
+                            base: None,
+                            key,
+                        };
+                        self.store.add(&delta, &metadata)?;
+                    }
+                }
+            }
+
+            // The mutable store is keyed by Mercurial key, so check content
+            // keys for the presence of the key they were fetched for.
+            let lookup_keys = keys
+                .iter()
+                .map(|k| match k {
+                    StoreKey::Content(_, Some(k)) => StoreKey::hgid(k.clone()),
+                    k => k.clone(),
+                })
+                .collect::<Vec<_>>();
+            let missing = self
+                .store
+                .get_missing(&lookup_keys)?
+                .into_iter()
+                .collect::<HashSet<_>>();
+            Ok(keys
+                .iter()
+                .zip(lookup_keys.iter())
+                .filter(|(_, lookup_key)| missing.contains(lookup_key))
+                .map(|(k, _)| k.clone())
+                .collect())
         };
 
         block_on_future(fetch)
@@ -84,11 +168,19 @@ impl RemoteDataStore for EdenApiDataStore<Tree> {
                 Some(hgidkeys.len() as u64),
                 Unit::Named("trees"),
             )?;
-
-            let mut response = Tree::prefetch_trees(client, repo, hgidkeys, None, None).await?;
-            while let Some(Ok(entry)) = response.entries.try_next().await? {
+            let monitor = FetchMonitor::with_progress_bars(prog, None);
+
+            let response = Tree::prefetch_trees(
+                client,
+                repo,
+                hgidkeys,
+                None,
+                Some(monitor.transfer_callback()),
+            )
+            .await?;
+            let mut entries = monitor.monitor(response).entries;
+            while let Some(Ok(entry)) = entries.try_next().await? {
                 self.store.add_tree(&entry)?;
-                prog.increment(1)?;
             }
             self.store.get_missing(keys)
         };
@@ -144,9 +236,8 @@ impl<T: EdenApiStoreKind> LocalStore for EdenApiDataStore<T> {
 mod tests {
     use super::*;
 
-    use std::collections::HashMap;
-
     use maplit::hashmap;
+    use minibytes::Bytes;
     use tempfile::TempDir;
 
     use configparser::config::ConfigSet;
@@ -155,9 +246,11 @@ mod tests {
     use crate::{
         edenapi::{File, Tree},
         indexedlogdatastore::{IndexedLogDataStoreType, IndexedLogHgIdDataStore},
+        lfs::{LfsMultiplexer, LfsStore},
         localstore::ExtStoredPolicy,
         remotestore::HgIdRemoteStore,
         testutil::*,
+        types::ContentHash,
     };
 
     #[test]
@@ -227,6 +320,132 @@ mod tests {
         Ok(())
     }
 
+    /// Add an LFS pointer for `content` to the store, without the blob itself.
+    fn add_pointer(
+        store: &LfsMultiplexer,
+        key: &Key,
+        content: &Bytes,
+        copy_from: Option<&Key>,
+    ) -> Result<()> {
+        let mut pointer = format!(
+            "version https://git-lfs.github.com/spec/v1\noid sha256:{}\nsize {}\nx-is-binary 0\n",
+            ContentHash::sha256(content).unwrap_sha256().to_hex(),
+            content.len(),
+        );
+        if let Some(copy_from) = copy_from {
+            pointer.push_str(&format!(
+                "x-hg-copy {}\nx-hg-copyrev {}\n",
+                copy_from.path, copy_from.hgid
+            ));
+        }
+        store.add(
+            &Delta {
+                data: Bytes::copy_from_slice(pointer.as_bytes()),
+                base: None,
+                key: key.clone(),
+            },
+            &Metadata {
+                size: None,
+                flags: Some(Metadata::LFS_FLAG),
+            },
+        )
+    }
+
+    #[test]
+    fn test_get_file_by_content() -> Result<()> {
+        // Set up a mocked EdenAPI file store with two files that have
+        // identical content.
+        let k1 = key("a", "def6f29d7b61f9cb70b2f14f79cd5c43c38e21b2");
+        let k2 = key("b", "e1b3b3d40ea9e5c38a8d5c8d1fb5fd2ab1fa5c39");
+        let d = delta("1234", None, k1.clone());
+        let files = hashmap! {
+            k1.clone() => d.data.clone(),
+            k2.clone() => d.data.clone(),
+        };
+
+        let client = FakeEdenApi::new().files(files).into_arc();
+        let remote_files = EdenApiRemoteStore::<File>::new("repo", client, None);
+
+        // Set up a local store that has the LFS pointers, but not the blob.
+        let lfsdir = TempDir::new()?;
+        let lfs = Arc::new(LfsStore::shared(&lfsdir, &make_lfs_config(&lfsdir))?);
+        let tmp = TempDir::new()?;
+        let indexedlog = Arc::new(IndexedLogHgIdDataStore::new(
+            &tmp,
+            ExtStoredPolicy::Ignore,
+            &ConfigSet::new(),
+            IndexedLogDataStoreType::Shared,
+        )?);
+        let local = Arc::new(LfsMultiplexer::new(lfs, indexedlog.clone(), 100));
+        add_pointer(&local, &k1, &d.data, None)?;
+        add_pointer(&local, &k2, &d.data, None)?;
+        let edenapi_files = remote_files.datastore(local);
+
+        // Request both files by content hash.
+        let hash = ContentHash::sha256(&d.data);
+        let keys = vec![
+            StoreKey::Content(hash.clone(), Some(k1.clone())),
+            StoreKey::Content(hash, Some(k2.clone())),
+        ];
+        let missing = edenapi_files.prefetch(&keys)?;
+        assert!(missing.is_empty());
+
+        // Check that the content was written to the local store for both keys.
+        for k in &[k1, k2] {
+            let data = indexedlog.get(StoreKey::hgid(k.clone()))?;
+            assert_eq!(data, StoreResult::Found(d.data.as_ref().to_vec()));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_copied_file_by_content() -> Result<()> {
+        let content = Bytes::from(&b"1234"[..]);
+        let copy_from = key("foo/bar", "1234");
+        let k = key("a", "def6f29d7b61f9cb70b2f14f79cd5c43c38e21b2");
+        let blob = rebuild_metadata(content.clone(), Some(&copy_from));
+        let files = hashmap! { k.clone() => blob.clone() };
+
+        let client = FakeEdenApi::new().files(files).into_arc();
+        let remote_files = EdenApiRemoteStore::<File>::new("repo", client, None);
+
+        // Set up a local store that has the LFS pointer, with its copy
+        // metadata, but not the blob.
+        let lfsdir = TempDir::new()?;
+        let lfs = Arc::new(LfsStore::shared(&lfsdir, &make_lfs_config(&lfsdir))?);
+        let tmp = TempDir::new()?;
+        let indexedlog = Arc::new(IndexedLogHgIdDataStore::new(
+            &tmp,
+            ExtStoredPolicy::Ignore,
+            &ConfigSet::new(),
+            IndexedLogDataStoreType::Shared,
+        )?);
+        let local = Arc::new(LfsMultiplexer::new(lfs, indexedlog.clone(), 100));
+        add_pointer(&local, &k, &content, Some(&copy_from))?;
+        let edenapi_files = remote_files.datastore(local);
+
+        let hash = ContentHash::sha256(&content);
+        let missing = edenapi_files.prefetch(&[StoreKey::Content(hash, Some(k.clone()))])?;
+        assert!(missing.is_empty());
+
+        // The stored blob must carry the copy metadata header.
+        let k = StoreKey::hgid(k);
+        assert_eq!(
+            indexedlog.get(k.clone())?,
+            StoreResult::Found(blob.as_ref().to_vec())
+        );
+        assert_eq!(
+            indexedlog.get_meta(k)?,
+            StoreResult::Found(Metadata {
+                size: Some(content.len() as u64),
+                flags: None
+            })
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_get_tree() -> Result<()> {
         // Set up mocked EdenAPI file and tree stores.
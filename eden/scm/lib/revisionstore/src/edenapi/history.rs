@@ -11,6 +11,7 @@ use anyhow::Result;
 use futures::prelude::*;
 
 use async_runtime::block_on_exclusive as block_on_future;
+use edenapi::FetchMonitor;
 use progress::Unit;
 use types::{Key, NodeInfo};
 
@@ -58,11 +59,14 @@ impl RemoteHistoryStore for EdenApiHistoryStore {
                 None,
                 Unit::Named("entries"),
             )?;
+            let monitor = FetchMonitor::with_progress_bars(prog, None);
 
-            let mut response = client.history(repo, keys, None, None).await?;
-            while let Some(entry) = response.entries.try_next().await? {
+            let response = client
+                .history(repo, keys, None, Some(monitor.transfer_callback()))
+                .await?;
+            let mut entries = monitor.monitor(response).entries;
+            while let Some(entry) = entries.try_next().await? {
                 self.store.add_entry(&entry)?;
-                prog.increment(1)?;
             }
 
             Ok(())
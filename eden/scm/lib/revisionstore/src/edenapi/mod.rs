@@ -11,15 +11,17 @@ use std::sync::Arc;
 use async_trait::async_trait;
 
 use edenapi::{EdenApi, EdenApiError, Fetch, ProgressCallback};
-use edenapi_types::{EdenApiServerError, FileEntry, TreeAttributes, TreeEntry};
+use edenapi_types::{
+    EdenApiServerError, FileContentEntry, FileContentId, FileEntry, TreeAttributes, TreeEntry,
+};
 use progress::{NullProgressFactory, ProgressFactory};
-use types::Key;
+use types::{Key, Sha256};
 
 use crate::{
     datastore::{HgIdMutableDeltaStore, RemoteDataStore},
     historystore::{HgIdMutableHistoryStore, RemoteHistoryStore},
     remotestore::HgIdRemoteStore,
-    types::StoreKey,
+    types::{ContentHash, StoreKey},
 };
 
 mod data;
@@ -133,6 +135,15 @@ pub trait EdenApiStoreKind: Send + Sync + 'static {
         unimplemented!("fetching files not supported for this store")
     }
 
+    async fn prefetch_files_by_content(
+        _client: Arc<dyn EdenApi>,
+        _repo: String,
+        _ids: Vec<FileContentId>,
+        _progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<FileContentEntry>, EdenApiError> {
+        unimplemented!("fetching files not supported for this store")
+    }
+
     async fn prefetch_trees(
         _client: Arc<dyn EdenApi>,
         _repo: String,
@@ -154,6 +165,15 @@ impl EdenApiStoreKind for File {
     ) -> Result<Fetch<FileEntry>, EdenApiError> {
         client.files(repo, keys, progress).await
     }
+
+    async fn prefetch_files_by_content(
+        client: Arc<dyn EdenApi>,
+        repo: String,
+        ids: Vec<FileContentId>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<FileContentEntry>, EdenApiError> {
+        client.files_by_content(repo, ids, progress).await
+    }
 }
 
 #[async_trait]
@@ -170,7 +190,6 @@ impl EdenApiStoreKind for Tree {
 }
 
 /// Return only the HgId keys from the given iterator.
-/// EdenAPI cannot fetch content-addressed LFS blobs.
 fn hgid_keys<'a>(keys: impl IntoIterator<Item = &'a StoreKey>) -> Vec<Key> {
     keys.into_iter()
         .filter_map(|k| match k {
@@ -179,3 +198,16 @@ fn hgid_keys<'a>(keys: impl IntoIterator<Item = &'a StoreKey>) -> Vec<Key> {
         })
         .collect()
 }
+
+/// Return the content-addressed keys from the given iterator, along with the
+/// Mercurial key for which each content hash was requested. Content keys
+/// without an associated Mercurial key are skipped, since the fetched data
+/// could not be written into a Mercurial data store.
+fn content_keys<'a>(keys: impl IntoIterator<Item = &'a StoreKey>) -> Vec<(Sha256, Key)> {
+    keys.into_iter()
+        .filter_map(|k| match k {
+            StoreKey::Content(ContentHash::Sha256(sha256), Some(k)) => Some((*sha256, k.clone())),
+            _ => None,
+        })
+        .collect()
+}
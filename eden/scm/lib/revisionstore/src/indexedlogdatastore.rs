@@ -6,6 +6,7 @@
  */
 
 use std::{
+    collections::HashSet,
     io::{Cursor, Write},
     path::{Path, PathBuf},
 };
@@ -19,17 +20,24 @@ use configparser::{
     config::ConfigSet,
     hg::{ByteCount, ConfigSetHgExt},
 };
-use indexedlog::log::IndexOutput;
+use indexedlog::{
+    log::{Compression, IndexOutput},
+    Repair,
+};
 use lz4_pyframe::{compress, decompress};
-use types::{hgid::ReadHgIdExt, HgId, Key, RepoPath};
+use types::{hgid::ReadHgIdExt, HgId, Key, RepoPath, Sha256};
 
 use crate::{
     datastore::{Delta, HgIdDataStore, HgIdMutableDeltaStore, Metadata, StoreResult},
+    historystore::HgIdHistoryStore,
     indexedlogutil::{Store, StoreOpenOptions},
+    lfs::LfsIndexedLogBlobsStore,
     localstore::{ExtStoredPolicy, LocalStore},
     repack::ToKeys,
     sliceext::SliceExt,
-    types::StoreKey,
+    types::{ContentHash, StoreKey},
+    util::get_dedup_blobs_path,
+    verify::{check_hgid, VerifyReport},
 };
 
 #[derive(Clone, Copy)]
@@ -40,6 +48,18 @@ pub enum IndexedLogDataStoreType {
 
 struct IndexedLogHgIdDataStoreInner {
     log: Store,
+    dedup: Option<DedupStore>,
+}
+
+/// Content-addressed layer of a shared `IndexedLogHgIdDataStore`.
+///
+/// Like the `LfsStore`, entries are split in 2: a pointer holding the key, metadata and content
+/// hash of the entry, and a blob holding the content. The blobs are shared by all the repositories
+/// on the machine, identical content is thus only stored once, be it in forks, or in different
+/// files of a repository.
+struct DedupStore {
+    pointers: Store,
+    blobs: LfsIndexedLogBlobsStore,
 }
 
 pub struct IndexedLogHgIdDataStore {
@@ -65,6 +85,32 @@ impl Entry {
         }
     }
 
+    /// Read the key and metadata of an entry, they are common to entries and pointers.
+    fn read_header(data: &[u8], cur: &mut Cursor<&[u8]>) -> Result<(Key, Metadata)> {
+        let hgid = cur.read_hgid()?;
+
+        let name_len = cur.read_u16::<BigEndian>()? as u64;
+        let name_slice =
+            data.get_err(cur.position() as usize..(cur.position() + name_len) as usize)?;
+        cur.set_position(cur.position() + name_len);
+        let filename = RepoPath::from_utf8(name_slice)?;
+
+        let key = Key::new(filename.to_owned(), hgid);
+
+        let metadata = Metadata::read(cur)?;
+
+        Ok((key, metadata))
+    }
+
+    fn write_header(&self, buf: &mut Vec<u8>) -> Result<()> {
+        buf.write_all(self.key.hgid.as_ref())?;
+        let path_slice = self.key.path.as_byte_slice();
+        buf.write_u16::<BigEndian>(path_slice.len() as u16)?;
+        buf.write_all(path_slice)?;
+        self.metadata.write(buf)?;
+        Ok(())
+    }
+
     /// Read an entry from the slice and deserialize it.
     ///
     /// The on-disk format of an entry is the following:
@@ -81,17 +127,7 @@ impl Entry {
     /// - Value: <Len> bytes, big-endian
     fn from_slice(data: &[u8]) -> Result<Self> {
         let mut cur = Cursor::new(data);
-        let hgid = cur.read_hgid()?;
-
-        let name_len = cur.read_u16::<BigEndian>()? as u64;
-        let name_slice =
-            data.get_err(cur.position() as usize..(cur.position() + name_len) as usize)?;
-        cur.set_position(cur.position() + name_len);
-        let filename = RepoPath::from_utf8(name_slice)?;
-
-        let key = Key::new(filename.to_owned(), hgid);
-
-        let metadata = Metadata::read(&mut cur)?;
+        let (key, metadata) = Entry::read_header(data, &mut cur)?;
 
         let compressed_len = cur.read_u64::<BigEndian>()?;
         let compressed =
@@ -113,27 +149,15 @@ impl Entry {
             Some(buf) => buf?,
         };
 
-        Entry::from_slice(buf).map(Some)
+        Entry::from_slice(&buf).map(Some)
     }
 
     /// Write an entry to the IndexedLog. See [`from_log`] for the detail about the on-disk format.
-    pub fn write_to_log(self, log: &mut Store) -> Result<()> {
+    pub fn write_to_log(mut self, log: &mut Store) -> Result<()> {
         let mut buf = Vec::new();
-        buf.write_all(self.key.hgid.as_ref())?;
-        let path_slice = self.key.path.as_byte_slice();
-        buf.write_u16::<BigEndian>(path_slice.len() as u16)?;
-        buf.write_all(path_slice)?;
-        self.metadata.write(&mut buf)?;
+        self.write_header(&mut buf)?;
 
-        let compressed = if let Some(compressed) = self.compressed_content {
-            compressed
-        } else {
-            if let Some(raw) = self.content {
-                compress(&raw)?.into()
-            } else {
-                bail!("No content");
-            }
-        };
+        let compressed = self.compressed_content()?;
 
         buf.write_u64::<BigEndian>(compressed.len() as u64)?;
         buf.write_all(&compressed)?;
@@ -141,6 +165,47 @@ impl Entry {
         Ok(log.append(buf)?)
     }
 
+    /// Read a pointer from the slice. Its content needs to be read from the blobs.
+    ///
+    /// The on-disk format of a pointer is the same as the one of an entry, up to the metadata.
+    /// The content is replaced by the SHA-256 of its compressed form <32 bytes>.
+    fn pointer_from_slice(data: &[u8]) -> Result<(Self, Sha256)> {
+        let mut cur = Cursor::new(data);
+        let (key, metadata) = Entry::read_header(data, &mut cur)?;
+
+        let hash =
+            data.get_err(cur.position() as usize..cur.position() as usize + Sha256::len())?;
+        let hash = Sha256::from_slice(hash)?;
+
+        Ok((
+            Entry {
+                key,
+                content: None,
+                compressed_content: None,
+                metadata,
+            },
+            hash,
+        ))
+    }
+
+    /// Write an entry to a `DedupStore`. Its content is stored compressed, like in the log, and
+    /// only added to the blobs if no identical content is present. As compression is
+    /// deterministic, the blobs are addressed by the SHA-256 of the compressed content.
+    fn write_to_dedup(mut self, dedup: &mut DedupStore) -> Result<()> {
+        let compressed = self.compressed_content()?;
+        let hash = ContentHash::sha256(&compressed).unwrap_sha256();
+        // Some chunks of a blob may have been rotated out, `contains` isn't enough.
+        if !dedup.blobs.contains_all(&hash, compressed.len())? {
+            dedup.blobs.add(&hash, compressed)?;
+        }
+
+        let mut buf = Vec::new();
+        self.write_header(&mut buf)?;
+        buf.write_all(hash.as_ref())?;
+
+        Ok(dedup.pointers.append(buf)?)
+    }
+
     pub fn content(&mut self) -> Result<Bytes> {
         if let Some(content) = self.content.as_ref() {
             return Ok(content.clone());
@@ -155,13 +220,86 @@ impl Entry {
         }
     }
 
+    fn compressed_content(&mut self) -> Result<Bytes> {
+        if let Some(compressed) = self.compressed_content.as_ref() {
+            return Ok(compressed.clone());
+        }
+
+        if let Some(raw) = self.content.as_ref() {
+            let compressed = Bytes::from(compress(&raw)?);
+            self.compressed_content = Some(compressed.clone());
+            Ok(compressed)
+        } else {
+            bail!("No content");
+        }
+    }
+
     pub fn metadata(&self) -> &Metadata {
         &self.metadata
     }
 }
 
+impl DedupStore {
+    fn new(path: &Path, config: &ConfigSet) -> Result<Self> {
+        let pointers =
+            IndexedLogHgIdDataStore::open_options(config)?.shared(Self::pointers_path(path))?;
+        let blobs = LfsIndexedLogBlobsStore::open(get_dedup_blobs_path(config)?, config)?;
+        Ok(DedupStore { pointers, blobs })
+    }
+
+    /// Path of the pointers of the shared store at `path`. It's a sibling of `path` as everything
+    /// under `path` belongs to the store's rotatelog.
+    fn pointers_path(path: &Path) -> PathBuf {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push("-dedup");
+        path.with_file_name(name)
+    }
+
+    /// Read the compressed content with the given hash.
+    fn blob(&self, hash: &Sha256) -> Result<Option<Bytes>> {
+        self.blobs.get(hash)
+    }
+
+    /// Read the entry for `key`, along with its content.
+    fn entry(&self, key: &Key) -> Result<Option<Entry>> {
+        let mut pointers = self.pointers.lookup(0, key.hgid.as_ref().to_vec())?;
+        let buf = match pointers.next() {
+            None => return Ok(None),
+            Some(buf) => buf?,
+        };
+
+        let (mut entry, hash) = Entry::pointer_from_slice(&buf)?;
+        // The blobs are rotated independently of the pointers, the blob may be gone.
+        match self.blob(&hash)? {
+            None => Ok(None),
+            Some(compressed) => {
+                entry.compressed_content = Some(compressed);
+                Ok(Some(entry))
+            }
+        }
+    }
+}
+
+impl IndexedLogHgIdDataStoreInner {
+    /// Read the entry for `key`. Entries are looked up in the log first, as it holds the entries
+    /// that were added before the content-addressed layer was enabled.
+    fn entry(&self, key: &Key) -> Result<Option<Entry>> {
+        if let Some(entry) = Entry::from_log(key, &self.log)? {
+            return Ok(Some(entry));
+        }
+
+        match &self.dedup {
+            None => Ok(None),
+            Some(dedup) => dedup.entry(key),
+        }
+    }
+}
+
 impl IndexedLogHgIdDataStore {
     /// Create or open an `IndexedLogHgIdDataStore`.
+    ///
+    /// When `indexedlog.data.dedup` is set, the content of the entries added to a shared store is
+    /// stored in a content-addressed layer shared by all the repositories.
     pub fn new(
         path: impl AsRef<Path>,
         extstored_policy: ExtStoredPolicy,
@@ -175,8 +313,17 @@ impl IndexedLogHgIdDataStore {
             IndexedLogDataStoreType::Shared => open_options.shared(&path),
         }?;
 
+        let dedup = match store_type {
+            IndexedLogDataStoreType::Shared
+                if config.get_or_default::<bool>("indexedlog", "data.dedup")? =>
+            {
+                Some(DedupStore::new(path.as_ref(), config)?)
+            }
+            _ => None,
+        };
+
         Ok(IndexedLogHgIdDataStore {
-            inner: RwLock::new(IndexedLogHgIdDataStoreInner { log }),
+            inner: RwLock::new(IndexedLogHgIdDataStoreInner { log, dedup }),
             extstored_policy,
         })
     }
@@ -206,6 +353,14 @@ impl IndexedLogHgIdDataStore {
             open_options =
                 open_options.max_bytes_per_log((max_bytes_per_log.value() / log_count).max(1));
         }
+        if let Some(access_sample_rate) =
+            config.get_opt::<u64>("indexedlog", "data.access-sample-rate")?
+        {
+            open_options = open_options.access_sample_rate(access_sample_rate);
+        }
+        if let Some(level) = config.get_opt::<i32>("indexedlog", "data.zstd-level")? {
+            open_options = open_options.compression(Compression::Zstd { level });
+        }
         Ok(open_options)
     }
 
@@ -219,10 +374,151 @@ impl IndexedLogHgIdDataStore {
                 IndexedLogHgIdDataStore::open_options(config)?.repair_local(path)
             }
             IndexedLogDataStoreType::Shared => {
-                IndexedLogHgIdDataStore::open_options(config)?.repair_shared(path)
+                let pointers_path = DedupStore::pointers_path(&path);
+                let mut message =
+                    IndexedLogHgIdDataStore::open_options(config)?.repair_shared(path)?;
+                // The content-addressed layer may have been enabled earlier, repair it even if
+                // it no longer is.
+                if pointers_path.exists() {
+                    message += &IndexedLogHgIdDataStore::open_options(config)?
+                        .repair_shared(pointers_path)?;
+                }
+                if config.get_or_default::<bool>("indexedlog", "data.dedup")? {
+                    message += &LfsIndexedLogBlobsStore::repair(get_dedup_blobs_path(config)?)?;
+                }
+                Ok(message)
+            }
+        }
+    }
+
+    /// Verify that the entries of the store at `path` hash to their `HgId`, using the parents
+    /// found in `history`. The pointers of the content-addressed layer are verified against the
+    /// blobs they point to; pointers whose blob is gone can't be checked.
+    ///
+    /// When `delete_corrupt` is set, corrupt entries are removed so they can be fetched again.
+    /// Care must be taken to call this only when no other store is opened at `path`.
+    pub fn verify(
+        path: PathBuf,
+        config: &ConfigSet,
+        store_type: IndexedLogDataStoreType,
+        history: &dyn HgIdHistoryStore,
+        delete_corrupt: bool,
+    ) -> Result<VerifyReport> {
+        let mut report = VerifyReport::default();
+        let mut corrupt = HashSet::new();
+        let mut corrupt_pointers = HashSet::new();
+        let mut pointers_path = None;
+
+        let store = IndexedLogHgIdDataStore::new(&path, ExtStoredPolicy::Use, config, store_type)?;
+        let inner = store.inner.read();
+        for buf in inner.log.iter() {
+            let entry = Entry::from_slice(&buf?).map_err(|e| format!("unreadable entry: {}", e));
+            if let Some(hgid) = verify_entry(&path, entry, history, &mut report)? {
+                corrupt.insert(hgid);
+            }
+        }
+        if let Some(dedup) = inner.dedup.as_ref() {
+            let path = DedupStore::pointers_path(&path);
+            for buf in dedup.pointers.iter() {
+                let entry = match Entry::pointer_from_slice(&buf?) {
+                    Ok((mut entry, hash)) => match dedup.blob(&hash)? {
+                        Some(compressed) => {
+                            entry.compressed_content = Some(compressed);
+                            Ok(entry)
+                        }
+                        None => {
+                            report.checked += 1;
+                            report.unverifiable += 1;
+                            continue;
+                        }
+                    },
+                    Err(e) => Err(format!("unreadable pointer: {}", e)),
+                };
+                if let Some(hgid) = verify_entry(&path, entry, history, &mut report)? {
+                    corrupt_pointers.insert(hgid);
+                }
+            }
+            pointers_path = Some(path);
+        }
+        drop(inner);
+        drop(store);
+
+        if delete_corrupt && !report.is_ok() {
+            let mut removed = 0;
+            let filter = |buf: &[u8]| {
+                let is_corrupt = match Entry::from_slice(buf) {
+                    Ok(entry) => corrupt.contains(&entry.key.hgid),
+                    Err(_) => true,
+                };
+                if is_corrupt {
+                    removed += 1;
+                }
+                !is_corrupt
+            };
+            let open_options = IndexedLogHgIdDataStore::open_options(config)?;
+            match store_type {
+                IndexedLogDataStoreType::Local => open_options.compact_local(path, filter)?,
+                IndexedLogDataStoreType::Shared => open_options.compact_shared(path, filter)?,
+            };
+            if let Some(pointers_path) = pointers_path {
+                let filter = |buf: &[u8]| {
+                    let is_corrupt = match Entry::pointer_from_slice(buf) {
+                        Ok((entry, _)) => corrupt_pointers.contains(&entry.key.hgid),
+                        Err(_) => true,
+                    };
+                    if is_corrupt {
+                        removed += 1;
+                    }
+                    !is_corrupt
+                };
+                IndexedLogHgIdDataStore::open_options(config)?
+                    .compact_shared(pointers_path, filter)?;
             }
+            report.removed = removed;
+        }
+
+        Ok(report)
+    }
+}
+
+/// Verify one entry of the store at `path`, recording the outcome in `report`. Returns the
+/// `HgId` of the entry when it is corrupt.
+fn verify_entry(
+    path: &Path,
+    entry: Result<Entry, String>,
+    history: &dyn HgIdHistoryStore,
+    report: &mut VerifyReport,
+) -> Result<Option<HgId>> {
+    report.checked += 1;
+    let mut entry = match entry {
+        Ok(entry) => entry,
+        Err(reason) => {
+            report
+                .corrupt
+                .push(format!("{}: {}", path.display(), reason));
+            return Ok(None);
         }
+    };
+    if entry.metadata().is_lfs() {
+        report.unverifiable += 1;
+        return Ok(None);
     }
+
+    let reason = match entry.content() {
+        Ok(content) => match check_hgid(&entry.key, &content, history)? {
+            Some(true) => return Ok(None),
+            Some(false) => "content does not match its hash".to_string(),
+            None => {
+                report.unverifiable += 1;
+                return Ok(None);
+            }
+        },
+        Err(e) => e.to_string(),
+    };
+    report
+        .corrupt
+        .push(format!("{}: {}: {}", path.display(), entry.key, reason));
+    Ok(Some(entry.key.hgid))
 }
 
 impl HgIdMutableDeltaStore for IndexedLogHgIdDataStore {
@@ -231,11 +527,19 @@ impl HgIdMutableDeltaStore for IndexedLogHgIdDataStore {
 
         let entry = Entry::new(delta.key.clone(), delta.data.clone(), metadata.clone());
         let mut inner = self.inner.write();
-        entry.write_to_log(&mut inner.log)
+        match inner.dedup.as_mut() {
+            None => entry.write_to_log(&mut inner.log),
+            Some(dedup) => entry.write_to_dedup(dedup),
+        }
     }
 
     fn flush(&self) -> Result<Option<Vec<PathBuf>>> {
-        self.inner.write().log.flush()?;
+        let mut inner = self.inner.write();
+        inner.log.flush()?;
+        if let Some(dedup) = inner.dedup.as_mut() {
+            dedup.blobs.flush()?;
+            dedup.pointers.flush()?;
+        }
         Ok(None)
     }
 }
@@ -246,7 +550,7 @@ impl LocalStore for IndexedLogHgIdDataStore {
         Ok(keys
             .iter()
             .filter(|k| match k {
-                StoreKey::HgId(k) => match Entry::from_log(k, &inner.log) {
+                StoreKey::HgId(k) => match inner.entry(k) {
                     Ok(None) | Err(_) => true,
                     Ok(Some(_)) => false,
                 },
@@ -265,7 +569,7 @@ impl HgIdDataStore for IndexedLogHgIdDataStore {
         };
 
         let inner = self.inner.read();
-        let mut entry = match Entry::from_log(&key, &inner.log)? {
+        let mut entry = match inner.entry(&key)? {
             None => return Ok(StoreResult::NotFound(StoreKey::HgId(key))),
             Some(entry) => entry,
         };
@@ -285,7 +589,7 @@ impl HgIdDataStore for IndexedLogHgIdDataStore {
         };
 
         let inner = self.inner.read();
-        let entry = match Entry::from_log(&key, &inner.log)? {
+        let entry = match inner.entry(&key)? {
             None => return Ok(StoreResult::NotFound(StoreKey::HgId(key))),
             Some(entry) => entry,
         };
@@ -305,13 +609,22 @@ impl HgIdDataStore for IndexedLogHgIdDataStore {
 
 impl ToKeys for IndexedLogHgIdDataStore {
     fn to_keys(&self) -> Vec<Result<Key>> {
-        self.inner
-            .read()
+        let inner = self.inner.read();
+        let mut keys: Vec<Result<Key>> = inner
             .log
             .iter()
-            .map(|entry| Entry::from_slice(entry?))
+            .map(|entry| Entry::from_slice(&entry?))
             .map(|entry| Ok(entry?.key))
-            .collect()
+            .collect();
+        if let Some(dedup) = inner.dedup.as_ref() {
+            keys.extend(
+                dedup
+                    .pointers
+                    .iter()
+                    .map(|entry| Ok(Entry::pointer_from_slice(&entry?)?.0.key)),
+            );
+        }
+        keys
     }
 }
 
@@ -324,7 +637,13 @@ mod tests {
     use minibytes::Bytes;
     use tempfile::TempDir;
 
-    use types::testutil::*;
+    use types::{testutil::*, NodeInfo, Parents};
+
+    use crate::{
+        historystore::HgIdMutableHistoryStore,
+        indexedloghistorystore::{IndexedLogHgIdHistoryStore, IndexedLogHistoryStoreType},
+        testutil::make_config,
+    };
 
     #[test]
     fn test_empty() {
@@ -393,6 +712,62 @@ mod tests {
         assert_eq!(StoreResult::Found(delta.data.as_ref().to_vec()), read_data);
     }
 
+    #[test]
+    fn test_add_get_zstd() -> Result<()> {
+        let tempdir = TempDir::new()?;
+        let mut config = ConfigSet::new();
+        config.set(
+            "indexedlog",
+            "data.zstd-level",
+            Some("3"),
+            &Default::default(),
+        );
+
+        let log = IndexedLogHgIdDataStore::new(
+            &tempdir,
+            ExtStoredPolicy::Use,
+            &ConfigSet::new(),
+            IndexedLogDataStoreType::Shared,
+        )?;
+        let delta1 = Delta {
+            data: Bytes::from(&[1, 2, 3, 4][..]),
+            base: None,
+            key: key("a", "1"),
+        };
+        log.add(&delta1, &Default::default())?;
+        log.flush()?;
+        drop(log);
+
+        // Entries written before compression was enabled stay readable.
+        let log = IndexedLogHgIdDataStore::new(
+            &tempdir,
+            ExtStoredPolicy::Use,
+            &config,
+            IndexedLogDataStoreType::Shared,
+        )?;
+        let delta2 = Delta {
+            data: Bytes::from(b"a b c d e f g h ".repeat(64)),
+            base: None,
+            key: key("b", "2"),
+        };
+        log.add(&delta2, &Default::default())?;
+        log.flush()?;
+
+        let log = IndexedLogHgIdDataStore::new(
+            &tempdir,
+            ExtStoredPolicy::Use,
+            &ConfigSet::new(),
+            IndexedLogDataStoreType::Shared,
+        )?;
+        for delta in &[delta1, delta2] {
+            assert_eq!(
+                log.get(StoreKey::hgid(delta.key.clone()))?,
+                StoreResult::Found(delta.data.as_ref().to_vec())
+            );
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_lookup_failure() {
         let tempdir = TempDir::new().unwrap();
@@ -565,4 +940,280 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_verify() -> Result<()> {
+        let tempdir = TempDir::new()?;
+        let history_tempdir = TempDir::new()?;
+        let config = ConfigSet::new();
+        let history = IndexedLogHgIdHistoryStore::new(
+            &history_tempdir,
+            &config,
+            IndexedLogHistoryStoreType::Shared,
+        )?;
+        let log = IndexedLogHgIdDataStore::new(
+            &tempdir,
+            ExtStoredPolicy::Use,
+            &config,
+            IndexedLogDataStoreType::Shared,
+        )?;
+
+        let data = Bytes::from(&[1, 2, 3, 4][..]);
+        let good = Key::new(repo_path_buf("a"), HgId::from_content(&data, Parents::None));
+        let bad = key("b", "1");
+        let unknown = key("c", "2");
+        for key in vec![&good, &bad, &unknown] {
+            let delta = Delta {
+                data: data.clone(),
+                base: None,
+                key: key.clone(),
+            };
+            log.add(&delta, &Default::default())?;
+        }
+        for key in vec![&good, &bad] {
+            let nodeinfo = NodeInfo {
+                parents: [
+                    Key::new(key.path.clone(), HgId::null_id().clone()),
+                    Key::new(key.path.clone(), HgId::null_id().clone()),
+                ],
+                linknode: hgid("2"),
+            };
+            history.add(key, &nodeinfo)?;
+        }
+        log.flush()?;
+        drop(log);
+
+        let report = IndexedLogHgIdDataStore::verify(
+            tempdir.path().to_path_buf(),
+            &config,
+            IndexedLogDataStoreType::Shared,
+            &history,
+            true,
+        )?;
+        assert_eq!(report.checked, 3);
+        assert_eq!(report.unverifiable, 1);
+        assert_eq!(report.corrupt.len(), 1);
+        assert_eq!(report.removed, 1);
+
+        let log = IndexedLogHgIdDataStore::new(
+            &tempdir,
+            ExtStoredPolicy::Use,
+            &config,
+            IndexedLogDataStoreType::Shared,
+        )?;
+        let missing = log.get_missing(&[
+            StoreKey::hgid(good.clone()),
+            StoreKey::hgid(bad.clone()),
+            StoreKey::hgid(unknown),
+        ])?;
+        assert_eq!(missing, vec![StoreKey::hgid(bad)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedup() -> Result<()> {
+        let cachedir = TempDir::new()?;
+        let mut config = make_config(&cachedir);
+        config.set(
+            "indexedlog",
+            "data.dedup",
+            Some("true"),
+            &Default::default(),
+        );
+
+        let repo1 = TempDir::new()?;
+        let log1 = IndexedLogHgIdDataStore::new(
+            repo1.path().join("indexedlogdatastore"),
+            ExtStoredPolicy::Use,
+            &config,
+            IndexedLogDataStoreType::Shared,
+        )?;
+        let delta1 = Delta {
+            data: Bytes::from(&[1, 2, 3, 4][..]),
+            base: None,
+            key: key("a", "1"),
+        };
+        log1.add(&delta1, &Default::default())?;
+        log1.flush()?;
+
+        // The content added by another repository is already present.
+        let repo2 = TempDir::new()?;
+        let log2 = IndexedLogHgIdDataStore::new(
+            repo2.path().join("indexedlogdatastore"),
+            ExtStoredPolicy::Use,
+            &config,
+            IndexedLogDataStoreType::Shared,
+        )?;
+        // The blobs hold the compressed content.
+        let compressed = Bytes::from(compress(&delta1.data)?);
+        let hash = ContentHash::sha256(&compressed).unwrap_sha256();
+        assert!(log2
+            .inner
+            .read()
+            .dedup
+            .as_ref()
+            .unwrap()
+            .blobs
+            .contains(&hash)?);
+
+        let delta2 = Delta {
+            data: delta1.data.clone(),
+            base: None,
+            key: key("b", "2"),
+        };
+        log2.add(&delta2, &Default::default())?;
+        log2.flush()?;
+
+        let k1 = StoreKey::hgid(delta1.key.clone());
+        let k2 = StoreKey::hgid(delta2.key.clone());
+        assert_eq!(log2.get(k2.clone())?, StoreResult::Found(vec![1, 2, 3, 4]));
+        assert_eq!(log2.get(k1.clone())?, StoreResult::NotFound(k1.clone()));
+        assert_eq!(log2.get_missing(&[k1.clone(), k2])?, vec![k1.clone()]);
+        assert_eq!(log1.get(k1)?, StoreResult::Found(vec![1, 2, 3, 4]));
+        assert_eq!(log2.to_keys().len(), 1);
+
+        let empty = Delta {
+            data: Bytes::new(),
+            base: None,
+            key: key("c", "3"),
+        };
+        log2.add(&empty, &Default::default())?;
+        let k3 = StoreKey::hgid(empty.key.clone());
+        assert_eq!(log2.get(k3)?, StoreResult::Found(vec![]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_dedup() -> Result<()> {
+        let cachedir = TempDir::new()?;
+        let mut config = make_config(&cachedir);
+        config.set(
+            "indexedlog",
+            "data.dedup",
+            Some("true"),
+            &Default::default(),
+        );
+        let tempdir = TempDir::new()?;
+        let path = tempdir.path().join("indexedlogdatastore");
+        let history_tempdir = TempDir::new()?;
+        let history = IndexedLogHgIdHistoryStore::new(
+            &history_tempdir,
+            &config,
+            IndexedLogHistoryStoreType::Shared,
+        )?;
+        let log = IndexedLogHgIdDataStore::new(
+            &path,
+            ExtStoredPolicy::Use,
+            &config,
+            IndexedLogDataStoreType::Shared,
+        )?;
+
+        let data = Bytes::from(&[1, 2, 3, 4][..]);
+        let good = Key::new(repo_path_buf("a"), HgId::from_content(&data, Parents::None));
+        let bad = key("b", "1");
+        let unknown = key("c", "2");
+        for key in &[&good, &bad, &unknown] {
+            let delta = Delta {
+                data: data.clone(),
+                base: None,
+                key: (*key).clone(),
+            };
+            log.add(&delta, &Default::default())?;
+        }
+        for key in &[&good, &bad] {
+            let nodeinfo = NodeInfo {
+                parents: [
+                    Key::new(key.path.clone(), HgId::null_id().clone()),
+                    Key::new(key.path.clone(), HgId::null_id().clone()),
+                ],
+                linknode: hgid("2"),
+            };
+            history.add(key, &nodeinfo)?;
+        }
+        log.flush()?;
+        drop(log);
+
+        // All the entries are pointers to the same blob.
+        let report = IndexedLogHgIdDataStore::verify(
+            path.clone(),
+            &config,
+            IndexedLogDataStoreType::Shared,
+            &history,
+            true,
+        )?;
+        assert_eq!(report.checked, 3);
+        assert_eq!(report.unverifiable, 1);
+        assert_eq!(report.corrupt.len(), 1);
+        assert_eq!(report.removed, 1);
+
+        let log = IndexedLogHgIdDataStore::new(
+            &path,
+            ExtStoredPolicy::Use,
+            &config,
+            IndexedLogDataStoreType::Shared,
+        )?;
+        let missing = log.get_missing(&[
+            StoreKey::hgid(good.clone()),
+            StoreKey::hgid(bad.clone()),
+            StoreKey::hgid(unknown),
+        ])?;
+        assert_eq!(missing, vec![StoreKey::hgid(bad)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_repair_dedup() -> Result<()> {
+        let cachedir = TempDir::new()?;
+        let mut config = make_config(&cachedir);
+        config.set(
+            "indexedlog",
+            "data.dedup",
+            Some("true"),
+            &Default::default(),
+        );
+        let tempdir = TempDir::new()?;
+        let path = tempdir.path().join("indexedlogdatastore");
+
+        let log = IndexedLogHgIdDataStore::new(
+            &path,
+            ExtStoredPolicy::Use,
+            &config,
+            IndexedLogDataStoreType::Shared,
+        )?;
+        let delta = Delta {
+            data: Bytes::from(&[1, 2, 3, 4][..]),
+            base: None,
+            key: key("a", "1"),
+        };
+        log.add(&delta, &Default::default())?;
+        log.flush()?;
+        drop(log);
+
+        // The pointers live next to the rotatelog, not inside of it.
+        let pointers_path = DedupStore::pointers_path(&path);
+        assert_eq!(
+            pointers_path,
+            tempdir.path().join("indexedlogdatastore-dedup")
+        );
+        assert!(pointers_path.is_dir());
+        assert!(!path.join("dedup").exists());
+
+        let message = IndexedLogHgIdDataStore::repair(
+            path.clone(),
+            &config,
+            IndexedLogDataStoreType::Shared,
+        )?;
+        // The data log, the pointers and the blobs are all repaired.
+        assert_eq!(message.matches("Attempt to repair").count(), 3);
+
+        let log = IndexedLogHgIdDataStore::new(
+            &path,
+            ExtStoredPolicy::Use,
+            &config,
+            IndexedLogDataStoreType::Shared,
+        )?;
+        let k = StoreKey::hgid(delta.key);
+        assert_eq!(log.get(k)?, StoreResult::Found(vec![1, 2, 3, 4]));
+        Ok(())
+    }
 }
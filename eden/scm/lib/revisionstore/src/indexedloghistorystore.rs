@@ -19,7 +19,7 @@ use configparser::{
     config::ConfigSet,
     hg::{ByteCount, ConfigSetHgExt},
 };
-use indexedlog::log::IndexOutput;
+use indexedlog::log::{Compression, IndexOutput};
 use types::{
     hgid::{ReadHgIdExt, WriteHgIdExt},
     HgId, Key, NodeInfo, RepoPath, RepoPathBuf,
@@ -32,6 +32,7 @@ use crate::{
     repack::ToKeys,
     sliceext::SliceExt,
     types::StoreKey,
+    verify::{check_node_info, VerifyReport},
 };
 
 #[derive(Clone, Copy)]
@@ -151,7 +152,7 @@ impl Entry {
             Some(buf) => buf?,
         };
 
-        Self::from_slice(buf).map(Some)
+        Self::from_slice(&buf).map(Some)
     }
 
     /// Write an entry to the `IndexedLog`. See [`from_slice`] for the detail about the on-disk
@@ -227,6 +228,14 @@ impl IndexedLogHgIdHistoryStore {
         if let Some(max_log_count) = config.get_opt::<u8>("indexedlog", "history.max-log-count")? {
             open_options = open_options.max_log_count(max_log_count);
         }
+        if let Some(access_sample_rate) =
+            config.get_opt::<u64>("indexedlog", "history.access-sample-rate")?
+        {
+            open_options = open_options.access_sample_rate(access_sample_rate);
+        }
+        if let Some(level) = config.get_opt::<i32>("indexedlog", "history.zstd-level")? {
+            open_options = open_options.compression(Compression::Zstd { level });
+        }
         Ok(open_options)
     }
 
@@ -244,6 +253,57 @@ impl IndexedLogHgIdHistoryStore {
             }
         }
     }
+
+    /// Verify that the entries of the store at `path` have valid parents.
+    ///
+    /// When `delete_corrupt` is set, corrupt entries are removed so they can be fetched again.
+    /// Care must be taken to call this only when no other store is opened at `path`.
+    pub fn verify(
+        path: PathBuf,
+        config: &ConfigSet,
+        store_type: IndexedLogHistoryStoreType,
+        delete_corrupt: bool,
+    ) -> Result<VerifyReport> {
+        let mut report = VerifyReport::default();
+
+        let store = IndexedLogHgIdHistoryStore::new(&path, config, store_type)?;
+        for buf in store.inner.read().unwrap().log.iter() {
+            report.checked += 1;
+            let reason = match Entry::from_slice(&buf?) {
+                Ok(entry) => match check_node_info(&entry.key, &entry.node_info()) {
+                    None => continue,
+                    Some(reason) => format!("{}: {}", entry.key, reason),
+                },
+                Err(e) => format!("unreadable entry: {}", e),
+            };
+            report
+                .corrupt
+                .push(format!("{}: {}", path.display(), reason));
+        }
+        drop(store);
+
+        if delete_corrupt && !report.is_ok() {
+            let mut removed = 0;
+            let filter = |buf: &[u8]| {
+                let is_corrupt = match Entry::from_slice(buf) {
+                    Ok(entry) => check_node_info(&entry.key, &entry.node_info()).is_some(),
+                    Err(_) => true,
+                };
+                if is_corrupt {
+                    removed += 1;
+                }
+                !is_corrupt
+            };
+            let open_options = IndexedLogHgIdHistoryStore::open_options(config)?;
+            match store_type {
+                IndexedLogHistoryStoreType::Local => open_options.compact_local(path, filter)?,
+                IndexedLogHistoryStoreType::Shared => open_options.compact_shared(path, filter)?,
+            };
+            report.removed = removed;
+        }
+
+        Ok(report)
+    }
 }
 
 impl LocalStore for IndexedLogHgIdHistoryStore {
@@ -298,7 +358,7 @@ impl ToKeys for IndexedLogHgIdHistoryStore {
             .unwrap()
             .log
             .iter()
-            .map(|entry| Entry::from_slice(entry?))
+            .map(|entry| Entry::from_slice(&entry?))
             .map(|entry| Ok(entry?.key))
             .collect()
     }
@@ -430,4 +490,51 @@ mod tests {
         assert!(log.to_keys().into_iter().all(|e| e.unwrap() == k));
         Ok(())
     }
+
+    #[test]
+    fn test_verify() -> Result<()> {
+        let tempdir = TempDir::new()?;
+        let log = IndexedLogHgIdHistoryStore::new(
+            &tempdir,
+            &ConfigSet::new(),
+            IndexedLogHistoryStoreType::Shared,
+        )?;
+        let good = key("a", "1");
+        log.add(
+            &good,
+            &NodeInfo {
+                parents: [key("a", "2"), null_key("a")],
+                linknode: hgid("3"),
+            },
+        )?;
+        let bad = key("b", "1");
+        log.add(
+            &bad,
+            &NodeInfo {
+                parents: [null_key("b"), key("b", "2")],
+                linknode: hgid("3"),
+            },
+        )?;
+        log.flush()?;
+        drop(log);
+
+        let report = IndexedLogHgIdHistoryStore::verify(
+            tempdir.path().to_path_buf(),
+            &ConfigSet::new(),
+            IndexedLogHistoryStoreType::Shared,
+            true,
+        )?;
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.corrupt.len(), 1);
+        assert_eq!(report.removed, 1);
+
+        let log = IndexedLogHgIdHistoryStore::new(
+            &tempdir,
+            &ConfigSet::new(),
+            IndexedLogHistoryStoreType::Shared,
+        )?;
+        assert!(log.get_node_info(&good)?.is_some());
+        assert!(log.get_node_info(&bad)?.is_none());
+        Ok(())
+    }
 }
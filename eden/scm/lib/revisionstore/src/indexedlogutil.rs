@@ -5,13 +5,21 @@
  * GNU General Public License version 2.
  */
 
-use std::path::{Path, PathBuf};
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use anyhow::Result;
+use parking_lot::Mutex;
 
 use indexedlog::{
-    log::{self, IndexDef, IndexOutput, Log, LogLookupIter},
-    rotate::{self, RotateLog, RotateLogLookupIter},
+    lock::ScopedDirLock,
+    log::{self, Compression, IndexDef, IndexOutput, Log, LogLookupIter},
+    rotate::{self, RotateLog, RotateLogLookupIter, RotateLowLevelExt},
     Result as IndexedlogResult,
 };
 use minibytes::Bytes;
@@ -21,7 +29,170 @@ use minibytes::Bytes;
 /// with the subtle differences.
 pub enum Store {
     Local(Log),
-    Shared(RotateLog),
+    Shared(RotateLog, AccessTracker),
+}
+
+/// Upper bound of the number of keys remembered by an `AccessTracker` between two flushes.
+const MAX_TRACKED_KEYS: usize = 100_000;
+
+/// Sampled record of lookups in a shared store.
+///
+/// A `RotateLog` evicts data by insertion age: the oldest log is dropped when a new one is
+/// created. To keep data that is still being read, keys that were recently read from the oldest
+/// log are copied forward into the newest log on `Store::flush`, once the newest log is about to
+/// be rotated.
+pub struct AccessTracker {
+    /// Record one out of `sample_rate` lookups. 0 disables tracking.
+    sample_rate: u64,
+    /// The oldest log is only dropped once the `RotateLog` has this many logs.
+    max_log_count: u8,
+    /// Size of the newest log from which it may be rotated before the next flush, either by the
+    /// flush itself or by an automatic sync.
+    rotate_size: u64,
+    keys: Mutex<HashSet<(usize, Bytes)>>,
+    lookups: AtomicU64,
+    hits: AtomicU64,
+    stats: Mutex<AccessStats>,
+}
+
+/// Statistics about lookups and evictions of a shared store.
+#[derive(Clone, Debug, Default)]
+pub struct AccessStats {
+    /// Number of lookups.
+    pub lookups: u64,
+    /// Number of lookups that found an entry.
+    pub hits: u64,
+    /// Number of sampled keys considered by eviction passes.
+    pub sampled_keys: u64,
+    /// Number of sampled keys that are present in logs that will not be dropped.
+    pub kept_keys: u64,
+    /// Number of sampled keys copied forward from the oldest log.
+    pub copied_keys: u64,
+    /// Number of bytes copied forward from the oldest log.
+    pub copied_bytes: u64,
+}
+
+impl AccessStats {
+    /// Ratio of lookups that found an entry.
+    pub fn hit_rate(&self) -> f64 {
+        ratio(self.hits, self.lookups)
+    }
+
+    /// Ratio of sampled keys that would still be found after dropping the oldest log, without
+    /// copying them forward.
+    pub fn retained_rate_before_eviction(&self) -> f64 {
+        ratio(self.kept_keys, self.sampled_keys)
+    }
+
+    /// Ratio of sampled keys that will still be found after dropping the oldest log.
+    pub fn retained_rate_after_eviction(&self) -> f64 {
+        ratio(self.kept_keys + self.copied_keys, self.sampled_keys)
+    }
+}
+
+fn ratio(numerator: u64, denominator: u64) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+impl AccessTracker {
+    fn new(sample_rate: u64, opts: &rotate::OpenOptions) -> Self {
+        let rotate_size = opts
+            .max_bytes_per_log
+            .saturating_sub(opts.auto_sync_threshold.unwrap_or(0));
+        Self {
+            sample_rate,
+            max_log_count: opts.max_log_count,
+            rotate_size,
+            keys: Default::default(),
+            lookups: Default::default(),
+            hits: Default::default(),
+            stats: Default::default(),
+        }
+    }
+
+    /// Count a lookup. Return whether the key should be recorded if it is found.
+    fn sample_lookup(&self) -> bool {
+        let lookups = self.lookups.fetch_add(1, Ordering::Relaxed);
+        self.sample_rate > 0 && lookups % self.sample_rate == 0
+    }
+
+    /// Count a lookup that found an entry.
+    fn record_hit(&self, sampled: Option<(usize, Bytes)>) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        if let Some(key) = sampled {
+            let mut keys = self.keys.lock();
+            if keys.len() < MAX_TRACKED_KEYS {
+                keys.insert(key);
+            }
+        }
+    }
+
+    /// Copy entries of recently read keys from the oldest log into the newest log, if the
+    /// oldest log is going to be dropped by an upcoming rotation.
+    fn copy_forward(&self, log: &mut RotateLog) -> Result<()> {
+        if self.sample_rate == 0 {
+            return Ok(());
+        }
+        let entries = {
+            let logs = log.logs();
+            if logs.len() < self.max_log_count as usize
+                || logs.len() < 2
+                || logs[0].size() < self.rotate_size
+            {
+                return Ok(());
+            }
+            let keys = std::mem::take(&mut *self.keys.lock());
+            let (oldest, newer) = logs.split_last().unwrap();
+
+            let mut stats = self.stats.lock();
+            let mut entries = Vec::new();
+            for (index_id, key) in keys {
+                stats.sampled_keys += 1;
+                let mut kept = false;
+                for log in newer {
+                    if log.lookup(index_id, &key)?.next().is_some() {
+                        kept = true;
+                        break;
+                    }
+                }
+                if kept {
+                    stats.kept_keys += 1;
+                } else if let Some(entry) = oldest.lookup(index_id, &key)?.next() {
+                    let entry = entry?;
+                    stats.copied_keys += 1;
+                    stats.copied_bytes += entry.len() as u64;
+                    entries.push(entry.to_vec());
+                }
+            }
+            entries
+        };
+
+        for entry in entries {
+            log.append(entry)?;
+        }
+
+        let stats = self.stats();
+        tracing::debug!(
+            hit_rate = stats.hit_rate(),
+            retained_before = stats.retained_rate_before_eviction(),
+            retained_after = stats.retained_rate_after_eviction(),
+            copied_keys = stats.copied_keys,
+            copied_bytes = stats.copied_bytes,
+            "copied recently read entries forward"
+        );
+        Ok(())
+    }
+
+    fn stats(&self) -> AccessStats {
+        let mut stats = self.stats.lock().clone();
+        stats.lookups = self.lookups.load(Ordering::Relaxed);
+        stats.hits = self.hits.load(Ordering::Relaxed);
+        stats
+    }
 }
 
 impl Store {
@@ -38,9 +209,19 @@ impl Store {
         let key = key.as_ref();
         match self {
             Store::Local(log) => Ok(LookupIter::Local(log.lookup(index_id, key)?)),
-            Store::Shared(log) => Ok(LookupIter::Shared(
-                log.lookup(index_id, Bytes::copy_from_slice(key))?,
-            )),
+            Store::Shared(log, tracker) => {
+                let key = Bytes::copy_from_slice(key);
+                let sampled = if tracker.sample_lookup() {
+                    Some((index_id, key.clone()))
+                } else {
+                    None
+                };
+                Ok(LookupIter::Shared {
+                    iter: log.lookup(index_id, key)?,
+                    tracker,
+                    first: Some(sampled),
+                })
+            }
         }
     }
 
@@ -48,14 +229,14 @@ impl Store {
     pub fn append(&mut self, buf: impl AsRef<[u8]>) -> Result<()> {
         match self {
             Store::Local(log) => Ok(log.append(buf)?),
-            Store::Shared(log) => Ok(log.append(buf)?),
+            Store::Shared(log, _) => Ok(log.append(buf)?),
         }
     }
 
-    pub fn iter(&self) -> Box<dyn Iterator<Item = IndexedlogResult<&[u8]>> + '_> {
+    pub fn iter(&self) -> Box<dyn Iterator<Item = IndexedlogResult<Cow<[u8]>>> + '_> {
         match self {
             Store::Local(log) => Box::new(log.iter()),
-            Store::Shared(log) => Box::new(log.iter()),
+            Store::Shared(log, _) => Box::new(log.iter()),
         }
     }
 
@@ -64,7 +245,8 @@ impl Store {
             Store::Local(log) => {
                 log.flush()?;
             }
-            Store::Shared(log) => {
+            Store::Shared(log, tracker) => {
+                tracker.copy_forward(log)?;
                 log.flush()?;
             }
         };
@@ -75,16 +257,33 @@ impl Store {
 /// Iterator returned from `Store::lookup`.
 pub enum LookupIter<'a> {
     Local(LogLookupIter<'a>),
-    Shared(RotateLogLookupIter<'a>),
+    Shared {
+        iter: RotateLogLookupIter<'a>,
+        tracker: &'a AccessTracker,
+        /// Set until the first entry is read. Contains the key if the lookup is sampled.
+        first: Option<Option<(usize, Bytes)>>,
+    },
 }
 
 impl<'a> Iterator for LookupIter<'a> {
-    type Item = Result<&'a [u8]>;
+    type Item = Result<Cow<'a, [u8]>>;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self {
             LookupIter::Local(iter) => iter.next().map(|res| res.map_err(Into::into)),
-            LookupIter::Shared(iter) => iter.next().map(|res| res.map_err(Into::into)),
+            LookupIter::Shared {
+                iter,
+                tracker,
+                first,
+            } => {
+                let next = iter.next();
+                if let Some(sampled) = first.take() {
+                    if let Some(Ok(_)) = next {
+                        tracker.record_hit(sampled);
+                    }
+                }
+                next.map(|res| res.map_err(Into::into))
+            }
         }
     }
 }
@@ -95,6 +294,8 @@ pub struct StoreOpenOptions {
     pub max_bytes_per_log: Option<u64>,
     indexes: Vec<IndexDef>,
     create: bool,
+    access_sample_rate: u64,
+    compression: Compression,
 }
 
 impl StoreOpenOptions {
@@ -105,6 +306,8 @@ impl StoreOpenOptions {
             max_bytes_per_log: None,
             indexes: Vec::new(),
             create: true,
+            access_sample_rate: 0,
+            compression: Compression::None,
         }
     }
 
@@ -138,12 +341,27 @@ impl StoreOpenOptions {
         self
     }
 
+    /// When the store is shared, record one out of `rate` lookups, and keep recently read entries
+    /// when old logs are rotated out. 0, the default, disables it.
+    pub fn access_sample_rate(mut self, rate: u64) -> Self {
+        self.access_sample_rate = rate;
+        self
+    }
+
+    /// Compress the entries added to the store. Existing entries are left untouched, and stay
+    /// readable.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
     fn into_local_open_options(self) -> log::OpenOptions {
         log::OpenOptions::new()
             .create(self.create)
             .fsync(true)
             .index_defs(self.indexes)
             .auto_sync_threshold(self.auto_sync_threshold)
+            .compression(self.compression)
     }
 
     /// Create a local `Store`.
@@ -163,7 +381,8 @@ impl StoreOpenOptions {
         let mut opts = rotate::OpenOptions::new()
             .create(self.create)
             .auto_sync_threshold(self.auto_sync_threshold)
-            .index_defs(self.indexes);
+            .index_defs(self.indexes)
+            .compression(self.compression);
 
         if let Some(max_log_count) = self.max_log_count {
             opts = opts.max_log_count(max_log_count);
@@ -181,12 +400,14 @@ impl StoreOpenOptions {
     /// Data added to a shared store will be rotated out depending on the values of `max_log_count`
     /// and `max_bytes_per_log`.
     pub fn shared(self, path: impl AsRef<Path>) -> Result<Store> {
+        let access_sample_rate = self.access_sample_rate;
         let opts = self.into_shared_open_options();
+        let tracker = AccessTracker::new(access_sample_rate, &opts);
         let mut rotate_log = opts.open(path.as_ref())?;
         // Attempt to clean up old logs that might be left around. On Windows, other
         // Mercurial processes that have the store opened might prevent their removal.
         let _ = rotate_log.remove_old_logs();
-        Ok(Store::Shared(rotate_log))
+        Ok(Store::Shared(rotate_log, tracker))
     }
 
     /// Attempts to repair corruption in a local indexedlog store.
@@ -209,6 +430,41 @@ impl StoreOpenOptions {
             .repair(path)
             .map_err(|e| e.into())
     }
+
+    /// Rewrite a local indexedlog store, keeping only the entries selected by `filter`.
+    pub fn compact_local(
+        self,
+        path: PathBuf,
+        mut filter: impl FnMut(&[u8]) -> bool,
+    ) -> Result<String> {
+        self.into_local_open_options()
+            .compact(path, |_, data| Ok(filter(data)))
+            .map_err(|e| e.into())
+    }
+
+    /// Rewrite all the logs of a shared rotatelog store, keeping only the entries selected by
+    /// `filter`. The store is locked for the whole rewrite so that other processes can't rotate
+    /// it, or write to it, in the meantime.
+    pub fn compact_shared(
+        self,
+        path: PathBuf,
+        mut filter: impl FnMut(&[u8]) -> bool,
+    ) -> Result<String> {
+        let log_open_options = self.into_shared_open_options().log_open_options;
+        let _lock = ScopedDirLock::new(&path)?;
+        let mut message = String::new();
+        for entry in fs::read_dir(&path)? {
+            let entry = entry?;
+            let is_log = match entry.file_name().to_str() {
+                Some(name) => name.parse::<u8>().is_ok(),
+                None => false,
+            };
+            if is_log && entry.file_type()?.is_dir() {
+                message += &log_open_options.compact(entry.path(), |_, data| Ok(filter(data)))?;
+            }
+        }
+        Ok(message)
+    }
 }
 
 #[cfg(test)]
@@ -229,7 +485,7 @@ mod tests {
 
         assert_eq!(
             store.lookup(0, b"aa")?.collect::<Result<Vec<_>>>()?,
-            vec![b"aabcd"]
+            vec![&b"aabcd"[..]]
         );
         Ok(())
     }
@@ -246,7 +502,7 @@ mod tests {
 
         assert_eq!(
             store.lookup(0, b"aa")?.collect::<Result<Vec<_>>>()?,
-            vec![b"aabcd"]
+            vec![&b"aabcd"[..]]
         );
         Ok(())
     }
@@ -292,4 +548,75 @@ mod tests {
         assert_eq!(store.lookup(0, b"aa")?.count(), 0);
         Ok(())
     }
+
+    #[test]
+    fn test_shared_keep_recently_read() -> Result<()> {
+        let dir = TempDir::new()?;
+
+        let mut store = StoreOpenOptions::new()
+            .index("hex", |_| vec![IndexOutput::Reference(0..2)])
+            .max_log_count(2)
+            .max_bytes_per_log(10)
+            .access_sample_rate(1)
+            .shared(&dir)?;
+
+        store.append(b"aabcd")?;
+        store.append(b"abbcd")?;
+        store.flush()?;
+
+        // "aa" is read from the oldest log, and copied forward before it is dropped.
+        assert_eq!(store.lookup(0, b"aa")?.count(), 1);
+        assert_eq!(store.lookup(0, b"zz")?.count(), 0);
+        store.append(b"acbcd")?;
+        store.append(b"adbcd")?;
+        store.flush()?;
+
+        assert_eq!(store.lookup(0, b"aa")?.count(), 1);
+        assert_eq!(store.lookup(0, b"ab")?.count(), 0);
+
+        let stats = match &store {
+            Store::Shared(_, tracker) => tracker.stats(),
+            Store::Local(_) => unreachable!(),
+        };
+        assert_eq!(stats.lookups, 4);
+        assert_eq!(stats.hits, 2);
+        assert_eq!(stats.sampled_keys, 1);
+        assert_eq!(stats.copied_keys, 1);
+        assert_eq!(stats.retained_rate_before_eviction(), 0.0);
+        assert_eq!(stats.retained_rate_after_eviction(), 1.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shared_copy_forward_before_rotation_only() -> Result<()> {
+        let dir = TempDir::new()?;
+
+        let mut store = StoreOpenOptions::new()
+            .index("hex", |_| vec![IndexOutput::Reference(0..2)])
+            .max_log_count(2)
+            .max_bytes_per_log(100)
+            .access_sample_rate(1)
+            .shared(&dir)?;
+
+        store.append(b"aabcd")?;
+        store.flush()?;
+        match &mut store {
+            Store::Shared(log, _) => log.force_rotate()?,
+            Store::Local(_) => unreachable!(),
+        }
+
+        // "aa" is read from the oldest log, but the newest log is far from full, so it is not
+        // copied forward yet.
+        assert_eq!(store.lookup(0, b"aa")?.count(), 1);
+        store.append(b"abbcd")?;
+        store.flush()?;
+
+        let stats = match &store {
+            Store::Shared(_, tracker) => tracker.stats(),
+            Store::Local(_) => unreachable!(),
+        };
+        assert_eq!(stats.sampled_keys, 0);
+        assert_eq!(stats.copied_keys, 0);
+        Ok(())
+    }
 }
@@ -6,11 +6,11 @@
  */
 
 use std::{
-    cmp::min,
+    cmp::{max, min},
     collections::{HashMap, HashSet},
     convert::TryInto,
-    fs::File,
-    io::{Cursor, ErrorKind, Read, Write},
+    fs::{self, File},
+    io::{Cursor, ErrorKind, Read, Seek, SeekFrom, Write},
     iter, mem,
     ops::Range,
     path::{Path, PathBuf},
@@ -24,12 +24,15 @@ use std::{
 };
 
 use anyhow::{bail, ensure, format_err, Context, Result};
+use fs2::FileExt;
 use futures::stream::{iter, StreamExt, TryStreamExt};
 use http::status::StatusCode;
+use memmap::Mmap;
 use minibytes::Bytes;
 use parking_lot::{Mutex, RwLock};
 use rand::{thread_rng, Rng};
 use serde_derive::{Deserialize, Serialize};
+use sha2::Digest;
 use tokio::{
     task::spawn_blocking,
     time::{sleep, timeout},
@@ -51,12 +54,13 @@ use lfs_protocol::{
     Sha256 as LfsSha256,
 };
 use mincode::{deserialize, serialize};
+use progress::{ProgressBar, ProgressFactory, Unit};
 use types::{HgId, Key, RepoPath, Sha256};
 use util::path::{create_dir, create_shared_dir, remove_file};
 
 use crate::{
     datastore::{
-        strip_metadata, ContentDataStore, ContentMetadata, Delta, HgIdDataStore,
+        rebuild_metadata, strip_metadata, ContentDataStore, ContentMetadata, Delta, HgIdDataStore,
         HgIdMutableDeltaStore, Metadata, RemoteDataStore, StoreResult,
     },
     error::{FetchError, TransferError},
@@ -67,13 +71,17 @@ use crate::{
     remotestore::HgIdRemoteStore,
     types::{ContentHash, StoreKey},
     uniondatastore::UnionHgIdDataStore,
-    util::{get_lfs_blobs_path, get_lfs_objects_path, get_lfs_pointers_path, get_str_config},
+    util::{
+        get_lfs_blobs_path, get_lfs_objects_path, get_lfs_pointers_path, get_lfs_staging_path,
+        get_str_config,
+    },
+    verify::VerifyReport,
 };
 
 /// The `LfsPointersStore` holds the mapping between a `HgId` and the content hash (sha256) of the LFS blob.
 struct LfsPointersStore(Store);
 
-struct LfsIndexedLogBlobsStore {
+pub(crate) struct LfsIndexedLogBlobsStore {
     inner: RwLock<Store>,
     chunk_size: usize,
 }
@@ -96,10 +104,13 @@ struct HttpLfsRemote {
     auth: Option<Auth>,
     user_agent: String,
     concurrent_fetches: usize,
+    concurrent_uploads: usize,
     backoff_times: Vec<f32>,
     request_timeout: Duration,
     client: HttpClient,
     http_options: HttpOptions,
+    /// When set, downloaded blobs are streamed to disk and interrupted downloads are resumed.
+    staging: Option<LfsStaging>,
 }
 
 struct HttpOptions {
@@ -119,6 +130,22 @@ pub struct LfsRemote {
     shared: Arc<LfsStore>,
     remote: LfsRemoteInner,
     move_after_upload: bool,
+    progress: Arc<dyn ProgressFactory>,
+}
+
+/// Blobs to upload to the LFS server, as computed by `LfsRemoteInner::plan_upload`.
+#[derive(Default)]
+struct LfsUploadPlan {
+    /// Blobs that the server is missing, along with the action to upload them.
+    uploads: Vec<(Sha256, u64, ObjectAction)>,
+    /// Number of blobs that the server already has.
+    skipped: usize,
+}
+
+impl LfsUploadPlan {
+    fn size(&self) -> u64 {
+        self.uploads.iter().map(|(_, size, _)| size).sum()
+    }
 }
 
 /// Main LFS store to be used within the `ContentStore`.
@@ -132,6 +159,9 @@ pub struct LfsRemote {
 pub struct LfsStore {
     pointers: RwLock<LfsPointersStore>,
     blobs: LfsBlobsStore,
+    /// Partially downloaded blobs, only present for shared stores. It lives in the top-level
+    /// directory "staging".
+    staging: Option<LfsStaging>,
 }
 
 /// When a blob is added to the `LfsMultiplexer`, is will either be written to an `LfsStore`, or to
@@ -242,7 +272,7 @@ impl LfsPointersStore {
             Some(buf) => buf?,
         };
 
-        Self::get_from_slice(buf).map(Some)
+        Self::get_from_slice(&buf).map(Some)
     }
 
     /// Find the pointer corresponding to the passed in `Key`.
@@ -263,6 +293,16 @@ struct LfsIndexedLogBlobsEntry {
     data: Bytes,
 }
 
+/// The leading fields of a `LfsIndexedLogBlobsEntry`, to look at the chunks of a blob without
+/// reading their data.
+#[derive(Deserialize)]
+struct LfsIndexedLogBlobsEntryRange {
+    #[serde(with = "types::serde_with::sha256::tuple")]
+    #[allow(dead_code)]
+    sha256: Sha256,
+    range: Range<usize>,
+}
+
 impl DefaultOpenOptions<rotate::OpenOptions> for LfsIndexedLogBlobsStore {
     fn default_open_options() -> rotate::OpenOptions {
         Self::default_store_open_options().into_shared_open_options()
@@ -300,9 +340,13 @@ impl LfsIndexedLogBlobsStore {
     }
 
     pub fn shared(path: &Path, config: &ConfigSet) -> Result<Self> {
-        let path = get_lfs_blobs_path(path)?;
+        LfsIndexedLogBlobsStore::open(get_lfs_blobs_path(path)?, config)
+    }
+
+    /// Open the blobs stored directly in `blobs_path`.
+    pub fn open(blobs_path: PathBuf, config: &ConfigSet) -> Result<Self> {
         Ok(Self {
-            inner: RwLock::new(LfsIndexedLogBlobsStore::open_options(config)?.shared(path)?),
+            inner: RwLock::new(LfsIndexedLogBlobsStore::open_options(config)?.shared(blobs_path)?),
             chunk_size: LfsIndexedLogBlobsStore::chunk_size(config)?,
         })
     }
@@ -311,7 +355,7 @@ impl LfsIndexedLogBlobsStore {
         let store = self.inner.read();
         let chunks_iter = store
             .lookup(0, hash)?
-            .map(|data| Ok(deserialize::<LfsIndexedLogBlobsEntry>(data?)?));
+            .map(|data| Ok(deserialize::<LfsIndexedLogBlobsEntry>(&data?)?));
 
         // Filter errors. It's possible that one entry is corrupted, or for whatever reason can't
         // be deserialized, whenever this blob/entry is refetched, the corrupted entry will still be
@@ -374,6 +418,29 @@ impl LfsIndexedLogBlobsStore {
         Ok(self.inner.read().lookup(0, hash)?.next().is_some())
     }
 
+    /// Test whether all the chunks of a blob of `size` bytes are in the store. Unlike `get`, the
+    /// data of the chunks is neither copied nor hashed.
+    pub fn contains_all(&self, hash: &Sha256, size: usize) -> Result<bool> {
+        let store = self.inner.read();
+        let mut ranges = store
+            .lookup(0, hash)?
+            .filter_map(|data| deserialize::<LfsIndexedLogBlobsEntryRange>(&data.ok()?).ok())
+            .map(|entry| entry.range)
+            .collect::<Vec<_>>();
+        drop(store);
+
+        ranges.sort_unstable_by_key(|range| range.start);
+        let mut next_start = 0;
+        for range in ranges {
+            // A chunk is missing.
+            if range.start > next_start {
+                return Ok(false);
+            }
+            next_start = max(next_start, range.end);
+        }
+        Ok(next_start >= size)
+    }
+
     fn chunk(mut data: Bytes, chunk_size: usize) -> impl Iterator<Item = (Range<usize>, Bytes)> {
         let mut start = 0;
         iter::from_fn(move || {
@@ -409,6 +476,59 @@ impl LfsIndexedLogBlobsStore {
     pub fn flush(&self) -> Result<()> {
         self.inner.write().flush()
     }
+
+    /// Verify that the blobs of the shared store at `path` are complete and hash to their
+    /// `Sha256`. When `delete_corrupt` is set, the chunks of corrupt blobs are removed.
+    fn verify(path: &Path, config: &ConfigSet, delete_corrupt: bool) -> Result<VerifyReport> {
+        let mut report = VerifyReport::default();
+        let blobs_path = get_lfs_blobs_path(path)?;
+
+        let store = LfsIndexedLogBlobsStore::shared(path, config)?;
+        let mut hashes = HashSet::new();
+        for buf in store.inner.read().iter() {
+            match deserialize::<LfsIndexedLogBlobsEntry>(&buf?) {
+                Ok(entry) => {
+                    hashes.insert(entry.sha256);
+                }
+                Err(e) => report.corrupt.push(format!(
+                    "{}: unreadable chunk: {}",
+                    blobs_path.display(),
+                    e
+                )),
+            }
+        }
+
+        let mut corrupt = HashSet::new();
+        for hash in hashes {
+            report.checked += 1;
+            if store.get(&hash)?.is_none() {
+                report.corrupt.push(format!(
+                    "{}: {}: blob is incomplete or does not match its hash",
+                    blobs_path.display(),
+                    hash
+                ));
+                corrupt.insert(hash);
+            }
+        }
+        drop(store);
+
+        if delete_corrupt && !report.is_ok() {
+            let mut removed = 0;
+            LfsIndexedLogBlobsStore::open_options(config)?.compact_shared(blobs_path, |buf| {
+                let is_corrupt = match deserialize::<LfsIndexedLogBlobsEntry>(buf) {
+                    Ok(entry) => corrupt.contains(&entry.sha256),
+                    Err(_) => true,
+                };
+                if is_corrupt {
+                    removed += 1;
+                }
+                !is_corrupt
+            })?;
+            report.removed = removed;
+        }
+
+        Ok(report)
+    }
 }
 
 impl LfsBlobsStore {
@@ -553,11 +673,147 @@ impl LfsBlobsStore {
     }
 }
 
+/// Staging area for blobs being downloaded.
+///
+/// Partially downloaded blobs are kept on disk, one file per blob, so an interrupted download can
+/// be resumed, possibly by another process. Blobs are written to disk as they are received, thus
+/// large blobs don't need to be held in memory.
+#[derive(Clone)]
+struct LfsStaging {
+    path: PathBuf,
+}
+
+/// A blob being downloaded into the `LfsStaging` area. Its SHA-256 is computed as data is written.
+struct LfsPartialBlob {
+    path: PathBuf,
+    file: File,
+    hasher: sha2::Sha256,
+    len: u64,
+    /// Number of bytes written since the blob was opened, including discarded ones.
+    written: u64,
+}
+
+impl LfsStaging {
+    /// Partially downloaded blobs older than this are considered abandoned.
+    const MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+    fn new(store_path: &Path) -> Result<Self> {
+        let staging = LfsStaging {
+            path: get_lfs_staging_path(store_path)?,
+        };
+        staging.remove_abandoned();
+        Ok(staging)
+    }
+
+    /// Remove abandoned partial blobs. Best effort, as blobs might be in use by other processes.
+    fn remove_abandoned(&self) {
+        let entries = match fs::read_dir(&self.path) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let age = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok());
+            if age.map_or(false, |age| age > Self::MAX_AGE) {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+
+    /// Open the partial blob for `oid`, to start or resume its download. Returns `None` if
+    /// another process is downloading it.
+    fn open(&self, oid: &Sha256) -> Result<Option<LfsPartialBlob>> {
+        let path = self.path.join(oid.to_hex());
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&path)?;
+        if file.try_lock_exclusive().is_err() {
+            return Ok(None);
+        }
+
+        // Hash the data that was previously downloaded.
+        let mut hasher = sha2::Sha256::new();
+        let mut len = 0;
+        let mut buf = vec![0; 1 << 20];
+        loop {
+            let read = file.read(&mut buf)?;
+            if read == 0 {
+                break;
+            }
+            hasher.input(&buf[..read]);
+            len += read as u64;
+        }
+
+        Ok(Some(LfsPartialBlob {
+            path,
+            file,
+            hasher,
+            len,
+            written: 0,
+        }))
+    }
+}
+
+impl LfsPartialBlob {
+    /// Discard the downloaded data, to restart the download from the beginning.
+    fn truncate(&mut self) -> Result<()> {
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.hasher = sha2::Sha256::new();
+        self.len = 0;
+        Ok(())
+    }
+
+    /// Validate the SHA-256 of the downloaded data, and return it. The data is mapped from disk
+    /// and not read in memory.
+    fn finish(mut self, oid: &Sha256) -> Result<Bytes> {
+        let hash: [u8; Sha256::len()] = self.hasher.clone().result().into();
+        if &Sha256::from(hash) != oid {
+            self.truncate()?;
+            bail!("downloaded blob doesn't match its hash {}", oid);
+        }
+
+        let data = if self.len == 0 {
+            Bytes::new()
+        } else {
+            Bytes::from_owner(unsafe { Mmap::map(&self.file)? })
+        };
+        // The data stays readable while mapped. On Windows, a mapped file cannot be removed, it
+        // will be removed once abandoned.
+        let _ = fs::remove_file(&self.path);
+        Ok(data)
+    }
+}
+
+impl Write for LfsPartialBlob {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.file.write(buf)?;
+        self.hasher.input(&buf[..written]);
+        self.len += written as u64;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
 impl LfsStore {
-    fn new(pointers: LfsPointersStore, blobs: LfsBlobsStore) -> Result<Self> {
+    fn new(
+        pointers: LfsPointersStore,
+        blobs: LfsBlobsStore,
+        staging: Option<LfsStaging>,
+    ) -> Result<Self> {
         Ok(Self {
             pointers: RwLock::new(pointers),
             blobs,
+            staging,
         })
     }
 
@@ -568,7 +824,7 @@ impl LfsStore {
         let path = path.as_ref();
         let pointers = LfsPointersStore::local(path, config)?;
         let blobs = LfsBlobsStore::local(path)?;
-        LfsStore::new(pointers, blobs)
+        LfsStore::new(pointers, blobs, None)
     }
 
     /// Create a new shared `LfsStore`.
@@ -576,7 +832,8 @@ impl LfsStore {
         let path = path.as_ref();
         let pointers = LfsPointersStore::shared(path, config)?;
         let blobs = LfsBlobsStore::shared(path, config)?;
-        LfsStore::new(pointers, blobs)
+        let staging = LfsStaging::new(path)?;
+        LfsStore::new(pointers, blobs, Some(staging))
     }
 
     pub fn repair(path: impl AsRef<Path>) -> Result<String> {
@@ -589,6 +846,15 @@ impl LfsStore {
         Ok(repair_str)
     }
 
+    /// Verify the blobs of the shared `LfsStore` at `path`. See `ContentStore::verify`.
+    pub fn verify(
+        path: impl AsRef<Path>,
+        config: &ConfigSet,
+        delete_corrupt: bool,
+    ) -> Result<VerifyReport> {
+        LfsIndexedLogBlobsStore::verify(path.as_ref(), config, delete_corrupt)
+    }
+
     fn blob_impl(&self, key: StoreKey) -> Result<StoreResult<(LfsPointersEntry, Bytes)>> {
         let pointer = self.pointers.read().entry(&key)?;
 
@@ -653,40 +919,11 @@ impl LocalStore for LfsStore {
     }
 }
 
-/// When a file was copied, Mercurial expects the blob that the store returns to contain this copy
-/// information
-fn rebuild_metadata(data: Bytes, entry: &LfsPointersEntry) -> Bytes {
-    if let Some(copy_from) = &entry.copy_from {
-        let copy_from_path: &[u8] = copy_from.path.as_ref();
-        let mut ret = Vec::with_capacity(data.len() + copy_from_path.len() + 128);
-
-        ret.extend_from_slice(&b"\x01\n"[..]);
-        ret.extend_from_slice(&b"copy: "[..]);
-        ret.extend_from_slice(copy_from_path);
-        ret.extend_from_slice(&b"\n"[..]);
-        ret.extend_from_slice(&b"copyrev: "[..]);
-        ret.extend_from_slice(copy_from.hgid.to_hex().as_bytes());
-        ret.extend_from_slice(&b"\n"[..]);
-        ret.extend_from_slice(&b"\x01\n"[..]);
-        ret.extend_from_slice(data.as_ref());
-        ret.into()
-    } else {
-        if data.as_ref().starts_with(b"\x01\n") {
-            let mut ret = Vec::with_capacity(data.len() + 4);
-            ret.extend_from_slice(&b"\x01\n\x01\n"[..]);
-            ret.extend_from_slice(data.as_ref());
-            ret.into()
-        } else {
-            data
-        }
-    }
-}
-
 impl HgIdDataStore for LfsStore {
     fn get(&self, key: StoreKey) -> Result<StoreResult<Vec<u8>>> {
         match self.blob_impl(key)? {
             StoreResult::Found((entry, content)) => {
-                let content = rebuild_metadata(content, &entry);
+                let content = rebuild_metadata(content, entry.copy_from.as_ref());
                 // PERF: Consider changing HgIdDataStore::get() to return Bytes to avoid copying data.
                 Ok(StoreResult::Found(content.as_ref().to_vec()))
             }
@@ -740,6 +977,15 @@ impl HgIdMutableDeltaStore for LfsStore {
         self.pointers.write().0.flush()?;
         Ok(None)
     }
+
+    fn get_copy_from(&self, key: StoreKey) -> Result<StoreResult<Option<Key>>> {
+        let entry = self.pointers.read().get(&key)?;
+        if let Some(entry) = entry {
+            Ok(StoreResult::Found(entry.copy_from))
+        } else {
+            Ok(StoreResult::NotFound(key))
+        }
+    }
 }
 
 impl From<LfsPointersEntry> for ContentMetadata {
@@ -919,9 +1165,16 @@ impl HgIdMutableDeltaStore for LfsMultiplexer {
         self.lfs.flush()?;
         Ok(ret)
     }
+
+    fn get_copy_from(&self, key: StoreKey) -> Result<StoreResult<Option<Key>>> {
+        self.lfs.get_copy_from(key)
+    }
 }
 
 impl LfsRemoteInner {
+    /// A download that keeps making progress is resumed, up to this many attempts in total.
+    const MAX_DOWNLOAD_ATTEMPTS: usize = 10;
+
     fn batch_fetch(
         &self,
         objs: &HashSet<(Sha256, usize)>,
@@ -944,20 +1197,61 @@ impl LfsRemoteInner {
         &self,
         objs: &HashSet<(Sha256, usize)>,
         read_from_store: impl Fn(Sha256) -> Result<Option<Bytes>> + Send + Clone + 'static,
+        progress: &dyn ProgressFactory,
     ) -> Result<()> {
-        let write_to_store = |_, _| unreachable!();
         match self {
-            LfsRemoteInner::Http(http) => Self::batch_http(
-                http,
-                objs,
-                Operation::Upload,
-                read_from_store,
-                write_to_store,
-            ),
+            LfsRemoteInner::Http(http) => {
+                let plan = Self::plan_upload(http, objs)?;
+                let bar = progress.bar("Uploading LFS blobs", Some(plan.size()), Unit::Bytes)?;
+                Self::execute_upload(http, plan, read_from_store, bar.as_ref())
+            }
             LfsRemoteInner::File(file) => Self::batch_upload_file(file, objs, read_from_store),
         }
     }
 
+    fn new_request(
+        method: Method,
+        url: Url,
+        user_agent: &str,
+        attempt: usize,
+        auth: Option<&Auth>,
+        accept_zstd: bool,
+        http_options: &HttpOptions,
+    ) -> Request {
+        let mut req = Request::new(url, method)
+            .header("Accept", "application/vnd.git-lfs+json")
+            .header("Content-Type", "application/vnd.git-lfs+json")
+            .header("User-Agent", user_agent)
+            .header("X-Attempt", attempt.to_string())
+            .http_version(http_options.http_version);
+
+        if let Some(ref correlator) = http_options.correlator {
+            req = req.header("X-Client-Correlator", correlator.clone());
+        }
+
+        if accept_zstd {
+            req = req.header("Accept-Encoding", "zstd");
+        }
+
+        if let Some(mts) = http_options.min_transfer_speed {
+            req = req.min_transfer_speed(mts);
+        }
+
+        if let Some(auth) = auth {
+            if let Some(cert) = &auth.cert {
+                req = req.cert(cert);
+            }
+            if let Some(key) = &auth.key {
+                req = req.key(key);
+            }
+            if let Some(ca) = &auth.cacerts {
+                req = req.cainfo(ca);
+            }
+        }
+
+        req
+    }
+
     async fn send_with_retry(
         client: &HttpClient,
         auth: Option<&Auth>,
@@ -976,36 +1270,15 @@ impl LfsRemoteInner {
         loop {
             attempt += 1;
 
-            let mut req = Request::new(url.clone(), method)
-                .header("Accept", "application/vnd.git-lfs+json")
-                .header("Content-Type", "application/vnd.git-lfs+json")
-                .header("User-Agent", &user_agent)
-                .header("X-Attempt", attempt.to_string())
-                .http_version(http_options.http_version);
-
-            if let Some(ref correlator) = http_options.correlator {
-                req = req.header("X-Client-Correlator", correlator.clone());
-            }
-
-            if http_options.accept_zstd {
-                req = req.header("Accept-Encoding", "zstd");
-            }
-
-            if let Some(mts) = http_options.min_transfer_speed {
-                req = req.min_transfer_speed(mts);
-            }
-
-            if let Some(auth) = &auth {
-                if let Some(cert) = &auth.cert {
-                    req = req.cert(cert);
-                }
-                if let Some(key) = &auth.key {
-                    req = req.key(key);
-                }
-                if let Some(ca) = &auth.cacerts {
-                    req = req.cainfo(ca);
-                }
-            }
+            let req = LfsRemoteInner::new_request(
+                method,
+                url.clone(),
+                user_agent,
+                attempt,
+                auth,
+                http_options.accept_zstd,
+                http_options,
+            );
 
             let res = async {
                 let req = add_extra(req);
@@ -1111,6 +1384,191 @@ impl LfsRemoteInner {
         }
     }
 
+    /// Download a blob into `blob`, resuming from the data that it already holds.
+    ///
+    /// The data is written to disk as it is received. In addition to the errors retried by
+    /// `send_with_retry`, a transfer that fails after having received some data, or that has to be
+    /// restarted from the beginning, is retried after a backoff. Receiving data resets the backoff,
+    /// and the download gives up after `MAX_DOWNLOAD_ATTEMPTS` attempts.
+    async fn download_with_resume(
+        client: &HttpClient,
+        auth: Option<&Auth>,
+        url: Url,
+        user_agent: &str,
+        backoff_times: Vec<f32>,
+        request_timeout: Duration,
+        header: Option<&HashMap<String, String>>,
+        http_options: &HttpOptions,
+        blob: &mut LfsPartialBlob,
+    ) -> Result<()> {
+        let method = Method::Get;
+        let mut backoff = backoff_times.clone().into_iter();
+        let mut rng = thread_rng();
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+
+            let offset = blob.len;
+            let written = blob.written;
+
+            // A range of a zstd encoded response wouldn't match the decoded data already received.
+            let accept_zstd = http_options.accept_zstd && offset == 0;
+            let mut req = LfsRemoteInner::new_request(
+                method,
+                url.clone(),
+                user_agent,
+                attempt,
+                auth,
+                accept_zstd,
+                http_options,
+            );
+            if let Some(header) = header {
+                for (key, val) in header {
+                    req = req.header(key, val);
+                }
+            }
+            if offset > 0 {
+                req = req.header("Range", format!("bytes={}-", offset));
+            }
+
+            let res = async {
+                let (mut stream, _) = client.send_async(vec![req])?;
+
+                let reply = timeout(request_timeout, stream.next())
+                    .await
+                    .map_err(|_| TransferError::Timeout(request_timeout))?;
+
+                let reply = match reply {
+                    Some(r) => r?,
+                    None => {
+                        return Err(TransferError::EndOfStream);
+                    }
+                };
+
+                let status = reply.status;
+                let headers = reply.headers;
+
+                match status {
+                    StatusCode::PARTIAL_CONTENT => {
+                        let content_range = headers.get("Content-Range");
+                        let start = content_range
+                            .and_then(|c| str::from_utf8(c.as_bytes()).ok())
+                            .and_then(parse_content_range_start);
+                        if start != Some(offset) {
+                            return Err(TransferError::InvalidResponse(format_err!(
+                                "Unexpected Content-Range: {:?}",
+                                content_range
+                            )));
+                        }
+                    }
+                    StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => {
+                        // The partially downloaded blob is larger than the blob, start over.
+                        blob.truncate().map_err(TransferError::InvalidResponse)?;
+                        return Err(TransferError::HttpStatus(status));
+                    }
+                    status if status.is_success() => {
+                        if offset > 0 {
+                            // The range was ignored, the whole blob is being sent.
+                            blob.truncate().map_err(TransferError::InvalidResponse)?;
+                        }
+                    }
+                    status => return Err(TransferError::HttpStatus(status)),
+                }
+
+                let content_encoding = headers.get("Content-Encoding");
+
+                let mut writer: Box<dyn Write + Send + '_> = match content_encoding
+                    .map(|c| str::from_utf8(c.as_bytes()))
+                    .transpose()
+                    .with_context(|| format!("Invalid Content-Encoding: {:?}", content_encoding))
+                    .map_err(TransferError::InvalidResponse)?
+                {
+                    Some("identity") | None => Box::new(&mut *blob),
+                    Some("zstd") => Box::new(
+                        zstd::stream::write::Decoder::new(&mut *blob)
+                            .context("Error decoding zstd stream")
+                            .map_err(TransferError::InvalidResponse)?,
+                    ),
+                    Some(other) => {
+                        return Err(TransferError::InvalidResponse(format_err!(
+                            "Unsupported Content-Encoding: {}",
+                            other
+                        )));
+                    }
+                };
+
+                let start = Instant::now();
+                let mut bytes = 0;
+                let mut body = reply.body;
+                while let Some(res) = timeout(request_timeout, body.next()).await.transpose() {
+                    let chunk = res.map_err(|_| {
+                        let request_id = headers
+                            .get("x-request-id")
+                            .and_then(|c| std::str::from_utf8(c.as_bytes()).ok())
+                            .unwrap_or("?")
+                            .into();
+                        let elapsed = start.elapsed().as_millis();
+                        TransferError::ChunkTimeout {
+                            timeout: request_timeout,
+                            bytes,
+                            elapsed,
+                            request_id,
+                        }
+                    })??;
+
+                    bytes += chunk.len();
+                    writer
+                        .write_all(&chunk)
+                        .context("Error writing downloaded blob")
+                        .map_err(TransferError::InvalidResponse)?;
+                }
+
+                writer
+                    .flush()
+                    .context("Error writing downloaded blob")
+                    .map_err(TransferError::InvalidResponse)?;
+
+                Result::<_, TransferError>::Ok(())
+            }
+            .await;
+
+            let error = match res {
+                Ok(()) => return Ok(()),
+                Err(error) => error,
+            };
+
+            let retry = match &error {
+                TransferError::HttpStatus(status) => should_retry_http_status(*status),
+                TransferError::HttpClientError(http_error) => should_retry_http_error(&http_error),
+                TransferError::EndOfStream => false,
+                TransferError::Timeout(..) => false,
+                TransferError::ChunkTimeout { .. } => false,
+                TransferError::InvalidResponse(..) => false,
+            };
+
+            if attempt >= Self::MAX_DOWNLOAD_ATTEMPTS {
+                return Err(FetchError { url, method, error }.into());
+            }
+
+            // The download is restarted from the beginning if the data received was discarded.
+            let restarted = blob.len < offset;
+            if blob.written > written {
+                backoff = backoff_times.clone().into_iter();
+            } else if !retry && !restarted {
+                return Err(FetchError { url, method, error }.into());
+            }
+
+            if let Some(backoff_time) = backoff.next() {
+                let sleep_time = Duration::from_secs_f32(rng.gen_range(0.0, backoff_time));
+                sleep(sleep_time).await;
+                continue;
+            }
+
+            return Err(FetchError { url, method, error }.into());
+        }
+    }
+
     fn send_batch_request(
         http: &HttpLfsRemote,
         objs: &HashSet<(Sha256, usize)>,
@@ -1172,6 +1630,7 @@ impl LfsRemoteInner {
         read_from_store: impl Fn(Sha256) -> Result<Option<Bytes>> + Send + 'static,
         write_to_store: impl Fn(Sha256, Bytes) -> Result<()> + Send + 'static,
         http_options: &HttpOptions,
+        staging: Option<LfsStaging>,
     ) -> Result<()> {
         let body = if op == Operation::Upload {
             spawn_blocking(move || read_from_store(oid)).await??
@@ -1179,36 +1638,61 @@ impl LfsRemoteInner {
             None
         };
 
+        let partial = match staging {
+            Some(staging) if op == Operation::Download => {
+                spawn_blocking(move || staging.open(&oid)).await??
+            }
+            _ => None,
+        };
+
         let method = match op {
             Operation::Download => Method::Get,
             Operation::Upload => Method::Put,
         };
 
         let url = Url::from_str(&action.href.to_string())?;
-        let data = LfsRemoteInner::send_with_retry(
-            client,
-            auth,
-            method,
-            url,
-            user_agent,
-            backoff_times,
-            request_timeout,
-            move |mut builder| {
-                if let Some(header) = action.header.as_ref() {
-                    for (key, val) in header {
-                        builder = builder.header(key, val)
-                    }
-                }
+        let data = match partial {
+            Some(mut partial) => LfsRemoteInner::download_with_resume(
+                client,
+                auth,
+                url,
+                user_agent,
+                backoff_times,
+                request_timeout,
+                action.header.as_ref(),
+                http_options,
+                &mut partial,
+            )
+            .await
+            .and_then(|()| partial.finish(&oid))
+            .map(Some),
+            None => {
+                LfsRemoteInner::send_with_retry(
+                    client,
+                    auth,
+                    method,
+                    url,
+                    user_agent,
+                    backoff_times,
+                    request_timeout,
+                    move |mut builder| {
+                        if let Some(header) = action.header.as_ref() {
+                            for (key, val) in header {
+                                builder = builder.header(key, val)
+                            }
+                        }
 
-                if let Some(body) = body.clone() {
-                    builder.body(Vec::from(body.as_ref()))
-                } else {
-                    builder.header("Content-Length", 0)
-                }
-            },
-            http_options,
-        )
-        .await;
+                        if let Some(body) = body.clone() {
+                            builder.body(Vec::from(body.as_ref()))
+                        } else {
+                            builder.header("Content-Length", 0)
+                        }
+                    },
+                    http_options,
+                )
+                .await
+            }
+        };
 
         if op == Operation::Download {
             let data = match data {
@@ -1283,6 +1767,7 @@ impl LfsRemoteInner {
                     read_from_store,
                     write_to_store,
                     &http.http_options,
+                    http.staging.clone(),
                 );
 
                 futures.push(Ok(fut));
@@ -1293,6 +1778,83 @@ impl LfsRemoteInner {
         block_on_future(iter(futures).try_for_each_concurrent(http.concurrent_fetches, |fut| fut))
     }
 
+    /// Ask the server which of the blobs it is missing, with a single batch request for all of them.
+    fn plan_upload(http: &HttpLfsRemote, objs: &HashSet<(Sha256, usize)>) -> Result<LfsUploadPlan> {
+        let span = info_span!(
+            "LfsRemote::plan_upload",
+            num_blobs = objs.len(),
+            skipped = &0
+        );
+        let _guard = span.enter();
+
+        let mut plan = LfsUploadPlan::default();
+        if objs.is_empty() {
+            return Ok(plan);
+        }
+
+        let response = match LfsRemoteInner::send_batch_request(http, objs, Operation::Upload)? {
+            None => return Ok(plan),
+            Some(response) => response,
+        };
+
+        for object in response.objects {
+            let oid = object.object.oid;
+            let mut actions = match object.status {
+                ObjectStatus::Ok {
+                    authenticated: _,
+                    actions,
+                } => actions,
+                ObjectStatus::Err { error: e } => bail!("Couldn't upload oid {}: {:?}", oid, e),
+            };
+
+            // The server omits the upload action for the blobs it already has.
+            match actions.remove(&Operation::Upload) {
+                Some(action) => {
+                    plan.uploads
+                        .push((Sha256::from(oid.0), object.object.size, action))
+                }
+                None => plan.skipped += 1,
+            }
+        }
+
+        span.record("skipped", &plan.skipped);
+        Ok(plan)
+    }
+
+    /// Upload the blobs of `plan`, a couple of them concurrently.
+    fn execute_upload(
+        http: &HttpLfsRemote,
+        plan: LfsUploadPlan,
+        read_from_store: impl Fn(Sha256) -> Result<Option<Bytes>> + Send + Clone + 'static,
+        bar: &dyn ProgressBar,
+    ) -> Result<()> {
+        let write_to_store = |_, _| unreachable!();
+
+        let futures = plan.uploads.into_iter().map(|(oid, size, action)| {
+            let fut = LfsRemoteInner::process_action(
+                &http.client,
+                http.auth.as_ref(),
+                &http.user_agent,
+                http.backoff_times.clone(),
+                http.request_timeout,
+                Operation::Upload,
+                action,
+                oid,
+                read_from_store.clone(),
+                write_to_store,
+                &http.http_options,
+                None,
+            );
+
+            Ok(async move {
+                fut.await?;
+                bar.increment(size)
+            })
+        });
+
+        block_on_future(iter(futures).try_for_each_concurrent(http.concurrent_uploads, |fut| fut))
+    }
+
     /// Fetch files from the filesystem.
     fn batch_fetch_file(
         file: &LfsBlobsStore,
@@ -1329,6 +1891,7 @@ impl LfsRemote {
         local: Option<Arc<LfsStore>>,
         config: &ConfigSet,
         correlator: Option<String>,
+        progress: Arc<dyn ProgressFactory>,
     ) -> Result<Self> {
         let mut url = get_str_config(config, "lfs", "url")?;
         // A trailing '/' needs to be present so that `Url::join` doesn't remove the reponame
@@ -1346,6 +1909,7 @@ impl LfsRemote {
                 shared,
                 local,
                 move_after_upload,
+                progress,
                 remote: LfsRemoteInner::File(file),
             })
         } else {
@@ -1365,6 +1929,9 @@ impl LfsRemote {
 
             let concurrent_fetches = config.get_or("lfs", "concurrentfetches", || 1)?;
 
+            let concurrent_uploads =
+                config.get_or("lfs", "concurrentuploads", || concurrent_fetches)?;
+
             let backoff_times = config.get_or("lfs", "backofftimes", || vec![1f32, 4f32, 8f32])?;
 
             let request_timeout =
@@ -1393,15 +1960,23 @@ impl LfsRemote {
 
             let client = http_client("lfs");
 
+            let staging = if config.get_or("lfs", "resumable-downloads", || true)? {
+                shared.staging.clone()
+            } else {
+                None
+            };
+
             Ok(Self {
                 shared,
                 local,
                 move_after_upload,
+                progress,
                 remote: LfsRemoteInner::Http(HttpLfsRemote {
                     url,
                     auth,
                     user_agent,
                     concurrent_fetches,
+                    concurrent_uploads,
                     backoff_times,
                     request_timeout,
                     client,
@@ -1411,6 +1986,7 @@ impl LfsRemote {
                         min_transfer_speed,
                         correlator,
                     },
+                    staging,
                 }),
             })
         }
@@ -1429,7 +2005,8 @@ impl LfsRemote {
         objs: &HashSet<(Sha256, usize)>,
         read_from_store: impl Fn(Sha256) -> Result<Option<Bytes>> + Send + Clone + 'static,
     ) -> Result<()> {
-        self.remote.batch_upload(objs, read_from_store)
+        self.remote
+            .batch_upload(objs, read_from_store, self.progress.as_ref())
     }
 }
 
@@ -1731,6 +2308,13 @@ impl LocalStore for LfsFallbackRemoteStore {
     }
 }
 
+/// Parse the first byte position of a "Content-Range: bytes <start>-<end>/<length>" header.
+fn parse_content_range_start(content_range: &str) -> Option<u64> {
+    let range = content_range.strip_prefix("bytes ")?;
+    let (start, _) = range.split_at(range.find('-')?);
+    start.trim().parse().ok()
+}
+
 fn should_retry_http_status(status: StatusCode) -> bool {
     if status == StatusCode::SERVICE_UNAVAILABLE {
         return false;
@@ -1762,9 +2346,11 @@ mod tests {
 
     use std::str::FromStr;
 
+    use mockito::Matcher;
     use quickcheck::quickcheck;
     use tempfile::TempDir;
 
+    use progress::null::NullProgressFactory;
     use types::testutil::*;
 
     use crate::{
@@ -2039,7 +2625,7 @@ mod tests {
                 content_hashes,
             };
 
-            let with_metadata = rebuild_metadata(data.clone(), &pointer);
+            let with_metadata = rebuild_metadata(data.clone(), pointer.copy_from.as_ref());
             let (without, copy) = strip_metadata(&with_metadata)?;
 
             Ok(data == without && copy == copy_from)
@@ -2338,6 +2924,114 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_lfs_concurrent_uploads_config() -> Result<()> {
+        let cachedir = TempDir::new()?;
+        let lfsdir = TempDir::new()?;
+        let mut config = make_lfs_config(&cachedir);
+        config.set("lfs", "concurrentfetches", Some("3"), &Default::default());
+
+        let lfs = Arc::new(LfsStore::shared(&lfsdir, &config)?);
+        let remote = LfsRemote::new(lfs.clone(), None, &config, None, NullProgressFactory::arc())?;
+        match &remote.remote {
+            LfsRemoteInner::Http(http) => assert_eq!(http.concurrent_uploads, 3),
+            LfsRemoteInner::File(_) => panic!("expected an http remote"),
+        }
+
+        config.set("lfs", "concurrentuploads", Some("5"), &Default::default());
+        let remote = LfsRemote::new(lfs, None, &config, None, NullProgressFactory::arc())?;
+        match &remote.remote {
+            LfsRemoteInner::Http(http) => {
+                assert_eq!(http.concurrent_fetches, 3);
+                assert_eq!(http.concurrent_uploads, 5);
+            }
+            LfsRemoteInner::File(_) => panic!("expected an http remote"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lfs_upload_plan() -> Result<()> {
+        let _env_lock = crate::env_lock();
+
+        let cachedir = TempDir::new()?;
+        let lfsdir = TempDir::new()?;
+        let mut config = make_lfs_config(&cachedir);
+        let url = format!("{}/test_lfs_upload_plan", mockito::server_url());
+        config.set("lfs", "url", Some(url.as_str()), &Default::default());
+        config.set("lfs", "http-version", Some("1.1"), &Default::default());
+        config.set(
+            "lfs",
+            "use-client-certs",
+            Some("false"),
+            &Default::default(),
+        );
+
+        let lfs = Arc::new(LfsStore::shared(&lfsdir, &config)?);
+        let remote = LfsRemote::new(lfs, None, &config, None, NullProgressFactory::arc())?;
+        let http = match &remote.remote {
+            LfsRemoteInner::Http(http) => http,
+            LfsRemoteInner::File(_) => panic!("expected an http remote"),
+        };
+
+        let present = Bytes::from(&b"present"[..]);
+        let missing = Bytes::from(&b"missing blob"[..]);
+        let present_sha = ContentHash::sha256(&present).unwrap_sha256();
+        let missing_sha = ContentHash::sha256(&missing).unwrap_sha256();
+
+        let batch = mockito::mock("POST", "/test_lfs_upload_plan/objects/batch")
+            .with_status(200)
+            .with_body(format!(
+                r#"{{"transfer":"basic","objects":[
+                    {{"oid":"{}","size":{},"authenticated":true,"actions":{{}}}},
+                    {{"oid":"{}","size":{},"authenticated":true,"actions":{{
+                        "upload":{{"href":"{}/upload"}}
+                    }}}}
+                ]}}"#,
+                present_sha.to_hex(),
+                present.len(),
+                missing_sha.to_hex(),
+                missing.len(),
+                url,
+            ))
+            .expect(1)
+            .create();
+        let upload = mockito::mock("PUT", "/test_lfs_upload_plan/upload")
+            .match_body("missing blob")
+            .with_status(200)
+            .expect(1)
+            .create();
+
+        let objs = [(present_sha, present.len()), (missing_sha, missing.len())]
+            .iter()
+            .cloned()
+            .collect::<HashSet<_>>();
+        let plan = LfsRemoteInner::plan_upload(http, &objs)?;
+        assert_eq!(plan.skipped, 1);
+        assert_eq!(plan.uploads.len(), 1);
+        assert_eq!(plan.uploads[0].0, missing_sha);
+        assert_eq!(plan.size(), missing.len() as u64);
+
+        let bar = NullProgressFactory.bar("Uploading LFS blobs", Some(plan.size()), Unit::Bytes)?;
+        LfsRemoteInner::execute_upload(
+            http,
+            plan,
+            move |sha256| {
+                assert_eq!(sha256, missing_sha);
+                Ok(Some(missing.clone()))
+            },
+            bar.as_ref(),
+        )?;
+        assert_eq!(bar.position()?, 12);
+        assert_eq!(bar.total()?, Some(12));
+
+        batch.assert();
+        upload.assert();
+
+        Ok(())
+    }
+
     #[cfg(feature = "fb")]
     mod fb_test {
         use super::*;
@@ -2352,7 +3046,7 @@ mod tests {
             let config = make_lfs_config(&cachedir);
 
             let lfs = Arc::new(LfsStore::shared(&lfsdir, &config)?);
-            let remote = LfsRemote::new(lfs, None, &config, None)?;
+            let remote = LfsRemote::new(lfs, None, &config, None, NullProgressFactory::arc())?;
 
             let blob = (
                 Sha256::from_str(
@@ -2385,7 +3079,7 @@ mod tests {
             set_var("https_proxy", "fwdproxy:8082");
 
             let lfs = Arc::new(LfsStore::shared(&lfsdir, &config)?);
-            let remote = LfsRemote::new(lfs, None, &config, None)?;
+            let remote = LfsRemote::new(lfs, None, &config, None, NullProgressFactory::arc())?;
 
             let blob = (
                 Sha256::from_str(
@@ -2416,7 +3110,7 @@ mod tests {
             set_var("https_proxy", "http://fwdproxy:8082");
 
             let lfs = Arc::new(LfsStore::shared(&lfsdir, &config)?);
-            let remote = LfsRemote::new(lfs, None, &config, None)?;
+            let remote = LfsRemote::new(lfs, None, &config, None, NullProgressFactory::arc())?;
 
             let blob = (
                 Sha256::from_str(
@@ -2448,7 +3142,7 @@ mod tests {
             );
 
             let lfs = Arc::new(LfsStore::shared(&lfsdir, &config)?);
-            let remote = LfsRemote::new(lfs, None, &config, None)?;
+            let remote = LfsRemote::new(lfs, None, &config, None, NullProgressFactory::arc())?;
 
             let blob = (
                 Sha256::from_str(
@@ -2481,7 +3175,7 @@ mod tests {
             set_var("NO_PROXY", ".facebook.com,.tfbnw.net");
 
             let lfs = Arc::new(LfsStore::shared(&lfsdir, &config)?);
-            let remote = LfsRemote::new(lfs, None, &config, None)?;
+            let remote = LfsRemote::new(lfs, None, &config, None, NullProgressFactory::arc())?;
 
             let blob = (
                 Sha256::from_str(
@@ -2518,7 +3212,7 @@ mod tests {
                 );
 
                 let lfs = Arc::new(LfsStore::shared(&lfsdir, &config)?);
-                let remote = LfsRemote::new(lfs, None, &config, None)?;
+                let remote = LfsRemote::new(lfs, None, &config, None, NullProgressFactory::arc())?;
 
                 let blob1 = (
                     Sha256::from_str(
@@ -2568,7 +3262,7 @@ mod tests {
             config.set("lfs", "http-version", Some("3"), &Default::default());
 
             let lfs = Arc::new(LfsStore::shared(&lfsdir, &config).unwrap());
-            let result = LfsRemote::new(lfs, None, &config, None);
+            let result = LfsRemote::new(lfs, None, &config, None, NullProgressFactory::arc());
 
             assert!(result.is_err());
 
@@ -2586,7 +3280,7 @@ mod tests {
             config.set("lfs", "requesttimeout", Some("0"), &Default::default());
 
             let lfs = Arc::new(LfsStore::shared(&lfsdir, &config)?);
-            let remote = LfsRemote::new(lfs, None, &config, None)?;
+            let remote = LfsRemote::new(lfs, None, &config, None, NullProgressFactory::arc())?;
 
             let blob = (
                 Sha256::from_str(
@@ -2612,7 +3306,13 @@ mod tests {
             let config = make_lfs_config(&cachedir);
 
             let lfs = Arc::new(LfsStore::shared(&lfsdir, &config)?);
-            let remote = Arc::new(LfsRemote::new(lfs.clone(), None, &config, None)?);
+            let remote = Arc::new(LfsRemote::new(
+                lfs.clone(),
+                None,
+                &config,
+                None,
+                NullProgressFactory::arc(),
+            )?);
 
             let key = key("a/b", "1234");
 
@@ -2667,7 +3367,7 @@ mod tests {
             );
 
             let lfs = Arc::new(LfsStore::shared(&lfsdir, &config)?);
-            let remote = LfsRemote::new(lfs, None, &config, None)?;
+            let remote = LfsRemote::new(lfs, None, &config, None, NullProgressFactory::arc())?;
 
             let blob = (
                 Sha256::from_str(
@@ -2717,7 +3417,7 @@ mod tests {
         let url = Url::from_file_path(&remote).unwrap();
         config.set("lfs", "url", Some(url.as_str()), &Default::default());
 
-        let remote = LfsRemote::new(lfs, None, &config, None)?;
+        let remote = LfsRemote::new(lfs, None, &config, None, NullProgressFactory::arc())?;
 
         let objs = [(blob1.0, blob1.1), (blob2.0, blob2.1)]
             .iter()
@@ -2773,7 +3473,13 @@ mod tests {
         let url = Url::from_file_path(&remote_dir).unwrap();
         config.set("lfs", "url", Some(url.as_str()), &Default::default());
 
-        let remote = LfsRemote::new(shared_lfs, Some(local_lfs.clone()), &config, None)?;
+        let remote = LfsRemote::new(
+            shared_lfs,
+            Some(local_lfs.clone()),
+            &config,
+            None,
+            NullProgressFactory::arc(),
+        )?;
 
         let objs = [(blob1.0, blob1.1), (blob2.0, blob2.1)]
             .iter()
@@ -2816,6 +3522,7 @@ mod tests {
             Some(local_lfs.clone()),
             &config,
             None,
+            NullProgressFactory::arc(),
         )?);
         let remote = remote.datastore(shared_lfs.clone());
         let k = StoreKey::hgid(k1.clone());
@@ -2898,7 +3605,13 @@ mod tests {
         config.set("lfs", "url", Some("http://192.0.2.0/"), &Default::default());
 
         let lfs = Arc::new(LfsStore::shared(&lfsdir, &config)?);
-        let remote = Arc::new(LfsRemote::new(lfs, None, &config, None)?);
+        let remote = Arc::new(LfsRemote::new(
+            lfs,
+            None,
+            &config,
+            None,
+            NullProgressFactory::arc(),
+        )?);
 
         let resp = remote.datastore(store).prefetch(&[]);
         assert!(resp.is_ok());
@@ -2920,4 +3633,192 @@ mod tests {
 
         assert!(should_retry_http_status(StatusCode::TOO_MANY_REQUESTS));
     }
+
+    #[test]
+    fn test_parse_content_range() {
+        assert_eq!(parse_content_range_start("bytes 0-99/100"), Some(0));
+        assert_eq!(parse_content_range_start("bytes 42-99/100"), Some(42));
+        assert_eq!(parse_content_range_start("bytes 42-99/*"), Some(42));
+        assert_eq!(parse_content_range_start("bytes */100"), None);
+        assert_eq!(parse_content_range_start("42-99/100"), None);
+    }
+
+    #[test]
+    fn test_staging_resume() -> Result<()> {
+        let dir = TempDir::new()?;
+        let staging = LfsStaging::new(dir.path())?;
+
+        let data = Bytes::from(&[1, 2, 3, 4, 5, 6][..]);
+        let sha256 = ContentHash::sha256(&data).unwrap_sha256();
+
+        let mut partial = staging.open(&sha256)?.unwrap();
+        partial.write_all(&data[..4])?;
+        // The blob is being downloaded by another process.
+        assert!(staging.open(&sha256)?.is_none());
+        drop(partial);
+
+        let mut partial = staging.open(&sha256)?.unwrap();
+        assert_eq!(partial.len, 4);
+        partial.write_all(&data[4..])?;
+        assert_eq!(partial.finish(&sha256)?, data);
+
+        let partial = staging.open(&sha256)?.unwrap();
+        assert_eq!(partial.len, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_staging_hash_mismatch() -> Result<()> {
+        let dir = TempDir::new()?;
+        let staging = LfsStaging::new(dir.path())?;
+
+        let data = Bytes::from(&[1, 2, 3, 4, 5, 6][..]);
+        let sha256 = ContentHash::sha256(&data).unwrap_sha256();
+
+        let mut partial = staging.open(&sha256)?.unwrap();
+        partial.write_all(&data[1..])?;
+        assert!(partial.finish(&sha256).is_err());
+
+        // The corrupt data was discarded.
+        let mut partial = staging.open(&sha256)?.unwrap();
+        assert_eq!(partial.len, 0);
+        partial.write_all(&data)?;
+        assert_eq!(partial.finish(&sha256)?, data);
+        Ok(())
+    }
+
+    /// Download `path` from the mock server into the partial blob for `data`, which already holds
+    /// `partial`.
+    fn download_resumed(path: &str, data: &Bytes, partial: &[u8]) -> Result<Bytes> {
+        let _env_lock = crate::env_lock();
+
+        let cachedir = TempDir::new()?;
+        let lfsdir = TempDir::new()?;
+        let mut config = make_lfs_config(&cachedir);
+        let url = format!("{}/{}", mockito::server_url(), path);
+        config.set("lfs", "url", Some(url.as_str()), &Default::default());
+        config.set("lfs", "http-version", Some("1.1"), &Default::default());
+        config.set(
+            "lfs",
+            "use-client-certs",
+            Some("false"),
+            &Default::default(),
+        );
+
+        let lfs = Arc::new(LfsStore::shared(&lfsdir, &config)?);
+        let remote = LfsRemote::new(lfs, None, &config, None, NullProgressFactory::arc())?;
+        let http = match &remote.remote {
+            LfsRemoteInner::Http(http) => http,
+            LfsRemoteInner::File(_) => panic!("expected an http remote"),
+        };
+
+        let staging = LfsStaging::new(lfsdir.path())?;
+        let sha256 = ContentHash::sha256(data).unwrap_sha256();
+        let mut blob = staging.open(&sha256)?.unwrap();
+        blob.write_all(partial)?;
+
+        block_on_future(LfsRemoteInner::download_with_resume(
+            &http.client,
+            http.auth.as_ref(),
+            Url::parse(&url)?,
+            &http.user_agent,
+            vec![0.01],
+            http.request_timeout,
+            None,
+            &http.http_options,
+            &mut blob,
+        ))?;
+        blob.finish(&sha256)
+    }
+
+    #[test]
+    fn test_download_resume_partial_content() -> Result<()> {
+        let data = Bytes::from(&b"partially downloaded"[..]);
+
+        let get = mockito::mock("GET", "/test_download_resume_partial_content")
+            .match_header("Range", "bytes=9-")
+            .with_status(206)
+            .with_header("Content-Range", "bytes 9-19/20")
+            .with_body(&data[9..])
+            .expect(1)
+            .create();
+
+        assert_eq!(
+            download_resumed("test_download_resume_partial_content", &data, &data[..9])?,
+            data
+        );
+        get.assert();
+        Ok(())
+    }
+
+    #[test]
+    fn test_download_resume_range_ignored() -> Result<()> {
+        let data = Bytes::from(&b"range ignored"[..]);
+
+        // The server sends the whole blob, the partial data is discarded.
+        let get = mockito::mock("GET", "/test_download_resume_range_ignored")
+            .match_header("Range", "bytes=4-")
+            .with_status(200)
+            .with_body(&data[..])
+            .expect(1)
+            .create();
+
+        assert_eq!(
+            download_resumed("test_download_resume_range_ignored", &data, b"junk")?,
+            data
+        );
+        get.assert();
+        Ok(())
+    }
+
+    #[test]
+    fn test_download_resume_range_not_satisfiable() -> Result<()> {
+        let data = Bytes::from(&b"short"[..]);
+
+        // The partial blob is larger than the blob, the download restarts from the beginning.
+        let resume = mockito::mock("GET", "/test_download_resume_range_not_satisfiable")
+            .match_header("Range", "bytes=9-")
+            .with_status(416)
+            .expect(1)
+            .create();
+        let restart = mockito::mock("GET", "/test_download_resume_range_not_satisfiable")
+            .match_header("Range", Matcher::Missing)
+            .with_status(200)
+            .with_body(&data[..])
+            .expect(1)
+            .create();
+
+        assert_eq!(
+            download_resumed(
+                "test_download_resume_range_not_satisfiable",
+                &data,
+                b"too long!"
+            )?,
+            data
+        );
+        resume.assert();
+        restart.assert();
+        Ok(())
+    }
+
+    #[test]
+    fn test_download_resume_max_attempts() -> Result<()> {
+        let data = Bytes::from(&b"never completes"[..]);
+
+        // Every attempt makes progress before failing, the download still gives up.
+        let get = mockito::mock("GET", "/test_download_resume_max_attempts")
+            .with_status(200)
+            .with_body_from_fn(|w| {
+                w.write_all(b"ne")?;
+                w.flush()?;
+                Err(std::io::ErrorKind::ConnectionReset.into())
+            })
+            .expect(LfsRemoteInner::MAX_DOWNLOAD_ATTEMPTS)
+            .create();
+
+        let res = download_resumed("test_download_resume_max_attempts", &data, b"");
+        assert!(res.unwrap_err().downcast_ref::<FetchError>().is_some());
+        get.assert();
+        Ok(())
+    }
 }
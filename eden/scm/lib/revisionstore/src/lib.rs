@@ -142,6 +142,7 @@ mod sliceext;
 mod types;
 mod unionstore;
 mod util;
+mod verify;
 
 pub mod datapack;
 pub mod datastore;
@@ -187,6 +188,7 @@ pub use crate::repack::{repack, RepackKind, RepackLocation, Repackable, ToKeys};
 pub use crate::types::{ContentHash, StoreKey};
 pub use crate::uniondatastore::UnionHgIdDataStore;
 pub use crate::util::Error;
+pub use crate::verify::VerifyReport;
 
 pub use revisionstore_types::*;
 
@@ -33,6 +33,7 @@ use crate::{
         get_cache_packs_path, get_cache_path, get_indexedloghistorystore_path, get_local_path,
         get_packs_path,
     },
+    verify::VerifyReport,
 };
 
 /// A `MetadataStore` aggregate all the local and remote stores and expose them as one. Both local and
@@ -87,6 +88,45 @@ impl MetadataStore {
         }
         Ok(repair_str)
     }
+
+    /// Verify that the entries of the underlying stores that the `MetadataStore` is comprised of
+    /// have valid parents.
+    ///
+    /// When `delete_corrupt` is set, corrupt entries are removed from the shared store so they
+    /// can be fetched again. Local data is never removed. As for `repair`, care must be taken to
+    /// call this only when no other `MetadataStore` have been created for the `shared_path`.
+    pub fn verify(
+        shared_path: impl AsRef<Path>,
+        local_path: Option<impl AsRef<Path>>,
+        suffix: Option<impl AsRef<Path>>,
+        config: &ConfigSet,
+        delete_corrupt: bool,
+    ) -> Result<VerifyReport> {
+        let mut shared_path = shared_path.as_ref().to_path_buf();
+        if let Some(suffix) = suffix.as_ref() {
+            shared_path.push(suffix);
+        }
+        let local_path = get_local_path(
+            &local_path.as_ref().map(|l| l.as_ref().to_path_buf()),
+            &suffix.map(|p| p.as_ref().to_path_buf()),
+        )?;
+
+        let mut report = IndexedLogHgIdHistoryStore::verify(
+            get_indexedloghistorystore_path(&shared_path)?,
+            config,
+            IndexedLogHistoryStoreType::Shared,
+            delete_corrupt,
+        )?;
+        if let Some(local_path) = local_path {
+            report.merge(IndexedLogHgIdHistoryStore::verify(
+                get_indexedloghistorystore_path(local_path)?,
+                config,
+                IndexedLogHistoryStoreType::Local,
+                false,
+            )?);
+        }
+        Ok(report)
+    }
 }
 
 // Repack specific methods, not to be used directly but by the repack code.
@@ -72,6 +72,17 @@ impl<T: HgIdMutableDeltaStore> HgIdMutableDeltaStore for MultiplexDeltaStore<T>
 
         Ok(ret)
     }
+
+    fn get_copy_from(&self, mut key: StoreKey) -> Result<StoreResult<Option<Key>>> {
+        for store in self.stores.iter() {
+            match store.get_copy_from(key)? {
+                StoreResult::Found(copy_from) => return Ok(StoreResult::Found(copy_from)),
+                StoreResult::NotFound(next) => key = next,
+            }
+        }
+
+        Ok(StoreResult::NotFound(key))
+    }
 }
 
 impl<T: HgIdMutableDeltaStore> HgIdDataStore for MultiplexDeltaStore<T> {
@@ -15,19 +15,22 @@ use minibytes::Bytes;
 use configparser::config::ConfigSet;
 use edenapi::{EdenApi, EdenApiError, Fetch, ProgressCallback, ResponseMeta, Stats};
 use edenapi_types::{
-    CloneData, CommitRevlogData, EdenApiServerError, FileEntry, HistoryEntry, TreeAttributes,
-    TreeEntry,
+    AnyId, BookmarkEntry, CloneData, CommitGraphEntry, CommitIsAncestor, CommitRevlogData,
+    EdenApiServerError, FileContentEntry, FileContentId, FileEntry, HistoryEntry,
+    LandStackResponse, LookupResponse, TreeAttributes, TreeEntry, UploadHgChangesetEntry,
+    UploadHgFilenodeEntry, UploadResponse, UploadTreeEntry,
 };
-use types::{HgId, Key, NodeInfo, Parents, RepoPathBuf};
+use types::{HgId, Key, NodeInfo, Parents, RepoPathBuf, Sha256};
 
 use crate::{
     datastore::{
-        Delta, HgIdDataStore, HgIdMutableDeltaStore, Metadata, RemoteDataStore, StoreResult,
+        strip_metadata, Delta, HgIdDataStore, HgIdMutableDeltaStore, Metadata, RemoteDataStore,
+        StoreResult,
     },
     historystore::{HgIdHistoryStore, HgIdMutableHistoryStore, RemoteHistoryStore},
     localstore::LocalStore,
     remotestore::HgIdRemoteStore,
-    types::StoreKey,
+    types::{ContentHash, StoreKey},
 };
 
 pub fn delta(data: &str, base: Option<Key>, key: Key) -> Delta {
@@ -240,6 +243,39 @@ impl FakeEdenApi {
         })
     }
 
+    fn get_files_by_content(
+        map: &HashMap<Key, Bytes>,
+        ids: Vec<FileContentId>,
+    ) -> Result<Fetch<FileContentEntry>, EdenApiError> {
+        // Content is addressed by the hash of the file without its copy metadata.
+        let by_sha256 = map
+            .values()
+            .map(|data| {
+                let (data, _) = strip_metadata(data)?;
+                Ok((ContentHash::sha256(&data).unwrap_sha256(), data))
+            })
+            .collect::<Result<HashMap<_, _>>>()
+            .map_err(EdenApiError::Other)?;
+
+        let entries = ids
+            .into_iter()
+            .filter_map(|id| {
+                let sha256 = match id {
+                    FileContentId::Sha256(sha256) => Sha256::from_byte_array(sha256.0),
+                    _ => return None,
+                };
+                let data = by_sha256.get(&sha256)?.to_vec().into();
+                Some(Ok(FileContentEntry::new(id, data)))
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Fetch {
+            meta: vec![ResponseMeta::default()],
+            entries: Box::pin(stream::iter(entries)),
+            stats: Box::pin(future::ok(Stats::default())),
+        })
+    }
+
     fn get_trees(
         map: &HashMap<Key, Bytes>,
         keys: Vec<Key>,
@@ -277,6 +313,15 @@ impl EdenApi for FakeEdenApi {
         Self::get_files(&self.files, keys)
     }
 
+    async fn files_by_content(
+        &self,
+        _repo: String,
+        ids: Vec<FileContentId>,
+        _progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<FileContentEntry>, EdenApiError> {
+        Self::get_files_by_content(&self.files, ids)
+    }
+
     async fn history(
         &self,
         _repo: String,
@@ -330,6 +375,35 @@ impl EdenApi for FakeEdenApi {
         unimplemented!()
     }
 
+    async fn commit_graph(
+        &self,
+        _repo: String,
+        _hgids: Vec<HgId>,
+        _depth: u64,
+        _progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<CommitGraphEntry>, EdenApiError> {
+        unimplemented!()
+    }
+
+    async fn commit_is_ancestor(
+        &self,
+        _repo: String,
+        _pairs: Vec<(HgId, HgId)>,
+        _progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<CommitIsAncestor>, EdenApiError> {
+        unimplemented!()
+    }
+
+    async fn bookmarks(
+        &self,
+        _repo: String,
+        _bookmarks: Vec<String>,
+        _prefixes: Vec<String>,
+        _progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<BookmarkEntry>, EdenApiError> {
+        unimplemented!()
+    }
+
     async fn clone_data(
         &self,
         _repo: String,
@@ -345,6 +419,61 @@ impl EdenApi for FakeEdenApi {
     ) -> Result<CloneData<HgId>, EdenApiError> {
         unimplemented!()
     }
+
+    async fn lookup(
+        &self,
+        _repo: String,
+        _ids: Vec<AnyId>,
+        _progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<LookupResponse>, EdenApiError> {
+        unimplemented!()
+    }
+
+    async fn upload_file_contents(
+        &self,
+        _repo: String,
+        _contents: Vec<bytes::Bytes>,
+        _progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<UploadResponse>, EdenApiError> {
+        unimplemented!()
+    }
+
+    async fn upload_filenodes(
+        &self,
+        _repo: String,
+        _filenodes: Vec<UploadHgFilenodeEntry>,
+        _progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<UploadResponse>, EdenApiError> {
+        unimplemented!()
+    }
+
+    async fn upload_trees(
+        &self,
+        _repo: String,
+        _trees: Vec<UploadTreeEntry>,
+        _progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<UploadResponse>, EdenApiError> {
+        unimplemented!()
+    }
+
+    async fn upload_changesets(
+        &self,
+        _repo: String,
+        _changesets: Vec<UploadHgChangesetEntry>,
+        _progress: Option<ProgressCallback>,
+    ) -> Result<Fetch<UploadResponse>, EdenApiError> {
+        unimplemented!()
+    }
+
+    async fn land_stack(
+        &self,
+        _repo: String,
+        _bookmark: String,
+        _head: HgId,
+        _base: HgId,
+    ) -> Result<LandStackResponse, EdenApiError> {
+        unimplemented!()
+    }
 }
 
 pub fn make_config(dir: impl AsRef<Path>) -> ConfigSet {
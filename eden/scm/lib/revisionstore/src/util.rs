@@ -33,14 +33,19 @@ pub fn get_repo_name(config: &ConfigSet) -> Result<String> {
     get_str_config(config, "remotefilelog", "reponame")
 }
 
-fn get_config_cache_path(config: &ConfigSet) -> Result<PathBuf> {
-    let reponame = get_repo_name(config)?;
+fn get_config_cache_root_path(config: &ConfigSet) -> Result<PathBuf> {
     let config_path: PathBuf = config
         .get_or_default::<Option<_>>("remotefilelog", "cachepath")?
         .ok_or_else(|| Error::ConfigNotSet("remotefilelog.cachepath".into()))?;
     let mut path = PathBuf::new();
     path.push(config_path);
     create_shared_dir(&path)?;
+    Ok(path)
+}
+
+fn get_config_cache_path(config: &ConfigSet) -> Result<PathBuf> {
+    let reponame = get_repo_name(config)?;
+    let mut path = get_config_cache_root_path(config)?;
     path.push(reponame);
     create_shared_dir(&path)?;
     Ok(path)
@@ -139,6 +144,26 @@ pub fn get_lfs_blobs_path(store_path: impl AsRef<Path>) -> Result<PathBuf> {
     Ok(path)
 }
 
+pub fn get_lfs_staging_path(store_path: impl AsRef<Path>) -> Result<PathBuf> {
+    let mut path = get_lfs_path(store_path)?;
+    path.push("staging");
+    create_shared_dir(&path)?;
+
+    Ok(path)
+}
+
+/// The deduplicated blobs are shared by all the repositories, and thus aren't stored under the
+/// cache directory of a repository.
+pub fn get_dedup_blobs_path(config: &ConfigSet) -> Result<PathBuf> {
+    let mut path = get_config_cache_root_path(config)?;
+    path.push("dedup");
+    create_shared_dir(&path)?;
+    path.push("blobs");
+    create_shared_dir(&path)?;
+
+    Ok(path)
+}
+
 pub const RUN_ONCE_FILENAME: &str = "runoncemarker";
 pub fn check_run_once(store_path: impl AsRef<Path>, key: &str, cutoff: HgTime) -> bool {
     if HgTime::now() > Some(cutoff) {
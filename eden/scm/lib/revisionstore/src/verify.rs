@@ -0,0 +1,220 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! End-to-end verification of the data held by the stores.
+//!
+//! Repairing a store only makes sure its on-disk structures can be read. Verifying a store
+//! checks that the data matches the keys it is stored under:
+//!  - file and tree data hash to their `HgId`, using the parents found in the history stores,
+//!  - delta chains in datapacks resolve to a full text,
+//!  - history entries have valid parents,
+//!  - LFS blobs hash to their `Sha256`.
+//!
+//! Corrupt entries can be removed from the cache so they are fetched again.
+
+use std::{
+    fmt,
+    fs::{read_dir, remove_file},
+    path::Path,
+};
+
+use anyhow::{bail, format_err, Error, Result};
+use mpatch::mpatch::get_full_text;
+
+use types::{HgId, Key, NodeInfo, Parents};
+
+use crate::{
+    datapack::DataPack,
+    datastore::{HgIdDataStore, StoreResult},
+    historystore::HgIdHistoryStore,
+    localstore::ExtStoredPolicy,
+    repack::{Repackable, ToKeys},
+    types::StoreKey,
+};
+
+/// Outcome of the verification of one or more stores.
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// Number of entries that were checked.
+    pub checked: usize,
+    /// Number of entries whose hash could not be checked, either because their parents are
+    /// unknown, or because the content is stored externally (LFS).
+    pub unverifiable: usize,
+    /// Description of the corrupt entries.
+    pub corrupt: Vec<String>,
+    /// Number of entries that were removed.
+    pub removed: usize,
+}
+
+impl VerifyReport {
+    /// Whether no corruption was found.
+    pub fn is_ok(&self) -> bool {
+        self.corrupt.is_empty()
+    }
+
+    pub(crate) fn merge(&mut self, other: VerifyReport) {
+        self.checked += other.checked;
+        self.unverifiable += other.unverifiable;
+        self.corrupt.extend(other.corrupt);
+        self.removed += other.removed;
+    }
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for corrupt in &self.corrupt {
+            writeln!(f, "{}", corrupt)?;
+        }
+        writeln!(
+            f,
+            "checked {} entries, {} corrupt, {} not hash-checked, {} removed",
+            self.checked,
+            self.corrupt.len(),
+            self.unverifiable,
+            self.removed
+        )
+    }
+}
+
+/// Check that `content` hashes to the `HgId` of `key`. Returns `None` when the parents of `key`
+/// are not present in `history`, or are themselves invalid.
+pub(crate) fn check_hgid(
+    key: &Key,
+    content: &[u8],
+    history: &dyn HgIdHistoryStore,
+) -> Result<Option<bool>> {
+    let info = match history.get_node_info(key)? {
+        Some(info) if check_node_info(key, &info).is_none() => info,
+        _ => return Ok(None),
+    };
+    let parents = Parents::new(info.parents[0].hgid, info.parents[1].hgid);
+    Ok(Some(HgId::from_content(content, parents) == key.hgid))
+}
+
+/// Check that the parents of a history entry are valid. Returns the reason why they aren't.
+pub(crate) fn check_node_info(key: &Key, info: &NodeInfo) -> Option<&'static str> {
+    let [p1, p2] = &info.parents;
+    if p1.hgid == key.hgid || p2.hgid == key.hgid {
+        Some("entry is its own parent")
+    } else if p1.hgid.is_null() && !p2.hgid.is_null() {
+        Some("entry has a p2 but no p1")
+    } else {
+        None
+    }
+}
+
+/// Apply the delta chain of `key`, following delta bases across `packs`.
+fn resolve_full_text(packs: &[DataPack], pack: &DataPack, key: &Key) -> Result<Vec<u8>> {
+    let mut chain = pack
+        .get_delta_chain(key)?
+        .ok_or_else(|| format_err!("{} cannot be found in its own datapack", key))?;
+    while let Some(base) = chain.last().and_then(|delta| delta.base.clone()) {
+        // Delta chains are bounded to 1000 entries within a pack, make sure that a cycle between
+        // packs cannot make us loop forever.
+        if chain.len() > 1000 {
+            bail!("delta chain of {} is too long", key);
+        }
+
+        let mut base_chain = None;
+        for pack in packs {
+            if let Some(deltas) = pack.get_delta_chain(&base)? {
+                base_chain = Some(deltas);
+                break;
+            }
+        }
+        match base_chain {
+            None => bail!("delta base {} of {} cannot be found", base, key),
+            Some(deltas) => chain.extend(deltas),
+        }
+    }
+
+    // unwrap safety: get_delta_chain never returns an empty chain.
+    let (basetext, deltas) = chain.split_last().unwrap();
+    let deltas: Vec<&[u8]> = deltas
+        .iter()
+        .rev()
+        .map(|delta| delta.data.as_ref())
+        .collect();
+    get_full_text(basetext.data.as_ref(), &deltas).map_err(Error::msg)
+}
+
+/// Verify one datapack entry.
+fn verify_datapack_entry(
+    packs: &[DataPack],
+    pack: &DataPack,
+    key: &Key,
+    history: &dyn HgIdHistoryStore,
+) -> Result<Option<bool>> {
+    let content = resolve_full_text(packs, pack, key)?;
+    match pack.get_meta(StoreKey::hgid(key.clone()))? {
+        // The hash of LFS entries is computed over the blob, not over the stored pointer.
+        StoreResult::Found(metadata) if metadata.is_lfs() => Ok(None),
+        _ => check_hgid(key, &content, history),
+    }
+}
+
+/// Verify the datapacks in `pack_dir`. When `delete_corrupt` is set, datapacks containing
+/// corrupt entries are removed.
+pub(crate) fn verify_datapacks(
+    pack_dir: &Path,
+    history: &dyn HgIdHistoryStore,
+    delete_corrupt: bool,
+) -> Result<VerifyReport> {
+    let mut report = VerifyReport::default();
+
+    let mut packs = Vec::new();
+    for entry in read_dir(pack_dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("datapack") {
+            continue;
+        }
+
+        match DataPack::new(&path, ExtStoredPolicy::Use) {
+            Ok(pack) => packs.push(pack),
+            Err(e) => {
+                report.corrupt.push(format!("{}: {}", path.display(), e));
+                if delete_corrupt {
+                    let _ = remove_file(&path);
+                    let _ = remove_file(path.with_extension("dataidx"));
+                }
+            }
+        }
+    }
+
+    let mut corrupt_per_pack = vec![0; packs.len()];
+    for (pack, corrupt) in packs.iter().zip(corrupt_per_pack.iter_mut()) {
+        for key in pack.to_keys() {
+            report.checked += 1;
+            let result =
+                key.and_then(|key| Ok((verify_datapack_entry(&packs, pack, &key, history)?, key)));
+            let reason = match result {
+                Ok((Some(true), _)) => continue,
+                Ok((None, _)) => {
+                    report.unverifiable += 1;
+                    continue;
+                }
+                Ok((Some(false), key)) => format!("{}: content does not match its hash", key),
+                Err(e) => e.to_string(),
+            };
+            report
+                .corrupt
+                .push(format!("{}: {}", pack.base_path().display(), reason));
+            *corrupt += 1;
+        }
+    }
+
+    if delete_corrupt {
+        for (pack, corrupt) in packs.into_iter().zip(corrupt_per_pack) {
+            if corrupt > 0 {
+                pack.delete()?;
+                report.removed += corrupt;
+            }
+        }
+    }
+
+    Ok(report)
+}
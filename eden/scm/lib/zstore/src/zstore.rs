@@ -283,10 +283,21 @@ impl Zstore {
         let mut results = self.log.lookup(0, id)?;
         match results.next() {
             None => Ok(None),
-            Some(Ok(bytes)) => {
+            Some(Ok(Cow::Borrowed(bytes))) => {
                 let result = mincode::deserialize(bytes)?;
                 Ok(Some(result))
             }
+            Some(Ok(Cow::Owned(bytes))) => {
+                let delta: Delta = mincode::deserialize(&bytes)?;
+                Ok(Some(Delta {
+                    id: delta.id,
+                    base_id: delta.base_id,
+                    depth: delta.depth,
+                    subchain_len: delta.subchain_len,
+                    chain_bytes: delta.chain_bytes,
+                    data: Cow::Owned(delta.data.into_owned()),
+                }))
+            }
             Some(Err(err)) => Err(err.into()),
         }
     }
@@ -384,7 +395,8 @@ impl Zstore {
         }
 
         for entry in self.log.iter() {
-            let id = &self.log.index_func(Self::ID20_INDEX, entry?)?[0];
+            let entry = entry?;
+            let id = &self.log.index_func(Self::ID20_INDEX, &entry)?[0];
             let mut id = Id20::from_slice(id).unwrap();
             let mut chain: Vec<Delta> = Vec::new();
             while id != *EMPTY_ID20 {
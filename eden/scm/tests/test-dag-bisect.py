@@ -0,0 +1,72 @@
+from __future__ import absolute_import
+
+import unittest
+
+import silenttestrunner
+from bindings import dag
+
+
+def node(name):
+    return name.encode("ascii") * 20
+
+
+def linear(names):
+    """Build a linear dag from the single character names"""
+    commits = dag.commits.openmemory()
+    parents = []
+    for name in names:
+        commits.addcommits([(node(name), parents, b"")])
+        parents = [node(name)]
+    return commits.dagalgo()
+
+
+class testbisect(unittest.TestCase):
+    def testnext(self):
+        algo = linear("ABCDEFGHIJKLMNOP")
+        bisect = dag.bisect([node("A")], [node("P")])
+        self.assertEqual(bisect.next(algo), ("test", node("H"), 15))
+
+    def testfound(self):
+        algo = linear("ABCDEFGHIJKLMNOP")
+        firstbad = node("F")
+        bisect = dag.bisect([node("A")], [node("P")])
+        steps = 0
+        while True:
+            step = bisect.next(algo)
+            if step[0] == "found":
+                self.assertEqual(list(step[1]), [firstbad])
+                break
+            steps += 1
+            if algo.isancestor(firstbad, step[1]):
+                bisect.markbad([step[1]])
+            else:
+                bisect.markgood([step[1]])
+        self.assertLessEqual(steps, 4)
+
+    def testskip(self):
+        algo = linear("ABCDE")
+        bisect = dag.bisect([node("A")], [node("E")], [node("B"), node("C")])
+        self.assertEqual(bisect.next(algo), ("test", node("D"), 4))
+        bisect.markbad([node("D")])
+        step = bisect.next(algo)
+        self.assertEqual(step[0], "found")
+        self.assertEqual(sorted(step[1]), [node("B"), node("C"), node("D")])
+
+    def teststate(self):
+        algo = linear("ABCDE")
+        bisect = dag.bisect([node("A")], [node("E")])
+        bisect.markgood([node("C")])
+        resumed = dag.bisect.fromstate(bisect.state())
+        self.assertEqual(list(resumed.good()), list(bisect.good()))
+        self.assertEqual(list(resumed.bad()), [node("E")])
+        self.assertEqual(resumed.next(algo), bisect.next(algo))
+
+    def testinvalid(self):
+        algo = linear("ABC")
+        bisect = dag.bisect([node("C")], [node("A")])
+        with self.assertRaises(Exception):
+            bisect.next(algo)
+
+
+if __name__ == "__main__":
+    silenttestrunner.main(__name__)